@@ -0,0 +1,302 @@
+use crate::access::{kick, parse_duration, AccessControl, AccessError, Ban, SERVER_SOURCE};
+use crate::CommandSpec;
+use crate::{argument, literal, ArgumentKind, CommandEvent, CommandFeedback, CommandSource};
+use bevy_ecs::prelude::*;
+use log::error;
+use std::net::IpAddr;
+use valence::client::Ip;
+use valence::prelude::*;
+use valence::UniqueId;
+
+/// `/whitelist <on|off|list|reload>` and `/whitelist <add|remove> <name>`.
+///
+/// `on` and `off` only last until the server stops, the `whitelist` option of the
+/// config decides whether the whitelist is enabled after a restart.
+pub fn whitelist_command() -> CommandSpec {
+    CommandSpec::new("whitelist", "justmine.command.whitelist")
+        .usage([literal("on")])
+        .usage([literal("off")])
+        .usage([literal("list")])
+        .usage([literal("reload")])
+        .usage([literal("add"), argument("name", ArgumentKind::Word)])
+        .usage([literal("remove"), argument("name", ArgumentKind::Word)])
+}
+
+/// `/ban <name> [reason]`.
+pub fn ban_command() -> CommandSpec {
+    CommandSpec::new("ban", "justmine.command.ban")
+        .usage([argument("name", ArgumentKind::Word)])
+        .usage([
+            argument("name", ArgumentKind::Word),
+            argument("reason", ArgumentKind::Greedy),
+        ])
+}
+
+/// `/tempban <name> <duration> [reason]`, with durations like `30m` or `1d12h`.
+pub fn tempban_command() -> CommandSpec {
+    CommandSpec::new("tempban", "justmine.command.ban")
+        .usage([
+            argument("name", ArgumentKind::Word),
+            argument("duration", ArgumentKind::Word),
+        ])
+        .usage([
+            argument("name", ArgumentKind::Word),
+            argument("duration", ArgumentKind::Word),
+            argument("reason", ArgumentKind::Greedy),
+        ])
+}
+
+/// `/pardon <name>`.
+pub fn pardon_command() -> CommandSpec {
+    CommandSpec::new("pardon", "justmine.command.pardon")
+        .usage([argument("name", ArgumentKind::Word)])
+}
+
+/// `/ban-ip <address|name> [reason]`.
+pub fn ban_ip_command() -> CommandSpec {
+    CommandSpec::new("ban-ip", "justmine.command.ban-ip")
+        .usage([argument("target", ArgumentKind::Word)])
+        .usage([
+            argument("target", ArgumentKind::Word),
+            argument("reason", ArgumentKind::Greedy),
+        ])
+}
+
+/// `/pardon-ip <address>`.
+pub fn pardon_ip_command() -> CommandSpec {
+    CommandSpec::new("pardon-ip", "justmine.command.pardon-ip")
+        .usage([argument("address", ArgumentKind::Word)])
+}
+
+/// `/banlist [players|ips]`.
+pub fn banlist_command() -> CommandSpec {
+    CommandSpec::new("banlist", "justmine.command.banlist")
+        .usage([])
+        .usage([literal("players")])
+        .usage([literal("ips")])
+}
+
+/// Handles the commands that change the whitelist and the ban lists, and kicks
+/// online players that get banned.
+pub fn handle_access_commands(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &Username, &UniqueId, &Ip)>,
+    access: Res<AccessControl>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    const NAMES: [&str; 7] = [
+        "whitelist",
+        "ban",
+        "tempban",
+        "pardon",
+        "ban-ip",
+        "pardon-ip",
+        "banlist",
+    ];
+
+    events
+        .iter()
+        .filter(|event| NAMES.contains(&event.name))
+        .for_each(|event| {
+            let mut lists = access.lock();
+            let name = event.args.string("name").unwrap_or_default();
+            // the UUID and exact name of an online player with the name
+            let online = clients
+                .iter()
+                .find(|(_, _, username, _, _)| username.0.eq_ignore_ascii_case(name))
+                .map(|(entity, _, username, id, ip)| {
+                    (entity, username.0.clone(), id.0.to_string(), ip.0)
+                });
+            let source = match event.source {
+                CommandSource::Player(player) => clients
+                    .get(player)
+                    .map_or(SERVER_SOURCE.to_string(), |(_, _, username, _, _)| {
+                        username.0.clone()
+                    }),
+                CommandSource::Console | CommandSource::Rcon(_) => SERVER_SOURCE.to_string(),
+            };
+            let reason = event.args.string("reason");
+
+            let result: Result<String, AccessError> = match (event.name, event.args.path()) {
+                ("whitelist", ["on"]) => {
+                    lists.whitelist_enabled = true;
+                    Ok("Whitelist is now turned on until the server stops".to_string())
+                }
+                ("whitelist", ["off"]) => {
+                    lists.whitelist_enabled = false;
+                    Ok("Whitelist is now turned off until the server stops".to_string())
+                }
+                ("whitelist", ["list"]) => {
+                    let names = lists
+                        .whitelist
+                        .entries
+                        .iter()
+                        .map(|entry| entry.name.as_str())
+                        .collect::<Vec<_>>();
+                    Ok(format!(
+                        "There are {} whitelisted player(s): {}",
+                        names.len(),
+                        names.join(", ")
+                    ))
+                }
+                ("whitelist", ["reload"]) => lists
+                    .reload()
+                    .map(|_| "Reloaded the whitelist and the ban lists".to_string()),
+                ("whitelist", ["add"]) => {
+                    let (uuid, name) = online
+                        .as_ref()
+                        .map_or((String::new(), name.to_string()), |(_, name, uuid, _)| {
+                            (uuid.clone(), name.clone())
+                        });
+                    lists.add_to_whitelist(&uuid, &name).map(|added| {
+                        if added {
+                            format!("Added {} to the whitelist", name)
+                        } else {
+                            format!("{} is already whitelisted", name)
+                        }
+                    })
+                }
+                ("whitelist", ["remove"]) => lists.remove_from_whitelist(name).map(|removed| {
+                    if removed {
+                        format!("Removed {} from the whitelist", name)
+                    } else {
+                        format!("{} is not whitelisted", name)
+                    }
+                }),
+                ("ban" | "tempban", _) => {
+                    let duration = match event.args.string("duration") {
+                        None => None,
+                        Some(duration) => match parse_duration(duration) {
+                            Some(duration) => Some(duration),
+                            None => {
+                                feedback.send(CommandFeedback::error(
+                                    event.source,
+                                    format!(
+                                        "Invalid duration: {}, use e.g. 30m, 12h or 7d",
+                                        duration
+                                    ),
+                                ));
+                                return;
+                            }
+                        },
+                    };
+                    let ban = Ban::new(&source, duration, reason);
+                    let kick_reason =
+                        format!("You are banned from this server.\n{}", ban.describe());
+                    let (uuid, name) = online
+                        .as_ref()
+                        .map_or((String::new(), name.to_string()), |(_, name, uuid, _)| {
+                            (uuid.clone(), name.clone())
+                        });
+                    let result = lists.ban_player(&uuid, &name, ban);
+                    if let (Ok(()), Some((entity, ..))) = (&result, &online) {
+                        if let Ok((_, mut client, ..)) = clients.get_mut(*entity) {
+                            kick(&mut commands, *entity, &mut client, &kick_reason);
+                        }
+                    }
+                    result.map(|_| format!("Banned {}", name))
+                }
+                ("pardon", _) => lists.pardon_player(name).map(|pardoned| {
+                    if pardoned {
+                        format!("Unbanned {}", name)
+                    } else {
+                        format!("{} is not banned", name)
+                    }
+                }),
+                ("ban-ip", _) => {
+                    let target = event.args.string("target").unwrap_or_default();
+                    let ip = match target.parse::<IpAddr>() {
+                        Ok(ip) => ip,
+                        Err(_) => match clients
+                            .iter()
+                            .find(|(_, _, username, _, _)| username.0.eq_ignore_ascii_case(target))
+                        {
+                            Some((_, _, _, _, ip)) => ip.0,
+                            None => {
+                                feedback.send(CommandFeedback::error(
+                                    event.source,
+                                    format!(
+                                        "{} is neither an IP address nor an online player",
+                                        target
+                                    ),
+                                ));
+                                return;
+                            }
+                        },
+                    };
+                    let ban = Ban::new(&source, None, reason);
+                    let kick_reason = format!(
+                        "Your IP address is banned from this server.\n{}",
+                        ban.describe()
+                    );
+                    let result = lists.ban_ip(ip, ban);
+                    if result.is_ok() {
+                        for (entity, mut client, _, _, client_ip) in clients.iter_mut() {
+                            if client_ip.0 == ip {
+                                kick(&mut commands, entity, &mut client, &kick_reason);
+                            }
+                        }
+                    }
+                    result.map(|_| format!("Banned IP {}", ip))
+                }
+                ("pardon-ip", _) => {
+                    let address = event.args.string("address").unwrap_or_default();
+                    let Ok(ip) = address.parse::<IpAddr>() else {
+                        feedback.send(CommandFeedback::error(
+                            event.source,
+                            format!("Invalid IP address: {}", address),
+                        ));
+                        return;
+                    };
+                    lists.pardon_ip(ip).map(|pardoned| {
+                        if pardoned {
+                            format!("Unbanned IP {}", ip)
+                        } else {
+                            format!("IP {} is not banned", ip)
+                        }
+                    })
+                }
+                ("banlist", path) => {
+                    let players = lists.banned_players.entries.iter().map(|entry| {
+                        format!(
+                            "{} was banned by {}: {}",
+                            entry.name, entry.ban.source, entry.ban.reason
+                        )
+                    });
+                    let ips = lists.banned_ips.entries.iter().map(|entry| {
+                        format!(
+                            "{} was banned by {}: {}",
+                            entry.ip, entry.ban.source, entry.ban.reason
+                        )
+                    });
+                    let lines = match path {
+                        ["players"] => players.collect::<Vec<_>>(),
+                        ["ips"] => ips.collect(),
+                        _ => players.chain(ips).collect(),
+                    };
+                    if lines.is_empty() {
+                        Ok("There are no bans".to_string())
+                    } else {
+                        Ok(format!(
+                            "There are {} ban(s):\n{}",
+                            lines.len(),
+                            lines.join("\n")
+                        ))
+                    }
+                }
+                _ => return,
+            };
+
+            match result {
+                Ok(message) => feedback.send(CommandFeedback::info(event.source, message)),
+                Err(e) => {
+                    error!("{}", e);
+                    feedback.send(CommandFeedback::error(
+                        event.source,
+                        "The whitelist or the ban lists could not be accessed, see the log",
+                    ));
+                }
+            }
+        });
+}
@@ -0,0 +1,388 @@
+use crate::write_replacing;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+
+/// The `expires` value of bans that never expire.
+pub const FOREVER: &str = "forever";
+/// The `source` of bans that weren't made by a player.
+pub const SERVER_SOURCE: &str = "Server";
+pub const DEFAULT_BAN_REASON: &str = "Banned by an operator.";
+
+/// An entry of one of the lists.
+pub trait ListEntry {
+    /// Whether vanilla can't read the entry yet, like a player that was added by name
+    /// and whose UUID isn't known. These entries are kept in a separate file next to
+    /// the list until they are complete.
+    fn is_pending(&self) -> bool {
+        false
+    }
+}
+
+/// An entry of `whitelist.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhitelistEntry {
+    /// Empty for players that were added by name and haven't joined since.
+    pub uuid: String,
+    pub name: String,
+}
+
+impl ListEntry for WhitelistEntry {
+    fn is_pending(&self) -> bool {
+        self.uuid.is_empty()
+    }
+}
+
+/// When, why and by whom something was banned, shared by both ban lists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ban {
+    pub created: String,
+    pub source: String,
+    /// A date, or [`FOREVER`].
+    pub expires: String,
+    pub reason: String,
+}
+
+impl Ban {
+    /// A ban that starts now and lasts for the given number of seconds, or forever.
+    pub fn new(source: &str, duration: Option<i64>, reason: Option<&str>) -> Self {
+        let now = unix_time();
+        Self {
+            created: format_date(now),
+            source: source.to_string(),
+            expires: duration.map_or(FOREVER.to_string(), |d| format_date(now + d)),
+            reason: reason.unwrap_or(DEFAULT_BAN_REASON).to_string(),
+        }
+    }
+
+    /// When the ban expires, in seconds since the unix epoch. `None` if it never does,
+    /// which includes expiry dates that can't be read.
+    pub fn expires_at(&self) -> Option<i64> {
+        parse_date(&self.expires)
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at().is_some_and(|expires| expires <= now)
+    }
+
+    /// The reason and, for temporary bans, the expiry, as shown in kick messages.
+    pub fn describe(&self) -> String {
+        match self.expires_at() {
+            Some(_) => format!(
+                "Reason: {}\nYour ban will be removed on {}",
+                self.reason, self.expires
+            ),
+            None => format!("Reason: {}", self.reason),
+        }
+    }
+}
+
+/// An entry of `banned-players.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerBan {
+    /// Empty for players that were banned by name and haven't joined since.
+    pub uuid: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub ban: Ban,
+}
+
+impl ListEntry for PlayerBan {
+    fn is_pending(&self) -> bool {
+        self.uuid.is_empty()
+    }
+}
+
+/// An entry of `banned-ips.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpBan {
+    pub ip: IpAddr,
+    #[serde(flatten)]
+    pub ban: Ban,
+}
+
+impl ListEntry for IpBan {}
+
+#[derive(Debug)]
+pub enum AccessError {
+    Io(PathBuf, io::Error),
+    Json(PathBuf, serde_json::Error),
+}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AccessError::Io(path, e) => write!(f, "unable to access {}: {}", path.display(), e),
+            AccessError::Json(path, e) => write!(f, "unable to parse {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/// The entries of a JSON file in the format of vanilla's lists.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JsonList<T> {
+    path: Option<PathBuf>,
+    pub entries: Vec<T>,
+}
+
+impl<T: Serialize + DeserializeOwned + ListEntry> JsonList<T> {
+    /// Reads the list from the given file, and its pending entries from the file next
+    /// to it. A missing file is an empty list, the file is created on the first change.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AccessError> {
+        let path = path.as_ref();
+        let mut entries = read_entries(path)?;
+        entries.extend(read_entries(&pending_path(path))?);
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            entries,
+        })
+    }
+
+    /// Writes the list to the file it was loaded from, and the pending entries to the
+    /// file next to it. Lists that weren't loaded from a file aren't saved.
+    pub fn save(&self) -> Result<(), AccessError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let (pending, complete) = self
+            .entries
+            .iter()
+            .partition::<Vec<_>, _>(|entry| entry.is_pending());
+        write_entries(path, &complete)?;
+
+        let pending_path = pending_path(path);
+        if !pending.is_empty() {
+            write_entries(&pending_path, &pending)
+        } else if pending_path.exists() {
+            fs::remove_file(&pending_path).map_err(|e| AccessError::Io(pending_path, e))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Reads the file again, to pick up changes that were made to it by hand.
+    pub fn reload(&mut self) -> Result<(), AccessError> {
+        if let Some(path) = &self.path {
+            *self = Self::load(path.clone())?;
+        }
+        Ok(())
+    }
+}
+
+/// The file with the pending entries of a list, e.g. `whitelist.pending.json`.
+pub fn pending_path(path: &Path) -> PathBuf {
+    path.with_extension("pending.json")
+}
+
+fn read_entries<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, AccessError> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let content = fs::read_to_string(path).map_err(|e| AccessError::Io(path.to_path_buf(), e))?;
+    serde_json::from_str(&content).map_err(|e| AccessError::Json(path.to_path_buf(), e))
+}
+
+fn write_entries<T: Serialize>(path: &Path, entries: &[&T]) -> Result<(), AccessError> {
+    let content = serde_json::to_string_pretty(entries)
+        .map_err(|e| AccessError::Json(path.to_path_buf(), e))?;
+    write_replacing(path, content).map_err(|e| AccessError::Io(path.to_path_buf(), e))
+}
+
+/// Seconds since the unix epoch.
+pub fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Formats seconds since the unix epoch like vanilla does, e.g.
+/// `2023-10-01 12:00:00 +0000`.
+pub fn format_date(time: i64) -> String {
+    let (days, seconds) = (time.div_euclid(86400), time.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} +0000",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Parses a date in the format of [`format_date`], with any time zone offset.
+pub fn parse_date(date: &str) -> Option<i64> {
+    let mut parts = date.split_whitespace();
+    let mut day = parts.next()?.split('-').map(str::parse::<i64>);
+    let (year, month, day) = (day.next()?.ok()?, day.next()?.ok()?, day.next()?.ok()?);
+    let mut time = parts.next()?.split(':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    let zone = parts.next().unwrap_or("+0000");
+    if parts.next().is_some()
+        || !(0..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..=60).contains(&second)
+    {
+        return None;
+    }
+
+    // hand-edited files may contain anything, only ASCII digits can be sliced safely
+    let (sign, zone) = match (zone.strip_prefix('+'), zone.strip_prefix('-')) {
+        (Some(zone), _) => (1, zone),
+        (_, Some(zone)) => (-1, zone),
+        _ => return None,
+    };
+    if zone.len() != 4 || !zone.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let offset =
+        sign * (zone[..2].parse::<i64>().ok()? * 3600 + zone[2..].parse::<i64>().ok()? * 60);
+
+    Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second - offset)
+}
+
+/// Parses a duration like `30m`, `12h`, `7d` or `1w2d`, in seconds.
+pub fn parse_duration(duration: &str) -> Option<i64> {
+    let mut total = 0i64;
+    let mut number = String::new();
+    for c in duration.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            'w' => 7 * 86400,
+            _ => return None,
+        };
+        total = total.checked_add(number.parse::<i64>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    (number.is_empty() && total > 0).then_some(total)
+}
+
+/// Days since the unix epoch of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// The date of a number of days since the unix epoch.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dates() {
+        assert_eq!("1970-01-01 00:00:00 +0000", format_date(0));
+        assert_eq!("2023-10-01 12:30:05 +0000", format_date(1696163405));
+        assert_eq!(Some(1696163405), parse_date("2023-10-01 12:30:05 +0000"));
+        assert_eq!(Some(1696163405), parse_date("2023-10-01 14:30:05 +0200"));
+        assert_eq!(Some(951782400), parse_date("2000-02-29 00:00:00 +0000"));
+        assert_eq!(None, parse_date(FOREVER));
+        assert_eq!(None, parse_date("2023-13-01 00:00:00 +0000"));
+        assert_eq!(None, parse_date("2023-10-01 12:30:05 +ä00"));
+        assert_eq!(None, parse_date("2023-10-01 12:30:05 ä"));
+        assert_eq!(None, parse_date("2023-10-01 12:30:05 +0a00"));
+        assert_eq!(None, parse_date("99999999999999-10-01 12:30:05 +0000"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(Some(30 * 60), parse_duration("30m"));
+        assert_eq!(Some(8 * 86400 + 3600), parse_duration("1w1d1h"));
+        assert_eq!(None, parse_duration("5"));
+        assert_eq!(None, parse_duration("0d"));
+        assert_eq!(None, parse_duration("1y"));
+    }
+
+    #[test]
+    fn test_pending_entries() {
+        let dir = std::env::temp_dir().join(format!(
+            "justmine-test-pending-entries-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("whitelist.json");
+
+        let mut list = JsonList::<WhitelistEntry>::load(&path).unwrap();
+        list.entries.push(WhitelistEntry {
+            uuid: "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string(),
+            name: "Notch".to_string(),
+        });
+        list.entries.push(WhitelistEntry {
+            uuid: String::new(),
+            name: "jeb_".to_string(),
+        });
+        list.save().unwrap();
+
+        // vanilla only gets the entries with a UUID
+        let vanilla: Vec<WhitelistEntry> =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(vec![list.entries[0].clone()], vanilla);
+        assert_eq!(list, JsonList::load(&path).unwrap());
+
+        // the pending file goes away once all entries are complete
+        list.entries[1].uuid = "853c80ef-3c37-49fd-aa49-938b674adae6".to_string();
+        list.save().unwrap();
+        assert!(!pending_path(&path).exists());
+        assert_eq!(list, JsonList::load(&path).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_vanilla_format() {
+        let json = r#"[
+            {
+                "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+                "name": "Notch",
+                "created": "2023-10-01 12:30:05 +0000",
+                "source": "Server",
+                "expires": "forever",
+                "reason": "Banned by an operator."
+            }
+        ]"#;
+        let bans: Vec<PlayerBan> = serde_json::from_str(json).unwrap();
+        assert_eq!("Notch", bans[0].name);
+        assert_eq!(None, bans[0].ban.expires_at());
+        assert!(!bans[0].ban.is_expired(i64::MAX));
+
+        let json = r#"[{"ip": "10.0.0.1", "created": "2023-10-01 12:30:05 +0000",
+            "source": "Notch", "expires": "2023-10-02 12:30:05 +0000", "reason": "spam"}]"#;
+        let bans: Vec<IpBan> = serde_json::from_str(json).unwrap();
+        assert!(!bans[0].ban.is_expired(1696163405));
+        assert!(bans[0].ban.is_expired(1696163405 + 86400));
+    }
+}
@@ -0,0 +1,320 @@
+use crate::ServerConfig;
+use bevy_ecs::prelude::*;
+use log::error;
+use std::borrow::Cow;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use valence::prelude::*;
+use valence::protocol::packets::play::DisconnectS2c;
+use valence::protocol::WritePacket;
+
+mod commands;
+mod lists;
+
+pub use commands::*;
+pub use lists::*;
+
+/// The whitelist and the ban lists of the server.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessLists {
+    /// Only players on the whitelist may join.
+    pub whitelist_enabled: bool,
+    pub whitelist: JsonList<WhitelistEntry>,
+    pub banned_players: JsonList<PlayerBan>,
+    pub banned_ips: JsonList<IpBan>,
+}
+
+impl AccessLists {
+    /// Loads the lists from the files in the config.
+    pub fn load(config: &ServerConfig) -> Result<Self, AccessError> {
+        Ok(Self {
+            whitelist_enabled: config.whitelist,
+            whitelist: JsonList::load(&config.whitelist_file)?,
+            banned_players: JsonList::load(&config.banned_players_file)?,
+            banned_ips: JsonList::load(&config.banned_ips_file)?,
+        })
+    }
+
+    /// Checks whether a player may join, and returns the kick reason if not.
+    ///
+    /// Removes bans that expired, and fills in the UUID of whitelist entries that
+    /// were added by name.
+    pub fn check_login(
+        &mut self,
+        uuid: &str,
+        name: &str,
+        ip: IpAddr,
+        now: i64,
+    ) -> Result<(), String> {
+        self.remove_expired(now);
+
+        if let Some(entry) = self
+            .banned_players
+            .entries
+            .iter_mut()
+            .find(|entry| matches(&entry.uuid, &entry.name, uuid, name))
+        {
+            let reason = format!("You are banned from this server.\n{}", entry.ban.describe());
+            // bans by name follow the player from now on, even after a rename
+            if entry.uuid.is_empty() {
+                entry.uuid = uuid.to_string();
+                entry.name = name.to_string();
+                save(self.banned_players.save());
+            }
+            return Err(reason);
+        }
+        if let Some(ban) = self.ip_ban(ip) {
+            return Err(format!(
+                "Your IP address is banned from this server.\n{}",
+                ban.describe()
+            ));
+        }
+        if !self.whitelist_enabled {
+            return Ok(());
+        }
+
+        let Some(entry) = self
+            .whitelist
+            .entries
+            .iter_mut()
+            .find(|entry| matches(&entry.uuid, &entry.name, uuid, name))
+        else {
+            return Err("You are not white-listed on this server!".to_string());
+        };
+        if entry.uuid.is_empty() {
+            entry.uuid = uuid.to_string();
+            entry.name = name.to_string();
+            save(self.whitelist.save());
+        }
+        Ok(())
+    }
+
+    pub fn player_ban(&self, uuid: &str, name: &str) -> Option<&Ban> {
+        self.banned_players
+            .entries
+            .iter()
+            .find(|entry| matches(&entry.uuid, &entry.name, uuid, name))
+            .map(|entry| &entry.ban)
+    }
+
+    pub fn ip_ban(&self, ip: IpAddr) -> Option<&Ban> {
+        self.banned_ips
+            .entries
+            .iter()
+            .find(|entry| entry.ip == ip)
+            .map(|entry| &entry.ban)
+    }
+
+    /// Adds a player to the whitelist. Returns `false` if the player is already on it.
+    pub fn add_to_whitelist(&mut self, uuid: &str, name: &str) -> Result<bool, AccessError> {
+        if self
+            .whitelist
+            .entries
+            .iter()
+            .any(|entry| matches(&entry.uuid, &entry.name, uuid, name))
+        {
+            return Ok(false);
+        }
+        self.whitelist.entries.push(WhitelistEntry {
+            uuid: uuid.to_string(),
+            name: name.to_string(),
+        });
+        self.whitelist.save().map(|_| true)
+    }
+
+    /// Removes a player from the whitelist by name. Returns `false` if the player
+    /// wasn't on it.
+    pub fn remove_from_whitelist(&mut self, name: &str) -> Result<bool, AccessError> {
+        let len = self.whitelist.entries.len();
+        self.whitelist
+            .entries
+            .retain(|entry| !entry.name.eq_ignore_ascii_case(name));
+        if self.whitelist.entries.len() == len {
+            return Ok(false);
+        }
+        self.whitelist.save().map(|_| true)
+    }
+
+    /// Bans a player, replacing an earlier ban of the player.
+    pub fn ban_player(&mut self, uuid: &str, name: &str, ban: Ban) -> Result<(), AccessError> {
+        self.banned_players
+            .entries
+            .retain(|entry| !matches(&entry.uuid, &entry.name, uuid, name));
+        self.banned_players.entries.push(PlayerBan {
+            uuid: uuid.to_string(),
+            name: name.to_string(),
+            ban,
+        });
+        self.banned_players.save()
+    }
+
+    /// Lifts the ban of a player by name. Returns `false` if the player wasn't banned.
+    pub fn pardon_player(&mut self, name: &str) -> Result<bool, AccessError> {
+        let len = self.banned_players.entries.len();
+        self.banned_players
+            .entries
+            .retain(|entry| !entry.name.eq_ignore_ascii_case(name));
+        if self.banned_players.entries.len() == len {
+            return Ok(false);
+        }
+        self.banned_players.save().map(|_| true)
+    }
+
+    /// Bans an IP address, replacing an earlier ban of it.
+    pub fn ban_ip(&mut self, ip: IpAddr, ban: Ban) -> Result<(), AccessError> {
+        self.banned_ips.entries.retain(|entry| entry.ip != ip);
+        self.banned_ips.entries.push(IpBan { ip, ban });
+        self.banned_ips.save()
+    }
+
+    /// Lifts the ban of an IP address. Returns `false` if it wasn't banned.
+    pub fn pardon_ip(&mut self, ip: IpAddr) -> Result<bool, AccessError> {
+        let len = self.banned_ips.entries.len();
+        self.banned_ips.entries.retain(|entry| entry.ip != ip);
+        if self.banned_ips.entries.len() == len {
+            return Ok(false);
+        }
+        self.banned_ips.save().map(|_| true)
+    }
+
+    /// Reads all lists from their files again.
+    pub fn reload(&mut self) -> Result<(), AccessError> {
+        self.whitelist.reload()?;
+        self.banned_players.reload()?;
+        self.banned_ips.reload()
+    }
+
+    fn remove_expired(&mut self, now: i64) {
+        let players = self.banned_players.entries.len();
+        self.banned_players
+            .entries
+            .retain(|entry| !entry.ban.is_expired(now));
+        if self.banned_players.entries.len() != players {
+            save(self.banned_players.save());
+        }
+
+        let ips = self.banned_ips.entries.len();
+        self.banned_ips
+            .entries
+            .retain(|entry| !entry.ban.is_expired(now));
+        if self.banned_ips.entries.len() != ips {
+            save(self.banned_ips.save());
+        }
+    }
+}
+
+/// Entries with a UUID only match that UUID, entries that were added by name match
+/// the name.
+fn matches(entry_uuid: &str, entry_name: &str, uuid: &str, name: &str) -> bool {
+    if entry_uuid.is_empty() {
+        entry_name.eq_ignore_ascii_case(name)
+    } else {
+        entry_uuid.eq_ignore_ascii_case(uuid)
+    }
+}
+
+/// Logs an error of saving a list that nobody waits for.
+fn save(result: Result<(), AccessError>) {
+    if let Err(e) = result {
+        error!("{}", e);
+    }
+}
+
+/// The access lists, shared between the login of new clients, which doesn't run
+/// in the ECS, and the commands that change them.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct AccessControl(Arc<Mutex<AccessLists>>);
+
+impl AccessControl {
+    pub fn new(lists: AccessLists) -> Self {
+        Self(Arc::new(Mutex::new(lists)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<AccessLists> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Disconnects a client with the given reason.
+pub fn kick(commands: &mut Commands, entity: Entity, client: &mut Client, reason: &str) {
+    client.write_packet(&DisconnectS2c {
+        reason: Cow::Owned(Text::from(reason.to_string())),
+    });
+    commands.entity(entity).insert(Despawned);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const UUID: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+    const IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+    #[test]
+    fn test_bans() {
+        let mut lists = AccessLists::default();
+        assert_eq!(Ok(()), lists.check_login(UUID, "Notch", IP, 0));
+
+        lists
+            .ban_player(UUID, "Notch", Ban::new("Server", None, Some("griefing")))
+            .unwrap();
+        let reason = lists.check_login(UUID, "Notch", IP, 0).unwrap_err();
+        assert!(reason.contains("griefing"));
+        // the ban follows the UUID, not the name
+        assert!(lists.check_login(UUID, "Renamed", IP, 0).is_err());
+        assert!(lists.pardon_player("notch").unwrap());
+        assert_eq!(Ok(()), lists.check_login(UUID, "Notch", IP, 0));
+
+        lists.ban_ip(IP, Ban::new("Server", None, None)).unwrap();
+        assert!(lists.check_login(UUID, "Notch", IP, 0).is_err());
+        assert!(lists.pardon_ip(IP).unwrap());
+        assert!(!lists.pardon_ip(IP).unwrap());
+    }
+
+    #[test]
+    fn test_ban_by_name() {
+        let mut lists = AccessLists::default();
+        lists
+            .ban_player("", "notch", Ban::new("Server", None, None))
+            .unwrap();
+        assert!(lists.check_login(UUID, "Notch", IP, 0).is_err());
+        assert_eq!(UUID, lists.banned_players.entries[0].uuid);
+        assert!(lists.check_login(UUID, "Renamed", IP, 0).is_err());
+    }
+
+    #[test]
+    fn test_temporary_ban_expires() {
+        let mut lists = AccessLists::default();
+        let ban = Ban::new("Server", Some(60), None);
+        let expires = ban.expires_at().unwrap();
+        lists.ban_player("", "Notch", ban).unwrap();
+
+        let reason = lists
+            .check_login(UUID, "Notch", IP, expires - 1)
+            .unwrap_err();
+        assert!(reason.contains("will be removed"));
+        assert_eq!(Ok(()), lists.check_login(UUID, "Notch", IP, expires));
+        assert!(lists.banned_players.entries.is_empty());
+    }
+
+    #[test]
+    fn test_whitelist() {
+        let mut lists = AccessLists {
+            whitelist_enabled: true,
+            ..Default::default()
+        };
+        assert!(lists.check_login(UUID, "Notch", IP, 0).is_err());
+
+        assert!(lists.add_to_whitelist("", "notch").unwrap());
+        assert!(!lists.add_to_whitelist("", "Notch").unwrap());
+        assert_eq!(Ok(()), lists.check_login(UUID, "Notch", IP, 0));
+        // the entry learned the UUID of the player
+        assert_eq!(UUID, lists.whitelist.entries[0].uuid);
+        assert!(lists.check_login(UUID, "Renamed", IP, 0).is_ok());
+        assert!(lists.check_login("", "notch", IP, 0).is_err());
+
+        assert!(lists.remove_from_whitelist("notch").unwrap());
+        assert!(lists.check_login(UUID, "Notch", IP, 0).is_err());
+    }
+}
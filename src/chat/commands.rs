@@ -0,0 +1,199 @@
+use crate::chat::{check_message, ChatFilters, ChatRateLimit, Mutes};
+use crate::{
+    argument, parse_duration, ArgumentKind, Ban, CommandEvent, CommandFeedback, CommandSource,
+    CommandSpec, ServerConfig, SERVER_SOURCE,
+};
+use bevy_ecs::prelude::*;
+use log::{error, info};
+use valence::message::SendMessage;
+use valence::prelude::*;
+use valence::UniqueId;
+
+const PLAYERS: ArgumentKind = ArgumentKind::Players { single: false };
+
+/// `/msg <targets> <message>`.
+pub fn msg_command() -> CommandSpec {
+    CommandSpec::new("msg", "justmine.command.msg").usage([
+        argument("targets", PLAYERS),
+        argument("message", ArgumentKind::Greedy),
+    ])
+}
+
+/// `/tell <targets> <message>`, the same as `/msg`.
+pub fn tell_command() -> CommandSpec {
+    CommandSpec::new("tell", "justmine.command.msg").usage([
+        argument("targets", PLAYERS),
+        argument("message", ArgumentKind::Greedy),
+    ])
+}
+
+/// `/mute <name> [reason]`, which also works for players that are offline.
+pub fn mute_command() -> CommandSpec {
+    CommandSpec::new("mute", "justmine.command.mute")
+        .usage([argument("name", ArgumentKind::Word)])
+        .usage([
+            argument("name", ArgumentKind::Word),
+            argument("reason", ArgumentKind::Greedy),
+        ])
+}
+
+/// `/tempmute <name> <duration> [reason]`, with durations like `30m` or `1d12h`.
+pub fn tempmute_command() -> CommandSpec {
+    CommandSpec::new("tempmute", "justmine.command.mute")
+        .usage([
+            argument("name", ArgumentKind::Word),
+            argument("duration", ArgumentKind::Word),
+        ])
+        .usage([
+            argument("name", ArgumentKind::Word),
+            argument("duration", ArgumentKind::Word),
+            argument("reason", ArgumentKind::Greedy),
+        ])
+}
+
+/// `/unmute <name>`, which also works for players that are offline.
+pub fn unmute_command() -> CommandSpec {
+    CommandSpec::new("unmute", "justmine.command.mute")
+        .usage([argument("name", ArgumentKind::Word)])
+}
+
+/// Handles `/msg` and `/tell`. Private messages pass the same checks as the chat.
+pub fn handle_msg_command(
+    mut events: EventReader<CommandEvent>,
+    mut senders: Query<(&Username, &UniqueId, &mut ChatRateLimit)>,
+    mut clients: Query<&mut Client>,
+    config: Res<ServerConfig>,
+    filters: Res<ChatFilters>,
+    mut mutes: ResMut<Mutes>,
+    server: Res<Server>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| matches!(event.name, "msg" | "tell"))
+        .for_each(|event| {
+            let message = event.args.string("message").unwrap_or_default();
+            let (sender, message) = match event.source {
+                CommandSource::Player(player) => {
+                    let Ok((username, uuid, mut rate_limit)) = senders.get_mut(player) else {
+                        return;
+                    };
+                    let checked = check_message(
+                        uuid,
+                        &username.0,
+                        &mut rate_limit,
+                        message,
+                        &config,
+                        &mut mutes,
+                        &filters,
+                        server.current_tick(),
+                    );
+                    match checked {
+                        Ok(message) => (username.0.clone(), message),
+                        Err(reason) => {
+                            feedback.send(CommandFeedback::error(event.source, reason));
+                            return;
+                        }
+                    }
+                }
+                CommandSource::Console | CommandSource::Rcon(_) => {
+                    (SERVER_SOURCE.to_string(), message.to_string())
+                }
+            };
+
+            for target in event.args.players("targets").unwrap_or_default() {
+                let Ok((target_name, ..)) = senders.get(*target) else {
+                    continue;
+                };
+                let target_name = target_name.0.clone();
+                info!("[msg] {} -> {}: {}", sender, target_name, message);
+                if let Ok(mut client) = clients.get_mut(*target) {
+                    client.send_chat_message(
+                        format!("{} whispers to you: {}", sender, message)
+                            .color(Color::GRAY)
+                            .italic(),
+                    );
+                }
+                feedback.send(CommandFeedback::info(
+                    event.source,
+                    format!("You whisper to {}: {}", target_name, message),
+                ));
+            }
+        });
+}
+
+/// Handles `/mute`, `/tempmute` and `/unmute`.
+pub fn handle_mute_commands(
+    mut events: EventReader<CommandEvent>,
+    mut clients: Query<(&mut Client, &Username, &UniqueId)>,
+    mut mutes: ResMut<Mutes>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| matches!(event.name, "mute" | "tempmute" | "unmute"))
+        .for_each(|event| {
+            let source = match event.source {
+                CommandSource::Player(player) => clients
+                    .get(player)
+                    .map_or(SERVER_SOURCE.to_string(), |(_, username, _)| {
+                        username.0.clone()
+                    }),
+                CommandSource::Console | CommandSource::Rcon(_) => SERVER_SOURCE.to_string(),
+            };
+
+            let name = event.args.string("name").unwrap_or_default();
+            let result = if event.name == "unmute" {
+                mutes.unmute(name).map(|unmuted| {
+                    if unmuted {
+                        format!("Unmuted {}", name)
+                    } else {
+                        format!("{} is not muted", name)
+                    }
+                })
+            } else {
+                let duration = match event.args.string("duration") {
+                    None => None,
+                    Some(duration) => match parse_duration(duration) {
+                        Some(duration) => Some(duration),
+                        None => {
+                            feedback.send(CommandFeedback::error(
+                                event.source,
+                                format!("Invalid duration: {}, use e.g. 30m, 12h or 7d", duration),
+                            ));
+                            return;
+                        }
+                    },
+                };
+                // the UUID and exact name of an online player with the name
+                let online = clients
+                    .iter_mut()
+                    .find(|(_, username, _)| username.0.eq_ignore_ascii_case(name));
+                let (mut client, uuid, name) = match online {
+                    Some((client, username, uuid)) => {
+                        (Some(client), Some(*uuid), username.0.clone())
+                    }
+                    None => (None, None, name.to_string()),
+                };
+                let ban = Ban::new(&source, duration, event.args.string("reason"));
+                let notice = format!("You were muted.\n{}", ban.describe());
+                mutes.mute(uuid.as_ref(), &name, ban).map(|_| {
+                    if let Some(client) = client.as_mut() {
+                        client.send_chat_message(notice.color(Color::RED));
+                    }
+                    format!("Muted {}", name)
+                })
+            };
+
+            match result {
+                Ok(message) => feedback.send(CommandFeedback::info(event.source, message)),
+                Err(e) => {
+                    error!("{}", e);
+                    feedback.send(CommandFeedback::error(
+                        event.source,
+                        "The mute list could not be accessed, see the log",
+                    ));
+                }
+            }
+        });
+}
@@ -0,0 +1,197 @@
+use crate::ChatConfig;
+use bevy_ecs::prelude::*;
+
+/// What a [`ChatFilter`] does with a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterResult {
+    /// The message passes unchanged.
+    Allow,
+    /// The message passes with the given text instead.
+    Replace(String),
+    /// The message isn't sent, the sender is told the reason.
+    Block(String),
+}
+
+/// Checks chat messages and private messages before they are sent.
+pub trait ChatFilter: Send + Sync {
+    fn filter(&self, message: &str) -> FilterResult;
+}
+
+/// The filters that every message passes through, in the order of their
+/// registration. A filter sees the message as the previous filters left it.
+#[derive(Resource, Default)]
+pub struct ChatFilters {
+    filters: Vec<Box<dyn ChatFilter>>,
+}
+
+impl ChatFilters {
+    /// The built-in filters that are enabled in the config.
+    pub fn from_config(config: &ChatConfig) -> Self {
+        let mut filters = Self::default();
+        if !config.blocked_words.is_empty() {
+            filters.register(WordFilter::new(&config.blocked_words));
+        }
+        if config.block_links {
+            filters.register(LinkFilter);
+        }
+        filters
+    }
+
+    pub fn register(&mut self, filter: impl ChatFilter + 'static) {
+        self.filters.push(Box::new(filter));
+    }
+
+    /// Runs the message through all filters, and returns what is left of it, or the
+    /// reason why it was blocked.
+    pub fn apply(&self, message: &str) -> Result<String, String> {
+        let mut message = message.to_string();
+        for filter in &self.filters {
+            match filter.filter(&message) {
+                FilterResult::Allow => {}
+                FilterResult::Replace(replaced) => message = replaced,
+                FilterResult::Block(reason) => return Err(reason),
+            }
+        }
+        Ok(message)
+    }
+}
+
+/// Replaces blocked words with asterisks, regardless of their case. Only whole
+/// words are replaced, so blocking "ass" leaves "class" alone.
+pub struct WordFilter {
+    words: Vec<String>,
+}
+
+impl WordFilter {
+    pub fn new(words: &[String]) -> Self {
+        Self {
+            words: words.iter().map(|word| word.to_lowercase()).collect(),
+        }
+    }
+}
+
+impl ChatFilter for WordFilter {
+    fn filter(&self, message: &str) -> FilterResult {
+        let mut filtered = String::with_capacity(message.len());
+        let mut changed = false;
+        let mut rest = message;
+        while !rest.is_empty() {
+            let end = rest
+                .find(|c: char| !c.is_alphanumeric())
+                .map_or(
+                    rest.len(),
+                    |i| if i == 0 { first_char_len(rest) } else { i },
+                );
+            let (part, tail) = rest.split_at(end);
+            if self.words.contains(&part.to_lowercase()) {
+                filtered.extend(std::iter::repeat('*').take(part.chars().count()));
+                changed = true;
+            } else {
+                filtered.push_str(part);
+            }
+            rest = tail;
+        }
+
+        if changed {
+            FilterResult::Replace(filtered)
+        } else {
+            FilterResult::Allow
+        }
+    }
+}
+
+/// The length in bytes of the first character.
+fn first_char_len(s: &str) -> usize {
+    s.chars().next().map_or(0, char::len_utf8)
+}
+
+/// Blocks messages that contain links.
+pub struct LinkFilter;
+
+/// Endings of domains that are treated as links even without `http://` or `www.`.
+const LINK_DOMAINS: [&str; 8] = [".com", ".net", ".org", ".io", ".gg", ".de", ".co", ".me"];
+
+impl ChatFilter for LinkFilter {
+    fn filter(&self, message: &str) -> FilterResult {
+        let is_link = |word: &str| {
+            let word = word
+                .to_lowercase()
+                .trim_end_matches(|c: char| !c.is_alphanumeric() && c != '/')
+                .to_string();
+            word.starts_with("http://")
+                || word.starts_with("https://")
+                || word.starts_with("www.")
+                || LINK_DOMAINS.iter().any(|domain| {
+                    word.split('/')
+                        .next()
+                        .is_some_and(|host| host.len() > domain.len() && host.ends_with(domain))
+                })
+        };
+
+        if message.split_whitespace().any(is_link) {
+            FilterResult::Block("Links are not allowed in the chat".to_string())
+        } else {
+            FilterResult::Allow
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_filter() {
+        let filter = WordFilter::new(&["darn".to_string(), "heck".to_string()]);
+        assert_eq!(
+            FilterResult::Replace("oh **** it, what the ****!".to_string()),
+            filter.filter("oh DARN it, what the heck!")
+        );
+        assert_eq!(FilterResult::Allow, filter.filter("darnit, heckle"));
+        assert_eq!(FilterResult::Allow, filter.filter("ünïcode ✓ is fine"));
+    }
+
+    #[test]
+    fn test_link_filter() {
+        for message in [
+            "join https://example.net now",
+            "see www.example",
+            "play.example.com/vote",
+            "discord.gg/abc",
+        ] {
+            assert!(
+                matches!(LinkFilter.filter(message), FilterResult::Block(_)),
+                "{} is not blocked",
+                message
+            );
+        }
+        for message in ["hello.", "that's a .com domain", "1.5 hearts"] {
+            assert_eq!(
+                FilterResult::Allow,
+                LinkFilter.filter(message),
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let mut filters = ChatFilters::from_config(&ChatConfig {
+            blocked_words: vec!["darn".to_string()],
+            block_links: true,
+            ..Default::default()
+        });
+        assert_eq!(Ok("**** it".to_string()), filters.apply("darn it"));
+        assert!(filters.apply("darn https://example.com").is_err());
+
+        struct Shout;
+        impl ChatFilter for Shout {
+            fn filter(&self, message: &str) -> FilterResult {
+                FilterResult::Replace(message.to_uppercase())
+            }
+        }
+        filters.register(Shout);
+        assert_eq!(Ok("**** IT".to_string()), filters.apply("darn it"));
+    }
+}
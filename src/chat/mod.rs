@@ -0,0 +1,343 @@
+use crate::{unix_time, AccessError, Ban, JsonList, ListEntry, PermissionStore, ServerConfig};
+use bevy_ecs::prelude::*;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::Path;
+use valence::message::{ChatMessageEvent, SendMessage};
+use valence::prelude::*;
+use valence::UniqueId;
+
+mod commands;
+mod filters;
+
+pub use commands::*;
+pub use filters::*;
+
+/// An entry of the mute list, in the format of the ban lists.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mute {
+    pub uuid: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub ban: Ban,
+}
+
+// the mute list isn't read by vanilla, so entries without a UUID are fine
+impl ListEntry for Mute {}
+
+/// The players that may not chat or send private messages.
+#[derive(Resource, Debug, Clone, Default, PartialEq)]
+pub struct Mutes {
+    pub list: JsonList<Mute>,
+}
+
+impl Mutes {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AccessError> {
+        Ok(Self {
+            list: JsonList::load(path)?,
+        })
+    }
+
+    /// The mute of a player, if it hasn't expired yet. Expired mutes are removed.
+    ///
+    /// Mutes of players that were offline only have a name, they get the UUID of the
+    /// player with the name the next time they are looked up.
+    pub fn get(&mut self, uuid: &UniqueId, name: &str, now: i64) -> Option<&Ban> {
+        let uuid = uuid.0.to_string();
+        let len = self.list.entries.len();
+        self.list.entries.retain(|entry| !entry.ban.is_expired(now));
+        let mut changed = self.list.entries.len() != len;
+
+        for entry in &mut self.list.entries {
+            if entry.uuid.is_empty() && entry.name.eq_ignore_ascii_case(name) {
+                entry.uuid = uuid.clone();
+                entry.name = name.to_string();
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(e) = self.list.save() {
+                error!("{}", e);
+            }
+        }
+        self.list
+            .entries
+            .iter()
+            .find(|entry| entry.uuid == uuid)
+            .map(|entry| &entry.ban)
+    }
+
+    /// Mutes a player, replacing an earlier mute of the player. Players that are
+    /// offline are muted by name, since their UUID isn't known.
+    pub fn mute(
+        &mut self,
+        uuid: Option<&UniqueId>,
+        name: &str,
+        ban: Ban,
+    ) -> Result<(), AccessError> {
+        let uuid = uuid.map_or(String::new(), |uuid| uuid.0.to_string());
+        self.list.entries.retain(|entry| {
+            !entry.name.eq_ignore_ascii_case(name) && (uuid.is_empty() || entry.uuid != uuid)
+        });
+        self.list.entries.push(Mute {
+            uuid,
+            name: name.to_string(),
+            ban,
+        });
+        self.list.save()
+    }
+
+    /// Lifts the mute of a player by name. Returns `false` if the player wasn't muted.
+    pub fn unmute(&mut self, name: &str) -> Result<bool, AccessError> {
+        let len = self.list.entries.len();
+        self.list
+            .entries
+            .retain(|entry| !entry.name.eq_ignore_ascii_case(name));
+        if self.list.entries.len() == len {
+            return Ok(false);
+        }
+        self.list.save().map(|_| true)
+    }
+}
+
+/// The ticks in which a player recently sent messages.
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChatRateLimit {
+    sent: VecDeque<i64>,
+}
+
+impl ChatRateLimit {
+    /// Records a message at tick `now`, unless the player already sent `limit`
+    /// messages within the last `interval` ticks. A limit of `0` allows everything.
+    pub fn allow(&mut self, now: i64, limit: u32, interval: i64) -> bool {
+        if limit == 0 {
+            return true;
+        }
+        while self.sent.front().is_some_and(|tick| now - tick >= interval) {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= limit as usize {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+/// Fills in `{prefix}`, `{player}` and `{message}` of a chat format.
+pub fn format_chat(format: &str, prefix: &str, player: &str, message: &str) -> String {
+    // the message goes last, so that players can't write placeholders themselves
+    format
+        .replace("{prefix}", prefix)
+        .replace("{player}", player)
+        .replace("{message}", message)
+}
+
+/// Checks whether a player may send a message right now, and runs it through the
+/// filters. Returns what is left of the message, or why it can't be sent.
+pub fn check_message(
+    uuid: &UniqueId,
+    name: &str,
+    rate_limit: &mut ChatRateLimit,
+    message: &str,
+    config: &ServerConfig,
+    mutes: &mut Mutes,
+    filters: &ChatFilters,
+    now: i64,
+) -> Result<String, String> {
+    if let Some(mute) = mutes.get(uuid, name, unix_time()) {
+        return Err(format!("You are muted.\n{}", mute.describe()));
+    }
+    let interval = config.chat.rate_limit_interval as i64 * 20;
+    if !rate_limit.allow(now, config.chat.rate_limit, interval) {
+        return Err("You are sending messages too fast".to_string());
+    }
+    filters.apply(message)
+}
+
+/// Broadcasts the chat messages of players in the configured format.
+pub fn handle_chat(
+    mut events: EventReader<ChatMessageEvent>,
+    mut senders: Query<(&Username, &UniqueId, &mut ChatRateLimit)>,
+    mut clients: Query<&mut Client>,
+    config: Res<ServerConfig>,
+    permissions: Res<PermissionStore>,
+    filters: Res<ChatFilters>,
+    mut mutes: ResMut<Mutes>,
+    server: Res<Server>,
+) {
+    events.iter().for_each(|event| {
+        let Ok((username, uuid, mut rate_limit)) = senders.get_mut(event.client) else {
+            return;
+        };
+
+        let checked = check_message(
+            uuid,
+            &username.0,
+            &mut rate_limit,
+            &event.message,
+            &config,
+            &mut mutes,
+            &filters,
+            server.current_tick(),
+        );
+        let message = match checked {
+            Ok(message) => message,
+            Err(reason) => {
+                info!(
+                    "[chat] blocked <{}> {}: {}",
+                    username.0, event.message, reason
+                );
+                if let Ok(mut client) = clients.get_mut(event.client) {
+                    client.send_chat_message(reason.color(Color::RED));
+                }
+                return;
+            }
+        };
+
+        let line = format_chat(
+            &config.chat.format,
+            permissions.prefix(uuid),
+            &username.0,
+            &message,
+        );
+        info!("[chat] {}", line);
+        clients.for_each_mut(|mut client| {
+            client.send_chat_message(line.as_str());
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::testing::ScenarioSingleClient;
+
+    #[test]
+    fn test_format_chat() {
+        assert_eq!(
+            "[Admin] <Notch> hi {player}",
+            format_chat(
+                "{prefix}<{player}> {message}",
+                "[Admin] ",
+                "Notch",
+                "hi {player}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut rate_limit = ChatRateLimit::default();
+        assert!(rate_limit.allow(0, 2, 100));
+        assert!(rate_limit.allow(10, 2, 100));
+        assert!(!rate_limit.allow(20, 2, 100));
+        // the first message is out of the interval
+        assert!(rate_limit.allow(100, 2, 100));
+        assert!(!rate_limit.allow(101, 2, 100));
+        assert!(ChatRateLimit::default().allow(0, 0, 100));
+    }
+
+    #[test]
+    fn test_mutes() {
+        let uuid = UniqueId::default();
+        let mut mutes = Mutes::default();
+        mutes
+            .mute(
+                Some(&uuid),
+                "Notch",
+                Ban::new("Server", Some(60), Some("spam")),
+            )
+            .unwrap();
+        let expires = mutes.list.entries[0].ban.expires_at().unwrap();
+        assert!(mutes.get(&uuid, "Notch", expires - 1).is_some());
+        assert!(mutes.get(&uuid, "Notch", expires).is_none());
+        assert!(mutes.list.entries.is_empty());
+
+        mutes
+            .mute(Some(&uuid), "Notch", Ban::new("Server", None, None))
+            .unwrap();
+        assert!(mutes.unmute("notch").unwrap());
+        assert!(!mutes.unmute("notch").unwrap());
+    }
+
+    #[test]
+    fn test_mute_offline() {
+        let uuid = UniqueId::default();
+        let mut mutes = Mutes::default();
+        mutes
+            .mute(None, "notch", Ban::new("Server", None, None))
+            .unwrap();
+        assert!(mutes.get(&uuid, "jeb_", 0).is_none());
+        assert!(mutes.get(&uuid, "Notch", 0).is_some());
+        assert_eq!(uuid.0.to_string(), mutes.list.entries[0].uuid);
+        assert_eq!("Notch", mutes.list.entries[0].name);
+
+        // muting again replaces the mute
+        mutes
+            .mute(Some(&uuid), "Notch", Ban::new("Server", None, None))
+            .unwrap();
+        assert_eq!(1, mutes.list.entries.len());
+    }
+
+    #[test]
+    fn test_check_message() {
+        let uuid = UniqueId::default();
+        let mut config = ServerConfig::default();
+        config.chat.rate_limit = 1;
+        config.chat.blocked_words = vec!["darn".to_string()];
+        let filters = ChatFilters::from_config(&config.chat);
+        let mut mutes = Mutes::default();
+        let mut rate_limit = ChatRateLimit::default();
+
+        let mut check = |mutes: &mut Mutes, message: &str, now: i64| {
+            check_message(
+                &uuid,
+                "Notch",
+                &mut rate_limit,
+                message,
+                &config,
+                mutes,
+                &filters,
+                now,
+            )
+        };
+        assert_eq!(Ok("**** it".to_string()), check(&mut mutes, "darn it", 0));
+        assert!(check(&mut mutes, "again", 1).is_err());
+
+        mutes
+            .mute(Some(&uuid), "Notch", Ban::new("Server", None, None))
+            .unwrap();
+        let reason = check(&mut mutes, "hello", 1000).unwrap_err();
+        assert!(reason.starts_with("You are muted"));
+    }
+
+    #[test]
+    fn test_handle_chat() {
+        let mut scenario = ScenarioSingleClient::new();
+        scenario
+            .app
+            .insert_resource(ServerConfig::default())
+            .init_resource::<PermissionStore>()
+            .init_resource::<ChatFilters>()
+            .init_resource::<Mutes>()
+            .add_systems(Update, handle_chat);
+        let client = scenario.client;
+        scenario
+            .app
+            .world
+            .entity_mut(client)
+            .insert(ChatRateLimit::default());
+
+        scenario.app.world.send_event(ChatMessageEvent {
+            client,
+            message: "hello".into(),
+            timestamp: 0,
+        });
+        scenario.app.update();
+
+        let rate_limit = scenario.app.world.get::<ChatRateLimit>(client).unwrap();
+        assert_eq!(1, rate_limit.sent.len());
+    }
+}
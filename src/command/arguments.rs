@@ -0,0 +1,441 @@
+use bevy_ecs::entity::Entity;
+use std::fmt::{Display, Formatter};
+use valence::prelude::*;
+
+/// The kinds of arguments that commands can declare.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ArgumentKind {
+    /// A whole number in the given range.
+    Integer {
+        min: i32,
+        max: i32,
+    },
+    /// A single word.
+    Word,
+    /// Everything up to the end of the command.
+    Greedy,
+    /// Block coordinates, each may be relative to the source with `~`.
+    BlockPos,
+    /// Exact coordinates, each may be relative to the source with `~`.
+    Position,
+    /// A player name or a selector like `@a`.
+    Players {
+        single: bool,
+    },
+    /// A block with optional properties, like `oak_stairs[facing=north]`.
+    BlockState,
+    Item,
+    /// One of the given words.
+    Choice(&'static [&'static str]),
+}
+
+impl ArgumentKind {
+    /// How many words of the command the argument takes, `None` for all remaining.
+    pub(crate) fn words(&self) -> Option<usize> {
+        match self {
+            ArgumentKind::Greedy => None,
+            ArgumentKind::BlockPos | ArgumentKind::Position => Some(3),
+            _ => Some(1),
+        }
+    }
+
+    /// What the argument looks like in a usage message.
+    pub(crate) fn placeholder(&self, name: &str) -> String {
+        match self {
+            ArgumentKind::Choice(choices) => choices.join("|"),
+            _ => format!("<{}>", name),
+        }
+    }
+}
+
+/// A parsed argument.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentValue {
+    Integer(i32),
+    String(String),
+    BlockPos(BlockPos),
+    Position(DVec3),
+    Players(Vec<Entity>),
+    BlockState(BlockState),
+    Item(ItemKind),
+    Choice(&'static str),
+}
+
+/// A player that selectors can pick.
+#[derive(Debug, Clone)]
+pub struct SelectablePlayer {
+    pub entity: Entity,
+    pub name: String,
+    pub position: DVec3,
+    /// Whether the player is in the world of the source. Positions in other worlds
+    /// can't be compared, so `@p` only picks players there if there are none here.
+    pub same_world: bool,
+}
+
+/// Everything that relative coordinates and selectors are resolved against.
+#[derive(Debug, Clone, Default)]
+pub struct ParseContext {
+    /// The position that `~` is relative to, and that `@p` is closest to.
+    pub origin: DVec3,
+    /// The player that runs the command, if it's a player.
+    pub sender: Option<Entity>,
+    /// The players of all worlds, like in vanilla names and `@a` aren't limited to
+    /// the world of the source.
+    pub players: Vec<SelectablePlayer>,
+    /// Picks the player for `@r`.
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    UnknownCommand(String),
+    NoPermission,
+    /// The command ended where another argument was expected.
+    Incomplete,
+    TooManyArguments(String),
+    Expected {
+        expected: String,
+        found: String,
+    },
+    Invalid(String),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::UnknownCommand(name) => write!(f, "Unknown command: {}", name),
+            CommandError::NoPermission => {
+                write!(f, "You don't have permission to use this command")
+            }
+            CommandError::Incomplete => write!(f, "The command is incomplete"),
+            CommandError::TooManyArguments(rest) => {
+                write!(f, "Unexpected arguments at the end: {}", rest)
+            }
+            CommandError::Expected { expected, found } => {
+                write!(f, "Expected {}, but found \"{}\"", expected, found)
+            }
+            CommandError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Parses the words of a single argument. Takes as many words as
+/// [`ArgumentKind::words`] says.
+pub fn parse_argument(
+    kind: &ArgumentKind,
+    words: &[&str],
+    ctx: &ParseContext,
+) -> Result<ArgumentValue, CommandError> {
+    let word = words[0];
+    match *kind {
+        ArgumentKind::Integer { min, max } => {
+            let value = word
+                .parse::<i32>()
+                .map_err(|_| expected("an integer", word))?;
+            if !(min..=max).contains(&value) {
+                return Err(CommandError::Invalid(format!(
+                    "The number must be between {} and {}, but is {}",
+                    min, max, value
+                )));
+            }
+            Ok(ArgumentValue::Integer(value))
+        }
+        ArgumentKind::Word => Ok(ArgumentValue::String(word.to_string())),
+        ArgumentKind::Greedy => Ok(ArgumentValue::String(words.join(" "))),
+        ArgumentKind::BlockPos => {
+            let position = parse_coordinates(words, ctx.origin, true)?;
+            Ok(ArgumentValue::BlockPos(BlockPos::new(
+                position.x.floor() as i32,
+                position.y.floor() as i32,
+                position.z.floor() as i32,
+            )))
+        }
+        ArgumentKind::Position => Ok(ArgumentValue::Position(parse_coordinates(
+            words, ctx.origin, false,
+        )?)),
+        ArgumentKind::Players { single } => {
+            let players = select_players(word, ctx)?;
+            if single && players.len() > 1 {
+                return Err(CommandError::Invalid(
+                    "Only one player is allowed, but the selector allows more than one".to_string(),
+                ));
+            }
+            Ok(ArgumentValue::Players(players))
+        }
+        ArgumentKind::BlockState => parse_block_state(word).map(ArgumentValue::BlockState),
+        ArgumentKind::Item => ItemKind::from_str(strip_namespace(word))
+            .map(ArgumentValue::Item)
+            .ok_or_else(|| CommandError::Invalid(format!("Unknown item: {}", word))),
+        ArgumentKind::Choice(choices) => choices
+            .iter()
+            .copied()
+            .find(|choice| *choice == word)
+            .map(ArgumentValue::Choice)
+            .ok_or_else(|| expected(&format!("one of {}", choices.join(", ")), word)),
+    }
+}
+
+fn expected(expected: &str, found: &str) -> CommandError {
+    CommandError::Expected {
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+/// Parses three coordinates like `1 ~ ~-2`. Block coordinates are relative to the
+/// block that the origin is in.
+fn parse_coordinates(words: &[&str], origin: DVec3, block: bool) -> Result<DVec3, CommandError> {
+    let origin = if block { origin.floor() } else { origin };
+    let mut coordinates = [0.0; 3];
+    for (i, word) in words.iter().enumerate() {
+        let axis = origin.to_array()[i];
+        coordinates[i] = match word.strip_prefix('~') {
+            Some("") => axis,
+            Some(offset) => {
+                axis + offset
+                    .parse::<f64>()
+                    .map_err(|_| expected("a coordinate", word))?
+            }
+            None if block => {
+                word.parse::<i32>()
+                    .map_err(|_| expected("a block coordinate", word))? as f64
+            }
+            None => {
+                let value = word
+                    .parse::<f64>()
+                    .map_err(|_| expected("a coordinate", word))?;
+                // whole x and z coordinates mean the center of the block, like in vanilla
+                if i != 1 && !word.contains('.') {
+                    value + 0.5
+                } else {
+                    value
+                }
+            }
+        };
+        if !coordinates[i].is_finite() {
+            return Err(expected("a coordinate", word));
+        }
+    }
+    Ok(DVec3::from_array(coordinates))
+}
+
+fn select_players(selector: &str, ctx: &ParseContext) -> Result<Vec<Entity>, CommandError> {
+    let players = match selector {
+        "@a" | "@e" => ctx.players.iter().map(|player| player.entity).collect(),
+        "@s" => {
+            let sender = ctx.sender.ok_or_else(|| {
+                CommandError::Invalid("@s can only be used by players".to_string())
+            })?;
+            vec![sender]
+        }
+        "@p" => ctx
+            .players
+            .iter()
+            .min_by(|a, b| {
+                let distance = |player: &SelectablePlayer| {
+                    if player.same_world {
+                        player.position.distance_squared(ctx.origin)
+                    } else {
+                        f64::INFINITY
+                    }
+                };
+                distance(a).total_cmp(&distance(b))
+            })
+            .map(|player| player.entity)
+            .into_iter()
+            .collect(),
+        "@r" => {
+            if ctx.players.is_empty() {
+                vec![]
+            } else {
+                let i = (ctx.seed % ctx.players.len() as u64) as usize;
+                vec![ctx.players[i].entity]
+            }
+        }
+        _ if selector.starts_with('@') => {
+            return Err(CommandError::Invalid(format!(
+                "Unknown selector: {}",
+                selector
+            )))
+        }
+        name => ctx
+            .players
+            .iter()
+            .filter(|player| player.name.eq_ignore_ascii_case(name))
+            .map(|player| player.entity)
+            .collect(),
+    };
+    if players.is_empty() {
+        return Err(CommandError::Invalid("No player was found".to_string()));
+    }
+    Ok(players)
+}
+
+/// Parses a block like `stone` or `minecraft:oak_stairs[facing=north,half=top]`.
+pub fn parse_block_state(input: &str) -> Result<BlockState, CommandError> {
+    let (name, properties) = match input.split_once('[') {
+        Some((name, rest)) => {
+            let properties = rest.strip_suffix(']').ok_or_else(|| {
+                CommandError::Invalid(format!("Missing ] at the end of {}", input))
+            })?;
+            (name, Some(properties))
+        }
+        None => (input, None),
+    };
+    let kind = BlockKind::from_str(strip_namespace(name))
+        .ok_or_else(|| CommandError::Invalid(format!("Unknown block: {}", name)))?;
+
+    let mut state = BlockState::from_kind(kind);
+    for property in properties
+        .into_iter()
+        .flat_map(|properties| properties.split(','))
+        .filter(|property| !property.is_empty())
+    {
+        let (name, value) = property.split_once('=').ok_or_else(|| {
+            CommandError::Invalid(format!("Expected name=value, but found {}", property))
+        })?;
+        let prop = PropName::from_str(name)
+            .filter(|prop| kind.props().contains(prop))
+            .ok_or_else(|| {
+                CommandError::Invalid(format!("{} has no property {}", kind.to_str(), name))
+            })?;
+        let value = PropValue::from_str(value)
+            .filter(|&value| state.set(prop, value).get(prop) == Some(value))
+            .ok_or_else(|| {
+                CommandError::Invalid(format!("Invalid value for {}: {}", name, value))
+            })?;
+        state = state.set(prop, value);
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> ParseContext {
+        let mut world = World::new();
+        let players = ["alice", "bob"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| SelectablePlayer {
+                entity: world.spawn_empty().id(),
+                name: name.to_string(),
+                position: DVec3::new(i as f64 * 10.0, 64.0, 0.0),
+                same_world: true,
+            })
+            .collect::<Vec<_>>();
+        ParseContext {
+            origin: DVec3::new(8.5, 64.0, -0.5),
+            sender: Some(players[0].entity),
+            players,
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_integer() {
+        let ctx = context();
+        let kind = ArgumentKind::Integer { min: 1, max: 64 };
+        assert_eq!(
+            Ok(ArgumentValue::Integer(5)),
+            parse_argument(&kind, &["5"], &ctx)
+        );
+        assert!(matches!(
+            parse_argument(&kind, &["five"], &ctx),
+            Err(CommandError::Expected { .. })
+        ));
+        assert!(matches!(
+            parse_argument(&kind, &["65"], &ctx),
+            Err(CommandError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_coordinates() {
+        let ctx = context();
+        assert_eq!(
+            Ok(ArgumentValue::BlockPos(BlockPos::new(10, 65, -1))),
+            parse_argument(&ArgumentKind::BlockPos, &["10", "~1", "~"], &ctx)
+        );
+        assert_eq!(
+            Ok(ArgumentValue::Position(DVec3::new(1.5, 64.0, 0.25))),
+            parse_argument(&ArgumentKind::Position, &["1", "~", "0.25"], &ctx)
+        );
+        assert!(parse_argument(&ArgumentKind::BlockPos, &["1.5", "0", "0"], &ctx).is_err());
+    }
+
+    #[test]
+    fn test_select_players() {
+        let ctx = context();
+        let (alice, bob) = (ctx.players[0].entity, ctx.players[1].entity);
+        let single = ArgumentKind::Players { single: true };
+        let many = ArgumentKind::Players { single: false };
+
+        assert_eq!(
+            Ok(ArgumentValue::Players(vec![alice, bob])),
+            parse_argument(&many, &["@a"], &ctx)
+        );
+        assert_eq!(
+            Ok(ArgumentValue::Players(vec![bob])),
+            parse_argument(&single, &["@p"], &ctx)
+        );
+        assert_eq!(
+            Ok(ArgumentValue::Players(vec![alice])),
+            parse_argument(&single, &["@s"], &ctx)
+        );
+        assert_eq!(
+            Ok(ArgumentValue::Players(vec![bob])),
+            parse_argument(&single, &["Bob"], &ctx)
+        );
+        assert!(parse_argument(&single, &["@a"], &ctx).is_err());
+        assert!(parse_argument(&single, &["carol"], &ctx).is_err());
+    }
+
+    #[test]
+    fn test_select_players_in_other_worlds() {
+        let mut ctx = context();
+        let (alice, bob) = (ctx.players[0].entity, ctx.players[1].entity);
+        let single = ArgumentKind::Players { single: true };
+        let many = ArgumentKind::Players { single: false };
+
+        // bob is closer, but in another world
+        ctx.players[1].same_world = false;
+        assert_eq!(
+            Ok(ArgumentValue::Players(vec![alice])),
+            parse_argument(&single, &["@p"], &ctx)
+        );
+        assert_eq!(
+            Ok(ArgumentValue::Players(vec![alice, bob])),
+            parse_argument(&many, &["@a"], &ctx)
+        );
+        assert_eq!(
+            Ok(ArgumentValue::Players(vec![bob])),
+            parse_argument(&single, &["bob"], &ctx)
+        );
+
+        ctx.players[0].same_world = false;
+        assert!(parse_argument(&single, &["@p"], &ctx).is_ok());
+    }
+
+    #[test]
+    fn test_parse_block_state() {
+        assert_eq!(Ok(BlockState::STONE), parse_block_state("minecraft:stone"));
+        assert_eq!(
+            Ok(BlockState::OAK_STAIRS
+                .set(PropName::Facing, PropValue::South)
+                .set(PropName::Half, PropValue::Top)),
+            parse_block_state("oak_stairs[facing=south,half=top]")
+        );
+        assert!(parse_block_state("unobtainium").is_err());
+        assert!(parse_block_state("stone[facing=south]").is_err());
+        assert!(parse_block_state("oak_stairs[facing=up]").is_err());
+        assert!(parse_block_state("oak_stairs[facing=south").is_err());
+    }
+}
@@ -0,0 +1,555 @@
+use crate::command::{argument, ArgumentKind, CommandEvent, CommandFeedback, CommandSpec};
+use crate::command::{source_world, CommandSource};
+use crate::{
+    ban_command, ban_ip_command, banlist_command, deop_command, insert_into_inventory, msg_command,
+    mute_command, op_command, pardon_command, pardon_ip_command, role_command, save_all_command,
+    spawn_item, tell_command, tempban_command, tempmute_command, time_command, unmute_command,
+    weather_command, whitelist_command,
+};
+use crate::{world_command, BlockChangeEvent, DamageCause, DamageEvent, SpawnPoint, WorldRegistry};
+use bevy_app::AppExit;
+use bevy_ecs::prelude::*;
+use valence::entity::EntityLayerId;
+use valence::prelude::*;
+
+/// The most blocks that `/fill` changes at once.
+pub const FILL_LIMIT: i64 = 32768;
+/// The most items that `/give` hands out at once.
+const GIVE_LIMIT: i32 = 6400;
+
+const PLAYERS: ArgumentKind = ArgumentKind::Players { single: false };
+const PLAYER: ArgumentKind = ArgumentKind::Players { single: true };
+const GAME_MODES: ArgumentKind =
+    ArgumentKind::Choice(&["survival", "creative", "adventure", "spectator"]);
+
+/// Sent as feedback when the console runs a command that needs a player, without
+/// naming one.
+const NEEDS_PLAYER: &str = "A player must be given when the console runs this command";
+
+/// All commands that the server comes with.
+pub fn builtin_commands() -> Vec<CommandSpec> {
+    vec![
+        CommandSpec::new("gamemode", "justmine.command.gamemode")
+            .usage([argument("mode", GAME_MODES)])
+            .usage([argument("mode", GAME_MODES), argument("targets", PLAYERS)]),
+        CommandSpec::new("tp", "justmine.command.tp")
+            .usage([argument("destination", PLAYER)])
+            .usage([
+                argument("targets", PLAYERS),
+                argument("destination", PLAYER),
+            ])
+            .usage([argument("location", ArgumentKind::Position)])
+            .usage([
+                argument("targets", PLAYERS),
+                argument("location", ArgumentKind::Position),
+            ]),
+        CommandSpec::new("give", "justmine.command.give")
+            .usage([
+                argument("targets", PLAYERS),
+                argument("item", ArgumentKind::Item),
+            ])
+            .usage([
+                argument("targets", PLAYERS),
+                argument("item", ArgumentKind::Item),
+                argument(
+                    "count",
+                    ArgumentKind::Integer {
+                        min: 1,
+                        max: GIVE_LIMIT,
+                    },
+                ),
+            ]),
+        CommandSpec::new("setblock", "justmine.command.setblock").usage([
+            argument("pos", ArgumentKind::BlockPos),
+            argument("block", ArgumentKind::BlockState),
+        ]),
+        CommandSpec::new("fill", "justmine.command.fill").usage([
+            argument("from", ArgumentKind::BlockPos),
+            argument("to", ArgumentKind::BlockPos),
+            argument("block", ArgumentKind::BlockState),
+        ]),
+        CommandSpec::new("kill", "justmine.command.kill")
+            .usage([])
+            .usage([argument("targets", PLAYERS)]),
+        CommandSpec::new("spawnpoint", "justmine.command.spawnpoint")
+            .usage([])
+            .usage([argument("targets", PLAYERS)])
+            .usage([
+                argument("targets", PLAYERS),
+                argument("pos", ArgumentKind::BlockPos),
+            ]),
+        CommandSpec::new("stop", "justmine.command.stop").usage([]),
+        time_command(),
+        weather_command(),
+        save_all_command(),
+        world_command(),
+        op_command(),
+        deop_command(),
+        role_command(),
+        whitelist_command(),
+        ban_command(),
+        tempban_command(),
+        pardon_command(),
+        ban_ip_command(),
+        pardon_ip_command(),
+        banlist_command(),
+        msg_command(),
+        tell_command(),
+        mute_command(),
+        tempmute_command(),
+        unmute_command(),
+    ]
+}
+
+fn game_mode_name(game_mode: GameMode) -> &'static str {
+    match game_mode {
+        GameMode::Survival => "Survival Mode",
+        GameMode::Creative => "Creative Mode",
+        GameMode::Adventure => "Adventure Mode",
+        GameMode::Spectator => "Spectator Mode",
+    }
+}
+
+/// Handles `/gamemode <mode> [targets]`.
+pub fn handle_gamemode_command(
+    mut clients: Query<(&Username, &mut GameMode)>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "gamemode")
+        .for_each(|event| {
+            let Some(targets) = event.targets("targets") else {
+                feedback.send(CommandFeedback::error(event.source, NEEDS_PLAYER));
+                return;
+            };
+            let game_mode = match event.args.choice("mode") {
+                Some("creative") => GameMode::Creative,
+                Some("adventure") => GameMode::Adventure,
+                Some("spectator") => GameMode::Spectator,
+                _ => GameMode::Survival,
+            };
+
+            for target in targets {
+                let Ok((username, mut current)) = clients.get_mut(target) else {
+                    continue;
+                };
+                *current = game_mode;
+                let message = if event.source == CommandSource::Player(target) {
+                    format!("Set own game mode to {}", game_mode_name(game_mode))
+                } else {
+                    format!(
+                        "Set {}'s game mode to {}",
+                        username.0,
+                        game_mode_name(game_mode)
+                    )
+                };
+                feedback.send(CommandFeedback::info(event.source, message));
+            }
+        });
+}
+
+/// Handles `/tp`, which teleports players to another player or to a location in
+/// their world.
+pub fn handle_tp_command(
+    mut clients: Query<(&Username, &mut Position, &EntityLayerId)>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "tp")
+        .for_each(|event| {
+            let Some(targets) = event.targets("targets") else {
+                feedback.send(CommandFeedback::error(event.source, NEEDS_PLAYER));
+                return;
+            };
+            let (location, destination, world) = match event.args.players("destination") {
+                Some(&[destination]) => {
+                    let Ok((username, position, layer)) = clients.get(destination) else {
+                        return;
+                    };
+                    (position.0, username.0.to_string(), Some(layer.0))
+                }
+                _ => {
+                    let Some(location) = event.args.position("location") else {
+                        return;
+                    };
+                    let name = format!("{:.2}, {:.2}, {:.2}", location.x, location.y, location.z);
+                    (location, name, None)
+                }
+            };
+
+            for target in targets {
+                let Ok((username, mut position, layer)) = clients.get_mut(target) else {
+                    continue;
+                };
+                // players can be picked from all worlds, but only teleport within one
+                if world.is_some_and(|world| world != layer.0) {
+                    feedback.send(CommandFeedback::error(
+                        event.source,
+                        format!("{} is in another world than {}", username.0, destination),
+                    ));
+                    continue;
+                }
+                position.set(location);
+                feedback.send(CommandFeedback::info(
+                    event.source,
+                    format!("Teleported {} to {}", username.0, destination),
+                ));
+            }
+        });
+}
+
+/// Handles `/give <targets> <item> [count]`. Items that don't fit into the
+/// inventory are dropped at the player.
+pub fn handle_give_command(
+    mut commands: Commands,
+    mut clients: Query<(&Username, &mut Inventory, &Position, &EntityLayerId)>,
+    server: Res<Server>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "give")
+        .for_each(|event| {
+            let (Some(targets), Some(item)) =
+                (event.args.players("targets"), event.args.item("item"))
+            else {
+                return;
+            };
+            let count = event.args.integer("count").unwrap_or(1);
+            let max = item.max_stack() as i32;
+
+            for &target in targets {
+                let Ok((username, mut inventory, position, layer)) = clients.get_mut(target) else {
+                    continue;
+                };
+                let mut remaining = count;
+                while remaining > 0 {
+                    let amount = remaining.min(max) as i8;
+                    remaining -= amount as i32;
+                    let left =
+                        insert_into_inventory(&mut inventory, ItemStack::new(item, amount, None));
+                    if left > 0 {
+                        spawn_item(
+                            &mut commands,
+                            layer.0,
+                            position.0,
+                            ItemStack::new(item, left, None),
+                            server.current_tick(),
+                        );
+                    }
+                }
+                feedback.send(CommandFeedback::info(
+                    event.source,
+                    format!("Gave {} [{}] to {}", count, item.to_str(), username.0),
+                ));
+            }
+        });
+}
+
+/// Handles `/setblock <pos> <block>` and `/fill <from> <to> <block>`, in the world
+/// of the source.
+pub fn handle_block_commands(
+    players: Query<&EntityLayerId>,
+    registry: Option<Res<WorldRegistry>>,
+    mut layers: Query<&mut ChunkLayer>,
+    mut events: EventReader<CommandEvent>,
+    mut changes: EventWriter<BlockChangeEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "setblock" || event.name == "fill")
+        .for_each(|event| {
+            let Some(block) = event.args.block_state("block") else {
+                return;
+            };
+            let (from, to) = match event.name {
+                "setblock" => match event.args.block_pos("pos") {
+                    Some(pos) => (pos, pos),
+                    None => return,
+                },
+                _ => match (event.args.block_pos("from"), event.args.block_pos("to")) {
+                    (Some(from), Some(to)) => (from, to),
+                    _ => return,
+                },
+            };
+            let min = BlockPos::new(from.x.min(to.x), from.y.min(to.y), from.z.min(to.z));
+            let max = BlockPos::new(from.x.max(to.x), from.y.max(to.y), from.z.max(to.z));
+            // the coordinates may span the whole i32 range, which overflows i32 and the
+            // volume overflows i64
+            let volume = (max.x as i128 - min.x as i128 + 1)
+                * (max.y as i128 - min.y as i128 + 1)
+                * (max.z as i128 - min.z as i128 + 1);
+            if volume > FILL_LIMIT as i128 {
+                feedback.send(CommandFeedback::error(
+                    event.source,
+                    format!(
+                        "Too many blocks in the specified area (maximum {}, specified {})",
+                        FILL_LIMIT, volume
+                    ),
+                ));
+                return;
+            }
+
+            let Some(world) = source_world(event.source, &players, registry.as_deref()) else {
+                return;
+            };
+            let Ok(mut layer) = layers.get_mut(world) else {
+                return;
+            };
+
+            let mut changed = 0;
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    for z in min.z..=max.z {
+                        let position = BlockPos::new(x, y, z);
+                        // unloaded blocks and blocks that already match are left alone
+                        if layer
+                            .block(position)
+                            .map_or(true, |current| current.state == block)
+                        {
+                            continue;
+                        }
+                        layer.set_block(position, block);
+                        changes.send(BlockChangeEvent {
+                            layer: world,
+                            position,
+                        });
+                        changed += 1;
+                    }
+                }
+            }
+
+            let message = match (event.name, changed) {
+                ("setblock", 0) => {
+                    feedback.send(CommandFeedback::error(
+                        event.source,
+                        "Could not set the block",
+                    ));
+                    return;
+                }
+                ("setblock", _) => {
+                    format!("Changed the block at {}, {}, {}", from.x, from.y, from.z)
+                }
+                (_, 0) => {
+                    feedback.send(CommandFeedback::error(
+                        event.source,
+                        "No blocks were filled",
+                    ));
+                    return;
+                }
+                _ => format!("Successfully filled {} block(s)", changed),
+            };
+            feedback.send(CommandFeedback::info(event.source, message));
+        });
+}
+
+/// Handles `/kill [targets]`, which kills players even in creative mode.
+pub fn handle_kill_command(
+    names: Query<&Username>,
+    mut events: EventReader<CommandEvent>,
+    mut damage: EventWriter<DamageEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "kill")
+        .for_each(|event| {
+            let Some(targets) = event.targets("targets") else {
+                feedback.send(CommandFeedback::error(event.source, NEEDS_PLAYER));
+                return;
+            };
+            for target in targets {
+                let Ok(username) = names.get(target) else {
+                    continue;
+                };
+                damage.send(DamageEvent {
+                    client: target,
+                    amount: f32::MAX,
+                    cause: DamageCause::Kill,
+                });
+                feedback.send(CommandFeedback::info(
+                    event.source,
+                    format!("Killed {}", username.0),
+                ));
+            }
+        });
+}
+
+/// Handles `/spawnpoint [targets] [pos]`, which makes players respawn at the
+/// position in the world of the source, whether or not there is a bed.
+pub fn handle_spawnpoint_command(
+    mut commands: Commands,
+    players: Query<(&Username, &Position, &EntityLayerId)>,
+    layers: Query<&EntityLayerId>,
+    registry: Option<Res<WorldRegistry>>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "spawnpoint")
+        .for_each(|event| {
+            let Some(targets) = event.targets("targets") else {
+                feedback.send(CommandFeedback::error(event.source, NEEDS_PLAYER));
+                return;
+            };
+            for target in targets {
+                let Ok((username, position, layer)) = players.get(target) else {
+                    continue;
+                };
+                let (world, position) = match event.args.block_pos("pos") {
+                    Some(pos) => match source_world(event.source, &layers, registry.as_deref()) {
+                        Some(world) => (world, pos),
+                        None => continue,
+                    },
+                    None => (
+                        layer.0,
+                        BlockPos::new(
+                            position.0.x.floor() as i32,
+                            position.0.y.floor() as i32,
+                            position.0.z.floor() as i32,
+                        ),
+                    ),
+                };
+                commands.entity(target).insert(SpawnPoint {
+                    world,
+                    position,
+                    forced: true,
+                });
+                feedback.send(CommandFeedback::info(
+                    event.source,
+                    format!(
+                        "Set spawn point to {}, {}, {} for {}",
+                        position.x, position.y, position.z, username.0
+                    ),
+                ));
+            }
+        });
+}
+
+/// Handles `/stop`, which saves all worlds and shuts the server down.
+pub fn handle_stop_command(
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if let Some(event) = events.iter().find(|event| event.name == "stop") {
+        feedback.send(CommandFeedback::info(event.source, "Stopping the server"));
+        exit.send(AppExit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch_commands;
+    use crate::testing::enable_commands;
+    use valence::message::CommandExecutionEvent;
+    use valence::testing::ScenarioSingleClient;
+
+    fn scenario() -> ScenarioSingleClient {
+        let mut scenario = ScenarioSingleClient::new();
+        enable_commands(&mut scenario.app, scenario.client);
+        scenario.app.add_event::<BlockChangeEvent>().add_systems(
+            Update,
+            (
+                dispatch_commands,
+                (
+                    handle_gamemode_command,
+                    handle_give_command,
+                    handle_block_commands,
+                ),
+            )
+                .chain(),
+        );
+        scenario
+            .app
+            .world
+            .get_mut::<ChunkLayer>(scenario.layer)
+            .unwrap()
+            .insert_chunk([0, 0], UnloadedChunk::new());
+        scenario.app.update();
+        scenario
+    }
+
+    fn run_command(scenario: &mut ScenarioSingleClient, command: &str) {
+        let client = scenario.client;
+        scenario.app.world.send_event(CommandExecutionEvent {
+            command: command.into(),
+            client,
+        });
+        scenario.app.update();
+    }
+
+    fn block(scenario: &ScenarioSingleClient, x: i32, y: i32, z: i32) -> BlockState {
+        let layer = scenario
+            .app
+            .world
+            .get::<ChunkLayer>(scenario.layer)
+            .unwrap();
+        layer.block(BlockPos::new(x, y, z)).unwrap().state
+    }
+
+    #[test]
+    fn test_gamemode() {
+        let mut scenario = scenario();
+        run_command(&mut scenario, "gamemode creative");
+        let game_mode = scenario.app.world.get::<GameMode>(scenario.client).unwrap();
+        assert_eq!(GameMode::Creative, *game_mode);
+
+        run_command(&mut scenario, "gamemode hardcore");
+        let game_mode = scenario.app.world.get::<GameMode>(scenario.client).unwrap();
+        assert_eq!(GameMode::Creative, *game_mode);
+    }
+
+    #[test]
+    fn test_give() {
+        let mut scenario = scenario();
+        run_command(&mut scenario, "give @s minecraft:stone 100");
+        let inventory = scenario
+            .app
+            .world
+            .get::<Inventory>(scenario.client)
+            .unwrap();
+        assert_eq!(
+            ItemStack::new(ItemKind::Stone, 64, None),
+            *inventory.slot(36)
+        );
+        assert_eq!(
+            ItemStack::new(ItemKind::Stone, 36, None),
+            *inventory.slot(37)
+        );
+    }
+
+    #[test]
+    fn test_setblock_and_fill() {
+        let mut scenario = scenario();
+        run_command(&mut scenario, "setblock 1 2 3 oak_stairs[facing=east]");
+        assert_eq!(
+            BlockState::OAK_STAIRS.set(PropName::Facing, PropValue::East),
+            block(&scenario, 1, 2, 3)
+        );
+
+        run_command(&mut scenario, "fill 0 0 0 2 1 2 stone");
+        assert_eq!(BlockState::STONE, block(&scenario, 0, 0, 0));
+        assert_eq!(BlockState::STONE, block(&scenario, 2, 1, 2));
+        assert_eq!(BlockState::AIR, block(&scenario, 2, 2, 2));
+
+        // too many blocks at once
+        run_command(&mut scenario, "fill 0 0 0 15 200 15 dirt");
+        assert_eq!(BlockState::STONE, block(&scenario, 0, 0, 0));
+        run_command(&mut scenario, "fill -2000000000 0 0 2000000000 0 0 dirt");
+        assert_eq!(BlockState::STONE, block(&scenario, 0, 0, 0));
+        run_command(
+            &mut scenario,
+            "fill -2000000000 -2000000000 -2000000000 2000000000 2000000000 2000000000 dirt",
+        );
+        assert_eq!(BlockState::STONE, block(&scenario, 0, 0, 0));
+    }
+}
@@ -0,0 +1,687 @@
+use crate::command::{builtin_commands, parse_argument, ArgumentKind, CommandError, ParseContext};
+use crate::command::{ArgumentValue, Permissions, SelectablePlayer};
+use crate::{WorldInfo, WorldRegistry};
+use bevy_ecs::prelude::*;
+use log::info;
+use std::collections::BTreeMap;
+use valence::entity::EntityLayerId;
+use valence::message::{CommandExecutionEvent, SendMessage};
+use valence::prelude::*;
+use valence::protocol::packets::play::command_tree_s2c::{Node, NodeData, Parser, StringArg};
+use valence::protocol::packets::play::CommandTreeS2c;
+use valence::protocol::{VarInt, WritePacket};
+
+/// Who runs a command.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum CommandSource {
+    Player(Entity),
+    Console,
+    /// A client of the remote console, by the number of its request.
+    Rcon(u32),
+}
+
+/// A word or an argument in the usage of a command.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Token {
+    Literal(&'static str),
+    Argument(&'static str, ArgumentKind),
+}
+
+pub fn literal(name: &'static str) -> Token {
+    Token::Literal(name)
+}
+
+pub fn argument(name: &'static str, kind: ArgumentKind) -> Token {
+    Token::Argument(name, kind)
+}
+
+/// A command and all the ways it can be used.
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    /// The permission node that players need for the command.
+    pub permission: &'static str,
+    pub usages: Vec<Vec<Token>>,
+}
+
+impl CommandSpec {
+    pub fn new(name: &'static str, permission: &'static str) -> Self {
+        Self {
+            name,
+            permission,
+            usages: vec![],
+        }
+    }
+
+    /// Adds a way to use the command. The first usage that matches the input wins.
+    pub fn usage(mut self, tokens: impl Into<Vec<Token>>) -> Self {
+        self.usages.push(tokens.into());
+        self
+    }
+
+    /// Parses the arguments of the command, everything after its name.
+    pub fn parse(&self, words: &[&str], ctx: &ParseContext) -> Result<CommandArgs, CommandError> {
+        let mut furthest: Option<(usize, CommandError)> = None;
+        for usage in &self.usages {
+            match parse_usage(usage, words, ctx) {
+                Ok(args) => return Ok(args),
+                Err((position, error)) => {
+                    if furthest.as_ref().map_or(true, |(p, _)| position > *p) {
+                        furthest = Some((position, error));
+                    }
+                }
+            }
+        }
+        Err(furthest.map_or(CommandError::Incomplete, |(_, error)| error))
+    }
+
+    /// All usages, like `/time set <time>`.
+    pub fn usage_lines(&self) -> Vec<String> {
+        self.usages
+            .iter()
+            .map(|usage| {
+                std::iter::once(format!("/{}", self.name))
+                    .chain(usage.iter().map(|token| match token {
+                        Token::Literal(name) => name.to_string(),
+                        Token::Argument(name, kind) => kind.placeholder(name),
+                    }))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+}
+
+/// Matches the words against a usage. On failure, returns how many words matched
+/// before the error, so the most fitting error can be shown.
+fn parse_usage(
+    usage: &[Token],
+    words: &[&str],
+    ctx: &ParseContext,
+) -> Result<CommandArgs, (usize, CommandError)> {
+    let mut args = CommandArgs::default();
+    let mut i = 0;
+    for token in usage {
+        if i >= words.len() {
+            return Err((i, CommandError::Incomplete));
+        }
+        match token {
+            Token::Literal(name) => {
+                if words[i] != *name {
+                    return Err((
+                        i,
+                        CommandError::Expected {
+                            expected: format!("\"{}\"", name),
+                            found: words[i].to_string(),
+                        },
+                    ));
+                }
+                args.path.push(name);
+                i += 1;
+            }
+            Token::Argument(name, kind) => {
+                let count = kind.words().unwrap_or(words.len() - i);
+                if i + count > words.len() {
+                    return Err((words.len(), CommandError::Incomplete));
+                }
+                let value = parse_argument(kind, &words[i..i + count], ctx).map_err(|e| (i, e))?;
+                args.values.push((name, value));
+                i += count;
+            }
+        }
+    }
+    if i < words.len() {
+        return Err((i, CommandError::TooManyArguments(words[i..].join(" "))));
+    }
+    Ok(args)
+}
+
+/// The parsed arguments of a command.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandArgs {
+    path: Vec<&'static str>,
+    values: Vec<(&'static str, ArgumentValue)>,
+}
+
+impl CommandArgs {
+    /// The literal words of the usage that matched, like `["set"]` for `/time set day`.
+    pub fn path(&self) -> &[&'static str] {
+        &self.path
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ArgumentValue> {
+        self.values
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn integer(&self, name: &str) -> Option<i32> {
+        match self.get(name)? {
+            ArgumentValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn string(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            ArgumentValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn block_pos(&self, name: &str) -> Option<BlockPos> {
+        match self.get(name)? {
+            ArgumentValue::BlockPos(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn position(&self, name: &str) -> Option<DVec3> {
+        match self.get(name)? {
+            ArgumentValue::Position(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn players(&self, name: &str) -> Option<&[Entity]> {
+        match self.get(name)? {
+            ArgumentValue::Players(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn block_state(&self, name: &str) -> Option<BlockState> {
+        match self.get(name)? {
+            ArgumentValue::BlockState(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn item(&self, name: &str) -> Option<ItemKind> {
+        match self.get(name)? {
+            ArgumentValue::Item(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn choice(&self, name: &str) -> Option<&'static str> {
+        match self.get(name)? {
+            ArgumentValue::Choice(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// All commands of the server, by name.
+#[derive(Resource, Debug)]
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, CommandSpec>,
+}
+
+impl Default for CommandRegistry {
+    /// A registry with all built-in commands.
+    fn default() -> Self {
+        let mut registry = Self {
+            commands: BTreeMap::new(),
+        };
+        for spec in builtin_commands() {
+            registry.register(spec);
+        }
+        registry
+    }
+}
+
+impl CommandRegistry {
+    /// Adds a command, or replaces the one with the same name.
+    pub fn register(&mut self, spec: CommandSpec) {
+        self.commands.insert(spec.name, spec);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands.get(name)
+    }
+
+    /// Checks the permission and parses a command line (without the leading `/`).
+    pub fn parse(
+        &self,
+        line: &str,
+        permissions: &Permissions,
+        ctx: &ParseContext,
+    ) -> Result<(&CommandSpec, CommandArgs), CommandError> {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let Some(name) = words.first() else {
+            return Err(CommandError::Incomplete);
+        };
+        let spec = self
+            .get(name)
+            .ok_or_else(|| CommandError::UnknownCommand(name.to_string()))?;
+        if !permissions.has(spec.permission) {
+            return Err(CommandError::NoPermission);
+        }
+        let args = spec.parse(&words[1..], ctx)?;
+        Ok((spec, args))
+    }
+
+    /// The Brigadier command tree with the commands that the given permissions allow,
+    /// which lets clients complete and check commands as they are typed.
+    pub fn tree(&self, permissions: &Permissions) -> CommandTreeS2c {
+        let mut nodes = vec![Node {
+            data: NodeData::Root,
+            executable: false,
+            children: vec![],
+            redirect_node: None,
+        }];
+        for spec in self
+            .commands
+            .values()
+            .filter(|spec| permissions.has(spec.permission))
+        {
+            for usage in &spec.usages {
+                // a choice branches into one literal per word, so a usage can end in several nodes
+                let mut parents = vec![child_node(
+                    &mut nodes,
+                    0,
+                    NodeData::Literal {
+                        name: spec.name.to_string(),
+                    },
+                )];
+                for token in usage {
+                    parents = parents
+                        .into_iter()
+                        .flat_map(|parent| match token {
+                            Token::Literal(name) => vec![child_node(
+                                &mut nodes,
+                                parent,
+                                NodeData::Literal {
+                                    name: name.to_string(),
+                                },
+                            )],
+                            Token::Argument(_, ArgumentKind::Choice(choices)) => choices
+                                .iter()
+                                .map(|choice| {
+                                    child_node(
+                                        &mut nodes,
+                                        parent,
+                                        NodeData::Literal {
+                                            name: choice.to_string(),
+                                        },
+                                    )
+                                })
+                                .collect(),
+                            Token::Argument(name, kind) => vec![child_node(
+                                &mut nodes,
+                                parent,
+                                NodeData::Argument {
+                                    name: name.to_string(),
+                                    parser: parser(kind),
+                                    suggestion: None,
+                                },
+                            )],
+                        })
+                        .collect();
+                }
+                for parent in parents {
+                    nodes[parent].executable = true;
+                }
+            }
+        }
+
+        CommandTreeS2c {
+            commands: nodes,
+            root_index: VarInt(0),
+        }
+    }
+}
+
+/// The child of `parent` with the same kind and name as `data`, which is added if it
+/// doesn't exist yet.
+fn child_node(nodes: &mut Vec<Node>, parent: usize, data: NodeData) -> usize {
+    let existing = nodes[parent]
+        .children
+        .iter()
+        .map(|child| child.0 as usize)
+        .find(|&child| same_node(&nodes[child].data, &data));
+    if let Some(child) = existing {
+        return child;
+    }
+
+    nodes.push(Node {
+        data,
+        executable: false,
+        children: vec![],
+        redirect_node: None,
+    });
+    let child = nodes.len() - 1;
+    nodes[parent].children.push(VarInt(child as i32));
+    child
+}
+
+fn same_node(a: &NodeData, b: &NodeData) -> bool {
+    match (a, b) {
+        (NodeData::Literal { name: a }, NodeData::Literal { name: b }) => a == b,
+        (NodeData::Argument { name: a, .. }, NodeData::Argument { name: b, .. }) => a == b,
+        _ => false,
+    }
+}
+
+fn parser(kind: &ArgumentKind) -> Parser {
+    match *kind {
+        ArgumentKind::Integer { min, max } => Parser::Integer {
+            min: Some(min),
+            max: Some(max),
+        },
+        ArgumentKind::Word | ArgumentKind::Choice(_) => Parser::String(StringArg::SingleWord),
+        ArgumentKind::Greedy => Parser::String(StringArg::GreedyPhrase),
+        ArgumentKind::BlockPos => Parser::BlockPos,
+        ArgumentKind::Position => Parser::Vec3,
+        ArgumentKind::Players { single } => Parser::Entity {
+            single,
+            only_players: true,
+        },
+        ArgumentKind::BlockState => Parser::BlockState,
+        ArgumentKind::Item => Parser::ItemStack,
+    }
+}
+
+/// Runs a command line for a source other than a player, like the console.
+#[derive(Event, Clone, Debug)]
+pub struct ExecuteCommandEvent {
+    pub source: CommandSource,
+    pub command: String,
+}
+
+/// A parsed command that its handler should run. Handlers read these events and
+/// only handle the ones with their name.
+#[derive(Event, Clone, Debug)]
+pub struct CommandEvent {
+    pub source: CommandSource,
+    pub name: &'static str,
+    pub args: CommandArgs,
+}
+
+impl CommandEvent {
+    /// The players in the given argument, or the player that runs the command if the
+    /// argument was left out. `None` for the console and RCON, which aren't players.
+    pub fn targets(&self, name: &str) -> Option<Vec<Entity>> {
+        match (self.args.players(name), self.source) {
+            (Some(players), _) => Some(players.to_vec()),
+            (None, CommandSource::Player(player)) => Some(vec![player]),
+            (None, CommandSource::Console | CommandSource::Rcon(_)) => None,
+        }
+    }
+}
+
+/// A message for the source of a command.
+#[derive(Event, Clone, Debug)]
+pub struct CommandFeedback {
+    pub source: CommandSource,
+    pub message: String,
+    pub error: bool,
+}
+
+impl CommandFeedback {
+    pub fn info(source: CommandSource, message: impl Into<String>) -> Self {
+        Self {
+            source,
+            message: message.into(),
+            error: false,
+        }
+    }
+
+    pub fn error(source: CommandSource, message: impl Into<String>) -> Self {
+        Self {
+            source,
+            message: message.into(),
+            error: true,
+        }
+    }
+}
+
+/// The world that a command runs in: the world of the player, or the default
+/// world for the console and RCON.
+pub fn source_world(
+    source: CommandSource,
+    players: &Query<&EntityLayerId>,
+    registry: Option<&WorldRegistry>,
+) -> Option<Entity> {
+    match source {
+        CommandSource::Player(player) => players.get(player).ok().map(|layer| layer.0),
+        CommandSource::Console | CommandSource::Rcon(_) => {
+            registry.map(WorldRegistry::default_world)
+        }
+    }
+}
+
+/// Parses the commands of players and other sources, and passes them on to their
+/// handlers as [`CommandEvent`]s.
+#[allow(clippy::too_many_arguments)]
+pub fn dispatch_commands(
+    registry: Res<CommandRegistry>,
+    players: Query<(
+        Entity,
+        &Username,
+        &Position,
+        &EntityLayerId,
+        Option<&Permissions>,
+    )>,
+    worlds: Query<&WorldInfo>,
+    worlds_registry: Option<Res<WorldRegistry>>,
+    server: Res<Server>,
+    mut executions: EventReader<CommandExecutionEvent>,
+    mut others: EventReader<ExecuteCommandEvent>,
+    mut commands: EventWriter<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    let lines = executions
+        .iter()
+        .map(|event| {
+            (
+                CommandSource::Player(event.client),
+                event.command.to_string(),
+            )
+        })
+        .chain(
+            others
+                .iter()
+                .map(|event| (event.source, event.command.clone())),
+        )
+        .collect::<Vec<_>>();
+
+    for (source, line) in lines {
+        let (sender, permissions, origin, layer) = match source {
+            CommandSource::Player(player) => {
+                let Ok((_, _, position, layer, permissions)) = players.get(player) else {
+                    continue;
+                };
+                let permissions = permissions.cloned().unwrap_or_default();
+                (Some(player), permissions, position.0, layer.0)
+            }
+            CommandSource::Console | CommandSource::Rcon(_) => {
+                let Some(layer) = worlds_registry.as_ref().map(|r| r.default_world()) else {
+                    continue;
+                };
+                let origin = worlds.get(layer).map_or(DVec3::ZERO, |world| world.spawn);
+                (None, Permissions::all(), origin, layer)
+            }
+        };
+
+        let ctx = ParseContext {
+            origin,
+            sender,
+            players: players
+                .iter()
+                .map(
+                    |(entity, username, position, player_layer, _)| SelectablePlayer {
+                        entity,
+                        name: username.0.clone(),
+                        position: position.0,
+                        same_world: player_layer.0 == layer,
+                    },
+                )
+                .collect(),
+            seed: server.current_tick() as u64,
+        };
+
+        match registry.parse(&line, &permissions, &ctx) {
+            Ok((spec, args)) => commands.send(CommandEvent {
+                source,
+                name: spec.name,
+                args,
+            }),
+            Err(error) => {
+                feedback.send(CommandFeedback::error(source, error.to_string()));
+                let spec = line
+                    .split_whitespace()
+                    .next()
+                    .and_then(|name| registry.get(name))
+                    .filter(|spec| permissions.has(spec.permission));
+                if let Some(spec) = spec {
+                    for usage in spec.usage_lines() {
+                        feedback.send(CommandFeedback::error(source, format!("Usage: {}", usage)));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Sends the command tree to players whenever their permissions change, which
+/// includes when they join.
+pub fn send_command_tree(
+    registry: Res<CommandRegistry>,
+    mut clients: Query<(&mut Client, &Permissions), Changed<Permissions>>,
+) {
+    clients.for_each_mut(|(mut client, permissions)| {
+        client.write_packet(&registry.tree(permissions));
+    });
+}
+
+/// Shows command feedback to players in the chat, and logs the feedback for the
+/// console. Feedback for RCON is sent back by [`reply_rcon`](crate::reply_rcon).
+pub fn deliver_feedback(mut clients: Query<&mut Client>, mut events: EventReader<CommandFeedback>) {
+    events.iter().for_each(|event| match event.source {
+        CommandSource::Player(player) => {
+            let Ok(mut client) = clients.get_mut(player) else {
+                return;
+            };
+            if event.error {
+                client.send_chat_message(event.message.clone().color(Color::RED));
+            } else {
+                client.send_chat_message(event.message.clone());
+            }
+        }
+        CommandSource::Console => info!("{}", event.message),
+        CommandSource::Rcon(_) => {}
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_spec() -> CommandSpec {
+        CommandSpec::new("time", "justmine.command.time")
+            .usage([])
+            .usage([
+                literal("set"),
+                argument("time", ArgumentKind::Choice(&["day", "night"])),
+            ])
+            .usage([
+                literal("set"),
+                argument("time", ArgumentKind::Integer { min: 0, max: 23999 }),
+            ])
+            .usage([
+                literal("add"),
+                argument(
+                    "time",
+                    ArgumentKind::Integer {
+                        min: 0,
+                        max: i32::MAX,
+                    },
+                ),
+            ])
+    }
+
+    #[test]
+    fn test_parse_usages() {
+        let spec = time_spec();
+        let ctx = ParseContext::default();
+
+        let args = spec.parse(&[], &ctx).unwrap();
+        assert!(args.path().is_empty());
+
+        let args = spec.parse(&["set", "night"], &ctx).unwrap();
+        assert_eq!(&["set"], args.path());
+        assert_eq!(Some("night"), args.choice("time"));
+
+        let args = spec.parse(&["set", "500"], &ctx).unwrap();
+        assert_eq!(Some(500), args.integer("time"));
+        assert_eq!(None, args.choice("time"));
+
+        // the error of the usage that got furthest is shown
+        assert!(matches!(
+            spec.parse(&["add", "soon"], &ctx),
+            Err(CommandError::Expected { .. })
+        ));
+        assert_eq!(Err(CommandError::Incomplete), spec.parse(&["add"], &ctx));
+        assert!(matches!(
+            spec.parse(&["add", "5", "6"], &ctx),
+            Err(CommandError::TooManyArguments(_))
+        ));
+    }
+
+    #[test]
+    fn test_registry_checks_permission() {
+        let mut registry = CommandRegistry::default();
+        registry.register(time_spec());
+        let ctx = ParseContext::default();
+
+        assert_eq!(
+            Err(CommandError::NoPermission),
+            registry
+                .parse("time set day", &Permissions::default(), &ctx)
+                .map(|(_, args)| args)
+        );
+        assert!(registry
+            .parse("time set day", &Permissions::all(), &ctx)
+            .is_ok());
+        assert_eq!(
+            Err(CommandError::UnknownCommand("nope".to_string())),
+            registry
+                .parse("nope", &Permissions::all(), &ctx)
+                .map(|(_, args)| args)
+        );
+    }
+
+    #[test]
+    fn test_usage_lines() {
+        assert_eq!(
+            vec![
+                "/time",
+                "/time set day|night",
+                "/time set <time>",
+                "/time add <time>"
+            ],
+            time_spec().usage_lines()
+        );
+    }
+
+    #[test]
+    fn test_command_tree() {
+        let mut registry = CommandRegistry::default();
+        registry.register(time_spec());
+
+        let tree = registry.tree(&Permissions::from_nodes(["justmine.command.time"]));
+        // root, time, set, day, night, <time> after set, add, <time> after add
+        assert_eq!(8, tree.commands.len());
+        let root = &tree.commands[0];
+        assert_eq!(1, root.children.len());
+        let time = &tree.commands[root.children[0].0 as usize];
+        assert!(time.executable);
+        assert_eq!(2, time.children.len());
+
+        let tree = registry.tree(&Permissions::default());
+        assert_eq!(1, tree.commands.len());
+    }
+}
@@ -0,0 +1,9 @@
+mod arguments;
+mod builtin;
+mod dispatch;
+mod permissions;
+
+pub use arguments::*;
+pub use builtin::*;
+pub use dispatch::*;
+pub use permissions::*;
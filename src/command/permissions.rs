@@ -0,0 +1,641 @@
+use crate::command::{argument, literal, ArgumentKind, CommandEvent, CommandFeedback};
+use crate::command::{CommandSource, CommandSpec};
+use crate::{write_replacing, BREAK_PERMISSION, PLACE_PERMISSION, SPAWN_PROTECTION_BYPASS};
+use bevy_ecs::prelude::*;
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+use valence::prelude::*;
+use valence::UniqueId;
+
+/// The file that roles and operators are persisted to if the config doesn't name one.
+pub const DEFAULT_PERMISSIONS_PATH: &str = "permissions.toml";
+
+/// The role that every player has.
+pub const DEFAULT_ROLE: &str = "default";
+
+/// The highest operator level.
+pub const MAX_OP_LEVEL: u8 = 4;
+
+/// The permissions that each operator level adds to the levels below it, like in
+/// vanilla: 1 may build in spawn protection, 2 may cheat, 3 may manage players and
+/// 4 may do everything.
+const LEVEL_PERMISSIONS: [&[&str]; MAX_OP_LEVEL as usize + 1] = [
+    &["justmine.command.msg"],
+    &[SPAWN_PROTECTION_BYPASS],
+    &[
+        "justmine.command.gamemode",
+        "justmine.command.tp",
+        "justmine.command.give",
+        "justmine.command.setblock",
+        "justmine.command.fill",
+        "justmine.command.time",
+        "justmine.command.weather",
+        "justmine.command.kill",
+        "justmine.command.spawnpoint",
+        "justmine.command.world",
+    ],
+    &[
+        "justmine.command.op",
+        "justmine.command.deop",
+        "justmine.command.role",
+        "justmine.command.whitelist",
+        "justmine.command.ban",
+        "justmine.command.pardon",
+        "justmine.command.ban-ip",
+        "justmine.command.pardon-ip",
+        "justmine.command.banlist",
+        "justmine.command.mute",
+    ],
+    &["*"],
+];
+
+/// The permission nodes that a player has, like `justmine.command.tp`. A node that
+/// ends with `*` grants every node that starts with the part before it.
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Permissions {
+    nodes: HashSet<String>,
+}
+
+impl Permissions {
+    /// Permissions that grant everything.
+    pub fn all() -> Self {
+        Self::from_nodes(["*"])
+    }
+
+    pub fn from_nodes<S: Into<String>>(nodes: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            nodes: nodes.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn has(&self, node: &str) -> bool {
+        self.nodes
+            .iter()
+            .any(|granted| match granted.strip_suffix('*') {
+                Some(prefix) => node.starts_with(prefix),
+                None => granted == node,
+            })
+    }
+}
+
+/// The operators and roles of the server, persisted to a TOML file.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PermissionStore {
+    #[serde(skip)]
+    path: Option<PathBuf>,
+    /// Roles by their name. Every player has the [`DEFAULT_ROLE`].
+    pub roles: BTreeMap<String, Role>,
+    /// Players that are operators or have roles, by their UUID.
+    pub players: BTreeMap<String, PlayerPermissions>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Role {
+    pub permissions: Vec<String>,
+    /// Shown in front of the names of the role's players in the chat.
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub prefix: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerPermissions {
+    /// The name of the player when the entry last changed, to make the file readable.
+    pub name: String,
+    /// The operator level, `0` for players that aren't operators.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub level: u8,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub roles: Vec<String>,
+}
+
+fn is_zero(level: &u8) -> bool {
+    *level == 0
+}
+
+impl Default for PermissionStore {
+    /// A store in which everyone may build, and nobody is an operator.
+    fn default() -> Self {
+        Self {
+            path: None,
+            roles: BTreeMap::from([(
+                DEFAULT_ROLE.to_string(),
+                Role {
+                    permissions: vec![BREAK_PERMISSION.to_string(), PLACE_PERMISSION.to_string()],
+                    prefix: String::new(),
+                },
+            )]),
+            players: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PermissionError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    Serialize(toml::ser::Error),
+    Invalid(String),
+}
+
+impl Display for PermissionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PermissionError::Io(path, e) => write!(f, "unable to access {}: {}", path.display(), e),
+            PermissionError::Parse(path, e) => {
+                write!(f, "unable to parse {}: {}", path.display(), e)
+            }
+            PermissionError::Serialize(e) => write!(f, "unable to serialize permissions: {}", e),
+            PermissionError::Invalid(msg) => write!(f, "invalid permissions: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PermissionError {}
+
+impl PermissionStore {
+    /// Loads the store from the given file. A missing file is not an error, the
+    /// default store is used and written there on the first change.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PermissionError> {
+        let path = path.as_ref();
+        let mut store = if path.exists() {
+            let content =
+                fs::read_to_string(path).map_err(|e| PermissionError::Io(path.to_path_buf(), e))?;
+            toml::from_str::<Self>(&content)
+                .map_err(|e| PermissionError::Parse(path.to_path_buf(), e))?
+        } else {
+            Self::default()
+        };
+        store.validate()?;
+        store.path = Some(path.to_path_buf());
+        Ok(store)
+    }
+
+    /// Writes the store to the file that it was loaded from. Stores that weren't
+    /// loaded from a file aren't saved.
+    pub fn save(&self) -> Result<(), PermissionError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let content = toml::to_string_pretty(self).map_err(PermissionError::Serialize)?;
+        write_replacing(path, content).map_err(|e| PermissionError::Io(path.clone(), e))
+    }
+
+    pub fn validate(&self) -> Result<(), PermissionError> {
+        for (uuid, player) in &self.players {
+            if player.level > MAX_OP_LEVEL {
+                return Err(PermissionError::Invalid(format!(
+                    "players.{}.level must be at most {}, but is {}",
+                    uuid, MAX_OP_LEVEL, player.level
+                )));
+            }
+            if let Some(role) = player.roles.iter().find(|r| !self.roles.contains_key(*r)) {
+                return Err(PermissionError::Invalid(format!(
+                    "players.{}.roles contains the unknown role {:?}",
+                    uuid, role
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The operator level of the player, `0` if the player isn't an operator.
+    pub fn level(&self, id: &UniqueId) -> u8 {
+        self.players.get(&id.0.to_string()).map_or(0, |p| p.level)
+    }
+
+    /// Makes the player an operator of the given level, or no operator for `0`.
+    pub fn set_level(&mut self, id: &UniqueId, name: &str, level: u8) {
+        self.entry(id, name).level = level.min(MAX_OP_LEVEL);
+        self.remove_if_empty(id);
+    }
+
+    /// Gives the player a role. Returns `false` if the role doesn't exist.
+    pub fn add_role(&mut self, id: &UniqueId, name: &str, role: &str) -> bool {
+        if !self.roles.contains_key(role) {
+            return false;
+        }
+        let entry = self.entry(id, name);
+        if !entry.roles.iter().any(|r| r == role) {
+            entry.roles.push(role.to_string());
+        }
+        true
+    }
+
+    /// Takes a role from the player. Returns `false` if the player didn't have it.
+    pub fn remove_role(&mut self, id: &UniqueId, name: &str, role: &str) -> bool {
+        let entry = self.entry(id, name);
+        let had = entry.roles.iter().any(|r| r == role);
+        entry.roles.retain(|r| r != role);
+        self.remove_if_empty(id);
+        had
+    }
+
+    /// All permissions of the player: those of the default role, of the player's
+    /// roles and of the player's operator level.
+    pub fn permissions(&self, id: &UniqueId) -> Permissions {
+        let player = self.players.get(&id.0.to_string());
+        let roles = std::iter::once(DEFAULT_ROLE)
+            .chain(
+                player
+                    .into_iter()
+                    .flat_map(|p| p.roles.iter().map(String::as_str)),
+            )
+            .filter_map(|role| self.roles.get(role))
+            .flat_map(|role| role.permissions.iter().cloned());
+        let level = player.map_or(0, |p| p.level) as usize;
+        let levels = LEVEL_PERMISSIONS[..=level]
+            .iter()
+            .flat_map(|nodes| nodes.iter().map(|node| node.to_string()));
+        Permissions::from_nodes(roles.chain(levels))
+    }
+
+    /// The chat prefix of the player's first role that has one, or the one of the
+    /// default role.
+    pub fn prefix(&self, id: &UniqueId) -> &str {
+        self.players
+            .get(&id.0.to_string())
+            .into_iter()
+            .flat_map(|p| p.roles.iter().map(String::as_str))
+            .chain(std::iter::once(DEFAULT_ROLE))
+            .filter_map(|role| self.roles.get(role))
+            .map(|role| role.prefix.as_str())
+            .find(|prefix| !prefix.is_empty())
+            .unwrap_or_default()
+    }
+
+    /// The UUID and the exact name of the player in the store with the given name,
+    /// ignoring case.
+    pub fn find(&self, name: &str) -> Option<(UniqueId, String)> {
+        self.players
+            .iter()
+            .filter(|(_, player)| player.name.eq_ignore_ascii_case(name))
+            .find_map(|(uuid, player)| {
+                Some((UniqueId(Uuid::parse_str(uuid).ok()?), player.name.clone()))
+            })
+    }
+
+    fn entry(&mut self, id: &UniqueId, name: &str) -> &mut PlayerPermissions {
+        let entry = self.players.entry(id.0.to_string()).or_default();
+        entry.name = name.to_string();
+        entry
+    }
+
+    fn remove_if_empty(&mut self, id: &UniqueId) {
+        let key = id.0.to_string();
+        if self
+            .players
+            .get(&key)
+            .is_some_and(|p| p.level == 0 && p.roles.is_empty())
+        {
+            self.players.remove(&key);
+        }
+    }
+}
+
+const OP_LEVEL: ArgumentKind = ArgumentKind::Integer {
+    min: 1,
+    max: MAX_OP_LEVEL as i32,
+};
+
+/// `/op <name> [level]`, the level defaults to the level of the source.
+pub fn op_command() -> CommandSpec {
+    CommandSpec::new("op", "justmine.command.op")
+        .usage([argument("name", ArgumentKind::Word)])
+        .usage([
+            argument("name", ArgumentKind::Word),
+            argument("level", OP_LEVEL),
+        ])
+}
+
+/// `/deop <name>`.
+pub fn deop_command() -> CommandSpec {
+    CommandSpec::new("deop", "justmine.command.deop").usage([argument("name", ArgumentKind::Word)])
+}
+
+/// `/role list`, `/role add <name> <role>` and `/role remove <name> <role>`.
+pub fn role_command() -> CommandSpec {
+    CommandSpec::new("role", "justmine.command.role")
+        .usage([literal("list")])
+        .usage([
+            literal("add"),
+            argument("name", ArgumentKind::Word),
+            argument("role", ArgumentKind::Word),
+        ])
+        .usage([
+            literal("remove"),
+            argument("name", ArgumentKind::Word),
+            argument("role", ArgumentKind::Word),
+        ])
+}
+
+/// Handles `/op`, `/deop` and `/role`, saves the store and updates the permissions
+/// of the player if it's online.
+///
+/// Players are looked up by name among the online players of all worlds, and then
+/// among the players in the store, so that offline players can be changed too.
+/// Nobody may hand out a level or permissions that they don't have, or change
+/// players whose level isn't below their own.
+pub fn handle_permission_commands(
+    mut commands: Commands,
+    players: Query<(Entity, &UniqueId, &Username)>,
+    mut store: ResMut<PermissionStore>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| matches!(event.name, "op" | "deop" | "role"))
+        .for_each(|event| {
+            if event.args.path() == ["list"] {
+                let roles = store.roles.keys().cloned().collect::<Vec<_>>().join(", ");
+                feedback.send(CommandFeedback::info(
+                    event.source,
+                    format!("Roles: {}", roles),
+                ));
+                return;
+            }
+
+            // the console and RCON may change everyone
+            let (source_id, source_level, source_permissions) = match event.source {
+                CommandSource::Player(player) => match players.get(player) {
+                    Ok((_, id, _)) => (Some(*id), store.level(id), store.permissions(id)),
+                    Err(_) => return,
+                },
+                CommandSource::Console | CommandSource::Rcon(_) => {
+                    (None, MAX_OP_LEVEL + 1, Permissions::all())
+                }
+            };
+
+            let name = event.args.string("name").unwrap_or_default();
+            let online = players
+                .iter()
+                .find(|(_, _, username)| username.0.eq_ignore_ascii_case(name));
+            let (entity, id, name) = match online {
+                Some((entity, id, username)) => (Some(entity), *id, username.0.clone()),
+                None => match store.find(name) {
+                    Some((id, name)) => (None, id, name),
+                    None => {
+                        feedback.send(CommandFeedback::error(
+                            event.source,
+                            format!("Unknown player: {}", name),
+                        ));
+                        return;
+                    }
+                },
+            };
+
+            if source_id != Some(id) && store.level(&id) >= source_level {
+                feedback.send(CommandFeedback::error(
+                    event.source,
+                    format!(
+                        "You can't change {}, whose operator level isn't below yours",
+                        name
+                    ),
+                ));
+                return;
+            }
+
+            let role = event.args.string("role").unwrap_or_default();
+            let result = match (event.name, event.args.path()) {
+                ("op", _) => {
+                    let level = match event.args.integer("level") {
+                        Some(level) => level as u8,
+                        None => source_level.clamp(1, MAX_OP_LEVEL),
+                    };
+                    if level > source_level {
+                        Err(format!(
+                            "You can't make operators above your own level {}",
+                            source_level
+                        ))
+                    } else {
+                        store.set_level(&id, &name, level);
+                        Ok(format!("Made {} a server operator (level {})", name, level))
+                    }
+                }
+                ("deop", _) => {
+                    store.set_level(&id, &name, 0);
+                    Ok(format!("Made {} no longer a server operator", name))
+                }
+                (_, ["add"]) => match store.roles.get(role) {
+                    None => Err(format!("Unknown role: {}", role)),
+                    Some(granted)
+                        if !granted
+                            .permissions
+                            .iter()
+                            .all(|node| source_permissions.has(node)) =>
+                    {
+                        Err(format!(
+                            "You can't give the role {}, it has permissions that you don't have",
+                            role
+                        ))
+                    }
+                    Some(_) => {
+                        store.add_role(&id, &name, role);
+                        Ok(format!("Gave {} the role {}", name, role))
+                    }
+                },
+                _ => {
+                    if store.remove_role(&id, &name, role) {
+                        Ok(format!("Took the role {} from {}", role, name))
+                    } else {
+                        Err(format!("{} doesn't have the role {}", name, role))
+                    }
+                }
+            };
+
+            let message = match result {
+                Ok(message) => message,
+                Err(message) => {
+                    feedback.send(CommandFeedback::error(event.source, message));
+                    return;
+                }
+            };
+            if let Some(entity) = entity {
+                commands.entity(entity).insert(store.permissions(&id));
+            }
+            match store.save() {
+                Ok(()) => feedback.send(CommandFeedback::info(event.source, message)),
+                Err(e) => {
+                    error!("{}", e);
+                    feedback.send(CommandFeedback::error(
+                        event.source,
+                        "The change could not be saved, see the log",
+                    ));
+                }
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch_commands;
+    use crate::testing::enable_commands;
+    use valence::message::CommandExecutionEvent;
+    use valence::testing::ScenarioSingleClient;
+
+    #[test]
+    fn test_permissions() {
+        let permissions = Permissions::from_nodes(["justmine.command.time", "other.*"]);
+        assert!(permissions.has("justmine.command.time"));
+        assert!(!permissions.has("justmine.command.tp"));
+        assert!(permissions.has("other.anything"));
+        assert!(Permissions::all().has("justmine.command.tp"));
+        assert!(!Permissions::default().has("justmine.command.time"));
+    }
+
+    #[test]
+    fn test_op_levels() {
+        let mut store = PermissionStore::default();
+        let id = UniqueId::default();
+
+        let permissions = store.permissions(&id);
+        assert!(permissions.has("justmine.build.place"));
+        assert!(!permissions.has("justmine.bypass.spawn_protection"));
+        assert!(!permissions.has("justmine.command.time"));
+
+        store.set_level(&id, "alice", 2);
+        let permissions = store.permissions(&id);
+        assert!(permissions.has("justmine.bypass.spawn_protection"));
+        assert!(permissions.has("justmine.command.time"));
+        assert!(!permissions.has("justmine.command.op"));
+        assert!(!permissions.has("justmine.command.stop"));
+
+        store.set_level(&id, "alice", 4);
+        assert!(store.permissions(&id).has("justmine.command.stop"));
+
+        store.set_level(&id, "alice", 0);
+        assert!(store.players.is_empty());
+    }
+
+    #[test]
+    fn test_roles() {
+        let mut store = PermissionStore::default();
+        store.roles.insert(
+            "builder".to_string(),
+            Role {
+                permissions: vec!["justmine.command.fill".to_string()],
+                prefix: "[Builder] ".to_string(),
+            },
+        );
+        let id = UniqueId::default();
+
+        assert!(!store.add_role(&id, "bob", "admin"));
+        assert_eq!("", store.prefix(&id));
+        assert!(store.add_role(&id, "bob", "builder"));
+        assert!(store.permissions(&id).has("justmine.command.fill"));
+        assert_eq!("[Builder] ", store.prefix(&id));
+        assert_eq!(0, store.level(&id));
+        assert_eq!(1, store.players.len());
+
+        assert!(store.remove_role(&id, "bob", "builder"));
+        assert!(!store.permissions(&id).has("justmine.command.fill"));
+        assert!(!store.remove_role(&id, "bob", "builder"));
+        assert!(store.players.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir =
+            std::env::temp_dir().join(format!("justmine-test-permissions-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(DEFAULT_PERMISSIONS_PATH);
+        let _ = fs::remove_file(&path);
+
+        let mut store = PermissionStore::load(&path).unwrap();
+        assert_eq!(PermissionStore::default().roles, store.roles);
+        let id = UniqueId::default();
+        store.set_level(&id, "alice", 3);
+        store.save().unwrap();
+
+        let loaded = PermissionStore::load(&path).unwrap();
+        assert_eq!(store, loaded);
+        assert_eq!(3, loaded.level(&id));
+
+        fs::write(&path, "[players.someone]\nlevel = 5\n").unwrap();
+        assert!(matches!(
+            PermissionStore::load(&path),
+            Err(PermissionError::Invalid(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find() {
+        let mut store = PermissionStore::default();
+        let id = UniqueId(Uuid::from_u128(1));
+        store.set_level(&id, "Alice", 2);
+        assert_eq!(Some((id, "Alice".to_string())), store.find("alice"));
+        assert_eq!(None, store.find("bob"));
+    }
+
+    #[test]
+    fn test_no_escalation() {
+        let mut scenario = ScenarioSingleClient::new();
+        enable_commands(&mut scenario.app, scenario.client);
+        scenario.app.add_systems(
+            Update,
+            (dispatch_commands, handle_permission_commands).chain(),
+        );
+        let client = scenario.client;
+        let own = *scenario.app.world.get::<UniqueId>(client).unwrap();
+        let name = scenario
+            .app
+            .world
+            .get::<Username>(client)
+            .unwrap()
+            .0
+            .clone();
+
+        let (boss, friend) = (UniqueId(Uuid::from_u128(1)), UniqueId(Uuid::from_u128(2)));
+        let mut store = PermissionStore::default();
+        store.roles.insert(
+            "admin".to_string(),
+            Role {
+                permissions: vec!["*".to_string()],
+                prefix: String::new(),
+            },
+        );
+        store.roles.insert(
+            "moderator".to_string(),
+            Role {
+                permissions: vec!["justmine.command.ban".to_string()],
+                prefix: String::new(),
+            },
+        );
+        store.set_level(&own, &name, 3);
+        store.set_level(&boss, "boss", 4);
+        store.set_level(&friend, "friend", 1);
+        scenario.app.insert_resource(store);
+
+        let mut run = |command: &str| {
+            scenario.app.world.send_event(CommandExecutionEvent {
+                command: command.into(),
+                client,
+            });
+            scenario.app.update();
+            scenario.app.world.resource::<PermissionStore>().clone()
+        };
+
+        let store = run(&format!("role add {} admin", name));
+        assert!(!store.permissions(&own).has("justmine.command.stop"));
+        assert_eq!(3, run(&format!("op {} 4", name)).level(&own));
+        assert_eq!(4, run("deop boss").level(&boss));
+        assert_eq!(4, run("op boss 1").level(&boss));
+
+        // offline players are found in the store
+        let store = run("role add friend moderator");
+        assert!(store.permissions(&friend).has("justmine.command.ban"));
+        assert_eq!(3, run("op friend 3").level(&friend));
+        assert_eq!(3, run("deop friend").level(&friend));
+
+        assert_eq!(3, run("op nobody").players.len());
+    }
+}
@@ -0,0 +1,825 @@
+use crate::{GeneratorSettings, DEFAULT_PERMISSIONS_PATH};
+use bevy_ecs::prelude::Resource;
+use bevy_log::Level;
+use serde::{Deserialize, Deserializer};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::{fs, io};
+use valence::network::ConnectionMode;
+use valence::prelude::GameMode;
+use valence::{ident, Ident};
+
+/// The file that the config is read from if `JUSTMINE_CONFIG` is not set.
+pub const DEFAULT_CONFIG_PATH: &str = "justmine.toml";
+/// The endpoint of Mojang's session server that verifies players in online mode.
+pub const DEFAULT_SESSION_SERVER: &str =
+    "https://sessionserver.mojang.com/session/minecraft/hasJoined";
+
+/// Every environment variable that starts with this prefix may override a value
+/// from the config file, e.g. `JUSTMINE_MAX_PLAYERS=10`.
+const ENV_PREFIX: &str = "JUSTMINE_";
+
+#[derive(Resource, Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: SocketAddr,
+    /// How players are authenticated, and whether they connect through a proxy.
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub connection_mode: ConnectionKind,
+    /// Refuses players in online mode that connect from a different IP address than
+    /// the one they authenticated from.
+    pub prevent_proxy_connections: bool,
+    /// The secret shared with a Velocity proxy, required for `velocity`.
+    pub forwarding_secret: String,
+    /// The endpoint that verifies players in online mode.
+    pub session_server: String,
+    pub max_players: usize,
+    pub view_distance: u8,
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub log_level: Level,
+    /// The game mode of players that aren't operators when they join.
+    #[serde(deserialize_with = "deserialize_game_mode")]
+    pub default_game_mode: GameMode,
+    /// The game mode of operators when they join.
+    #[serde(deserialize_with = "deserialize_game_mode")]
+    pub operator_game_mode: GameMode,
+    /// The file that operators and roles are persisted to.
+    pub permissions_file: PathBuf,
+    /// Only players on the whitelist may join.
+    pub whitelist: bool,
+    pub whitelist_file: PathBuf,
+    pub banned_players_file: PathBuf,
+    pub banned_ips_file: PathBuf,
+    /// The header and footer of the player list, with the placeholders `{player}`,
+    /// `{online}`, `{max_players}`, `{tps}` and `{ping}`. Both empty leaves the
+    /// player list without them.
+    pub tab_header: String,
+    pub tab_footer: String,
+    pub chat: ChatConfig,
+    pub rcon: RconConfig,
+    /// Seconds between two automatic saves, `0` disables autosaving.
+    pub autosave_interval: u64,
+    /// The world that players join and respawn in.
+    pub default_world: String,
+    /// All worlds of the server, keyed by their name.
+    pub worlds: BTreeMap<String, WorldConfig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChatConfig {
+    /// The format of chat messages, with the placeholders `{prefix}`, `{player}` and
+    /// `{message}`. The prefix is the one of the player's role.
+    pub format: String,
+    /// How many messages a player may send within the rate limit interval, `0`
+    /// disables the rate limit.
+    pub rate_limit: u32,
+    /// In seconds.
+    pub rate_limit_interval: u64,
+    /// Words that are replaced with asterisks.
+    pub blocked_words: Vec<String>,
+    /// Messages with links are not sent.
+    pub block_links: bool,
+    /// The file that mutes are persisted to, in the format of the ban lists.
+    pub mutes_file: PathBuf,
+}
+
+/// The remote console, which runs commands sent with the Source RCON protocol.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RconConfig {
+    pub enabled: bool,
+    pub bind_address: SocketAddr,
+    /// The password that clients authenticate with, required if RCON is enabled.
+    pub password: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldConfig {
+    pub path: PathBuf,
+    pub dimension: Dimension,
+    /// Where players spawn. Without it, they spawn on top of the generated terrain
+    /// at x 0.5 and z 0.5.
+    pub spawn: Option<[f64; 3]>,
+    /// Generates the chunks that don't exist in the world yet.
+    #[serde(deserialize_with = "deserialize_from_str")]
+    pub generator: GeneratorSettings,
+    pub rules: WorldRules,
+}
+
+/// Rules that change how the game plays in a world.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldRules {
+    /// Players keep their inventory and experience when they die.
+    pub keep_inventory: bool,
+    /// How many of the players in a world, in percent, have to sleep to skip the night.
+    pub players_sleeping_percentage: u32,
+    /// How far the time of day advances every tick, `0` stops the day/night cycle.
+    pub time_rate: i64,
+    /// Whether the weather changes by itself.
+    pub weather_cycle: bool,
+    /// How many blocks around the spawn only operators may change, `0` disables
+    /// spawn protection.
+    pub spawn_protection: u32,
+}
+
+/// How players connect to the server.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ConnectionKind {
+    /// Players aren't authenticated and get offline UUIDs.
+    Offline,
+    /// Players are verified with the session server.
+    #[default]
+    Online,
+    /// Players connect through a BungeeCord proxy with IP forwarding, which
+    /// authenticates them. The server must not be reachable without the proxy.
+    BungeeCord,
+    /// Players connect through a Velocity proxy with modern forwarding, which
+    /// authenticates them and signs the forwarded data with the forwarding secret.
+    Velocity,
+}
+
+impl FromStr for ConnectionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "offline" => Ok(ConnectionKind::Offline),
+            "online" => Ok(ConnectionKind::Online),
+            "bungeecord" => Ok(ConnectionKind::BungeeCord),
+            "velocity" => Ok(ConnectionKind::Velocity),
+            _ => Err(format!("unknown connection mode: {:?}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dimension {
+    #[default]
+    Overworld,
+    TheNether,
+    TheEnd,
+}
+
+impl Dimension {
+    /// The name of the dimension type in the [`DimensionTypeRegistry`](valence::prelude::DimensionTypeRegistry).
+    pub fn ident(self) -> Ident<&'static str> {
+        match self {
+            Dimension::Overworld => ident!("overworld"),
+            Dimension::TheNether => ident!("the_nether"),
+            Dimension::TheEnd => ident!("the_end"),
+        }
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0:25565".parse().unwrap(),
+            connection_mode: ConnectionKind::Online,
+            prevent_proxy_connections: false,
+            forwarding_secret: String::new(),
+            session_server: DEFAULT_SESSION_SERVER.to_string(),
+            max_players: 20,
+            view_distance: 10,
+            log_level: Level::INFO,
+            default_game_mode: GameMode::Survival,
+            operator_game_mode: GameMode::Creative,
+            permissions_file: PathBuf::from(DEFAULT_PERMISSIONS_PATH),
+            whitelist: false,
+            whitelist_file: PathBuf::from("whitelist.json"),
+            banned_players_file: PathBuf::from("banned-players.json"),
+            banned_ips_file: PathBuf::from("banned-ips.json"),
+            tab_header: String::new(),
+            tab_footer: String::new(),
+            chat: ChatConfig::default(),
+            rcon: RconConfig::default(),
+            autosave_interval: 300,
+            default_world: "overworld".to_string(),
+            worlds: BTreeMap::from([("overworld".to_string(), WorldConfig::default())]),
+        }
+    }
+}
+
+impl Default for ChatConfig {
+    fn default() -> Self {
+        Self {
+            format: "{prefix}<{player}> {message}".to_string(),
+            rate_limit: 5,
+            rate_limit_interval: 5,
+            blocked_words: vec![],
+            block_links: false,
+            mutes_file: PathBuf::from("mutes.json"),
+        }
+    }
+}
+
+impl Default for RconConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: "0.0.0.0:25575".parse().unwrap(),
+            password: String::new(),
+        }
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("world"),
+            dimension: Dimension::Overworld,
+            spawn: None,
+            generator: GeneratorSettings::Flat,
+            rules: WorldRules::default(),
+        }
+    }
+}
+
+impl Default for WorldRules {
+    fn default() -> Self {
+        Self {
+            keep_inventory: false,
+            players_sleeping_percentage: 100,
+            time_rate: 1,
+            weather_cycle: true,
+            spawn_protection: 0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    InvalidOverride { variable: String, value: String },
+    Invalid(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "unable to read {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "unable to parse {}: {}", path.display(), e),
+            ConfigError::InvalidOverride { variable, value } => {
+                write!(
+                    f,
+                    "invalid value {:?} for environment variable {}",
+                    value, variable
+                )
+            }
+            ConfigError::Invalid(msg) => write!(f, "invalid config: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ServerConfig {
+    /// Loads the config from the file named by `JUSTMINE_CONFIG` (or [`DEFAULT_CONFIG_PATH`]),
+    /// applies the `JUSTMINE_*` environment overrides and validates the result.
+    ///
+    /// A missing default config file is not an error, the defaults are used instead.
+    pub fn load() -> Result<Self, ConfigError> {
+        let (path, required) = match std::env::var_os("JUSTMINE_CONFIG") {
+            Some(path) => (PathBuf::from(path), true),
+            None => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+        };
+
+        let mut config = if required || path.exists() {
+            Self::from_file(&path)?
+        } else {
+            Self::default()
+        };
+        config.apply_overrides(std::env::vars())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let content =
+            fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.to_path_buf(), e))
+    }
+
+    /// Applies all `JUSTMINE_*` variables from the given iterator. Variables that
+    /// don't name a config value are ignored.
+    ///
+    /// The `WORLD_*` and `SPAWN` variables apply to the default world.
+    pub fn apply_overrides<I>(&mut self, vars: I) -> Result<(), ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut vars = vars.into_iter().collect::<Vec<_>>();
+        // the default world has to be known before its values can be overridden
+        vars.sort_by_key(|(variable, _)| variable != "JUSTMINE_DEFAULT_WORLD");
+
+        for (variable, value) in vars {
+            let Some(key) = variable.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+
+            let applied = match key {
+                "BIND_ADDRESS" => value.parse().map(|v| self.bind_address = v).is_ok(),
+                "CONNECTION_MODE" => value.parse().map(|v| self.connection_mode = v).is_ok(),
+                "FORWARDING_SECRET" => {
+                    self.forwarding_secret = value.clone();
+                    true
+                }
+                "MAX_PLAYERS" => value.parse().map(|v| self.max_players = v).is_ok(),
+                "VIEW_DISTANCE" => value.parse().map(|v| self.view_distance = v).is_ok(),
+                "LOG_LEVEL" => value.parse().map(|v| self.log_level = v).is_ok(),
+                "DEFAULT_GAME_MODE" => parse_game_mode(&value)
+                    .map(|v| self.default_game_mode = v)
+                    .is_some(),
+                "OPERATOR_GAME_MODE" => parse_game_mode(&value)
+                    .map(|v| self.operator_game_mode = v)
+                    .is_some(),
+                "PERMISSIONS_FILE" => {
+                    self.permissions_file = PathBuf::from(&value);
+                    true
+                }
+                "WHITELIST" => value.parse().map(|v| self.whitelist = v).is_ok(),
+                "RCON_ENABLED" => value.parse().map(|v| self.rcon.enabled = v).is_ok(),
+                "RCON_PASSWORD" => {
+                    self.rcon.password = value.clone();
+                    true
+                }
+                "AUTOSAVE_INTERVAL" => value.parse().map(|v| self.autosave_interval = v).is_ok(),
+                "DEFAULT_WORLD" => {
+                    self.default_world = value.clone();
+                    true
+                }
+                "WORLD_PATH" => {
+                    self.default_world_mut().path = PathBuf::from(&value);
+                    true
+                }
+                "WORLD_GENERATOR" => value
+                    .parse()
+                    .map(|v| self.default_world_mut().generator = v)
+                    .is_ok(),
+                "SPAWN" => parse_position(&value)
+                    .map(|v| self.default_world_mut().spawn = Some(v))
+                    .is_some(),
+                _ => continue,
+            };
+
+            if !applied {
+                return Err(ConfigError::InvalidOverride { variable, value });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_players == 0 {
+            return Err(ConfigError::Invalid(
+                "max_players must be at least 1".to_string(),
+            ));
+        }
+        if !(2..=32).contains(&self.view_distance) {
+            return Err(ConfigError::Invalid(format!(
+                "view_distance must be between 2 and 32, but is {}",
+                self.view_distance
+            )));
+        }
+        if self.connection_mode == ConnectionKind::Velocity && self.forwarding_secret.is_empty() {
+            return Err(ConfigError::Invalid(
+                "forwarding_secret must be set for the velocity connection mode".to_string(),
+            ));
+        }
+        if !self.session_server.starts_with("http://")
+            && !self.session_server.starts_with("https://")
+        {
+            return Err(ConfigError::Invalid(format!(
+                "session_server must be an http or https URL, but is {:?}",
+                self.session_server
+            )));
+        }
+        if !self.chat.format.contains("{message}") {
+            return Err(ConfigError::Invalid(
+                "chat.format must contain {message}".to_string(),
+            ));
+        }
+        if self.rcon.enabled && self.rcon.password.is_empty() {
+            return Err(ConfigError::Invalid(
+                "rcon.password must be set if rcon is enabled".to_string(),
+            ));
+        }
+        if !self.worlds.contains_key(&self.default_world) {
+            return Err(ConfigError::Invalid(format!(
+                "default_world {:?} is not one of the configured worlds",
+                self.default_world
+            )));
+        }
+
+        let mut paths = Vec::<&Path>::new();
+        for (name, world) in &self.worlds {
+            if world.path.as_os_str().is_empty() {
+                return Err(ConfigError::Invalid(format!(
+                    "worlds.{}.path must not be empty",
+                    name
+                )));
+            }
+            if paths.contains(&world.path.as_path()) {
+                return Err(ConfigError::Invalid(format!(
+                    "worlds.{}.path {} is already used by another world",
+                    name,
+                    world.path.display()
+                )));
+            }
+            paths.push(&world.path);
+
+            if let Some(spawn) = world.spawn {
+                if spawn.iter().any(|v| !v.is_finite()) {
+                    return Err(ConfigError::Invalid(format!(
+                        "worlds.{}.spawn must be finite, but is {:?}",
+                        name, spawn
+                    )));
+                }
+                let heights = match world.dimension {
+                    Dimension::Overworld => -64.0..320.0,
+                    Dimension::TheNether | Dimension::TheEnd => 0.0..256.0,
+                };
+                if !heights.contains(&spawn[1]) {
+                    return Err(ConfigError::Invalid(format!(
+                        "worlds.{}.spawn y must be between {} and {}, but is {}",
+                        name, heights.start, heights.end, spawn[1]
+                    )));
+                }
+            }
+            if world.rules.time_rate < 0 {
+                return Err(ConfigError::Invalid(format!(
+                    "worlds.{}.rules.time_rate must not be negative, but is {}",
+                    name, world.rules.time_rate
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// The connection mode of the network settings.
+    pub fn connection_mode(&self) -> ConnectionMode {
+        match self.connection_mode {
+            ConnectionKind::Offline => ConnectionMode::Offline,
+            ConnectionKind::Online => ConnectionMode::Online {
+                prevent_proxy_connections: self.prevent_proxy_connections,
+            },
+            ConnectionKind::BungeeCord => ConnectionMode::BungeeCord,
+            ConnectionKind::Velocity => ConnectionMode::Velocity {
+                secret: self.forwarding_secret.as_str().into(),
+            },
+        }
+    }
+
+    /// The world that players join and respawn in. Only call this on a validated config.
+    pub fn default_world(&self) -> &WorldConfig {
+        &self.worlds[&self.default_world]
+    }
+
+    fn default_world_mut(&mut self) -> &mut WorldConfig {
+        self.worlds.entry(self.default_world.clone()).or_default()
+    }
+}
+
+pub fn parse_game_mode(s: &str) -> Option<GameMode> {
+    match s.to_ascii_lowercase().as_str() {
+        "survival" => Some(GameMode::Survival),
+        "creative" => Some(GameMode::Creative),
+        "adventure" => Some(GameMode::Adventure),
+        "spectator" => Some(GameMode::Spectator),
+        _ => None,
+    }
+}
+
+fn parse_position(s: &str) -> Option<[f64; 3]> {
+    let mut parts = s.split(',').map(|p| p.trim().parse::<f64>());
+    let position = [
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+        parts.next()?.ok()?,
+    ];
+    parts.next().is_none().then_some(position)
+}
+
+fn deserialize_game_mode<'de, D>(deserializer: D) -> Result<GameMode, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_game_mode(&s)
+        .ok_or_else(|| serde::de::Error::custom(format!("unknown game mode: {:?}", s)))
+}
+
+fn deserialize_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_empty_config_is_default() {
+        let config: ServerConfig = toml::from_str("").unwrap();
+        assert_eq!(ServerConfig::default(), config);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config: ServerConfig = toml::from_str(
+            r#"
+            bind_address = "127.0.0.1:25566"
+            connection_mode = "velocity"
+            forwarding_secret = "s3cret"
+            max_players = 5
+            view_distance = 6
+            log_level = "debug"
+            default_game_mode = "adventure"
+            operator_game_mode = "survival"
+            permissions_file = "/data/permissions.toml"
+            whitelist = true
+            banned_ips_file = "/data/banned-ips.json"
+            tab_footer = "{online}/{max_players} online"
+            autosave_interval = 60
+            default_world = "lobby"
+
+            [chat]
+            format = "{player}: {message}"
+            rate_limit = 0
+            blocked_words = ["darn"]
+
+            [rcon]
+            enabled = true
+            password = "hunter2"
+
+            [worlds.lobby]
+            path = "/data/lobby"
+            spawn = [10.5, 80.0, -3.5]
+            generator = "noise:1234"
+
+            [worlds.lobby.rules]
+            keep_inventory = true
+            players_sleeping_percentage = 50
+            time_rate = 0
+            weather_cycle = false
+            spawn_protection = 16
+
+            [worlds.nether]
+            path = "/data/world/DIM-1"
+            dimension = "the_nether"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            "127.0.0.1:25566".parse::<SocketAddr>().unwrap(),
+            config.bind_address
+        );
+        assert_eq!(ConnectionKind::Velocity, config.connection_mode);
+        assert_eq!("s3cret", config.forwarding_secret);
+        assert_eq!(DEFAULT_SESSION_SERVER, config.session_server);
+        assert_eq!(5, config.max_players);
+        assert_eq!(6, config.view_distance);
+        assert_eq!(Level::DEBUG, config.log_level);
+        assert_eq!(GameMode::Adventure, config.default_game_mode);
+        assert_eq!(GameMode::Survival, config.operator_game_mode);
+        assert_eq!(
+            PathBuf::from("/data/permissions.toml"),
+            config.permissions_file
+        );
+        assert!(config.whitelist);
+        assert_eq!(PathBuf::from("whitelist.json"), config.whitelist_file);
+        assert_eq!(
+            PathBuf::from("/data/banned-ips.json"),
+            config.banned_ips_file
+        );
+        assert_eq!("", config.tab_header);
+        assert_eq!("{online}/{max_players} online", config.tab_footer);
+        assert_eq!("{player}: {message}", config.chat.format);
+        assert_eq!(0, config.chat.rate_limit);
+        assert_eq!(5, config.chat.rate_limit_interval);
+        assert_eq!(vec!["darn".to_string()], config.chat.blocked_words);
+        assert!(config.rcon.enabled);
+        assert_eq!(
+            "0.0.0.0:25575".parse::<SocketAddr>().unwrap(),
+            config.rcon.bind_address
+        );
+        assert_eq!("hunter2", config.rcon.password);
+        assert_eq!(60, config.autosave_interval);
+        config.validate().unwrap();
+
+        let lobby = config.default_world();
+        assert_eq!(PathBuf::from("/data/lobby"), lobby.path);
+        assert_eq!(Dimension::Overworld, lobby.dimension);
+        assert_eq!(Some([10.5, 80.0, -3.5]), lobby.spawn);
+        assert_eq!(GeneratorSettings::Noise(1234), lobby.generator);
+        assert!(lobby.rules.keep_inventory);
+        assert_eq!(50, lobby.rules.players_sleeping_percentage);
+        assert_eq!(0, lobby.rules.time_rate);
+        assert!(!lobby.rules.weather_cycle);
+        assert_eq!(16, lobby.rules.spawn_protection);
+
+        let nether = &config.worlds["nether"];
+        assert_eq!(Dimension::TheNether, nether.dimension);
+        assert_eq!(GeneratorSettings::Flat, nether.generator);
+        assert!(!nether.rules.keep_inventory);
+        assert_eq!(100, nether.rules.players_sleeping_percentage);
+        assert_eq!(1, nether.rules.time_rate);
+        assert!(nether.rules.weather_cycle);
+        assert_eq!(0, nether.rules.spawn_protection);
+        assert!(!config.worlds.contains_key("overworld"));
+    }
+
+    #[test]
+    fn test_parse_config_unknown_game_mode() {
+        assert!(toml::from_str::<ServerConfig>(r#"default_game_mode = "hardcore""#).is_err());
+    }
+
+    #[test]
+    fn test_parse_config_unknown_connection_mode() {
+        assert!(toml::from_str::<ServerConfig>(r#"connection_mode = "lan""#).is_err());
+    }
+
+    #[test]
+    fn test_connection_mode() {
+        let config = ServerConfig {
+            connection_mode: ConnectionKind::Offline,
+            ..Default::default()
+        };
+        assert!(matches!(config.connection_mode(), ConnectionMode::Offline));
+
+        let config = ServerConfig {
+            prevent_proxy_connections: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            config.connection_mode(),
+            ConnectionMode::Online {
+                prevent_proxy_connections: true
+            }
+        ));
+
+        let config = ServerConfig {
+            connection_mode: ConnectionKind::Velocity,
+            forwarding_secret: "s3cret".to_string(),
+            ..Default::default()
+        };
+        match config.connection_mode() {
+            ConnectionMode::Velocity { secret } => assert_eq!("s3cret", &*secret),
+            mode => panic!("unexpected connection mode {:?}", mode),
+        }
+    }
+
+    #[test]
+    fn test_parse_config_unknown_generator() {
+        let result = toml::from_str::<ServerConfig>(
+            r#"
+            [worlds.overworld]
+            generator = "superflat:2*unobtainium"
+            "#,
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut config = ServerConfig::default();
+        config
+            .apply_overrides(vars(&[
+                ("JUSTMINE_MAX_PLAYERS", "3"),
+                ("JUSTMINE_CONNECTION_MODE", "BungeeCord"),
+                ("JUSTMINE_DEFAULT_GAME_MODE", "adventure"),
+                ("JUSTMINE_OPERATOR_GAME_MODE", "spectator"),
+                ("JUSTMINE_PERMISSIONS_FILE", "/data/permissions.toml"),
+                ("JUSTMINE_WHITELIST", "true"),
+                ("JUSTMINE_SPAWN", "1, 2, 3"),
+                ("JUSTMINE_WORLD_PATH", "other"),
+                ("JUSTMINE_WORLD_GENERATOR", "noise:7"),
+                ("JUSTMINE_RCON_ENABLED", "true"),
+                ("JUSTMINE_RCON_PASSWORD", "hunter2"),
+                ("JUSTMINE_CONFIG", "ignored.toml"),
+                ("PATH", "/usr/bin"),
+            ]))
+            .unwrap();
+
+        assert_eq!(3, config.max_players);
+        assert_eq!(ConnectionKind::BungeeCord, config.connection_mode);
+        assert_eq!(GameMode::Adventure, config.default_game_mode);
+        assert_eq!(GameMode::Spectator, config.operator_game_mode);
+        assert_eq!(
+            PathBuf::from("/data/permissions.toml"),
+            config.permissions_file
+        );
+        assert!(config.whitelist);
+        let world = config.default_world();
+        assert_eq!(Some([1.0, 2.0, 3.0]), world.spawn);
+        assert_eq!(PathBuf::from("other"), world.path);
+        assert_eq!(GeneratorSettings::Noise(7), world.generator);
+        assert!(config.rcon.enabled);
+        assert_eq!("hunter2", config.rcon.password);
+    }
+
+    #[test]
+    fn test_overrides_default_world() {
+        let mut config = ServerConfig::default();
+        // the default world is switched first, regardless of the order of the variables
+        config
+            .apply_overrides(vars(&[
+                ("JUSTMINE_WORLD_PATH", "lobby"),
+                ("JUSTMINE_DEFAULT_WORLD", "lobby"),
+            ]))
+            .unwrap();
+
+        config.validate().unwrap();
+        assert_eq!(PathBuf::from("lobby"), config.default_world().path);
+        assert_eq!(PathBuf::from("world"), config.worlds["overworld"].path);
+    }
+
+    #[test]
+    fn test_invalid_override() {
+        let mut config = ServerConfig::default();
+        let result = config.apply_overrides(vars(&[("JUSTMINE_VIEW_DISTANCE", "far")]));
+        assert!(matches!(result, Err(ConfigError::InvalidOverride { .. })));
+    }
+
+    #[test]
+    fn test_validate() {
+        let config = ServerConfig {
+            view_distance: 64,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = ServerConfig {
+            max_players: 0,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = ServerConfig {
+            connection_mode: ConnectionKind::Velocity,
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let config = ServerConfig {
+            session_server: "sessionserver.example.com".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.chat.format = "<{player}>".to_string();
+        assert!(config.validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.rcon.enabled = true;
+        assert!(config.validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.default_world_mut().spawn = Some([0.5, 1000.0, 0.5]);
+        assert!(config.validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.default_world_mut().rules.time_rate = -1;
+        assert!(config.validate().is_err());
+
+        let config = ServerConfig {
+            default_world: "missing".to_string(),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        let mut config = ServerConfig::default();
+        config.worlds.insert(
+            "nether".to_string(),
+            WorldConfig {
+                dimension: Dimension::TheNether,
+                ..Default::default()
+            },
+        );
+        // both worlds use the default path
+        assert!(config.validate().is_err());
+    }
+}
@@ -1,12 +1,100 @@
+use crate::{
+    unix_time, AccessControl, ChatRateLimit, Dead, Experience, Food, Health, PermissionStore,
+    PlayerListEntity, PlayerStorage, Saturation, ServerConfig, SpawnPoint, SurvivalBundle,
+    WorldInfo, WorldRegistry,
+};
 use bevy_ecs::entity::Entity;
-use bevy_ecs::prelude::{Added, Commands, Query, With};
+use bevy_ecs::prelude::{Added, Changed, Commands, Query, Res};
 use bevy_ecs::query::WorldQuery;
-use log::info;
-use valence::client::{Client, Username, VisibleChunkLayer, VisibleEntityLayers};
-use valence::entity::{EntityLayerId, Position};
+use log::{error, info};
+use std::net::IpAddr;
+use std::sync::atomic::Ordering;
+use valence::client::{Client, Username, ViewDistance, VisibleChunkLayer, VisibleEntityLayers};
+use valence::entity::{EntityLayerId, Look, Position};
 use valence::message::SendMessage;
+use valence::network::{
+    async_trait, CleanupFn, NetworkCallbacks, NewClientInfo, SharedNetworkState,
+};
 use valence::player_list::PlayerListEntryBundle;
-use valence::{ChunkLayer, EntityLayer, GameMode, UniqueId};
+use valence::prelude::{Inventory, Text};
+use valence::{GameMode, UniqueId};
+
+/// Checks the access lists when clients log in, before they get a [`Client`], and
+/// verifies players in online mode with the configured session server.
+pub struct LoginCallbacks {
+    pub access: AccessControl,
+    pub session_server: String,
+    pub prevent_proxy_connections: bool,
+}
+
+impl LoginCallbacks {
+    pub fn new(config: &ServerConfig, access: AccessControl) -> Self {
+        Self {
+            access,
+            session_server: config.session_server.clone(),
+            prevent_proxy_connections: config.prevent_proxy_connections,
+        }
+    }
+
+    /// The URL that verifies that a player joined with the given server hash. The IP
+    /// is only sent if proxy connections are prevented.
+    pub fn session_server_url(&self, username: &str, auth_digest: &str, ip: IpAddr) -> String {
+        let mut url = format!(
+            "{}?username={}&serverId={}",
+            self.session_server, username, auth_digest
+        );
+        if self.prevent_proxy_connections {
+            url.push_str(&format!("&ip={}", ip));
+        }
+        url
+    }
+}
+
+#[async_trait]
+impl NetworkCallbacks for LoginCallbacks {
+    async fn session_server(
+        &self,
+        _shared: &SharedNetworkState,
+        username: &str,
+        auth_digest: &str,
+        player_ip: &IpAddr,
+    ) -> String {
+        self.session_server_url(username, auth_digest, *player_ip)
+    }
+
+    /// Behind a proxy, the UUID and IP address in `info` are the forwarded ones.
+    async fn login(
+        &self,
+        shared: &SharedNetworkState,
+        info: &NewClientInfo,
+    ) -> Result<CleanupFn, Text> {
+        let uuid = info.uuid.to_string();
+        if let Err(reason) =
+            self.access
+                .lock()
+                .check_login(&uuid, &info.username, info.ip, unix_time())
+        {
+            info!("{} ({}) was refused: {}", info.username, info.ip, reason);
+            return Err(Text::from(reason));
+        }
+
+        // the default login only limits the number of players
+        let max_players = shared.max_players();
+        let joined = shared
+            .player_count()
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max_players).then_some(count + 1)
+            })
+            .is_ok();
+        if !joined {
+            return Err(Text::from("Server Full".to_string()));
+        }
+        let shared = shared.clone();
+        Ok(Box::new(move || {
+            shared.player_count().fetch_sub(1, Ordering::SeqCst);
+        }))
+    }
+}
 
 #[derive(WorldQuery)]
 #[world_query(mutable)]
@@ -19,32 +107,354 @@ pub struct InitClientQuery {
     visible_chunk_layer: &'static mut VisibleChunkLayer,
     visible_entity_layers: &'static mut VisibleEntityLayers,
     pos: &'static mut Position,
+    look: &'static mut Look,
     game_mode: &'static mut GameMode,
+    inventory: &'static mut Inventory,
 }
 
+/// Puts new clients into the world, where they were when they left if they joined
+/// before.
 pub fn accept_connection(
     mut commands: Commands,
     mut clients: Query<InitClientQuery, Added<Client>>,
-    layers: Query<Entity, (With<ChunkLayer>, With<EntityLayer>)>,
+    config: Res<ServerConfig>,
+    permissions: Res<PermissionStore>,
+    storage: Res<PlayerStorage>,
+    registry: Res<WorldRegistry>,
+    worlds: Query<&WorldInfo>,
 ) {
     clients.for_each_mut(|mut client| {
         info!("new client connected");
 
-        let layer = layers.single();
+        let data = storage.load(client.uuid).unwrap_or_else(|e| {
+            error!("unable to load the data of {}: {}", client.username.0, e);
+            None
+        });
+        // players whose world is gone start over at the spawn of the default world
+        let saved_world = data
+            .as_ref()
+            .and_then(|data| registry.get(&data.world))
+            .filter(|layer| worlds.contains(*layer));
+        let layer = saved_world.unwrap_or_else(|| registry.default_world());
+        let Ok(world) = worlds.get(layer) else {
+            return;
+        };
 
         client.layer_id.0 = layer;
         client.visible_chunk_layer.0 = layer;
         client.visible_entity_layers.0.insert(layer);
-        client.pos.set([0.5, 65.5, 0.5]);
-        *client.game_mode = GameMode::Creative;
+        client.pos.set(world.spawn);
+        *client.game_mode = if permissions.level(client.uuid) > 0 {
+            config.operator_game_mode
+        } else {
+            config.default_game_mode
+        };
+        let mut survival = SurvivalBundle::default();
+        let mut experience = Experience::default();
 
-        commands.spawn(PlayerListEntryBundle {
-            uuid: *client.uuid,
-            username: client.username.clone(),
-            game_mode: *client.game_mode,
-            ..Default::default()
-        });
+        if let Some(data) = &data {
+            if saved_world.is_some() {
+                client.pos.set(data.position);
+                client.look.yaw = data.yaw;
+                client.look.pitch = data.pitch;
+            }
+            *client.game_mode = data.game_mode;
+            for (slot, stack) in &data.inventory {
+                client.inventory.set_slot(*slot, stack.clone());
+            }
+            survival.health = Health(data.health);
+            survival.food = Food(data.food);
+            survival.saturation = Saturation(data.saturation);
+            experience = Experience(data.experience);
+
+            let spawn_point = data.spawn_point.as_ref().and_then(|spawn| {
+                Some(SpawnPoint {
+                    world: registry.get(&spawn.world)?,
+                    position: spawn.position,
+                    forced: spawn.forced,
+                })
+            });
+            if let Some(spawn_point) = spawn_point {
+                commands.entity(client.entity).insert(spawn_point);
+            }
+        }
+
+        // players that left while they were dead have to respawn first
+        if survival.health.0 <= 0.0 {
+            commands.entity(client.entity).insert(Dead);
+            client.client.kill(format!("{} died", client.username.0));
+        }
+
+        let entry = commands
+            .spawn(PlayerListEntryBundle {
+                uuid: *client.uuid,
+                username: client.username.clone(),
+                game_mode: *client.game_mode,
+                ..Default::default()
+            })
+            .id();
+        commands.entity(client.entity).insert((
+            survival,
+            experience,
+            permissions.permissions(client.uuid),
+            PlayerListEntity(entry),
+            ChatRateLimit::default(),
+        ));
 
         client.client.send_chat_message("Welcome to the server!");
     })
 }
+
+/// Clamps the view distance that clients request to the configured maximum.
+pub fn limit_view_distance(
+    config: Res<ServerConfig>,
+    mut clients: Query<&mut ViewDistance, Changed<ViewDistance>>,
+) {
+    clients.for_each_mut(|mut view_distance| {
+        if view_distance.get() > config.view_distance {
+            view_distance.set(config.view_distance);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{respawn, BlockChangeEvent, PlayerData, DEFAULT_SESSION_SERVER};
+    use bevy_app::{App, PluginGroup};
+    use bevy_log::LogPlugin;
+    use rsa::pkcs8::DecodePublicKey;
+    use rsa::{Pkcs1v15Encrypt, RsaPublicKey};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use valence::network::{ConnectionMode, NetworkSettings};
+    use valence::prelude::{DVec3, Uuid};
+    use valence::protocol::decode::PacketFrame;
+    use valence::protocol::packets::handshaking::handshake_c2s::HandshakeNextState;
+    use valence::protocol::packets::handshaking::HandshakeC2s;
+    use valence::protocol::packets::login::{LoginHelloC2s, LoginHelloS2c, LoginKeyC2s};
+    use valence::protocol::{Bounded, PacketDecoder, PacketEncoder, VarInt, PROTOCOL_VERSION};
+    use valence::status::RequestRespawnEvent;
+    use valence::testing::{create_mock_client, ScenarioSingleClient};
+    use valence::DefaultPlugins;
+
+    const PROFILE: &str =
+        r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch","properties":[]}"#;
+    const PROFILE_ID: u128 = 0x069a79f444e94726a5befca90e38aaf5;
+
+    /// Answers one request with the profile of Notch, and sends the request line
+    /// back to the test.
+    fn mock_session_server() -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // read the headers, closing with unread data would reset the connection
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                header.clear();
+            }
+            sender.send(request_line.trim_end().to_string()).unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                PROFILE.len(),
+                PROFILE
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+        });
+        (
+            format!("http://{}/session/minecraft/hasJoined", address),
+            receiver,
+        )
+    }
+
+    fn callbacks(session_server: String, prevent_proxy_connections: bool) -> LoginCallbacks {
+        let config = ServerConfig {
+            session_server,
+            prevent_proxy_connections,
+            ..Default::default()
+        };
+        LoginCallbacks::new(&config, AccessControl::default())
+    }
+
+    /// Connects to the server, which starts listening after the first update.
+    fn connect(address: SocketAddr) -> TcpStream {
+        for _ in 0..100 {
+            if let Ok(stream) = TcpStream::connect(address) {
+                return stream;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the server did not listen on {}", address);
+    }
+
+    fn read_packet(stream: &mut TcpStream, decoder: &mut PacketDecoder) -> PacketFrame {
+        let mut buf = [0; 4096];
+        loop {
+            if let Some(frame) = decoder.try_next_packet().unwrap() {
+                return frame;
+            }
+            let read = stream.read(&mut buf).unwrap();
+            assert!(read > 0, "the server closed the connection");
+            decoder.queue_slice(&buf[..read]);
+        }
+    }
+
+    #[test]
+    fn test_session_server_url() {
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        let session = callbacks(DEFAULT_SESSION_SERVER.to_string(), false);
+        assert_eq!(
+            "https://sessionserver.mojang.com/session/minecraft/hasJoined?username=Notch&serverId=-4fa3",
+            session.session_server_url("Notch", "-4fa3", ip)
+        );
+
+        let session = callbacks(DEFAULT_SESSION_SERVER.to_string(), true);
+        assert!(session
+            .session_server_url("Notch", "-4fa3", ip)
+            .ends_with("&ip=10.0.0.1"));
+    }
+
+    #[test]
+    fn test_join_dead() {
+        let dir =
+            std::env::temp_dir().join(format!("justmine-test-join-dead-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut scenario = ScenarioSingleClient::new();
+        let layer = scenario.layer;
+        let mut registry = WorldRegistry::new("world");
+        registry.insert("world", layer);
+        scenario.app.world.entity_mut(layer).insert(WorldInfo {
+            name: "world".to_string(),
+            spawn: DVec3::new(0.5, 1.0, 0.5),
+            rules: Default::default(),
+        });
+
+        let (bundle, _) = create_mock_client("ghost");
+        let client = scenario.app.world.spawn(bundle).id();
+        let uuid = *scenario.app.world.get::<UniqueId>(client).unwrap();
+        let storage = PlayerStorage::new(&dir);
+        let data = PlayerData {
+            world: "world".to_string(),
+            position: DVec3::new(5.5, 1.0, 5.5),
+            yaw: 0.0,
+            pitch: 0.0,
+            game_mode: GameMode::Survival,
+            health: 0.0,
+            food: 20,
+            saturation: 5.0,
+            experience: 0,
+            inventory: vec![],
+            spawn_point: None,
+        };
+        storage.save(&uuid, &data).unwrap();
+
+        scenario
+            .app
+            .insert_resource(ServerConfig::default())
+            .insert_resource(PermissionStore::default())
+            .insert_resource(storage)
+            .insert_resource(registry)
+            .add_event::<BlockChangeEvent>()
+            .add_systems(Update, (accept_connection, respawn).chain());
+        scenario.app.update();
+        assert!(scenario.app.world.get::<Dead>(client).is_some());
+
+        scenario
+            .app
+            .world
+            .send_event(RequestRespawnEvent { client });
+        scenario.app.update();
+        assert!(scenario.app.world.get::<Dead>(client).is_none());
+        assert_eq!(
+            Some(&Health::default()),
+            scenario.app.world.get::<Health>(client)
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Logs in like an online mode client, so that valence verifies the player with
+    /// the session server of the callbacks.
+    #[test]
+    fn test_online_login() {
+        let (session_server, requests) = mock_session_server();
+        let address = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap()
+        };
+
+        let mut app = App::new();
+        app.insert_resource(NetworkSettings {
+            address,
+            connection_mode: ConnectionMode::Online {
+                prevent_proxy_connections: true,
+            },
+            callbacks: callbacks(session_server, true).into(),
+            ..Default::default()
+        })
+        .add_plugins(DefaultPlugins.build().disable::<LogPlugin>());
+        app.update();
+
+        let mut stream = connect(address);
+        let mut encoder = PacketEncoder::new();
+        encoder
+            .append_packet(&HandshakeC2s {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: Bounded("localhost"),
+                server_port: address.port(),
+                next_state: HandshakeNextState::Login,
+            })
+            .unwrap();
+        encoder
+            .append_packet(&LoginHelloC2s {
+                username: Bounded("Notch"),
+                profile_id: None,
+            })
+            .unwrap();
+        stream.write_all(&encoder.take()).unwrap();
+
+        let mut decoder = PacketDecoder::new();
+        let frame = read_packet(&mut stream, &mut decoder);
+        let hello = frame.decode::<LoginHelloS2c>().unwrap();
+        let key = RsaPublicKey::from_public_key_der(hello.public_key).unwrap();
+        let mut rng = rand::thread_rng();
+        let secret = rand::random::<[u8; 16]>();
+        let shared_secret = key.encrypt(&mut rng, Pkcs1v15Encrypt, &secret).unwrap();
+        let verify_token = key
+            .encrypt(&mut rng, Pkcs1v15Encrypt, hello.verify_token)
+            .unwrap();
+        encoder
+            .append_packet(&LoginKeyC2s {
+                shared_secret: &shared_secret,
+                verify_token: &verify_token,
+            })
+            .unwrap();
+        stream.write_all(&encoder.take()).unwrap();
+
+        let request = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(request.starts_with("GET /session/minecraft/hasJoined?username=Notch&serverId="));
+        assert!(request.ends_with("&ip=127.0.0.1 HTTP/1.1"));
+
+        // the client gets the UUID from the profile that the session server returned
+        for _ in 0..500 {
+            app.update();
+            let mut clients = app.world.query::<(&UniqueId, &Username)>();
+            if let Some((id, username)) = clients.iter(&app.world).next() {
+                assert_eq!(UniqueId(Uuid::from_u128(PROFILE_ID)), *id);
+                assert_eq!("Notch", username.0);
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("the client did not join");
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{CommandSource, ExecuteCommandEvent};
+use bevy_app::AppExit;
+use bevy_ecs::prelude::*;
+use log::{info, warn};
+use std::borrow::Cow;
+use std::io::BufRead;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use valence::prelude::*;
+use valence::protocol::packets::play::DisconnectS2c;
+use valence::protocol::WritePacket;
+
+/// The reason that clients see when the server shuts down.
+pub const SHUTDOWN_MESSAGE: &str = "Server closed";
+
+/// The command lines that were typed into the terminal.
+#[derive(Resource, Debug)]
+pub struct Console {
+    lines: Mutex<Receiver<String>>,
+}
+
+impl Console {
+    /// Reads lines from stdin on a separate thread. Without a terminal stdin is
+    /// closed, and the console just never has any lines.
+    pub fn spawn() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self::new(receiver)
+    }
+
+    pub fn new(lines: Receiver<String>) -> Self {
+        Self {
+            lines: Mutex::new(lines),
+        }
+    }
+}
+
+/// Set when the process receives SIGINT or SIGTERM.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct ShutdownSignal(Arc<AtomicBool>);
+
+impl ShutdownSignal {
+    /// Handles SIGINT and SIGTERM by shutting down gracefully. A second signal exits
+    /// right away, in case the shutdown hangs.
+    pub fn install() -> Self {
+        let signal = Self::default();
+        let flag = signal.0.clone();
+        let installed = ctrlc::set_handler(move || {
+            if flag.swap(true, Ordering::SeqCst) {
+                std::process::exit(1);
+            }
+        });
+        if let Err(e) = installed {
+            warn!("unable to handle shutdown signals: {}", e);
+        }
+        signal
+    }
+
+    pub fn trigger(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Runs the lines from the console as commands of the console. A leading slash is
+/// optional.
+pub fn read_console(console: Res<Console>, mut commands: EventWriter<ExecuteCommandEvent>) {
+    let lines = console.lines.lock().unwrap_or_else(|e| e.into_inner());
+    for line in lines.try_iter() {
+        let line = line.trim();
+        let command = line.strip_prefix('/').unwrap_or(line);
+        if command.is_empty() {
+            continue;
+        }
+        info!("console issued server command: /{}", command);
+        commands.send(ExecuteCommandEvent {
+            source: CommandSource::Console,
+            command: command.to_string(),
+        });
+    }
+}
+
+/// Exits the app once a shutdown signal was received, which saves everything like
+/// `/stop` does.
+pub fn handle_shutdown_signal(
+    signal: Res<ShutdownSignal>,
+    mut exiting: Local<bool>,
+    mut exit: EventWriter<AppExit>,
+) {
+    if signal.is_triggered() && !*exiting {
+        *exiting = true;
+        info!("received a shutdown signal, stopping the server");
+        exit.send(AppExit);
+    }
+}
+
+/// Disconnects all clients when the app is about to exit. Must run before the
+/// packets are flushed, and leaves the clients in place so that they are saved.
+pub fn disconnect_clients_on_exit(mut exit: EventReader<AppExit>, mut clients: Query<&mut Client>) {
+    if exit.iter().count() == 0 {
+        return;
+    }
+
+    clients.for_each_mut(|mut client| {
+        client.write_packet(&DisconnectS2c {
+            reason: Cow::Owned(Text::from(SHUTDOWN_MESSAGE.to_string())),
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_console() {
+        let (sender, receiver) = mpsc::channel();
+        let mut app = App::new();
+        app.insert_resource(Console::new(receiver))
+            .add_event::<ExecuteCommandEvent>()
+            .add_systems(Update, read_console);
+
+        for line in ["/time set day", "", "  weather clear  ", "/"] {
+            sender.send(line.to_string()).unwrap();
+        }
+        app.update();
+
+        let events = app.world.resource::<Events<ExecuteCommandEvent>>();
+        let commands = events
+            .iter_current_update_events()
+            .map(|event| {
+                assert_eq!(CommandSource::Console, event.source);
+                event.command.as_str()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["time set day", "weather clear"], commands);
+    }
+
+    #[test]
+    fn test_shutdown_signal() {
+        let signal = ShutdownSignal::default();
+        let mut app = App::new();
+        app.insert_resource(signal.clone())
+            .add_event::<AppExit>()
+            .add_systems(Update, handle_shutdown_signal);
+
+        let exits = |app: &App| {
+            app.world
+                .resource::<Events<AppExit>>()
+                .iter_current_update_events()
+                .count()
+        };
+        app.update();
+        assert_eq!(0, exits(&app));
+
+        signal.trigger();
+        app.update();
+        assert_eq!(1, exits(&app));
+        app.update();
+        assert_eq!(0, exits(&app));
+    }
+}
@@ -0,0 +1,954 @@
+use crate::{Tool, ToolKind, ToolTier};
+use valence::prelude::*;
+
+const WOOD_TYPES: [&str; 11] = [
+    "oak", "spruce", "birch", "jungle", "acacia", "dark_oak", "mangrove", "cherry", "bamboo",
+    "crimson", "warped",
+];
+
+/// Returns how long it takes to break the given block by hand, or `None` if the
+/// block can't be broken outside of creative mode. The values are vanilla's.
+pub fn hardness(kind: BlockKind) -> Option<f32> {
+    let name = kind.to_str();
+    let hardness = match name {
+        "bedrock"
+        | "barrier"
+        | "light"
+        | "end_portal"
+        | "end_portal_frame"
+        | "end_gateway"
+        | "nether_portal"
+        | "command_block"
+        | "chain_command_block"
+        | "repeating_command_block"
+        | "structure_block"
+        | "jigsaw"
+        | "moving_piston" => return None,
+        "air" | "cave_air" | "void_air" | "water" | "lava" | "bubble_column" => return None,
+        "obsidian" | "crying_obsidian" | "netherite_block" | "respawn_anchor" => 50.0,
+        "reinforced_deepslate" => 55.0,
+        "ancient_debris" => 30.0,
+        "ender_chest" => 22.5,
+        "iron_block" | "diamond_block" | "emerald_block" | "redstone_block" | "coal_block"
+        | "raw_iron_block" | "raw_copper_block" | "raw_gold_block" | "iron_door"
+        | "iron_trapdoor" | "iron_bars" | "spawner" | "anvil" | "chipped_anvil"
+        | "damaged_anvil" | "enchanting_table" | "bell" | "chain" => 5.0,
+        "furnace"
+        | "blast_furnace"
+        | "smoker"
+        | "dispenser"
+        | "dropper"
+        | "lantern"
+        | "soul_lantern"
+        | "stonecutter"
+        | "lodestone"
+        | "cobbled_deepslate"
+        | "polished_deepslate"
+        | "deepslate_bricks"
+        | "cracked_deepslate_bricks"
+        | "deepslate_tiles"
+        | "cracked_deepslate_tiles"
+        | "chiseled_deepslate" => 3.5,
+        "deepslate" | "gold_block" | "lapis_block" | "end_stone" | "end_stone_bricks"
+        | "beacon" | "dragon_egg" | "observer" | "hopper" | "conduit" | "lightning_rod"
+        | "sculk_catalyst" | "sculk_shrieker" | "iron_ore" | "coal_ore" | "copper_ore"
+        | "gold_ore" | "lapis_ore" | "redstone_ore" | "diamond_ore" | "emerald_ore"
+        | "nether_gold_ore" | "nether_quartz_ore" => 3.0,
+        "blue_ice" => 2.8,
+        "chest" | "trapped_chest" | "crafting_table" | "barrel" | "lectern" | "loom"
+        | "cartography_table" | "fletching_table" | "smithing_table" => 2.5,
+        "cobblestone"
+        | "mossy_cobblestone"
+        | "bricks"
+        | "smooth_stone"
+        | "jukebox"
+        | "campfire"
+        | "soul_campfire"
+        | "cauldron"
+        | "water_cauldron"
+        | "lava_cauldron"
+        | "powder_snow_cauldron"
+        | "grindstone"
+        | "bone_block"
+        | "nether_bricks"
+        | "red_nether_bricks"
+        | "cracked_nether_bricks"
+        | "chiseled_nether_bricks"
+        | "polished_blackstone"
+        | "smooth_quartz"
+        | "bamboo_block"
+        | "stripped_bamboo_block"
+        | "bamboo_mosaic"
+        | "smooth_sandstone"
+        | "smooth_red_sandstone" => 2.0,
+        "basalt" | "polished_basalt" | "smooth_basalt" => 1.25,
+        "bamboo"
+        | "bamboo_sapling"
+        | "melon"
+        | "pumpkin"
+        | "carved_pumpkin"
+        | "jack_o_lantern"
+        | "nether_wart_block"
+        | "warped_wart_block"
+        | "shroomlight"
+        | "packed_mud"
+        | "infested_cobblestone" => 1.0,
+        "sandstone"
+        | "chiseled_sandstone"
+        | "cut_sandstone"
+        | "red_sandstone"
+        | "chiseled_red_sandstone"
+        | "cut_red_sandstone"
+        | "quartz_block"
+        | "quartz_bricks"
+        | "quartz_pillar"
+        | "chiseled_quartz_block"
+        | "note_block" => 0.8,
+        "calcite"
+        | "infested_stone"
+        | "infested_stone_bricks"
+        | "infested_mossy_stone_bricks"
+        | "infested_cracked_stone_bricks"
+        | "infested_chiseled_stone_bricks" => 0.75,
+        "rail"
+        | "powered_rail"
+        | "detector_rail"
+        | "activator_rail"
+        | "mangrove_roots"
+        | "muddy_mangrove_roots" => 0.7,
+        "dirt_path" => 0.65,
+        "grass_block" | "mycelium" | "gravel" | "clay" | "farmland" | "sponge" | "wet_sponge"
+        | "composter" | "beehive" | "honeycomb_block" => 0.6,
+        "dirt"
+        | "coarse_dirt"
+        | "rooted_dirt"
+        | "podzol"
+        | "mud"
+        | "sand"
+        | "red_sand"
+        | "soul_sand"
+        | "soul_soil"
+        | "ice"
+        | "packed_ice"
+        | "frosted_ice"
+        | "hay_block"
+        | "cake"
+        | "lever"
+        | "magma_block"
+        | "dried_kelp_block"
+        | "target"
+        | "brewing_stand"
+        | "turtle_egg"
+        | "sniffer_egg"
+        | "stone_button"
+        | "polished_blackstone_button"
+        | "stone_pressure_plate"
+        | "polished_blackstone_pressure_plate"
+        | "light_weighted_pressure_plate"
+        | "heavy_weighted_pressure_plate"
+        | "piston"
+        | "sticky_piston"
+        | "piston_head" => 0.5,
+        "netherrack" | "crimson_nylium" | "warped_nylium" | "ladder" | "cactus"
+        | "chorus_plant" | "chorus_flower" => 0.4,
+        "glass"
+        | "tinted_glass"
+        | "glass_pane"
+        | "glowstone"
+        | "sea_lantern"
+        | "redstone_lamp"
+        | "bee_nest"
+        | "ochre_froglight"
+        | "verdant_froglight"
+        | "pearlescent_froglight" => 0.3,
+        "powder_snow" | "suspicious_sand" | "suspicious_gravel" => 0.25,
+        "vine"
+        | "glow_lichen"
+        | "cocoa"
+        | "snow_block"
+        | "daylight_detector"
+        | "brown_mushroom_block"
+        | "red_mushroom_block"
+        | "mushroom_stem"
+        | "sculk"
+        | "sculk_vein" => 0.2,
+        "snow" | "moss_block" | "moss_carpet" | "big_dripleaf" | "big_dripleaf_stem" => 0.1,
+        "cobweb" => 4.0,
+        "grass"
+        | "tall_grass"
+        | "fern"
+        | "large_fern"
+        | "dead_bush"
+        | "seagrass"
+        | "tall_seagrass"
+        | "kelp"
+        | "kelp_plant"
+        | "lily_pad"
+        | "sugar_cane"
+        | "sweet_berry_bush"
+        | "torch"
+        | "wall_torch"
+        | "soul_torch"
+        | "soul_wall_torch"
+        | "redstone_torch"
+        | "redstone_wall_torch"
+        | "redstone_wire"
+        | "repeater"
+        | "comparator"
+        | "tripwire"
+        | "tripwire_hook"
+        | "tnt"
+        | "fire"
+        | "soul_fire"
+        | "wheat"
+        | "carrots"
+        | "potatoes"
+        | "beetroots"
+        | "nether_wart"
+        | "torchflower_crop"
+        | "pitcher_crop"
+        | "pitcher_plant"
+        | "slime_block"
+        | "honey_block"
+        | "scaffolding"
+        | "flower_pot"
+        | "decorated_pot"
+        | "frogspawn"
+        | "structure_void"
+        | "end_rod"
+        | "sea_pickle"
+        | "dandelion"
+        | "poppy"
+        | "blue_orchid"
+        | "allium"
+        | "azure_bluet"
+        | "oxeye_daisy"
+        | "cornflower"
+        | "lily_of_the_valley"
+        | "wither_rose"
+        | "torchflower"
+        | "pink_petals"
+        | "sunflower"
+        | "lilac"
+        | "rose_bush"
+        | "peony"
+        | "brown_mushroom"
+        | "red_mushroom"
+        | "crimson_fungus"
+        | "warped_fungus"
+        | "crimson_roots"
+        | "warped_roots"
+        | "nether_sprouts"
+        | "hanging_roots"
+        | "azalea"
+        | "flowering_azalea"
+        | "spore_blossom"
+        | "small_dripleaf"
+        | "cave_vines"
+        | "cave_vines_plant"
+        | "twisting_vines"
+        | "twisting_vines_plant"
+        | "weeping_vines"
+        | "weeping_vines_plant"
+        | "mangrove_propagule"
+        | "pumpkin_stem"
+        | "melon_stem"
+        | "attached_pumpkin_stem"
+        | "attached_melon_stem" => 0.0,
+        _ if name.starts_with("deepslate_") && name.ends_with("_ore") => 4.5,
+        _ if name.ends_with("_tulip")
+            || name.ends_with("_sapling")
+            || name.starts_with("potted_")
+            || name.ends_with("_coral")
+            || name.ends_with("_coral_fan")
+            || name.ends_with("_coral_wall_fan") =>
+        {
+            0.0
+        }
+        _ if name.ends_with("candle") || name.ends_with("_candle_cake") => 0.1,
+        _ if name.ends_with("_leaves") || name.ends_with("_bed") => 0.2,
+        _ if name.ends_with("_stained_glass") || name.ends_with("_stained_glass_pane") => 0.3,
+        _ if name.ends_with("_carpet") => 0.1,
+        _ if name.ends_with("_wool") => 0.8,
+        _ if name.ends_with("_concrete_powder") => 0.5,
+        _ if name.ends_with("_concrete") => 1.8,
+        _ if name.ends_with("_glazed_terracotta") => 1.4,
+        _ if name.ends_with("terracotta") => 1.25,
+        _ if name.ends_with("_button") || name.ends_with("_pressure_plate") => 0.5,
+        _ if name.ends_with("_sign") || name.ends_with("_banner") => 1.0,
+        _ if name.ends_with("_head") || name.ends_with("_skull") => 1.0,
+        _ if name.ends_with("_door") || name.ends_with("_trapdoor") => 3.0,
+        _ if name.ends_with("_log")
+            || name.ends_with("_wood")
+            || name.ends_with("_stem")
+            || name.ends_with("_hyphae")
+            || name.ends_with("_planks")
+            || name.ends_with("_fence")
+            || name.ends_with("_fence_gate") =>
+        {
+            2.0
+        }
+        _ if name.ends_with("shulker_box") => 2.0,
+        _ if name.contains("copper") => 3.0,
+        _ if is_wooden(name) => 2.0,
+        _ if name.ends_with("_slab") => return slab_hardness(name),
+        // stairs and walls are as hard as the block they are made of
+        _ if name.ends_with("_stairs") || name.ends_with("_wall") => {
+            return shape_base(name).map_or(Some(1.5), hardness)
+        }
+        // the remaining blocks are stone variants, like andesite, prismarine and
+        // purpur, and blocks like amethyst and dripstone
+        _ => 1.5,
+    };
+    Some(hardness)
+}
+
+/// The hardness of a slab. Most slabs are as hard as their block, the older stone
+/// slabs all have the same hardness.
+fn slab_hardness(name: &str) -> Option<f32> {
+    match name {
+        "stone_slab"
+        | "smooth_stone_slab"
+        | "sandstone_slab"
+        | "cut_sandstone_slab"
+        | "red_sandstone_slab"
+        | "cut_red_sandstone_slab"
+        | "petrified_oak_slab"
+        | "cobblestone_slab"
+        | "brick_slab"
+        | "stone_brick_slab"
+        | "nether_brick_slab"
+        | "quartz_slab"
+        | "purpur_slab" => Some(2.0),
+        _ => shape_base(name).map_or(Some(1.5), hardness),
+    }
+}
+
+/// The block that a slab, stair or wall is made of, like `stone_bricks` for
+/// `stone_brick_stairs`.
+fn shape_base(name: &str) -> Option<BlockKind> {
+    let base = ["_slab", "_stairs", "_wall"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))?;
+    [
+        base.to_string(),
+        format!("{}s", base),
+        format!("{}_block", base),
+        format!("{}_planks", base),
+    ]
+    .iter()
+    .find_map(|name| BlockKind::from_str(name))
+}
+
+/// Returns the kind of tool that breaks the given block faster than the hand, if any.
+pub fn preferred_tool(kind: BlockKind) -> Option<ToolKind> {
+    let name = kind.to_str();
+    if name == "cobweb" {
+        Some(ToolKind::Sword)
+    } else if name.ends_with("_leaves") || name.ends_with("_wool") {
+        Some(ToolKind::Shears)
+    } else if is_shovel_block(name) {
+        Some(ToolKind::Shovel)
+    } else if is_hoe_block(name) {
+        Some(ToolKind::Hoe)
+    } else if is_wooden(name) || is_axe_block(name) {
+        Some(ToolKind::Axe)
+    } else if is_pickaxe_block(name) {
+        Some(ToolKind::Pickaxe)
+    } else {
+        None
+    }
+}
+
+/// Returns the lowest pickaxe tier that the given block drops anything with, or
+/// `None` if it can be harvested by any tool or by hand.
+pub fn required_tier(kind: BlockKind) -> Option<ToolTier> {
+    let name = kind.to_str();
+    if !is_pickaxe_block(name) || !requires_pickaxe(name) {
+        return None;
+    }
+
+    let base = name.strip_prefix("deepslate_").unwrap_or(name);
+    let tier = match base {
+        "obsidian" | "crying_obsidian" | "netherite_block" | "ancient_debris"
+        | "respawn_anchor" => ToolTier::Diamond,
+        "gold_ore" | "gold_block" | "raw_gold_block" | "diamond_ore" | "diamond_block"
+        | "emerald_ore" | "emerald_block" | "redstone_ore" => ToolTier::Iron,
+        "iron_ore" | "iron_block" | "raw_iron_block" | "lapis_ore" | "lapis_block"
+        | "copper_ore" | "raw_copper_block" => ToolTier::Stone,
+        _ if name.contains("copper") => ToolTier::Stone,
+        _ => ToolTier::Wood,
+    };
+    Some(tier)
+}
+
+/// Returns the number of ticks it takes to break the given block with the given
+/// tool, or `None` if the block can't be broken. A result of 0 means that the
+/// block breaks instantly.
+pub fn break_ticks(kind: BlockKind, tool: Option<Tool>) -> Option<u32> {
+    let hardness = hardness(kind)?;
+    if hardness == 0.0 {
+        return Some(0);
+    }
+
+    let preferred = preferred_tool(kind);
+    let mut speed = match tool {
+        Some(tool) if Some(tool.kind) == preferred => match (tool.kind, tool.tier) {
+            (ToolKind::Shears, _) if kind.to_str().ends_with("_wool") => 5.0,
+            (ToolKind::Shears, _) => 15.0,
+            (ToolKind::Sword, _) => 15.0,
+            (_, Some(tier)) => tier.speed(),
+            (_, None) => 1.0,
+        },
+        _ => 1.0,
+    };
+    if let Some(tool) = tool {
+        if speed > 1.0 && tool.efficiency > 0 {
+            speed += (tool.efficiency as f32).powi(2) + 1.0;
+        }
+    }
+
+    // vanilla adds `speed / hardness / divisor` to the break progress every tick
+    // and breaks the block once the progress reaches 1
+    let divisor = if can_harvest(kind, tool) { 30.0 } else { 100.0 };
+    let ticks = hardness * divisor / speed;
+    if ticks < 1.0 {
+        return Some(0);
+    }
+    Some(ticks.ceil() as u32)
+}
+
+/// Returns whether breaking the block with the given tool drops anything.
+pub fn can_harvest(kind: BlockKind, tool: Option<Tool>) -> bool {
+    let name = kind.to_str();
+    if name == "cobweb" {
+        return matches!(
+            tool.map(|t| t.kind),
+            Some(ToolKind::Sword | ToolKind::Shears)
+        );
+    }
+
+    let Some(required) = required_tier(kind) else {
+        return true;
+    };
+    match tool {
+        Some(Tool {
+            kind: ToolKind::Pickaxe,
+            tier: Some(tier),
+            ..
+        }) => tier.level() >= required.level(),
+        _ => false,
+    }
+}
+
+/// Returns whether placing a block at the position of the given block replaces
+/// it, like it happens with grass, water or a thin layer of snow.
+pub fn is_replaceable(state: BlockState) -> bool {
+    let name = state.to_kind().to_str();
+    match name {
+        "snow" => state.get(PropName::Layers) == Some(PropValue::_1),
+        "air" | "cave_air" | "void_air" | "water" | "lava" | "bubble_column" | "grass"
+        | "tall_grass" | "fern" | "large_fern" | "dead_bush" | "seagrass" | "tall_seagrass"
+        | "vine" | "glow_lichen" | "fire" | "soul_fire" | "structure_void" | "light"
+        | "hanging_roots" | "crimson_roots" | "warped_roots" | "nether_sprouts" => true,
+        _ => false,
+    }
+}
+
+/// Returns whether entities collide with the given block. Blocks that have a
+/// collision are treated as full cubes.
+pub fn has_collision(kind: BlockKind) -> bool {
+    let name = kind.to_str();
+    if is_replaceable(BlockState::from_kind(kind)) && name != "snow" {
+        return false;
+    }
+
+    !(name.contains("torch")
+        || name.contains("sign")
+        || name.ends_with("_banner")
+        || name.ends_with("_button")
+        || name.ends_with("_pressure_plate")
+        || name.ends_with("rail")
+        || name.ends_with("_sapling")
+        || name.ends_with("_tulip")
+        || name.ends_with("_mushroom")
+        || name.ends_with("_coral")
+        || name.ends_with("_coral_fan")
+        || name.ends_with("_coral_wall_fan")
+        || matches!(
+            name,
+            "lever"
+                | "redstone_wire"
+                | "tripwire"
+                | "tripwire_hook"
+                | "cobweb"
+                | "sugar_cane"
+                | "kelp"
+                | "kelp_plant"
+                | "wheat"
+                | "carrots"
+                | "potatoes"
+                | "beetroots"
+                | "nether_wart"
+                | "sweet_berry_bush"
+                | "dandelion"
+                | "poppy"
+                | "blue_orchid"
+                | "allium"
+                | "azure_bluet"
+                | "oxeye_daisy"
+                | "cornflower"
+                | "lily_of_the_valley"
+                | "wither_rose"
+                | "torchflower"
+                | "sunflower"
+                | "lilac"
+                | "rose_bush"
+                | "peony"
+                | "nether_portal"
+                | "end_portal"
+        ))
+}
+
+/// Returns whether players can climb the given block, which also stops their fall.
+pub fn is_climbable(kind: BlockKind) -> bool {
+    matches!(
+        kind.to_str(),
+        "ladder"
+            | "vine"
+            | "scaffolding"
+            | "twisting_vines"
+            | "twisting_vines_plant"
+            | "weeping_vines"
+            | "weeping_vines_plant"
+            | "cave_vines"
+            | "cave_vines_plant"
+    )
+}
+
+/// Returns whether the given block is a full, solid cube that other blocks can
+/// attach to and that fences and walls connect to.
+pub fn is_solid(kind: BlockKind) -> bool {
+    let name = kind.to_str();
+    has_collision(kind)
+        && !(name.ends_with("_slab")
+            || name.ends_with("_stairs")
+            || name.ends_with("_fence")
+            || name.ends_with("_fence_gate")
+            || name.ends_with("_wall")
+            || name.ends_with("glass_pane")
+            || name.ends_with("_door")
+            || name.ends_with("_trapdoor")
+            || name.ends_with("_bed")
+            || name.ends_with("_carpet")
+            || name.ends_with("_head")
+            || name.ends_with("_skull")
+            || name.ends_with("candle")
+            || name.ends_with("chest")
+            || name.ends_with("anvil")
+            || name.ends_with("_amethyst_bud")
+            || name.starts_with("potted_")
+            || matches!(
+                name,
+                "snow"
+                    | "iron_bars"
+                    | "chain"
+                    | "cactus"
+                    | "cake"
+                    | "lantern"
+                    | "soul_lantern"
+                    | "hopper"
+                    | "cauldron"
+                    | "ladder"
+                    | "flower_pot"
+                    | "farmland"
+                    | "dirt_path"
+                    | "scaffolding"
+                    | "end_rod"
+                    | "lightning_rod"
+                    | "amethyst_cluster"
+                    | "enchanting_table"
+                    | "brewing_stand"
+                    | "daylight_detector"
+                    | "repeater"
+                    | "comparator"
+                    | "bell"
+                    | "lectern"
+                    | "stonecutter"
+                    | "grindstone"
+                    | "campfire"
+                    | "soul_campfire"
+                    | "composter"
+                    | "conduit"
+                    | "sea_pickle"
+                    | "turtle_egg"
+                    | "piston_head"
+                    | "pointed_dripstone"
+                    | "bamboo"
+                    | "lily_pad"
+                    | "decorated_pot"
+            ))
+}
+
+fn is_wooden(name: &str) -> bool {
+    WOOD_TYPES.iter().any(|wood| {
+        name.strip_prefix(wood)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .is_some_and(|rest| {
+                [
+                    "planks",
+                    "door",
+                    "trapdoor",
+                    "fence",
+                    "fence_gate",
+                    "sign",
+                    "wall_sign",
+                    "hanging_sign",
+                    "wall_hanging_sign",
+                    "pressure_plate",
+                    "button",
+                    "slab",
+                    "stairs",
+                ]
+                .contains(&rest)
+            })
+    })
+}
+
+fn is_axe_block(name: &str) -> bool {
+    name.ends_with("_log")
+        || name.ends_with("_wood")
+        || name.ends_with("_stem")
+        || name.ends_with("_hyphae")
+        || name.ends_with("_mushroom_block")
+        || name.ends_with("_banner")
+        || matches!(
+            name,
+            "crafting_table"
+                | "chest"
+                | "trapped_chest"
+                | "barrel"
+                | "bookshelf"
+                | "chiseled_bookshelf"
+                | "lectern"
+                | "note_block"
+                | "jukebox"
+                | "ladder"
+                | "campfire"
+                | "soul_campfire"
+                | "loom"
+                | "cartography_table"
+                | "fletching_table"
+                | "smithing_table"
+                | "composter"
+                | "beehive"
+                | "bee_nest"
+                | "pumpkin"
+                | "carved_pumpkin"
+                | "jack_o_lantern"
+                | "melon"
+                | "cocoa"
+                | "daylight_detector"
+        )
+}
+
+fn is_shovel_block(name: &str) -> bool {
+    name.ends_with("_concrete_powder")
+        || matches!(
+            name,
+            "dirt"
+                | "grass_block"
+                | "coarse_dirt"
+                | "podzol"
+                | "mycelium"
+                | "rooted_dirt"
+                | "farmland"
+                | "dirt_path"
+                | "sand"
+                | "red_sand"
+                | "gravel"
+                | "clay"
+                | "snow"
+                | "snow_block"
+                | "powder_snow"
+                | "soul_sand"
+                | "soul_soil"
+                | "mud"
+                | "muddy_mangrove_roots"
+        )
+}
+
+fn is_hoe_block(name: &str) -> bool {
+    name.starts_with("sculk")
+        || matches!(
+            name,
+            "hay_block"
+                | "sponge"
+                | "wet_sponge"
+                | "target"
+                | "dried_kelp_block"
+                | "nether_wart_block"
+                | "warped_wart_block"
+                | "shroomlight"
+                | "moss_block"
+                | "moss_carpet"
+        )
+}
+
+fn is_pickaxe_block(name: &str) -> bool {
+    if name.contains("redstone_") && !name.ends_with("_ore") && name != "redstone_block" {
+        // redstone wire, torches and lamps
+        return false;
+    }
+
+    name.ends_with("_ore")
+        || name.contains("stone")
+        || name.contains("deepslate")
+        || name.contains("brick")
+        || name.contains("andesite")
+        || name.contains("diorite")
+        || name.contains("granite")
+        || name.contains("obsidian")
+        || name.contains("basalt")
+        || name.contains("terracotta")
+        || name.contains("quartz")
+        || name.contains("prismarine")
+        || name.contains("purpur")
+        || name.contains("copper")
+        || name.contains("amethyst")
+        || name.contains("anvil")
+        || name.ends_with("_concrete")
+        || name.ends_with("_shulker_box")
+        || name.ends_with("rail")
+        || matches!(
+            name,
+            "iron_block"
+                | "gold_block"
+                | "diamond_block"
+                | "emerald_block"
+                | "netherite_block"
+                | "lapis_block"
+                | "redstone_block"
+                | "coal_block"
+                | "raw_iron_block"
+                | "raw_copper_block"
+                | "raw_gold_block"
+                | "iron_door"
+                | "iron_trapdoor"
+                | "iron_bars"
+                | "ancient_debris"
+                | "netherrack"
+                | "nether_bricks"
+                | "respawn_anchor"
+                | "furnace"
+                | "blast_furnace"
+                | "smoker"
+                | "dispenser"
+                | "dropper"
+                | "observer"
+                | "hopper"
+                | "cauldron"
+                | "bell"
+                | "lantern"
+                | "soul_lantern"
+                | "chain"
+                | "spawner"
+                | "enchanting_table"
+                | "ender_chest"
+                | "beacon"
+                | "conduit"
+                | "ice"
+                | "packed_ice"
+                | "blue_ice"
+                | "magma_block"
+                | "calcite"
+                | "tuff"
+                | "dripstone_block"
+                | "pointed_dripstone"
+                | "shulker_box"
+                | "bone_block"
+                | "smooth_stone"
+                | "piston"
+                | "sticky_piston"
+                | "heavy_weighted_pressure_plate"
+                | "light_weighted_pressure_plate"
+        )
+}
+
+/// Pickaxe blocks that still drop without a pickaxe, they are only mined faster with one.
+fn requires_pickaxe(name: &str) -> bool {
+    !(name.ends_with("rail")
+        || name.ends_with("_button")
+        || matches!(
+            name,
+            "ice"
+                | "packed_ice"
+                | "blue_ice"
+                | "piston"
+                | "sticky_piston"
+                | "glowstone"
+                | "hopper"
+                | "cauldron"
+                | "ladder"
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pickaxe(tier: ToolTier) -> Option<Tool> {
+        Some(Tool {
+            kind: ToolKind::Pickaxe,
+            tier: Some(tier),
+            efficiency: 0,
+        })
+    }
+
+    #[test]
+    fn test_unbreakable() {
+        assert_eq!(None, break_ticks(BlockKind::Bedrock, None));
+        assert_eq!(None, break_ticks(BlockKind::Barrier, None));
+        assert_eq!(
+            None,
+            break_ticks(BlockKind::Bedrock, pickaxe(ToolTier::Netherite))
+        );
+    }
+
+    #[test]
+    fn test_break_ticks() {
+        assert_eq!(Some(0), break_ticks(BlockKind::Poppy, None));
+        assert_eq!(Some(0), break_ticks(BlockKind::Grass, None));
+        assert_eq!(Some(15), break_ticks(BlockKind::Dirt, None));
+        // stone can't be harvested by hand, so it takes much longer
+        assert_eq!(Some(150), break_ticks(BlockKind::Stone, None));
+        assert_eq!(
+            Some(23),
+            break_ticks(BlockKind::Stone, pickaxe(ToolTier::Wood))
+        );
+        assert_eq!(
+            Some(188),
+            break_ticks(BlockKind::Obsidian, pickaxe(ToolTier::Diamond))
+        );
+        // an iron pickaxe is fast on obsidian, but can't harvest it
+        assert_eq!(
+            Some(834),
+            break_ticks(BlockKind::Obsidian, pickaxe(ToolTier::Iron))
+        );
+        // a pickaxe doesn't help with dirt
+        assert_eq!(
+            Some(15),
+            break_ticks(BlockKind::Dirt, pickaxe(ToolTier::Diamond))
+        );
+    }
+
+    #[test]
+    fn test_hardness() {
+        for kind in [
+            BlockKind::CrimsonRoots,
+            BlockKind::NetherSprouts,
+            BlockKind::Azalea,
+            BlockKind::SporeBlossom,
+            BlockKind::CaveVines,
+            BlockKind::TwistingVinesPlant,
+            BlockKind::EndRod,
+            BlockKind::Tripwire,
+            BlockKind::WarpedFungus,
+            BlockKind::MangrovePropagule,
+        ] {
+            assert_eq!(Some(0.0), hardness(kind), "{:?}", kind);
+        }
+        assert_eq!(Some(0.1), hardness(BlockKind::MossBlock));
+        assert_eq!(Some(0.2), hardness(BlockKind::MushroomStem));
+        assert_eq!(Some(0.2), hardness(BlockKind::RedMushroomBlock));
+        assert_eq!(Some(0.2), hardness(BlockKind::GlowLichen));
+        assert_eq!(Some(2.0), hardness(BlockKind::CrimsonStem));
+        assert_eq!(Some(3.5), hardness(BlockKind::Furnace));
+        // shapes take the hardness of their block
+        assert_eq!(Some(2.0), hardness(BlockKind::CobblestoneWall));
+        assert_eq!(Some(1.5), hardness(BlockKind::StoneBrickStairs));
+        assert_eq!(Some(3.5), hardness(BlockKind::DeepslateTileSlab));
+        assert_eq!(Some(0.8), hardness(BlockKind::QuartzStairs));
+        assert_eq!(Some(2.0), hardness(BlockKind::BambooStairs));
+        assert_eq!(Some(2.0), hardness(BlockKind::StoneSlab));
+    }
+
+    #[test]
+    fn test_efficiency() {
+        let tool = Some(Tool {
+            kind: ToolKind::Pickaxe,
+            tier: Some(ToolTier::Diamond),
+            efficiency: 5,
+        });
+        assert_eq!(Some(2), break_ticks(BlockKind::Stone, tool));
+        // efficiency only applies to the right tool
+        assert_eq!(Some(15), break_ticks(BlockKind::Dirt, tool));
+        let shovel = Some(Tool {
+            kind: ToolKind::Shovel,
+            tier: Some(ToolTier::Diamond),
+            efficiency: 5,
+        });
+        assert_eq!(Some(0), break_ticks(BlockKind::Dirt, shovel));
+    }
+
+    #[test]
+    fn test_is_replaceable() {
+        assert!(is_replaceable(BlockState::AIR));
+        assert!(is_replaceable(BlockState::GRASS));
+        assert!(is_replaceable(BlockState::WATER));
+        assert!(is_replaceable(BlockState::SNOW));
+        assert!(!is_replaceable(
+            BlockState::SNOW.set(PropName::Layers, PropValue::_2)
+        ));
+        assert!(!is_replaceable(BlockState::STONE));
+        assert!(!is_replaceable(BlockState::POPPY));
+    }
+
+    #[test]
+    fn test_has_collision() {
+        assert!(has_collision(BlockKind::Stone));
+        assert!(has_collision(BlockKind::OakPlanks));
+        assert!(has_collision(BlockKind::Snow));
+        assert!(!has_collision(BlockKind::Air));
+        assert!(!has_collision(BlockKind::Torch));
+        assert!(!has_collision(BlockKind::OakSign));
+        assert!(!has_collision(BlockKind::Poppy));
+    }
+
+    #[test]
+    fn test_is_solid() {
+        assert!(is_solid(BlockKind::Stone));
+        assert!(is_solid(BlockKind::OakPlanks));
+        assert!(is_solid(BlockKind::Glass));
+        assert!(!is_solid(BlockKind::Air));
+        assert!(!is_solid(BlockKind::Torch));
+        assert!(!is_solid(BlockKind::OakSlab));
+        assert!(!is_solid(BlockKind::OakFence));
+        assert!(!is_solid(BlockKind::Snow));
+        assert!(!is_solid(BlockKind::Chest));
+    }
+
+    #[test]
+    fn test_can_harvest() {
+        assert!(can_harvest(BlockKind::Dirt, None));
+        assert!(can_harvest(BlockKind::OakLog, None));
+        assert!(!can_harvest(BlockKind::Stone, None));
+        assert!(can_harvest(BlockKind::Stone, pickaxe(ToolTier::Wood)));
+        assert!(!can_harvest(BlockKind::IronOre, pickaxe(ToolTier::Gold)));
+        assert!(can_harvest(BlockKind::IronOre, pickaxe(ToolTier::Stone)));
+        assert!(!can_harvest(
+            BlockKind::DeepslateDiamondOre,
+            pickaxe(ToolTier::Stone)
+        ));
+        assert!(can_harvest(
+            BlockKind::DeepslateDiamondOre,
+            pickaxe(ToolTier::Iron)
+        ));
+        assert!(!can_harvest(BlockKind::Obsidian, pickaxe(ToolTier::Iron)));
+        assert!(can_harvest(
+            BlockKind::Obsidian,
+            pickaxe(ToolTier::Netherite)
+        ));
+        assert!(can_harvest(BlockKind::RedstoneWire, None));
+    }
+}
@@ -1,147 +1,428 @@
+use crate::{
+    break_ticks, has_collision, other_part, BlockChangeEvent, Interaction, InteractionContext,
+    InteractionHandlers, OpenBlockScreenEvent, Permissions, PlacementContext, PlacementResolvers,
+    SleepEvent, Tool, WorldInfo,
+};
 use bevy_ecs::prelude::*;
+use std::borrow::Cow;
+use valence::client::VisibleChunkLayer;
+use valence::entity::entity::Flags;
+use valence::entity::EntityLayerId;
 use valence::interact_block::InteractBlockEvent;
 use valence::inventory::HeldItem;
+use valence::nbt::Compound;
 use valence::prelude::*;
+use valence::protocol::packets::play::{BlockUpdateS2c, ScreenHandlerSlotUpdateS2c};
+use valence::protocol::{VarInt, WritePacket};
+
+/// Clients may finish digging a little early, because their start and stop
+/// packets don't arrive exactly as far apart as they were sent.
+const DIG_TIME_TOLERANCE: f32 = 0.7;
+
+/// How far away from a player's eyes the center of a clicked block may be.
+pub const PLACE_REACH: f64 = 6.0;
+
+/// The permission that players need to break blocks.
+pub const BREAK_PERMISSION: &str = "justmine.build.break";
+/// The permission that players need to place and use blocks.
+pub const PLACE_PERMISSION: &str = "justmine.build.place";
+/// The permission that players need to change blocks near the spawn of a world.
+pub const SPAWN_PROTECTION_BYPASS: &str = "justmine.bypass.spawn_protection";
+
+const PLAYER_EYE_HEIGHT: f64 = 1.62;
+const PLAYER_WIDTH: f64 = 0.6;
+const PLAYER_HEIGHT: f64 = 1.8;
+
+/// Sent when a client broke a block, after the block was removed from the layer.
+#[derive(Event, Clone, Debug)]
+pub struct BlockBreakEvent {
+    pub client: Entity,
+    pub layer: Entity,
+    pub position: BlockPos,
+    /// The state of the block before it was broken.
+    pub state: BlockState,
+    /// The block entity data of the block before it was broken, like the contents
+    /// of a chest.
+    pub nbt: Option<Compound>,
+}
+
+/// The block that a survival client is currently digging.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct Digging {
+    pub position: BlockPos,
+    /// The server tick in which the client started digging.
+    pub started: i64,
+}
 
 pub fn remove_block(
-    clients: Query<&GameMode>,
+    mut commands: Commands,
+    mut clients: Query<(
+        &mut Client,
+        &GameMode,
+        &HeldItem,
+        &Inventory,
+        &VisibleChunkLayer,
+        Option<&Digging>,
+        Option<&Permissions>,
+    )>,
     mut layers: Query<&mut ChunkLayer>,
+    worlds: Query<&WorldInfo>,
+    server: Res<Server>,
     mut events: EventReader<DiggingEvent>,
+    mut changes: EventWriter<BlockChangeEvent>,
+    mut breaks: EventWriter<BlockBreakEvent>,
 ) {
-    let mut layer = layers.single_mut();
-
     events.iter().for_each(|event| {
-        let Ok(game_mode) = clients.get(event.client) else {
+        let Ok((
+            mut client,
+            game_mode,
+            held_item,
+            inventory,
+            visible_chunk_layer,
+            digging,
+            permissions,
+        )) = clients.get_mut(event.client)
+        else {
+            return;
+        };
+        let layer_entity = visible_chunk_layer.0;
+        let Ok(mut layer) = layers.get_mut(layer_entity) else {
+            return;
+        };
+        let Some(state) = layer.block(event.position).map(|block| block.state) else {
             return;
         };
 
-        if (*game_mode == GameMode::Creative && event.state == DiggingState::Start)
-            || (*game_mode == GameMode::Survival && event.state == DiggingState::Stop)
-        {
-            layer.set_block(event.position, BlockState::AIR);
+        let world = worlds.get(layer_entity).ok();
+        if !may_build(permissions, BREAK_PERMISSION, world, event.position) {
+            commands.entity(event.client).remove::<Digging>();
+            resync_blocks(&mut client, &layer, &[event.position]);
+            return;
+        }
+
+        let tool = Tool::from_stack(inventory.slot(held_item.slot()));
+        let ticks = break_ticks(state.to_kind(), tool);
+        let now = server.current_tick();
+
+        let (broken, resync) = match (*game_mode, event.state) {
+            (GameMode::Creative, DiggingState::Start) => (true, false),
+            (GameMode::Survival, DiggingState::Start) => match ticks {
+                // the client doesn't send a stop for blocks that break instantly
+                Some(0) => (true, false),
+                Some(_) => {
+                    commands.entity(event.client).insert(Digging {
+                        position: event.position,
+                        started: now,
+                    });
+                    (false, false)
+                }
+                None => (false, true),
+            },
+            (GameMode::Survival, DiggingState::Stop) => {
+                commands.entity(event.client).remove::<Digging>();
+                let finished = match (digging, ticks) {
+                    (Some(digging), Some(ticks)) if digging.position == event.position => {
+                        (now - digging.started) as f32 >= ticks as f32 * DIG_TIME_TOLERANCE
+                    }
+                    _ => false,
+                };
+                (finished, !finished)
+            }
+            (_, DiggingState::Abort) => {
+                commands.entity(event.client).remove::<Digging>();
+                (false, false)
+            }
+            // the client may already show the block as broken
+            (_, DiggingState::Start) => (false, true),
+            _ => (false, false),
+        };
+
+        if broken {
+            // doors, beds and tall plants break as a whole
+            let other = other_part(event.position, state)
+                .and_then(|position| Some((position, layer.block(position)?.state)))
+                .filter(|(_, other)| other.to_kind() == state.to_kind());
+            for (position, state) in std::iter::once((event.position, state)).chain(other) {
+                let nbt = layer
+                    .set_block(position, BlockState::AIR)
+                    .and_then(|block| block.nbt);
+                changes.send(BlockChangeEvent {
+                    layer: layer_entity,
+                    position,
+                });
+                breaks.send(BlockBreakEvent {
+                    client: event.client,
+                    layer: layer_entity,
+                    position,
+                    state,
+                    nbt,
+                });
+            }
+        } else if resync {
+            // the client already removed the block on its side, so tell it that it's still there
+            resync_blocks(&mut client, &layer, &[event.position]);
         }
     });
 }
 
 pub fn place_block(
-    mut clients: Query<(&GameMode, &HeldItem, &Look, &mut Inventory)>,
+    mut clients: Query<(
+        &mut Client,
+        &GameMode,
+        &HeldItem,
+        &Look,
+        &Position,
+        &mut Inventory,
+        &VisibleChunkLayer,
+        &Flags,
+        Option<&Permissions>,
+    )>,
+    players: Query<(&Position, &EntityLayerId), With<Client>>,
     mut layers: Query<&mut ChunkLayer>,
+    worlds: Query<&WorldInfo>,
+    resolvers: Res<PlacementResolvers>,
+    handlers: Res<InteractionHandlers>,
     mut events: EventReader<InteractBlockEvent>,
+    mut changes: EventWriter<BlockChangeEvent>,
+    mut screens: EventWriter<OpenBlockScreenEvent>,
+    mut sleeps: EventWriter<SleepEvent>,
 ) {
-    let mut layer = layers.single_mut();
-
     events.iter().for_each(|event| {
-        let (game_mode, held_item, look, mut inventory) = match clients.get_mut(event.client) {
-            Ok(v) => v,
-            Err(_) => return,
+        let Ok((
+            mut client,
+            game_mode,
+            held_item,
+            look,
+            position,
+            mut inventory,
+            visible_chunk_layer,
+            flags,
+            permissions,
+        )) = clients.get_mut(event.client)
+        else {
+            return;
+        };
+        let layer_entity = visible_chunk_layer.0;
+        let Ok(mut layer) = layers.get_mut(layer_entity) else {
+            return;
+        };
+
+        let Some(clicked) = layer.block(event.position).map(|block| block.state) else {
+            return;
         };
 
         let slot = held_item.slot();
-        let stack = inventory.slot(slot);
+        let stack = inventory.slot(slot).clone();
+        let eye = position.0 + DVec3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+        let in_reach = eye.distance(block_center(event.position)) <= PLACE_REACH;
+        let world = worlds.get(layer_entity).ok();
+        let allowed = may_build(permissions, PLACE_PERMISSION, world, event.position);
+
+        // sneaking players place the block they hold instead of using the clicked one,
+        // and spectators can't use blocks at all
+        if allowed
+            && in_reach
+            && *game_mode != GameMode::Spectator
+            && !(flags.sneaking() && !stack.is_empty())
+        {
+            let interaction = handlers.interact(&InteractionContext {
+                layer: &layer,
+                position: event.position,
+                state: clicked,
+                face: event.face,
+                cursor: event.cursor_pos,
+                yaw: look.yaw,
+            });
+            match interaction {
+                Interaction::Pass => {}
+                Interaction::Change(blocks) => {
+                    for (position, state) in blocks {
+                        layer.set_block(position, state);
+                        changes.send(BlockChangeEvent {
+                            layer: layer_entity,
+                            position,
+                        });
+                    }
+                    return;
+                }
+                Interaction::Open(kind, title) => {
+                    screens.send(OpenBlockScreenEvent {
+                        client: event.client,
+                        layer: layer_entity,
+                        position: event.position,
+                        kind,
+                        title,
+                    });
+                    return;
+                }
+                Interaction::Sleep => {
+                    sleeps.send(SleepEvent {
+                        client: event.client,
+                        layer: layer_entity,
+                        position: event.position,
+                    });
+                    return;
+                }
+            }
+        }
+
         if stack.is_empty() {
             // client is not holding anything, our work is done for this event
             return;
         }
 
-        let target_position = event.position.get_in_direction(event.face);
-
         let block = match BlockKind::from_item_kind(stack.item) {
             Some(block) => block,
             None => return,
         };
 
+        let parts = if in_reach {
+            resolvers.plan(PlacementContext {
+                layer: &layer,
+                kind: block,
+                clicked: event.position,
+                target: event.position,
+                existing: clicked,
+                face: event.face,
+                cursor: event.cursor_pos,
+                yaw: look.yaw,
+                pitch: look.pitch,
+            })
+        } else {
+            None
+        };
+        // the block may go into a neighbor of the clicked block, or into several blocks
+        let parts = parts.filter(|parts| {
+            parts.iter().all(|&(position, _)| {
+                eye.distance(block_center(position)) <= PLACE_REACH
+                    && may_build(permissions, PLACE_PERMISSION, world, position)
+            })
+        });
+        let parts = parts.filter(|parts| {
+            parts.iter().all(|&(position, state)| {
+                !has_collision(state.to_kind())
+                    || !players.iter().any(|(player, player_layer)| {
+                        player_layer.0 == layer_entity && intersects_player(player.0, position)
+                    })
+            })
+        });
+
+        let Some(parts) = parts else {
+            // the client may have placed the block in or next to the clicked block
+            let neighbor = event.position.get_in_direction(event.face);
+            resync_blocks(
+                &mut client,
+                &layer,
+                &[
+                    event.position,
+                    event.position.get_in_direction(Direction::Up),
+                    neighbor,
+                    neighbor.get_in_direction(Direction::Up),
+                ],
+            );
+            resync_slot(&mut client, slot, &stack);
+            return;
+        };
+
         // don't decrement the stack amount in creative mode
         if game_mode == &GameMode::Survival {
-            let count = stack.count;
-            inventory.set_slot_amount(slot, count - 1);
+            inventory.set_slot_amount(slot, stack.count - 1);
         }
 
-        let mut state = BlockState::from_kind(block);
-        if block.props().contains(&PropName::Axis) {
-            // respect placement orientation if the block has an axis property
-            state = state.set(
-                PropName::Axis,
-                match event.face {
-                    Direction::Down | Direction::Up => PropValue::Y,
-                    Direction::North | Direction::South => PropValue::Z,
-                    Direction::West | Direction::East => PropValue::X,
-                },
-            );
+        for (position, state) in parts {
+            layer.set_block(position, state);
+            changes.send(BlockChangeEvent {
+                layer: layer_entity,
+                position,
+            });
         }
+    });
+}
 
-        if block.props().contains(&PropName::Facing) {
-            // we're placing a door, fence gate, or similar block
-            let yaw = look.yaw % 360_f32;
-            let yaw = if yaw > 180_f32 {
-                yaw - 360_f32
-            } else if yaw < -180_f32 {
-                yaw + 360_f32
-            } else {
-                yaw
-            };
-            let facing = match look {
-                _ if (-135_f32..-45_f32).contains(&yaw) => PropValue::East,
-                _ if (-45_f32..45_f32).contains(&yaw) => PropValue::South,
-                _ if (45_f32..135_f32).contains(&yaw) => PropValue::West,
-                _ if (135_f32..=180_f32).contains(&yaw) | (-180_f32..-135_f32).contains(&yaw) => {
-                    PropValue::North
-                }
-                _ => panic!("invalid look angle: {}", yaw),
-            };
-            state = state.set(PropName::Facing, facing);
-
-            if block.props().contains(&PropName::Half) {
-                // we're placing a door
-                state = state.set(PropName::Half, PropValue::Lower);
-                let (v, invert) = match facing {
-                    PropValue::South => (event.cursor_pos.x, false),
-                    PropValue::North => (event.cursor_pos.x, true),
-                    PropValue::East => (event.cursor_pos.z, true),
-                    PropValue::West => (event.cursor_pos.z, false),
-                    _ => unreachable!(),
-                };
-                let hinge_right = if invert { v > 0.5 } else { v < 0.5 };
-                let hinge = if hinge_right {
-                    PropValue::Right
-                } else {
-                    PropValue::Left
-                };
+/// Whether a player with the given permissions may change the block at the
+/// position. Clients without [`Permissions`] aren't restricted.
+pub fn may_build(
+    permissions: Option<&Permissions>,
+    node: &str,
+    world: Option<&WorldInfo>,
+    position: BlockPos,
+) -> bool {
+    let Some(permissions) = permissions else {
+        return true;
+    };
+    if !permissions.has(node) {
+        return false;
+    }
 
-                if !hinge_right {
-                    if let Some(block) =
-                        layer.block(target_position.get_in_direction(match facing {
-                            PropValue::South => Direction::East,
-                            PropValue::North => Direction::West,
-                            PropValue::East => Direction::North,
-                            PropValue::West => Direction::South,
-                            _ => unreachable!(),
-                        }))
-                    {
-                        if is_door(block.state) {
-                            state = state.set(PropName::Hinge, PropValue::Right);
-                        }
-                    }
-                } else {
-                    state = state.set(PropName::Hinge, hinge);
-                }
+    let protected = world.is_some_and(|world| {
+        let radius = world.rules.spawn_protection as i32;
+        let spawn = world.spawn_block_position();
+        radius > 0
+            && (position.x - spawn.x).abs() <= radius
+            && (position.z - spawn.z).abs() <= radius
+    });
+    !protected || permissions.has(SPAWN_PROTECTION_BYPASS)
+}
 
-                let upper = state.set(PropName::Half, PropValue::Upper);
-                // FIXME:: check that that block is air, otherwise we can't place it
-                layer.set_block(target_position.get_in_direction(Direction::Up), upper);
-            }
-        }
-        layer.set_block(target_position, state);
+/// Sends the actual state of the given blocks to a client, after rejecting a
+/// change that the client already made on its side.
+fn resync_blocks(client: &mut Client, layer: &ChunkLayer, positions: &[BlockPos]) {
+    for &position in positions {
+        let state = layer
+            .block(position)
+            .map(|block| block.state)
+            .unwrap_or(BlockState::AIR);
+        client.write_packet(&BlockUpdateS2c {
+            position,
+            block_id: state,
+        });
+    }
+}
+
+/// Sends the actual content of an inventory slot to a client, after rejecting a
+/// placement that the client already took the item for.
+fn resync_slot(client: &mut Client, slot: u16, stack: &ItemStack) {
+    // window -2 addresses the player's inventory directly, which numbers its slots
+    // differently than the inventory screen
+    let index = match slot {
+        36..=44 => slot - 36,
+        45 => 40,
+        5..=8 => 44 - slot,
+        _ => slot,
+    };
+    client.write_packet(&ScreenHandlerSlotUpdateS2c {
+        window_id: -2,
+        state_id: VarInt(0),
+        slot_idx: index as i16,
+        slot_data: Cow::Borrowed(stack),
     });
 }
 
-fn is_door(block: BlockState) -> bool {
-    block.to_kind().to_str().contains("door")
+fn block_center(position: BlockPos) -> DVec3 {
+    DVec3::new(
+        position.x as f64 + 0.5,
+        position.y as f64 + 0.5,
+        position.z as f64 + 0.5,
+    )
+}
+
+/// Returns whether a player standing at `player` overlaps the block at `position`.
+fn intersects_player(player: DVec3, position: BlockPos) -> bool {
+    let half_width = PLAYER_WIDTH / 2.0;
+    let (x, y, z) = (position.x as f64, position.y as f64, position.z as f64);
+    player.x - half_width < x + 1.0
+        && player.x + half_width > x
+        && player.y < y + 1.0
+        && player.y + PLAYER_HEIGHT > y
+        && player.z - half_width < z + 1.0
+        && player.z + half_width > z
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::testing::{eval_script, TestableEnvironment};
+    use crate::WorldRules;
     use std::ops::{Deref, DerefMut};
     use valence::testing::ScenarioSingleClient;
 
@@ -154,6 +435,11 @@ mod tests {
     impl BlockPlacementScenario {
         fn new() -> Self {
             let mut scenario = ScenarioSingleClient::new();
+            scenario.app.add_event::<BlockChangeEvent>();
+            scenario.app.init_resource::<PlacementResolvers>();
+            scenario.app.init_resource::<InteractionHandlers>();
+            scenario.app.add_event::<OpenBlockScreenEvent>();
+            scenario.app.add_event::<SleepEvent>();
             scenario.app.add_systems(Update, place_block);
             scenario.app.update();
 
@@ -222,10 +508,15 @@ mod tests {
 
         {
             // set up entity
-            let mut q = scenario.world.query::<(&mut HeldItem, &mut Inventory)>();
-            let (mut held_item, mut inventory) = q.get_single_mut(&mut scenario.world).unwrap();
+            let mut q = scenario
+                .world
+                .query::<(&mut HeldItem, &mut Inventory, &mut Position)>();
+            let (mut held_item, mut inventory, mut position) =
+                q.get_single_mut(&mut scenario.world).unwrap();
             held_item.set_slot(INVENTORY_SLOT);
             inventory.set_slot(INVENTORY_SLOT, ItemStack::new(ItemKind::OakLog, 6, None));
+            // stand next to the placed blocks, not inside them
+            position.set(DVec3::new(2.5, 0.0, 2.5));
         }
 
         for direction in [
@@ -300,6 +591,11 @@ mod tests {
             Self {
                 env: {
                     let mut inner = T::new();
+                    inner.app().add_event::<BlockChangeEvent>();
+                    inner.app().init_resource::<PlacementResolvers>();
+                    inner.app().init_resource::<InteractionHandlers>();
+                    inner.app().add_event::<OpenBlockScreenEvent>();
+                    inner.app().add_event::<SleepEvent>();
                     inner.app().add_systems(Update, place_block);
                     inner.app().update();
                     inner
@@ -325,6 +621,7 @@ mod tests {
         eval_script::<PlaceBlockScenarioEnvironment>(
             r#"
             set gamemode survival
+            set player position 2.5 0 2.5
             set inventory slot 37 item oak_planks count 2
             set held_item 37
             
@@ -338,4 +635,387 @@ mod tests {
             "#,
         );
     }
+
+    #[test]
+    fn test_place_block_on_occupied_position() {
+        eval_script::<PlaceBlockScenarioEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item oak_planks count 2
+            set held_item 36
+            set position 0 1 0 block stone
+
+            interact position 0 0 0 face up
+
+            assert position 0 1 0 block stone
+            assert inventory slot 36 item oak_planks count 2
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_place_block_replaces_clicked_block() {
+        eval_script::<PlaceBlockScenarioEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item oak_planks count 2
+            set held_item 36
+            set position 0 1 0 block grass
+
+            interact position 0 1 0 face up
+
+            assert position 0 1 0 block oak_planks
+            assert position 0 2 0 block air
+            assert inventory slot 36 item oak_planks count 1
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_place_block_out_of_reach() {
+        eval_script::<PlaceBlockScenarioEnvironment>(
+            r#"
+            set player position 10.5 0 0.5
+            set inventory slot 36 item oak_planks count 2
+            set held_item 36
+
+            interact position 0 0 0 face up
+
+            assert position 0 1 0 block air
+            assert inventory slot 36 item oak_planks count 2
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_place_block_inside_player() {
+        eval_script::<PlaceBlockScenarioEnvironment>(
+            r#"
+            set player position 0.5 1 0.5
+            set inventory slot 36 item oak_planks count 2
+            set inventory slot 37 item torch count 1
+            set held_item 36
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block air
+            assert inventory slot 36 item oak_planks count 2
+
+            # torches have no collision
+            set held_item 37
+            interact position 0 0 0 face up
+            assert position 0 1 0 block torch
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_place_door_without_room() {
+        eval_script::<PlaceBlockScenarioEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item oak_door count 1
+            set held_item 36
+            set position 0 2 0 block stone
+
+            interact position 0 0 0 face up
+
+            assert position 0 1 0 block air
+            assert position 0 2 0 block stone
+            assert inventory slot 36 item oak_door count 1
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_place_block_next_to_spawn_protection() {
+        let mut scenario = BlockPlacementScenario::new();
+        let client = scenario.client_entity();
+        let layer = scenario.scenario.layer;
+        scenario.world.entity_mut(layer).insert(WorldInfo {
+            name: "world".to_string(),
+            spawn: DVec3::new(0.5, 0.0, 0.5),
+            rules: WorldRules {
+                spawn_protection: 2,
+                ..Default::default()
+            },
+        });
+        {
+            let mut client = scenario.world.entity_mut(client);
+            client.insert(Permissions::from_nodes([PLACE_PERMISSION]));
+            client
+                .get_mut::<HeldItem>()
+                .unwrap()
+                .set_slot(INVENTORY_SLOT);
+            client
+                .get_mut::<Inventory>()
+                .unwrap()
+                .set_slot(INVENTORY_SLOT, ItemStack::new(ItemKind::OakPlanks, 2, None));
+            client
+                .get_mut::<Position>()
+                .unwrap()
+                .set(DVec3::new(5.5, 0.0, 0.5));
+        }
+        scenario
+            .world
+            .get_mut::<ChunkLayer>(layer)
+            .unwrap()
+            .set_block(BlockPos::new(3, 0, 0), BlockState::STONE);
+
+        // the clicked block is outside of the protection, the placed one isn't
+        scenario.world.send_event(InteractBlockEvent {
+            client,
+            hand: Hand::Main,
+            position: BlockPos::new(3, 0, 0),
+            face: Direction::West,
+            cursor_pos: Vec3::new(0.0, 0.5, 0.5),
+            head_inside_block: false,
+            sequence: 0,
+        });
+        scenario.update();
+
+        let state = scenario
+            .layer()
+            .block(BlockPos::new(2, 0, 0))
+            .unwrap()
+            .state;
+        assert_eq!(BlockState::AIR, state);
+        let inventory = scenario.world.get::<Inventory>(client).unwrap();
+        assert_eq!(2, inventory.slot(INVENTORY_SLOT).count);
+    }
+
+    #[test]
+    fn test_spectator_cannot_use_blocks() {
+        eval_script::<PlaceBlockScenarioEnvironment>(
+            r#"
+            set gamemode spectator
+            set player position 2.5 0 2.5
+            set position 0 1 0 block lever[face=floor,facing=north,powered=false]
+            set position 1 1 0 block oak_trapdoor[facing=north,half=bottom,open=false]
+
+            interact position 0 1 0 face up
+            interact position 1 1 0 face up
+
+            assert position 0 1 0 block lever[face=floor,facing=north,powered=false]
+            assert position 1 1 0 block oak_trapdoor[facing=north,half=bottom,open=false]
+            "#,
+        );
+    }
+
+    struct DiggingScenario {
+        scenario: ScenarioSingleClient,
+    }
+
+    impl DiggingScenario {
+        fn new(game_mode: GameMode, block: BlockKind) -> Self {
+            let mut scenario = ScenarioSingleClient::new();
+            scenario.app.add_event::<BlockChangeEvent>();
+            scenario.app.add_event::<BlockBreakEvent>();
+            scenario.app.add_systems(Update, remove_block);
+            scenario.app.update();
+
+            {
+                let mut entity_mut = scenario.app.world.entity_mut(scenario.layer);
+                let mut layer = entity_mut.get_mut::<ChunkLayer>().unwrap();
+                layer.insert_chunk([0, 0], UnloadedChunk::new());
+                layer.set_block(BlockPos::new(0, 0, 0), BlockState::from_kind(block));
+            }
+            *scenario
+                .app
+                .world
+                .get_mut::<GameMode>(scenario.client)
+                .unwrap() = game_mode;
+
+            scenario.app.update();
+
+            Self { scenario }
+        }
+
+        fn dig(&mut self, state: DiggingState) {
+            let client = self.scenario.client;
+            self.scenario.app.world.send_event(DiggingEvent {
+                client,
+                position: BlockPos::new(0, 0, 0),
+                direction: Direction::Up,
+                state,
+                sequence: 0,
+            });
+            self.scenario.app.update();
+        }
+
+        fn wait(&mut self, ticks: u32) {
+            for _ in 0..ticks {
+                self.scenario.app.update();
+            }
+        }
+
+        fn set(&mut self, position: BlockPos, state: BlockState) {
+            self.scenario
+                .app
+                .world
+                .get_mut::<ChunkLayer>(self.scenario.layer)
+                .unwrap()
+                .set_block(position, state);
+        }
+
+        fn block(&self) -> BlockKind {
+            self.block_at(BlockPos::new(0, 0, 0))
+        }
+
+        fn block_at(&self, position: BlockPos) -> BlockKind {
+            self.scenario
+                .app
+                .world
+                .get::<ChunkLayer>(self.scenario.layer)
+                .unwrap()
+                .block(position)
+                .unwrap()
+                .state
+                .to_kind()
+        }
+    }
+
+    #[test]
+    fn test_dig_after_break_time() {
+        let mut scenario = DiggingScenario::new(GameMode::Survival, BlockKind::Dirt);
+        scenario.dig(DiggingState::Start);
+        scenario.wait(15);
+        scenario.dig(DiggingState::Stop);
+        assert_eq!(BlockKind::Air, scenario.block());
+    }
+
+    #[test]
+    fn test_dig_too_early() {
+        let mut scenario = DiggingScenario::new(GameMode::Survival, BlockKind::Dirt);
+        scenario.dig(DiggingState::Start);
+        scenario.wait(2);
+        scenario.dig(DiggingState::Stop);
+        assert_eq!(BlockKind::Dirt, scenario.block());
+    }
+
+    #[test]
+    fn test_dig_stop_without_start() {
+        let mut scenario = DiggingScenario::new(GameMode::Survival, BlockKind::Dirt);
+        scenario.wait(20);
+        scenario.dig(DiggingState::Stop);
+        assert_eq!(BlockKind::Dirt, scenario.block());
+    }
+
+    #[test]
+    fn test_dig_instant() {
+        for block in [
+            BlockKind::Poppy,
+            BlockKind::CrimsonRoots,
+            BlockKind::EndRod,
+            BlockKind::MangrovePropagule,
+        ] {
+            let mut scenario = DiggingScenario::new(GameMode::Survival, block);
+            scenario.dig(DiggingState::Start);
+            assert_eq!(BlockKind::Air, scenario.block(), "{:?}", block);
+        }
+    }
+
+    #[test]
+    fn test_dig_unbreakable() {
+        for block in [BlockKind::Bedrock, BlockKind::Barrier] {
+            let mut scenario = DiggingScenario::new(GameMode::Survival, block);
+            scenario.dig(DiggingState::Start);
+            scenario.wait(1000);
+            scenario.dig(DiggingState::Stop);
+            assert_eq!(block, scenario.block());
+
+            let mut scenario = DiggingScenario::new(GameMode::Creative, block);
+            scenario.dig(DiggingState::Start);
+            assert_eq!(BlockKind::Air, scenario.block());
+        }
+    }
+
+    #[test]
+    fn test_dig_door_breaks_both_halves() {
+        let mut scenario = DiggingScenario::new(GameMode::Creative, BlockKind::OakDoor);
+        let door = BlockState::OAK_DOOR;
+        scenario.set(
+            BlockPos::new(0, 0, 0),
+            door.set(PropName::Half, PropValue::Upper),
+        );
+        scenario.set(
+            BlockPos::new(0, -1, 0),
+            door.set(PropName::Half, PropValue::Lower),
+        );
+        scenario.dig(DiggingState::Start);
+        assert_eq!(BlockKind::Air, scenario.block());
+        assert_eq!(BlockKind::Air, scenario.block_at(BlockPos::new(0, -1, 0)));
+    }
+
+    #[test]
+    fn test_dig_bed_breaks_both_parts() {
+        let mut scenario = DiggingScenario::new(GameMode::Creative, BlockKind::RedBed);
+        let bed = BlockState::RED_BED.set(PropName::Facing, PropValue::South);
+        scenario.set(
+            BlockPos::new(0, 0, 0),
+            bed.set(PropName::Part, PropValue::Foot),
+        );
+        scenario.set(
+            BlockPos::new(0, 0, 1),
+            bed.set(PropName::Part, PropValue::Head),
+        );
+        scenario.dig(DiggingState::Start);
+        assert_eq!(BlockKind::Air, scenario.block());
+        assert_eq!(BlockKind::Air, scenario.block_at(BlockPos::new(0, 0, 1)));
+    }
+
+    #[test]
+    fn test_dig_without_permission() {
+        let mut scenario = DiggingScenario::new(GameMode::Creative, BlockKind::Dirt);
+        let client = scenario.scenario.client;
+        scenario
+            .scenario
+            .app
+            .world
+            .entity_mut(client)
+            .insert(Permissions::from_nodes([PLACE_PERMISSION]));
+        scenario.dig(DiggingState::Start);
+        assert_eq!(BlockKind::Dirt, scenario.block());
+    }
+
+    #[test]
+    fn test_may_build_near_spawn() {
+        let world = WorldInfo {
+            name: "world".to_string(),
+            spawn: DVec3::new(0.5, 65.0, 0.5),
+            rules: WorldRules {
+                spawn_protection: 4,
+                ..Default::default()
+            },
+        };
+        let player = Permissions::from_nodes([PLACE_PERMISSION]);
+        let operator = Permissions::from_nodes([PLACE_PERMISSION, SPAWN_PROTECTION_BYPASS]);
+
+        let near = BlockPos::new(4, 70, -4);
+        let far = BlockPos::new(5, 70, 0);
+        assert!(!may_build(
+            Some(&player),
+            PLACE_PERMISSION,
+            Some(&world),
+            near
+        ));
+        assert!(may_build(
+            Some(&player),
+            PLACE_PERMISSION,
+            Some(&world),
+            far
+        ));
+        assert!(may_build(
+            Some(&operator),
+            PLACE_PERMISSION,
+            Some(&world),
+            near
+        ));
+        assert!(!may_build(
+            Some(&operator),
+            BREAK_PERMISSION,
+            Some(&world),
+            far
+        ));
+        assert!(may_build(None, BREAK_PERMISSION, Some(&world), near));
+    }
 }
@@ -0,0 +1,267 @@
+use crate::{spawn_experience, spawn_item, DeathEvent, Experience, WorldInfo};
+use bevy_ecs::prelude::*;
+use valence::entity::EntityLayerId;
+use valence::inventory::CursorItem;
+use valence::message::SendMessage;
+use valence::prelude::*;
+
+/// The bed or respawn anchor that a player respawns at, instead of the spawn of
+/// the default world.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SpawnPoint {
+    pub world: Entity,
+    pub position: BlockPos,
+    /// Set with `/spawnpoint`, the player respawns at the position even if there is
+    /// no bed or respawn anchor.
+    pub forced: bool,
+}
+
+/// Where a player that respawns at the given bed or respawn anchor appears, or
+/// `None` if the block is gone. Respawning at an anchor uses up one of its charges.
+pub fn use_spawn_point(layer: &mut ChunkLayer, position: BlockPos) -> Option<DVec3> {
+    let state = layer.block(position)?.state;
+    let name = state.to_kind().to_str();
+
+    let height = if name.ends_with("_bed") {
+        0.5625
+    } else if state.to_kind() == BlockKind::RespawnAnchor {
+        let charges = match state.get(PropName::Charges)? {
+            PropValue::_1 => PropValue::_0,
+            PropValue::_2 => PropValue::_1,
+            PropValue::_3 => PropValue::_2,
+            PropValue::_4 => PropValue::_3,
+            _ => return None,
+        };
+        layer.set_block(position, state.set(PropName::Charges, charges));
+        1.0
+    } else {
+        return None;
+    };
+
+    Some(DVec3::new(
+        position.x as f64 + 0.5,
+        position.y as f64 + height,
+        position.z as f64 + 0.5,
+    ))
+}
+
+/// Drops the inventory and experience of players where they died, unless the
+/// world keeps them.
+pub fn drop_inventory_on_death(
+    mut commands: Commands,
+    mut clients: Query<(
+        &EntityLayerId,
+        &Position,
+        &mut Inventory,
+        &mut CursorItem,
+        &mut Experience,
+    )>,
+    worlds: Query<&WorldInfo>,
+    server: Res<Server>,
+    mut events: EventReader<DeathEvent>,
+) {
+    events.iter().for_each(|event| {
+        let Ok((layer, position, mut inventory, mut cursor, mut experience)) =
+            clients.get_mut(event.client)
+        else {
+            return;
+        };
+        if worlds
+            .get(layer.0)
+            .map_or(false, |world| world.rules.keep_inventory)
+        {
+            return;
+        }
+
+        let now = server.current_tick();
+        // slot 0 is the crafting result, which only exists while the grid is filled
+        for slot in 1..inventory.slot_count() {
+            let stack = inventory.replace_slot(slot, ItemStack::EMPTY);
+            if !stack.is_empty() {
+                spawn_item(&mut commands, layer.0, position.0, stack, now);
+            }
+        }
+        let stack = std::mem::replace(&mut cursor.0, ItemStack::EMPTY);
+        if !stack.is_empty() {
+            spawn_item(&mut commands, layer.0, position.0, stack, now);
+        }
+
+        spawn_experience(
+            &mut commands,
+            layer.0,
+            position.0,
+            experience.death_drop(),
+            now,
+        );
+        experience.0 = 0;
+    });
+}
+
+/// Tells every player how someone died.
+pub fn broadcast_death_messages(
+    mut clients: Query<&mut Client>,
+    mut events: EventReader<DeathEvent>,
+) {
+    events.iter().for_each(|event| {
+        clients.for_each_mut(|mut client| {
+            client.send_chat_message(event.message.as_str());
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        apply_damage, DamageCause, DamageEvent, DroppedItem, ExperienceOrb, SurvivalBundle,
+        WorldRules,
+    };
+    use valence::testing::ScenarioSingleClient;
+
+    struct DeathScenario {
+        scenario: ScenarioSingleClient,
+    }
+
+    impl DeathScenario {
+        fn new(rules: WorldRules) -> Self {
+            let mut scenario = ScenarioSingleClient::new();
+            scenario.app.add_event::<DamageEvent>();
+            scenario.app.add_event::<DeathEvent>();
+            scenario.app.add_systems(
+                Update,
+                (
+                    apply_damage,
+                    (drop_inventory_on_death, broadcast_death_messages),
+                )
+                    .chain(),
+            );
+            scenario
+                .app
+                .world
+                .entity_mut(scenario.layer)
+                .insert(WorldInfo {
+                    name: "world".to_string(),
+                    spawn: DVec3::ZERO,
+                    rules,
+                });
+            let mut client = scenario.app.world.entity_mut(scenario.client);
+            client.insert((SurvivalBundle::default(), Experience(30)));
+            *client.get_mut::<GameMode>().unwrap() = GameMode::Survival;
+            let mut inventory = client.get_mut::<Inventory>().unwrap();
+            inventory.set_slot(36, ItemStack::new(ItemKind::Dirt, 10, None));
+            inventory.set_slot(5, ItemStack::new(ItemKind::IronHelmet, 1, None));
+            scenario.app.update();
+
+            Self { scenario }
+        }
+
+        fn kill(&mut self) {
+            let client = self.scenario.client;
+            self.scenario.app.world.send_event(DamageEvent {
+                client,
+                amount: 100.0,
+                cause: DamageCause::Fall,
+            });
+            self.scenario.app.update();
+        }
+
+        fn count<F: Component>(&mut self) -> usize {
+            self.scenario
+                .app
+                .world
+                .query_filtered::<(), With<F>>()
+                .iter(&self.scenario.app.world)
+                .count()
+        }
+    }
+
+    #[test]
+    fn test_drop_inventory_on_death() {
+        let mut scenario = DeathScenario::new(WorldRules::default());
+        scenario.kill();
+
+        let client = scenario.scenario.client;
+        let inventory = scenario
+            .scenario
+            .app
+            .world
+            .get::<Inventory>(client)
+            .unwrap();
+        assert!(inventory.slot(36).is_empty());
+        assert!(inventory.slot(5).is_empty());
+        assert_eq!(2, scenario.count::<DroppedItem>());
+        // level 3 drops 21 points, as orbs of 17, 3 and 1
+        assert_eq!(3, scenario.count::<ExperienceOrb>());
+        assert_eq!(
+            Experience(0),
+            *scenario
+                .scenario
+                .app
+                .world
+                .get::<Experience>(client)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_keep_inventory() {
+        let mut scenario = DeathScenario::new(WorldRules {
+            keep_inventory: true,
+            ..Default::default()
+        });
+        scenario.kill();
+
+        let client = scenario.scenario.client;
+        let inventory = scenario
+            .scenario
+            .app
+            .world
+            .get::<Inventory>(client)
+            .unwrap();
+        assert_eq!(10, inventory.slot(36).count);
+        assert_eq!(0, scenario.count::<DroppedItem>());
+        assert_eq!(0, scenario.count::<ExperienceOrb>());
+        assert_eq!(
+            Experience(30),
+            *scenario
+                .scenario
+                .app
+                .world
+                .get::<Experience>(client)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_use_spawn_point() {
+        let mut scenario = ScenarioSingleClient::new();
+        let mut layer = scenario
+            .app
+            .world
+            .get_mut::<ChunkLayer>(scenario.layer)
+            .unwrap();
+        layer.insert_chunk([0, 0], UnloadedChunk::new());
+
+        let bed = BlockPos::new(1, 1, 1);
+        layer.set_block(bed, BlockState::RED_BED);
+        assert_eq!(
+            Some(DVec3::new(1.5, 1.5625, 1.5)),
+            use_spawn_point(&mut layer, bed)
+        );
+
+        let anchor = BlockPos::new(3, 1, 1);
+        layer.set_block(
+            anchor,
+            BlockState::RESPAWN_ANCHOR.set(PropName::Charges, PropValue::_1),
+        );
+        assert_eq!(
+            Some(DVec3::new(3.5, 2.0, 1.5)),
+            use_spawn_point(&mut layer, anchor)
+        );
+        // the only charge is used up
+        assert_eq!(None, use_spawn_point(&mut layer, anchor));
+
+        layer.set_block(bed, BlockState::AIR);
+        assert_eq!(None, use_spawn_point(&mut layer, bed));
+    }
+}
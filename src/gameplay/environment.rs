@@ -1,17 +1,22 @@
-use crate::Dead;
+use crate::{DamageCause, DamageEvent, Dead};
 use bevy_ecs::entity::Entity;
-use bevy_ecs::prelude::{Commands, Query, Without};
-use valence::client::Client;
+use bevy_ecs::prelude::{EventWriter, Query, Without};
 use valence::entity::Position;
 
+/// The damage that players below the world take every tick.
+const VOID_DAMAGE: f32 = 4.0;
+
 pub fn fell_out_of_world(
-    mut commands: Commands,
-    mut clients: Query<(Entity, &mut Client, &Position), Without<Dead>>,
+    clients: Query<(Entity, &Position), Without<Dead>>,
+    mut damage: EventWriter<DamageEvent>,
 ) {
-    clients.for_each_mut(|(entity, mut client, pos)| {
+    clients.for_each(|(entity, pos)| {
         if pos.y < -64.0 {
-            commands.entity(entity).insert(Dead);
-            client.kill("What are you doing down there?");
+            damage.send(DamageEvent {
+                client: entity,
+                amount: VOID_DAMAGE,
+                cause: DamageCause::Void,
+            });
         }
     });
 }
@@ -19,16 +24,19 @@ pub fn fell_out_of_world(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{apply_damage, DeathEvent, SurvivalBundle};
     use valence::prelude::*;
     use valence::testing::create_mock_client;
 
     #[test]
     fn test_fell_out_of_world() {
         let mut app = App::new();
-        app.add_systems(Update, fell_out_of_world);
+        app.add_event::<DamageEvent>();
+        app.add_event::<DeathEvent>();
+        app.add_systems(Update, (fell_out_of_world, apply_damage).chain());
 
         let (client, _) = create_mock_client("test");
-        let entity = app.world.spawn(client).id();
+        let entity = app.world.spawn((client, SurvivalBundle::default())).id();
 
         app.update();
 
@@ -39,6 +47,12 @@ mod tests {
             .expect("position component is missing")
             .y = -64.1;
 
+        // the void takes a few ticks to kill a player with full health
+        for _ in 0..4 {
+            app.update();
+        }
+        assert!(app.world.get::<Dead>(entity).is_none());
+
         app.update();
 
         let entity_ref = app.world.get_entity(entity).unwrap();
@@ -0,0 +1,212 @@
+use crate::{Dead, ITEM_LIFETIME, PICKUP_DELAY};
+use bevy_ecs::prelude::*;
+use valence::entity::experience_orb::ExperienceOrbEntityBundle;
+use valence::entity::{EntityId, EntityLayerId, ObjectData};
+use valence::prelude::*;
+use valence::protocol::packets::play::{ExperienceBarUpdateS2c, ItemPickupAnimationS2c};
+use valence::protocol::{VarInt, WritePacket};
+
+/// The most experience that a player drops when they die.
+const MAX_DEATH_DROP: i32 = 100;
+
+/// Orbs are split into these sizes, largest first.
+const ORB_SIZES: [i32; 11] = [2477, 1237, 617, 307, 149, 73, 37, 17, 7, 3, 1];
+
+/// The total experience points of a player.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Experience(pub i32);
+
+impl Experience {
+    /// The level and how far the player has progressed towards the next level,
+    /// from `0.0` to `1.0`.
+    pub fn level(&self) -> (i32, f32) {
+        let mut level = 0;
+        let mut remaining = self.0.max(0);
+        while remaining >= points_to_next_level(level) {
+            remaining -= points_to_next_level(level);
+            level += 1;
+        }
+        (level, remaining as f32 / points_to_next_level(level) as f32)
+    }
+
+    /// The experience that the player drops when they die.
+    pub fn death_drop(&self) -> i32 {
+        (self.level().0 * 7).min(MAX_DEATH_DROP)
+    }
+}
+
+/// The points that are needed to get from `level` to the next level.
+fn points_to_next_level(level: i32) -> i32 {
+    match level {
+        0..=15 => 2 * level + 7,
+        16..=30 => 5 * level - 38,
+        _ => 9 * level - 158,
+    }
+}
+
+/// An experience orb, lives on the orb entity.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct ExperienceOrb {
+    pub value: i32,
+    /// The server tick in which the orb was spawned.
+    pub spawned: i64,
+}
+
+/// Spawns orbs that are worth `points` together in the given layer.
+pub fn spawn_experience(
+    commands: &mut Commands,
+    layer: Entity,
+    position: DVec3,
+    mut points: i32,
+    now: i64,
+) {
+    while points > 0 {
+        let value = ORB_SIZES
+            .into_iter()
+            .find(|size| *size <= points)
+            .unwrap_or(1);
+        points -= value;
+
+        commands.spawn((
+            ExperienceOrbEntityBundle {
+                layer: EntityLayerId(layer),
+                position: Position(position),
+                object_data: ObjectData(value),
+                ..Default::default()
+            },
+            ExperienceOrb {
+                value,
+                spawned: now,
+            },
+        ));
+    }
+}
+
+/// Sends the experience bar to clients whenever their experience changes.
+pub fn sync_experience(mut clients: Query<(&mut Client, &Experience), Changed<Experience>>) {
+    clients.for_each_mut(|(mut client, experience)| {
+        let (level, progress) = experience.level();
+        client.write_packet(&ExperienceBarUpdateS2c {
+            bar: progress,
+            level: VarInt(level),
+            total_xp: VarInt(experience.0),
+        });
+    });
+}
+
+/// Gives the experience of orbs that are close to a player to the player.
+pub fn pick_up_experience(
+    mut commands: Commands,
+    mut clients: Query<
+        (
+            &mut Client,
+            &EntityId,
+            &EntityLayerId,
+            &Position,
+            &GameMode,
+            &mut Experience,
+        ),
+        Without<Dead>,
+    >,
+    orbs: Query<(Entity, &EntityId, &EntityLayerId, &Position, &ExperienceOrb), Without<Despawned>>,
+    server: Res<Server>,
+) {
+    let now = server.current_tick();
+    orbs.for_each(|(entity, orb_id, orb_layer, orb_pos, orb)| {
+        if now - orb.spawned < PICKUP_DELAY {
+            return;
+        }
+        if now - orb.spawned >= ITEM_LIFETIME {
+            commands.entity(entity).insert(Despawned);
+            return;
+        }
+
+        let collector = clients.iter_mut().find(|(_, _, layer, pos, game_mode, _)| {
+            // the player hitbox, grown by 1 block horizontally and half a block vertically
+            let offset = orb_pos.0 - pos.0;
+            layer.0 == orb_layer.0
+                && **game_mode != GameMode::Spectator
+                && offset.x.abs() <= 1.3
+                && offset.z.abs() <= 1.3
+                && (-0.5..2.3).contains(&offset.y)
+        });
+        let Some((mut client, client_id, _, _, _, mut experience)) = collector else {
+            return;
+        };
+
+        client.write_packet(&ItemPickupAnimationS2c {
+            collected_entity_id: VarInt(orb_id.get()),
+            collector_entity_id: VarInt(client_id.get()),
+            pickup_item_count: VarInt(1),
+        });
+        experience.0 += orb.value;
+        commands.entity(entity).insert(Despawned);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ecs::system::CommandQueue;
+    use valence::testing::ScenarioSingleClient;
+
+    #[test]
+    fn test_level() {
+        assert_eq!((0, 0.0), Experience(0).level());
+        assert_eq!((1, 0.0), Experience(7).level());
+        assert_eq!((2, 0.0), Experience(7 + 9).level());
+        assert_eq!((16, 0.0), Experience(352).level());
+        assert_eq!((16, 0.5), Experience(352 + 21).level());
+        assert_eq!((31, 0.0), Experience(1507).level());
+    }
+
+    #[test]
+    fn test_death_drop() {
+        assert_eq!(0, Experience(5).death_drop());
+        assert_eq!(21, Experience(30).death_drop());
+        assert_eq!(MAX_DEATH_DROP, Experience(5000).death_drop());
+    }
+
+    #[test]
+    fn test_pick_up_experience() {
+        let mut scenario = ScenarioSingleClient::new();
+        scenario.app.add_systems(Update, pick_up_experience);
+        scenario
+            .app
+            .world
+            .entity_mut(scenario.client)
+            .insert(Experience::default());
+        scenario
+            .app
+            .world
+            .get_mut::<Position>(scenario.client)
+            .unwrap()
+            .set(DVec3::new(0.5, 1.0, 0.5));
+
+        let layer = scenario.layer;
+        let now = scenario.app.world.resource::<Server>().current_tick();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &scenario.app.world);
+        spawn_experience(&mut commands, layer, DVec3::new(1.0, 1.0, 1.0), 10, now);
+        queue.apply(&mut scenario.app.world);
+
+        let mut orbs = scenario.app.world.query::<&ExperienceOrb>();
+        let values = orbs
+            .iter(&scenario.app.world)
+            .map(|orb| orb.value)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![7, 3], values);
+
+        for _ in 0..PICKUP_DELAY + 1 {
+            scenario.app.update();
+        }
+        assert_eq!(
+            Experience(10),
+            *scenario
+                .app
+                .world
+                .get::<Experience>(scenario.client)
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,811 @@
+use crate::{is_climbable, is_solid, Dead, Tool};
+use bevy_ecs::prelude::*;
+use valence::client::{Username, VisibleChunkLayer};
+use valence::entity::entity::{Air, Flags};
+use valence::entity::EntityLayerId;
+use valence::interact_entity::{EntityInteraction, InteractEntityEvent};
+use valence::inventory::HeldItem;
+use valence::movement::MovementEvent;
+use valence::prelude::*;
+use valence::protocol::packets::play::HealthUpdateS2c;
+use valence::protocol::{VarInt, WritePacket};
+
+pub const MAX_HEALTH: f32 = 20.0;
+pub const MAX_FOOD: i32 = 20;
+pub const MAX_AIR: i32 = 300;
+
+/// Every this much exhaustion uses up a point of saturation, or of food once the
+/// saturation is gone.
+const EXHAUSTION_PER_FOOD: f32 = 4.0;
+/// Players that have at least this much food slowly regenerate health.
+const REGENERATION_FOOD: i32 = 18;
+/// How often regeneration and starvation happen, in ticks.
+const HUNGER_INTERVAL: i64 = 80;
+/// How often players in lava, fire or a wall take damage, in ticks.
+const BLOCK_DAMAGE_INTERVAL: i64 = 10;
+/// How often burning players take damage, in ticks.
+const BURNING_INTERVAL: i64 = 20;
+/// How long players keep burning after leaving lava and fire, in ticks.
+const LAVA_BURN_TICKS: i32 = 300;
+const FIRE_BURN_TICKS: i32 = 160;
+/// Players can fall this many blocks without taking damage.
+const SAFE_FALL_DISTANCE: f32 = 3.0;
+/// The damage of a hit with the bare hand, or with an item that isn't a tool.
+const ATTACK_DAMAGE: f32 = 1.0;
+/// How far away from a player's eyes the body of another player can be hit.
+const ATTACK_REACH: f64 = 6.0;
+/// How long players can't be hurt by other players after they were hit, in ticks.
+const HURT_COOLDOWN: i64 = 10;
+
+const PLAYER_EYE_HEIGHT: f64 = 1.62;
+
+/// The health of a player, in half hearts.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct Health(pub f32);
+
+impl Default for Health {
+    fn default() -> Self {
+        Self(MAX_HEALTH)
+    }
+}
+
+/// The food level of a player, in half drumsticks.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Food(pub i32);
+
+impl Default for Food {
+    fn default() -> Self {
+        Self(MAX_FOOD)
+    }
+}
+
+/// The hidden food reserve of a player, which is used up before the food level.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct Saturation(pub f32);
+
+impl Default for Saturation {
+    fn default() -> Self {
+        Self(5.0)
+    }
+}
+
+/// Accumulates with every action that makes a player hungry.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Default)]
+pub struct Exhaustion(pub f32);
+
+/// How far a player has fallen since they last stood on the ground.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Default)]
+pub struct FallDistance(pub f32);
+
+/// How many more ticks a player burns for.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Burning(pub i32);
+
+/// The server tick in which a player was last hit by another player.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct LastHurt(pub Option<i64>);
+
+/// The survival stats of a player.
+#[derive(Bundle, Default)]
+pub struct SurvivalBundle {
+    pub health: Health,
+    pub food: Food,
+    pub saturation: Saturation,
+    pub exhaustion: Exhaustion,
+    pub fall_distance: FallDistance,
+    pub burning: Burning,
+    pub last_hurt: LastHurt,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DamageCause {
+    /// Hit by another player.
+    Player(Entity),
+    Fall,
+    Void,
+    Drowning,
+    Suffocation,
+    Lava,
+    /// Standing in fire.
+    Fire,
+    /// Burning after leaving fire or lava.
+    Burning,
+    Starvation,
+    Lightning,
+    /// Killed with `/kill`.
+    Kill,
+}
+
+impl DamageCause {
+    /// Whether players in creative and spectator mode take this damage too.
+    pub fn bypasses_invulnerability(&self) -> bool {
+        matches!(self, DamageCause::Void | DamageCause::Kill)
+    }
+
+    /// The message that is shown to everyone when a player dies of this cause.
+    /// `killer` is the name of the attacking player, if there is one.
+    pub fn death_message(&self, name: &str, killer: Option<&str>) -> String {
+        match self {
+            DamageCause::Player(_) => match killer {
+                Some(killer) => format!("{} was slain by {}", name, killer),
+                None => format!("{} was slain", name),
+            },
+            DamageCause::Fall => format!("{} fell from a high place", name),
+            DamageCause::Void => format!("{} fell out of the world", name),
+            DamageCause::Drowning => format!("{} drowned", name),
+            DamageCause::Suffocation => format!("{} suffocated in a wall", name),
+            DamageCause::Lava => format!("{} tried to swim in lava", name),
+            DamageCause::Fire => format!("{} went up in flames", name),
+            DamageCause::Burning => format!("{} burned to death", name),
+            DamageCause::Starvation => format!("{} starved to death", name),
+            DamageCause::Lightning => format!("{} was struck by lightning", name),
+            DamageCause::Kill => format!("{} was killed", name),
+        }
+    }
+}
+
+/// Deals damage to a player.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct DamageEvent {
+    pub client: Entity,
+    pub amount: f32,
+    pub cause: DamageCause,
+}
+
+/// Sent when a player dies.
+#[derive(Event, Clone, Debug)]
+pub struct DeathEvent {
+    pub client: Entity,
+    pub cause: DamageCause,
+    pub message: String,
+}
+
+/// Subtracts damage from the health of players, and kills the ones whose health
+/// reaches zero.
+pub fn apply_damage(
+    mut commands: Commands,
+    mut clients: Query<
+        (
+            &mut Client,
+            &Username,
+            &GameMode,
+            &mut Health,
+            &mut Exhaustion,
+        ),
+        Without<Dead>,
+    >,
+    names: Query<&Username>,
+    mut events: EventReader<DamageEvent>,
+    mut deaths: EventWriter<DeathEvent>,
+) {
+    events.iter().for_each(|event| {
+        let Ok((mut client, username, game_mode, mut health, mut exhaustion)) =
+            clients.get_mut(event.client)
+        else {
+            return;
+        };
+        if health.0 <= 0.0 {
+            // already died from earlier damage in this tick
+            return;
+        }
+        if matches!(game_mode, GameMode::Creative | GameMode::Spectator)
+            && !event.cause.bypasses_invulnerability()
+        {
+            return;
+        }
+
+        health.0 = (health.0 - event.amount).max(0.0);
+        exhaustion.0 += 0.1;
+        if health.0 <= 0.0 {
+            let killer = match event.cause {
+                DamageCause::Player(attacker) => names.get(attacker).ok(),
+                _ => None,
+            };
+            let message = event
+                .cause
+                .death_message(&username.0, killer.map(|name| name.0.as_str()));
+
+            commands.entity(event.client).insert(Dead);
+            client.kill(message.clone());
+            deaths.send(DeathEvent {
+                client: event.client,
+                cause: event.cause,
+                message,
+            });
+        }
+    });
+}
+
+/// Lets players hurt each other by hitting, with the damage of the held item.
+/// Players can only hit players in their own world and within reach, and every
+/// player can only be hit once per [`HURT_COOLDOWN`].
+pub fn attack_players(
+    attackers: Query<(&GameMode, &EntityLayerId, &Position, &HeldItem, &Inventory), Without<Dead>>,
+    mut targets: Query<(&EntityLayerId, &Position, &mut LastHurt), (With<Health>, Without<Dead>)>,
+    server: Res<Server>,
+    mut events: EventReader<InteractEntityEvent>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    let now = server.current_tick();
+    events.iter().for_each(|event| {
+        if !matches!(event.interact, EntityInteraction::Attack) || event.client == event.entity {
+            return;
+        }
+        let Ok((game_mode, attacker_layer, attacker_pos, held_item, inventory)) =
+            attackers.get(event.client)
+        else {
+            return;
+        };
+        let Ok((target_layer, target_pos, mut last_hurt)) = targets.get_mut(event.entity) else {
+            return;
+        };
+        if *game_mode == GameMode::Spectator || attacker_layer.0 != target_layer.0 {
+            return;
+        }
+
+        let eyes = attacker_pos.0 + DVec3::new(0.0, PLAYER_EYE_HEIGHT, 0.0);
+        let body = target_pos.0 + DVec3::new(0.0, PLAYER_EYE_HEIGHT / 2.0, 0.0);
+        if eyes.distance(body) > ATTACK_REACH {
+            return;
+        }
+        if last_hurt.0.is_some_and(|tick| now - tick < HURT_COOLDOWN) {
+            return;
+        }
+        last_hurt.0 = Some(now);
+
+        let amount = Tool::from_stack(inventory.slot(held_item.slot()))
+            .map_or(ATTACK_DAMAGE, |tool| tool.attack_damage());
+        damage.send(DamageEvent {
+            client: event.entity,
+            amount,
+            cause: DamageCause::Player(event.client),
+        });
+    });
+}
+
+/// Sends the health bar and food bar to clients whenever they change.
+pub fn sync_health(
+    mut clients: Query<
+        (&mut Client, &Health, &Food, &Saturation),
+        Or<(Changed<Health>, Changed<Food>, Changed<Saturation>)>,
+    >,
+) {
+    clients.for_each_mut(|(mut client, health, food, saturation)| {
+        client.write_packet(&HealthUpdateS2c {
+            health: health.0,
+            food: VarInt(food.0),
+            food_saturation: saturation.0,
+        });
+    });
+}
+
+/// Uses up saturation and food as players get exhausted, regenerates the health of
+/// players with enough food and starves the ones without.
+pub fn update_hunger(
+    mut clients: Query<
+        (
+            Entity,
+            &GameMode,
+            &mut Health,
+            &mut Food,
+            &mut Saturation,
+            &mut Exhaustion,
+        ),
+        Without<Dead>,
+    >,
+    server: Res<Server>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    let tick = server.current_tick() % HUNGER_INTERVAL == 0;
+    clients.for_each_mut(
+        |(entity, game_mode, mut health, mut food, mut saturation, mut exhaustion)| {
+            if matches!(game_mode, GameMode::Creative | GameMode::Spectator) {
+                return;
+            }
+
+            if exhaustion.0 >= EXHAUSTION_PER_FOOD {
+                exhaustion.0 -= EXHAUSTION_PER_FOOD;
+                if saturation.0 > 0.0 {
+                    saturation.0 = (saturation.0 - 1.0).max(0.0);
+                } else {
+                    food.0 = (food.0 - 1).max(0);
+                }
+            }
+
+            if !tick {
+                return;
+            }
+            if food.0 >= REGENERATION_FOOD && health.0 < MAX_HEALTH {
+                health.0 = (health.0 + 1.0).min(MAX_HEALTH);
+                exhaustion.0 += 6.0;
+            } else if food.0 == 0 && health.0 > 1.0 {
+                // starvation leaves players at half a heart
+                damage.send(DamageEvent {
+                    client: entity,
+                    amount: 1.0,
+                    cause: DamageCause::Starvation,
+                });
+            }
+        },
+    );
+}
+
+/// Tracks how far players fall, deals fall damage when they land, and makes
+/// players hungry as they sprint, jump and swim.
+pub fn track_movement(
+    mut clients: Query<(&Flags, &mut FallDistance, &mut Exhaustion), Without<Dead>>,
+    mut events: EventReader<MovementEvent>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    events.iter().for_each(|event| {
+        let Ok((flags, mut fall_distance, mut exhaustion)) = clients.get_mut(event.client) else {
+            return;
+        };
+
+        let dy = event.position.y - event.old_position.y;
+        let horizontal = DVec3::new(
+            event.position.x - event.old_position.x,
+            0.0,
+            event.position.z - event.old_position.z,
+        )
+        .length() as f32;
+        if flags.sprinting() {
+            exhaustion.0 += 0.1 * horizontal;
+        }
+        if event.old_on_ground && !event.on_ground && dy > 0.0 {
+            exhaustion.0 += if flags.sprinting() { 0.2 } else { 0.05 };
+        }
+
+        if event.on_ground {
+            let distance = fall_distance.0;
+            fall_distance.0 = 0.0;
+            if distance > SAFE_FALL_DISTANCE {
+                damage.send(DamageEvent {
+                    client: event.client,
+                    amount: (distance - SAFE_FALL_DISTANCE).ceil(),
+                    cause: DamageCause::Fall,
+                });
+            }
+        } else if dy < 0.0 {
+            fall_distance.0 -= dy as f32;
+        }
+    });
+}
+
+/// Deals damage to players in lava, fire, water and walls, and to burning players.
+pub fn environment_damage(
+    mut clients: Query<
+        (
+            Entity,
+            &Position,
+            &VisibleChunkLayer,
+            &mut Air,
+            &mut Flags,
+            &mut Burning,
+            &mut FallDistance,
+            &mut Exhaustion,
+        ),
+        Without<Dead>,
+    >,
+    layers: Query<&ChunkLayer>,
+    server: Res<Server>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    let now = server.current_tick();
+    clients.for_each_mut(
+        |(
+            entity,
+            position,
+            visible_chunk_layer,
+            mut air,
+            mut flags,
+            mut burning,
+            mut fall_distance,
+            mut exhaustion,
+        )| {
+            let Ok(layer) = layers.get(visible_chunk_layer.0) else {
+                return;
+            };
+            let block_at = |y: f64| {
+                let position = BlockPos::new(
+                    position.0.x.floor() as i32,
+                    y.floor() as i32,
+                    position.0.z.floor() as i32,
+                );
+                layer
+                    .block(position)
+                    .map(|block| block.state)
+                    .unwrap_or(BlockState::AIR)
+            };
+            let feet = block_at(position.0.y).to_kind();
+            let head = block_at(position.0.y + PLAYER_EYE_HEIGHT).to_kind();
+            let mut hurt = |amount: f32, cause: DamageCause| {
+                damage.send(DamageEvent {
+                    client: entity,
+                    amount,
+                    cause,
+                })
+            };
+
+            let in_water = feet == BlockKind::Water || head == BlockKind::Water;
+            if in_water {
+                // water breaks falls and puts out fires
+                fall_distance.0 = 0.0;
+                burning.0 = 0;
+                exhaustion.0 += 0.01;
+            }
+            if is_climbable(feet) {
+                fall_distance.0 = 0.0;
+            }
+
+            if head == BlockKind::Water {
+                air.0 -= 1;
+                if air.0 <= -20 {
+                    air.0 = 0;
+                    hurt(2.0, DamageCause::Drowning);
+                }
+            } else if air.0 < MAX_AIR {
+                air.0 = (air.0 + 4).min(MAX_AIR);
+            }
+
+            if is_solid(head) && now % BLOCK_DAMAGE_INTERVAL == 0 {
+                hurt(1.0, DamageCause::Suffocation);
+            }
+
+            if feet == BlockKind::Lava || head == BlockKind::Lava {
+                burning.0 = burning.0.max(LAVA_BURN_TICKS);
+                fall_distance.0 = 0.0;
+                if now % BLOCK_DAMAGE_INTERVAL == 0 {
+                    hurt(4.0, DamageCause::Lava);
+                }
+            } else if matches!(feet, BlockKind::Fire | BlockKind::SoulFire) {
+                burning.0 = burning.0.max(FIRE_BURN_TICKS);
+                if now % BLOCK_DAMAGE_INTERVAL == 0 {
+                    let amount = if feet == BlockKind::SoulFire {
+                        2.0
+                    } else {
+                        1.0
+                    };
+                    hurt(amount, DamageCause::Fire);
+                }
+            } else if burning.0 > 0 {
+                burning.0 -= 1;
+                if now % BURNING_INTERVAL == 0 {
+                    hurt(1.0, DamageCause::Burning);
+                }
+            }
+
+            if flags.on_fire() != (burning.0 > 0) {
+                flags.set_on_fire(burning.0 > 0);
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::testing::{create_mock_client, ScenarioSingleClient};
+
+    struct SurvivalScenario {
+        scenario: ScenarioSingleClient,
+    }
+
+    impl SurvivalScenario {
+        fn new() -> Self {
+            let mut scenario = ScenarioSingleClient::new();
+            scenario.app.add_event::<DamageEvent>();
+            scenario.app.add_event::<DeathEvent>();
+            scenario.app.add_systems(
+                Update,
+                (
+                    attack_players,
+                    track_movement,
+                    environment_damage,
+                    update_hunger,
+                    apply_damage,
+                )
+                    .chain(),
+            );
+            scenario
+                .app
+                .world
+                .entity_mut(scenario.client)
+                .insert(SurvivalBundle::default());
+            scenario
+                .app
+                .world
+                .get_mut::<ChunkLayer>(scenario.layer)
+                .unwrap()
+                .insert_chunk([0, 0], UnloadedChunk::new());
+            scenario
+                .app
+                .world
+                .get_mut::<Position>(scenario.client)
+                .unwrap()
+                .set(DVec3::new(0.5, 1.0, 0.5));
+            scenario.app.update();
+
+            Self { scenario }
+        }
+
+        fn damage(&mut self, amount: f32, cause: DamageCause) {
+            let client = self.scenario.client;
+            self.scenario.app.world.send_event(DamageEvent {
+                client,
+                amount,
+                cause,
+            });
+            self.scenario.app.update();
+        }
+
+        fn fall(&mut self, from: f64, to: f64, on_ground: bool) {
+            let client = self.scenario.client;
+            self.scenario.app.world.send_event(MovementEvent {
+                client,
+                position: DVec3::new(0.5, to, 0.5),
+                old_position: DVec3::new(0.5, from, 0.5),
+                look: Look::default(),
+                old_look: Look::default(),
+                on_ground,
+                old_on_ground: false,
+            });
+            self.scenario.app.update();
+        }
+
+        fn health(&self) -> f32 {
+            self.get::<Health>().0
+        }
+
+        fn get<T: Component>(&self) -> &T {
+            self.scenario
+                .app
+                .world
+                .get::<T>(self.scenario.client)
+                .unwrap()
+        }
+
+        fn get_mut<T: Component>(&mut self) -> Mut<T> {
+            self.scenario
+                .app
+                .world
+                .get_mut::<T>(self.scenario.client)
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_damage_kills() {
+        let mut scenario = SurvivalScenario::new();
+        scenario.damage(5.0, DamageCause::Fall);
+        assert_eq!(15.0, scenario.health());
+        assert!(scenario
+            .scenario
+            .app
+            .world
+            .get::<Dead>(scenario.scenario.client)
+            .is_none());
+
+        scenario.damage(20.0, DamageCause::Lava);
+        assert_eq!(0.0, scenario.health());
+        assert!(scenario
+            .scenario
+            .app
+            .world
+            .get::<Dead>(scenario.scenario.client)
+            .is_some());
+    }
+
+    impl SurvivalScenario {
+        /// Spawns another player in the same world, next to the client.
+        fn spawn_victim(&mut self) -> Entity {
+            let (client, _) = create_mock_client("victim");
+            let layer = self.scenario.layer;
+            let victim = self
+                .scenario
+                .app
+                .world
+                .spawn((client, SurvivalBundle::default()))
+                .id();
+            let mut entity = self.scenario.app.world.entity_mut(victim);
+            entity.get_mut::<EntityLayerId>().unwrap().0 = layer;
+            entity
+                .get_mut::<Position>()
+                .unwrap()
+                .set(DVec3::new(2.5, 1.0, 0.5));
+            victim
+        }
+
+        fn attack(&mut self, victim: Entity) {
+            let attacker = self.scenario.client;
+            self.scenario.app.world.send_event(InteractEntityEvent {
+                client: attacker,
+                entity: victim,
+                sneaking: false,
+                interact: EntityInteraction::Attack,
+            });
+            self.scenario.app.update();
+        }
+
+        fn health_of(&self, entity: Entity) -> f32 {
+            self.scenario.app.world.get::<Health>(entity).unwrap().0
+        }
+    }
+
+    #[test]
+    fn test_players_attack_each_other() {
+        let mut scenario = SurvivalScenario::new();
+        let attacker = scenario.scenario.client;
+        let victim = scenario.spawn_victim();
+        scenario
+            .scenario
+            .app
+            .world
+            .get_mut::<Health>(victim)
+            .unwrap()
+            .0 = 1.5;
+
+        // the second hit lands during the cooldown of the first one
+        scenario.attack(victim);
+        scenario.attack(victim);
+        assert_eq!(0.5, scenario.health_of(victim));
+
+        for _ in 0..HURT_COOLDOWN {
+            scenario.scenario.app.update();
+        }
+        scenario.attack(victim);
+
+        assert!(scenario.scenario.app.world.get::<Dead>(victim).is_some());
+        let deaths = scenario.scenario.app.world.resource::<Events<DeathEvent>>();
+        let death = deaths.iter_current_update_events().next().unwrap();
+        assert_eq!(victim, death.client);
+        assert_eq!(DamageCause::Player(attacker), death.cause);
+        assert_eq!("victim was slain by test", death.message);
+    }
+
+    #[test]
+    fn test_attack_with_weapon() {
+        let mut scenario = SurvivalScenario::new();
+        let victim = scenario.spawn_victim();
+        let slot = scenario.get::<HeldItem>().slot();
+        scenario
+            .get_mut::<Inventory>()
+            .set_slot(slot, ItemStack::new(ItemKind::DiamondSword, 1, None));
+        scenario.attack(victim);
+        assert_eq!(MAX_HEALTH - 7.0, scenario.health_of(victim));
+    }
+
+    #[test]
+    fn test_attack_out_of_reach() {
+        let mut scenario = SurvivalScenario::new();
+        let victim = scenario.spawn_victim();
+        scenario
+            .scenario
+            .app
+            .world
+            .get_mut::<Position>(victim)
+            .unwrap()
+            .set(DVec3::new(8.5, 1.0, 0.5));
+        scenario.attack(victim);
+        assert_eq!(MAX_HEALTH, scenario.health_of(victim));
+
+        // close by, but in another world
+        let other_world = scenario.scenario.app.world.spawn_empty().id();
+        let mut entity = scenario.scenario.app.world.entity_mut(victim);
+        entity
+            .get_mut::<Position>()
+            .unwrap()
+            .set(DVec3::new(1.5, 1.0, 0.5));
+        entity.get_mut::<EntityLayerId>().unwrap().0 = other_world;
+        scenario.attack(victim);
+        assert_eq!(MAX_HEALTH, scenario.health_of(victim));
+    }
+
+    #[test]
+    fn test_creative_is_invulnerable() {
+        let mut scenario = SurvivalScenario::new();
+        *scenario.get_mut::<GameMode>() = GameMode::Creative;
+        scenario.damage(5.0, DamageCause::Fall);
+        assert_eq!(MAX_HEALTH, scenario.health());
+        scenario.damage(5.0, DamageCause::Void);
+        assert_eq!(15.0, scenario.health());
+    }
+
+    #[test]
+    fn test_fall_damage() {
+        let mut scenario = SurvivalScenario::new();
+        scenario.fall(20.0, 15.0, false);
+        scenario.fall(15.0, 10.0, false);
+        assert_eq!(MAX_HEALTH, scenario.health());
+        assert_eq!(10.0, scenario.get::<FallDistance>().0);
+
+        scenario.fall(10.0, 10.0, true);
+        assert_eq!(MAX_HEALTH - 7.0, scenario.health());
+        assert_eq!(0.0, scenario.get::<FallDistance>().0);
+
+        // short falls don't hurt
+        scenario.fall(12.0, 10.0, false);
+        scenario.fall(10.0, 10.0, true);
+        assert_eq!(MAX_HEALTH - 7.0, scenario.health());
+    }
+
+    #[test]
+    fn test_climbing_down_stops_falls() {
+        let mut scenario = SurvivalScenario::new();
+        {
+            let mut layer = scenario
+                .scenario
+                .app
+                .world
+                .get_mut::<ChunkLayer>(scenario.scenario.layer)
+                .unwrap();
+            for y in 1..=20 {
+                layer.set_block(BlockPos::new(0, y, 0), BlockState::LADDER);
+            }
+        }
+
+        for y in (1..20).rev() {
+            scenario
+                .get_mut::<Position>()
+                .set(DVec3::new(0.5, y as f64, 0.5));
+            scenario.fall(y as f64 + 1.0, y as f64, y == 1);
+        }
+        assert_eq!(MAX_HEALTH, scenario.health());
+    }
+
+    #[test]
+    fn test_exhaustion_uses_saturation_then_food() {
+        let mut scenario = SurvivalScenario::new();
+        scenario.get_mut::<Saturation>().0 = 1.0;
+        scenario.get_mut::<Exhaustion>().0 = EXHAUSTION_PER_FOOD;
+        scenario.scenario.app.update();
+        assert_eq!(0.0, scenario.get::<Saturation>().0);
+        assert_eq!(MAX_FOOD, scenario.get::<Food>().0);
+
+        scenario.get_mut::<Exhaustion>().0 = EXHAUSTION_PER_FOOD;
+        scenario.scenario.app.update();
+        assert_eq!(MAX_FOOD - 1, scenario.get::<Food>().0);
+    }
+
+    #[test]
+    fn test_regeneration() {
+        let mut scenario = SurvivalScenario::new();
+        scenario.damage(5.0, DamageCause::Fall);
+        for _ in 0..HUNGER_INTERVAL {
+            scenario.scenario.app.update();
+        }
+        assert_eq!(16.0, scenario.health());
+
+        // hungry players don't regenerate
+        scenario.get_mut::<Food>().0 = REGENERATION_FOOD - 1;
+        for _ in 0..HUNGER_INTERVAL {
+            scenario.scenario.app.update();
+        }
+        assert_eq!(16.0, scenario.health());
+    }
+
+    #[test]
+    fn test_lava_burns() {
+        let mut scenario = SurvivalScenario::new();
+        let layer = scenario.scenario.layer;
+        scenario
+            .scenario
+            .app
+            .world
+            .get_mut::<ChunkLayer>(layer)
+            .unwrap()
+            .set_block(BlockPos::new(0, 1, 0), BlockState::LAVA);
+        for _ in 0..BLOCK_DAMAGE_INTERVAL {
+            scenario.scenario.app.update();
+        }
+        assert_eq!(MAX_HEALTH - 4.0, scenario.health());
+        assert!(scenario.get::<Flags>().on_fire());
+
+        // players keep burning after leaving the lava
+        scenario
+            .get_mut::<Position>()
+            .set(DVec3::new(5.5, 1.0, 5.5));
+        scenario.scenario.app.update();
+        assert!(scenario.get::<Burning>().0 > 0);
+    }
+}
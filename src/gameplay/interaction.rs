@@ -0,0 +1,910 @@
+use crate::{
+    direction_from_prop, direction_to_prop, horizontal_direction, insert_into_inventory, opposite,
+    other_part, spawn_item, BlockBreakEvent, BlockChangeEvent,
+};
+use bevy_ecs::prelude::*;
+use std::collections::{HashMap, HashSet};
+use valence::entity::EntityLayerId;
+use valence::inventory::OpenInventory;
+use valence::nbt::{compound, Compound, List, Value};
+use valence::prelude::*;
+
+/// How long stone buttons stay pressed, in ticks.
+const STONE_BUTTON_TICKS: i64 = 20;
+/// How long all other buttons stay pressed, in ticks.
+const WOODEN_BUTTON_TICKS: i64 = 30;
+
+/// Everything that decides what right-clicking a block does.
+#[derive(Clone)]
+pub struct InteractionContext<'a> {
+    pub layer: &'a ChunkLayer,
+    /// The block that the client clicked on.
+    pub position: BlockPos,
+    /// The current state of the clicked block.
+    pub state: BlockState,
+    /// The face of the clicked block.
+    pub face: Direction,
+    /// The position on the clicked face, relative to the clicked block.
+    pub cursor: Vec3,
+    pub yaw: f32,
+}
+
+/// What right-clicking a block does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Interaction {
+    /// The block can't be used, so the click places the held block instead.
+    Pass,
+    /// The block was used, which changes the given blocks.
+    Change(Vec<(BlockPos, BlockState)>),
+    /// The block opens a screen with the given title, like a chest.
+    Open(InventoryKind, &'static str),
+    /// The block is a bed that the player tries to sleep in.
+    Sleep,
+}
+
+/// Decides what right-clicking a block does.
+pub type InteractionHandler = fn(&InteractionContext) -> Interaction;
+
+/// The interaction handlers of all block kinds. They run before a click is treated
+/// as a placement, and consume it unless they pass. Kinds without a registered
+/// handler use the vanilla behavior of their family (doors, buttons, ...).
+#[derive(Resource, Default)]
+pub struct InteractionHandlers {
+    handlers: HashMap<BlockKind, InteractionHandler>,
+}
+
+impl InteractionHandlers {
+    /// Replaces the handler of a block kind.
+    pub fn register(&mut self, kind: BlockKind, handler: InteractionHandler) {
+        self.handlers.insert(kind, handler);
+    }
+
+    pub fn interact(&self, ctx: &InteractionContext) -> Interaction {
+        let kind = ctx.state.to_kind();
+        match self
+            .handlers
+            .get(&kind)
+            .copied()
+            .or_else(|| default_handler(kind))
+        {
+            Some(handler) => handler(ctx),
+            None => Interaction::Pass,
+        }
+    }
+}
+
+/// Sent when a client uses a block that has a screen, like a chest.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct OpenBlockScreenEvent {
+    pub client: Entity,
+    pub layer: Entity,
+    pub position: BlockPos,
+    pub kind: InventoryKind,
+    pub title: &'static str,
+}
+
+/// The inventories of the containers that clients have open, by layer and
+/// position. The contents themselves live in the block entity data of the
+/// container, so they are saved with the chunk, see [`save_block_inventories`].
+#[derive(Resource, Default)]
+pub struct BlockInventories {
+    inventories: HashMap<(Entity, BlockPos), Entity>,
+}
+
+/// Lives on the inventory entity of an open container.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct ContainerScreen {
+    pub layer: Entity,
+    pub position: BlockPos,
+    pub kind: BlockKind,
+}
+
+/// Lives on the inventory entity of an open crafting table. Unlike containers,
+/// every client gets a grid of its own, and its items go back to the client once
+/// the screen is closed, see [`close_crafting_grids`].
+#[derive(Component, Debug, Copy, Clone)]
+pub struct CraftingGrid {
+    pub client: Entity,
+    pub layer: Entity,
+    pub position: BlockPos,
+}
+
+pub fn open_block_screens(
+    mut commands: Commands,
+    mut inventories: ResMut<BlockInventories>,
+    layers: Query<&ChunkLayer>,
+    mut events: EventReader<OpenBlockScreenEvent>,
+) {
+    events.iter().for_each(|event| {
+        let inventory = if event.kind == InventoryKind::Crafting {
+            let grid = CraftingGrid {
+                client: event.client,
+                layer: event.layer,
+                position: event.position,
+            };
+            commands
+                .spawn((Inventory::with_title(event.kind, event.title), grid))
+                .id()
+        } else {
+            let Some(block) = layers
+                .get(event.layer)
+                .ok()
+                .and_then(|layer| layer.block(event.position))
+            else {
+                return;
+            };
+            *inventories
+                .inventories
+                .entry((event.layer, event.position))
+                .or_insert_with(|| {
+                    let mut inventory = Inventory::with_title(event.kind, event.title);
+                    for (slot, stack) in container_items(block.nbt) {
+                        if slot < inventory.slot_count() {
+                            inventory.set_slot(slot, stack);
+                        }
+                    }
+                    let screen = ContainerScreen {
+                        layer: event.layer,
+                        position: event.position,
+                        kind: block.state.to_kind(),
+                    };
+                    commands.spawn((inventory, screen)).id()
+                })
+        };
+        commands
+            .entity(event.client)
+            .insert(OpenInventory::new(inventory));
+    });
+}
+
+/// Writes the contents of open containers into the block entity data of their
+/// block whenever they change, and marks the chunk as changed.
+pub fn save_block_inventories(
+    containers: Query<(&ContainerScreen, &Inventory), Changed<Inventory>>,
+    mut layers: Query<&mut ChunkLayer>,
+    mut changes: EventWriter<BlockChangeEvent>,
+) {
+    containers.for_each(|(screen, inventory)| {
+        let Ok(mut layer) = layers.get_mut(screen.layer) else {
+            return;
+        };
+        let Some(block) = layer.block(screen.position) else {
+            return;
+        };
+        if block.state.to_kind() != screen.kind {
+            // the container was replaced, see `close_block_screens`
+            return;
+        }
+
+        let state = block.state;
+        let mut nbt = block.nbt.cloned().unwrap_or_default();
+        nbt.insert("Items", encode_items(inventory));
+        layer.set_block(
+            screen.position,
+            Block {
+                state,
+                nbt: Some(nbt),
+            },
+        );
+        changes.send(BlockChangeEvent {
+            layer: screen.layer,
+            position: screen.position,
+        });
+    });
+}
+
+/// Despawns the inventories of containers that nobody has open anymore, and of
+/// containers that were replaced by another block, like with `/setblock`. The
+/// contents are kept in the block, or went away with it.
+///
+/// Must run before [`open_block_screens`], so that a client has the screen open
+/// by the time this checks for it.
+pub fn close_block_screens(
+    mut commands: Commands,
+    mut inventories: ResMut<BlockInventories>,
+    screens: Query<&ContainerScreen>,
+    clients: Query<&OpenInventory>,
+    layers: Query<&ChunkLayer>,
+) {
+    let open = clients
+        .iter()
+        .map(|open| open.entity)
+        .collect::<HashSet<_>>();
+    inventories.inventories.retain(|_, inventory| {
+        let in_place = screens.get(*inventory).is_ok_and(|screen| {
+            layers
+                .get(screen.layer)
+                .ok()
+                .and_then(|layer| layer.block(screen.position))
+                .is_some_and(|block| block.state.to_kind() == screen.kind)
+        });
+        if in_place && open.contains(inventory) {
+            return true;
+        }
+        if let Some(mut entity) = commands.get_entity(*inventory) {
+            entity.despawn();
+        }
+        false
+    });
+}
+
+/// Gives the items in crafting grids back to their client once the screen is
+/// closed. Items that don't fit are dropped, like the items of clients that left.
+pub fn close_crafting_grids(
+    mut commands: Commands,
+    grids: Query<(Entity, &CraftingGrid, &Inventory)>,
+    mut clients: Query<
+        (
+            Option<&OpenInventory>,
+            &mut Inventory,
+            &EntityLayerId,
+            &Position,
+        ),
+        Without<CraftingGrid>,
+    >,
+    server: Res<Server>,
+) {
+    grids.for_each(|(entity, grid, contents)| {
+        let client = clients.get_mut(grid.client).ok();
+        if client
+            .as_ref()
+            .and_then(|(open, ..)| *open)
+            .is_some_and(|open| open.entity == entity)
+        {
+            return;
+        }
+
+        let (layer, position) = match &client {
+            Some((_, _, layer_id, position)) => (layer_id.0, position.0),
+            None => (
+                grid.layer,
+                DVec3::new(
+                    grid.position.x as f64 + 0.5,
+                    grid.position.y as f64 + 1.0,
+                    grid.position.z as f64 + 0.5,
+                ),
+            ),
+        };
+        let mut inventory = client.map(|(_, inventory, ..)| inventory);
+        for stack in contents.slots().iter().filter(|stack| !stack.is_empty()) {
+            let remaining = match inventory.as_mut() {
+                Some(inventory) => insert_into_inventory(inventory, stack.clone()),
+                None => stack.count,
+            };
+            if remaining > 0 {
+                spawn_item(
+                    &mut commands,
+                    layer,
+                    position,
+                    stack.clone().with_count(remaining),
+                    server.current_tick(),
+                );
+            }
+        }
+        commands.entity(entity).despawn();
+    });
+}
+
+/// Drops the contents of blocks with an inventory when they are broken.
+pub fn drop_block_inventories(
+    mut commands: Commands,
+    mut inventories: ResMut<BlockInventories>,
+    contents: Query<&Inventory>,
+    server: Res<Server>,
+    mut events: EventReader<BlockBreakEvent>,
+) {
+    events.iter().for_each(|event| {
+        // an open inventory may have changes that weren't written to the block yet
+        let open = inventories
+            .inventories
+            .remove(&(event.layer, event.position));
+        let stacks = match open.and_then(|inventory| contents.get(inventory).ok()) {
+            Some(contents) => contents.slots().to_vec(),
+            None => container_items(event.nbt.as_ref())
+                .into_iter()
+                .map(|(_, stack)| stack)
+                .collect(),
+        };
+        if let Some(inventory) = open {
+            commands.entity(inventory).despawn();
+        }
+
+        let position = DVec3::new(
+            event.position.x as f64 + 0.5,
+            event.position.y as f64 + 0.25,
+            event.position.z as f64 + 0.5,
+        );
+        for stack in stacks.into_iter().filter(|stack| !stack.is_empty()) {
+            spawn_item(
+                &mut commands,
+                event.layer,
+                position,
+                stack,
+                server.current_tick(),
+            );
+        }
+    });
+}
+
+/// Decodes the `Items` of a container's block entity data, in which vanilla
+/// stores the stacks by their slot.
+fn container_items(nbt: Option<&Compound>) -> Vec<(u16, ItemStack)> {
+    let Some(Value::List(List::Compound(items))) = nbt.and_then(|nbt| nbt.get("Items")) else {
+        return vec![];
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let (Some(Value::Byte(slot)), Some(Value::Byte(count)), Some(Value::String(id))) =
+                (item.get("Slot"), item.get("Count"), item.get("id"))
+            else {
+                return None;
+            };
+            let kind = ItemKind::from_str(id.strip_prefix("minecraft:").unwrap_or(id))?;
+            let tag = match item.get("tag") {
+                Some(Value::Compound(tag)) => Some(tag.clone()),
+                _ => None,
+            };
+            Some((
+                u16::try_from(*slot).ok()?,
+                ItemStack::new(kind, *count, tag),
+            ))
+        })
+        .collect()
+}
+
+fn encode_items(inventory: &Inventory) -> List {
+    let items = inventory
+        .slots()
+        .iter()
+        .enumerate()
+        .filter(|(_, stack)| !stack.is_empty())
+        .map(|(slot, stack)| {
+            let mut item = compound! {
+                "Slot" => slot as i8,
+                "id" => format!("minecraft:{}", stack.item.to_str()),
+                "Count" => stack.count,
+            };
+            if let Some(tag) = &stack.nbt {
+                item.insert("tag", tag.clone());
+            }
+            item
+        })
+        .collect();
+    List::Compound(items)
+}
+
+/// Releases pressed buttons once their time is up.
+pub fn release_buttons(
+    mut pressed: Local<Vec<(Entity, BlockPos, i64)>>,
+    mut layers: Query<&mut ChunkLayer>,
+    server: Res<Server>,
+    mut events: ParamSet<(EventReader<BlockChangeEvent>, EventWriter<BlockChangeEvent>)>,
+) {
+    let now = server.current_tick();
+    for event in events.p0().iter() {
+        let Ok(layer) = layers.get(event.layer) else {
+            continue;
+        };
+        if let Some(state) = layer
+            .block(event.position)
+            .map(|block| block.state)
+            .filter(|&state| is_pressed_button(state))
+        {
+            let ticks = match state.to_kind() {
+                BlockKind::StoneButton | BlockKind::PolishedBlackstoneButton => STONE_BUTTON_TICKS,
+                _ => WOODEN_BUTTON_TICKS,
+            };
+            pressed.push((event.layer, event.position, now + ticks));
+        }
+    }
+
+    let mut released = vec![];
+    pressed.retain(|&(layer_entity, position, release)| {
+        if release > now {
+            return true;
+        }
+        if let Ok(mut layer) = layers.get_mut(layer_entity) {
+            if let Some(state) = layer
+                .block(position)
+                .map(|block| block.state)
+                .filter(|&state| is_pressed_button(state))
+            {
+                layer.set_block(position, state.set(PropName::Powered, PropValue::False));
+                released.push(BlockChangeEvent {
+                    layer: layer_entity,
+                    position,
+                });
+            }
+        }
+        false
+    });
+
+    let mut writer = events.p1();
+    for event in released {
+        writer.send(event);
+    }
+}
+
+fn is_pressed_button(state: BlockState) -> bool {
+    state.to_kind().to_str().ends_with("_button")
+        && state.get(PropName::Powered) == Some(PropValue::True)
+}
+
+fn default_handler(kind: BlockKind) -> Option<InteractionHandler> {
+    let name = kind.to_str();
+    match name {
+        // iron doors and trapdoors only open when they are powered
+        "iron_door" | "iron_trapdoor" => None,
+        "lever" => Some(toggle_lever),
+        "crafting_table" => Some(open_crafting_table),
+        "chest" | "trapped_chest" => Some(open_chest),
+        "barrel" => Some(open_barrel),
+        _ if name.ends_with("_door") => Some(toggle_door),
+        _ if name.ends_with("_trapdoor") => Some(toggle_open),
+        _ if name.ends_with("_fence_gate") => Some(toggle_fence_gate),
+        _ if name.ends_with("_button") => Some(press_button),
+        _ if name.ends_with("_bed") => Some(sleep),
+        _ => None,
+    }
+}
+
+fn flipped(state: BlockState, prop: PropName) -> BlockState {
+    let value = if state.get(prop) == Some(PropValue::True) {
+        PropValue::False
+    } else {
+        PropValue::True
+    };
+    state.set(prop, value)
+}
+
+fn toggle_open(ctx: &InteractionContext) -> Interaction {
+    Interaction::Change(vec![(ctx.position, flipped(ctx.state, PropName::Open))])
+}
+
+/// Opens or closes both halves of a door.
+fn toggle_door(ctx: &InteractionContext) -> Interaction {
+    let open = flipped(ctx.state, PropName::Open)
+        .get(PropName::Open)
+        .unwrap_or(PropValue::False);
+    let other = other_part(ctx.position, ctx.state)
+        .and_then(|position| Some((position, ctx.layer.block(position)?.state)))
+        .filter(|(_, other)| other.to_kind() == ctx.state.to_kind());
+
+    Interaction::Change(
+        std::iter::once((ctx.position, ctx.state))
+            .chain(other)
+            .map(|(position, state)| (position, state.set(PropName::Open, open)))
+            .collect(),
+    )
+}
+
+/// Fence gates always open away from the player.
+fn toggle_fence_gate(ctx: &InteractionContext) -> Interaction {
+    let state = flipped(ctx.state, PropName::Open);
+    let direction = horizontal_direction(ctx.yaw);
+    let facing = ctx
+        .state
+        .get(PropName::Facing)
+        .and_then(direction_from_prop);
+    let state = if state.get(PropName::Open) == Some(PropValue::True)
+        && facing == Some(opposite(direction))
+    {
+        state.set(PropName::Facing, direction_to_prop(direction))
+    } else {
+        state
+    };
+    Interaction::Change(vec![(ctx.position, state)])
+}
+
+fn toggle_lever(ctx: &InteractionContext) -> Interaction {
+    Interaction::Change(vec![(ctx.position, flipped(ctx.state, PropName::Powered))])
+}
+
+/// Buttons stay pressed for a while, see [`release_buttons`].
+fn press_button(ctx: &InteractionContext) -> Interaction {
+    if is_pressed_button(ctx.state) {
+        return Interaction::Change(vec![]);
+    }
+    Interaction::Change(vec![(
+        ctx.position,
+        ctx.state.set(PropName::Powered, PropValue::True),
+    )])
+}
+
+// crafting isn't implemented, so the grid only holds the items until it is closed
+fn open_crafting_table(_: &InteractionContext) -> Interaction {
+    Interaction::Open(InventoryKind::Crafting, "Crafting")
+}
+
+fn open_chest(_: &InteractionContext) -> Interaction {
+    Interaction::Open(InventoryKind::Generic9x3, "Chest")
+}
+
+fn open_barrel(_: &InteractionContext) -> Interaction {
+    Interaction::Open(InventoryKind::Generic9x3, "Barrel")
+}
+
+/// Beds set the spawn point and let players sleep, see [`go_to_bed`](crate::go_to_bed).
+fn sleep(_: &InteractionContext) -> Interaction {
+    Interaction::Sleep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::place_block;
+    use crate::testing::{eval_script, TestableEnvironment};
+    use crate::{PlacementResolvers, SleepEvent};
+    use valence::interact_block::InteractBlockEvent;
+    use valence::testing::ScenarioSingleClient;
+
+    fn add_systems(app: &mut App) {
+        app.add_event::<BlockChangeEvent>();
+        app.add_event::<BlockBreakEvent>();
+        app.add_event::<OpenBlockScreenEvent>();
+        app.add_event::<SleepEvent>();
+        app.init_resource::<PlacementResolvers>();
+        app.init_resource::<InteractionHandlers>();
+        app.init_resource::<BlockInventories>();
+        app.add_systems(
+            Update,
+            (
+                drop_block_inventories,
+                close_block_screens,
+                close_crafting_grids,
+                place_block,
+                open_block_screens,
+                release_buttons,
+                save_block_inventories,
+            )
+                .chain(),
+        );
+    }
+
+    struct InteractionEnvironment<T: TestableEnvironment = ScenarioSingleClient> {
+        env: T,
+    }
+
+    impl<T: TestableEnvironment> TestableEnvironment for InteractionEnvironment<T> {
+        fn new() -> Self {
+            let mut env = T::new();
+            add_systems(env.app());
+            env.app().update();
+            Self { env }
+        }
+
+        fn app(&mut self) -> &mut App {
+            self.env.app()
+        }
+
+        fn layer(&self) -> Entity {
+            self.env.layer()
+        }
+
+        fn client(&self) -> Entity {
+            self.env.client()
+        }
+    }
+
+    struct InteractionScenario {
+        scenario: ScenarioSingleClient,
+    }
+
+    impl InteractionScenario {
+        fn new(state: BlockState) -> Self {
+            let mut scenario = ScenarioSingleClient::new();
+            add_systems(&mut scenario.app);
+            scenario.app.update();
+
+            {
+                let mut layer = scenario
+                    .app
+                    .world
+                    .get_mut::<ChunkLayer>(scenario.layer)
+                    .unwrap();
+                layer.insert_chunk([0, 0], UnloadedChunk::new());
+                layer.set_block(BlockPos::new(0, 0, 0), state);
+            }
+            scenario
+                .app
+                .world
+                .get_mut::<Position>(scenario.client)
+                .unwrap()
+                .set(DVec3::new(2.5, 0.0, 2.5));
+            scenario.app.update();
+
+            Self { scenario }
+        }
+
+        fn interact(&mut self) {
+            let client = self.scenario.client;
+            self.scenario.app.world.send_event(InteractBlockEvent {
+                client,
+                hand: Hand::Main,
+                position: BlockPos::new(0, 0, 0),
+                face: Direction::Up,
+                cursor_pos: Vec3::new(0.5, 1.0, 0.5),
+                head_inside_block: false,
+                sequence: 0,
+            });
+            self.scenario.app.update();
+        }
+
+        fn wait(&mut self, ticks: u32) {
+            for _ in 0..ticks {
+                self.scenario.app.update();
+            }
+        }
+
+        fn block(&self) -> BlockState {
+            self.scenario
+                .app
+                .world
+                .get::<ChunkLayer>(self.scenario.layer)
+                .unwrap()
+                .block(BlockPos::new(0, 0, 0))
+                .unwrap()
+                .state
+        }
+
+        fn block_nbt(&self) -> Option<Compound> {
+            self.scenario
+                .app
+                .world
+                .get::<ChunkLayer>(self.scenario.layer)
+                .unwrap()
+                .block(BlockPos::new(0, 0, 0))
+                .unwrap()
+                .nbt
+                .cloned()
+        }
+
+        /// The inventory of the screen that the client has open.
+        fn open_inventory(&mut self) -> Option<Mut<Inventory>> {
+            let client = self.scenario.client;
+            let open = self.scenario.app.world.get::<OpenInventory>(client)?.entity;
+            self.scenario.app.world.get_mut::<Inventory>(open)
+        }
+
+        fn close_screen(&mut self) {
+            let client = self.scenario.client;
+            self.scenario
+                .app
+                .world
+                .entity_mut(client)
+                .remove::<OpenInventory>();
+            self.scenario.app.update();
+        }
+    }
+
+    #[test]
+    fn test_open_door() {
+        eval_script::<InteractionEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item oak_planks count 2
+            set held_item 36
+            set position 0 1 0 block oak_door[half=lower]
+            set position 0 2 0 block oak_door[half=upper]
+
+            # clicking either half opens the whole door instead of placing a block
+            interact position 0 2 0 face north
+            assert position 0 1 0 block oak_door[half=lower,open=true]
+            assert position 0 2 0 block oak_door[half=upper,open=true]
+            assert position 0 2 -1 block air
+            assert inventory slot 36 item oak_planks count 2
+
+            interact position 0 1 0 face north
+            assert position 0 1 0 block oak_door[half=lower,open=false]
+            assert position 0 2 0 block oak_door[half=upper,open=false]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_iron_door_stays_closed() {
+        eval_script::<InteractionEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set position 0 1 0 block iron_door[half=lower]
+            set position 0 2 0 block iron_door[half=upper]
+
+            interact position 0 1 0 face north
+            assert position 0 1 0 block iron_door[half=lower,open=false]
+            assert position 0 2 0 block iron_door[half=upper,open=false]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_toggle_trapdoor_and_lever() {
+        eval_script::<InteractionEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set position 0 1 0 block oak_trapdoor
+            set position 1 1 0 block lever[face=floor]
+            set position 2 1 0 block iron_trapdoor
+
+            interact position 0 1 0 face up
+            assert position 0 1 0 block oak_trapdoor[open=true]
+
+            interact position 1 1 0 face up
+            assert position 1 1 0 block lever[face=floor,powered=true]
+            interact position 1 1 0 face up
+            assert position 1 1 0 block lever[face=floor,powered=false]
+
+            interact position 2 1 0 face up
+            assert position 2 1 0 block iron_trapdoor[open=false]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_fence_gate_opens_away_from_player() {
+        eval_script::<InteractionEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set player look 0 0
+            set position 0 1 0 block oak_fence_gate[facing=north]
+
+            interact position 0 1 0 face north
+            assert position 0 1 0 block oak_fence_gate[facing=south,open=true]
+
+            interact position 0 1 0 face north
+            assert position 0 1 0 block oak_fence_gate[facing=south,open=false]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_sneaking_places_block() {
+        eval_script::<InteractionEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item oak_planks count 2
+            set held_item 36
+            set position 0 1 0 block oak_trapdoor
+
+            set player sneaking true
+            interact position 0 1 0 face north
+            assert position 0 1 0 block oak_trapdoor
+            assert position 0 1 -1 block oak_planks
+            assert inventory slot 36 item oak_planks count 1
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_button_is_released() {
+        let mut scenario = InteractionScenario::new(BlockState::STONE_BUTTON);
+        scenario.interact();
+        assert_eq!(
+            Some(PropValue::True),
+            scenario.block().get(PropName::Powered)
+        );
+        scenario.wait(STONE_BUTTON_TICKS as u32);
+        assert_eq!(
+            Some(PropValue::False),
+            scenario.block().get(PropName::Powered)
+        );
+    }
+
+    #[test]
+    fn test_open_chest() {
+        let mut scenario = InteractionScenario::new(BlockState::CHEST);
+        scenario.interact();
+
+        let client = scenario.scenario.client;
+        let open = scenario
+            .scenario
+            .app
+            .world
+            .get::<OpenInventory>(client)
+            .expect("chest screen should be open");
+        let inventory = scenario
+            .scenario
+            .app
+            .world
+            .get::<Inventory>(open.entity)
+            .unwrap();
+        assert_eq!(InventoryKind::Generic9x3, inventory.kind());
+        assert_eq!(BlockState::CHEST, scenario.block());
+    }
+
+    #[test]
+    fn test_chest_contents_are_kept_in_the_block() {
+        let mut scenario = InteractionScenario::new(BlockState::CHEST);
+        scenario.interact();
+        let stack = ItemStack::new(ItemKind::Diamond, 3, None);
+        scenario
+            .open_inventory()
+            .unwrap()
+            .set_slot(4, stack.clone());
+        scenario.wait(1);
+        assert_eq!(
+            vec![(4, stack.clone())],
+            container_items(scenario.block_nbt().as_ref())
+        );
+
+        scenario.close_screen();
+        assert!(scenario
+            .scenario
+            .app
+            .world
+            .resource::<BlockInventories>()
+            .inventories
+            .is_empty());
+
+        scenario.interact();
+        assert_eq!(&stack, scenario.open_inventory().unwrap().slot(4));
+    }
+
+    #[test]
+    fn test_replaced_chest_closes() {
+        let mut scenario = InteractionScenario::new(BlockState::CHEST);
+        scenario.interact();
+        let layer = scenario.scenario.layer;
+        scenario
+            .scenario
+            .app
+            .world
+            .get_mut::<ChunkLayer>(layer)
+            .unwrap()
+            .set_block(BlockPos::new(0, 0, 0), BlockState::STONE);
+        scenario.wait(2);
+
+        assert!(scenario.open_inventory().is_none());
+        assert!(scenario
+            .scenario
+            .app
+            .world
+            .resource::<BlockInventories>()
+            .inventories
+            .is_empty());
+    }
+
+    #[test]
+    fn test_broken_chest_drops_contents() {
+        let mut scenario = InteractionScenario::new(BlockState::AIR);
+        let stack = ItemStack::new(ItemKind::Diamond, 3, None);
+        let mut inventory = Inventory::new(InventoryKind::Generic9x3);
+        inventory.set_slot(10, stack.clone());
+        let (client, layer) = (scenario.scenario.client, scenario.scenario.layer);
+        scenario.scenario.app.world.send_event(BlockBreakEvent {
+            client,
+            layer,
+            position: BlockPos::new(0, 0, 0),
+            state: BlockState::CHEST,
+            nbt: Some(compound! { "Items" => encode_items(&inventory) }),
+        });
+        scenario.wait(1);
+
+        let dropped = scenario
+            .scenario
+            .app
+            .world
+            .query::<&valence::entity::item::Stack>()
+            .iter(&scenario.scenario.app.world)
+            .map(|dropped| dropped.0.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec![stack], dropped);
+    }
+
+    #[test]
+    fn test_crafting_grid_returns_items() {
+        let mut scenario = InteractionScenario::new(BlockState::CRAFTING_TABLE);
+        scenario.interact();
+        let stack = ItemStack::new(ItemKind::OakPlanks, 4, None);
+        scenario
+            .open_inventory()
+            .unwrap()
+            .set_slot(1, stack.clone());
+
+        scenario.close_screen();
+        let client = scenario.scenario.client;
+        let world = &mut scenario.scenario.app.world;
+        assert_eq!(&stack, world.get::<Inventory>(client).unwrap().slot(36));
+        assert_eq!(0, world.query::<&CraftingGrid>().iter(world).count());
+    }
+}
@@ -0,0 +1,551 @@
+use crate::{block_drops, BlockBreakEvent, Dead};
+use bevy_ecs::prelude::*;
+use std::collections::HashMap;
+use valence::entity::item::{ItemEntityBundle, Stack};
+use valence::entity::{EntityId, EntityLayerId};
+use valence::inventory::HeldItem;
+use valence::prelude::*;
+use valence::protocol::packets::play::ItemPickupAnimationS2c;
+use valence::protocol::{VarInt, WritePacket};
+
+/// Ticks after spawning before a dropped item can be picked up.
+pub const PICKUP_DELAY: i64 = 10;
+/// Ticks after which a dropped item despawns (5 minutes).
+pub const ITEM_LIFETIME: i64 = 5 * 60 * 20;
+
+/// How far items fall per tick while there is air below them.
+const FALL_SPEED: f64 = 0.25;
+
+/// A dropped item, lives on an item entity next to its [`Stack`].
+#[derive(Component, Debug, Copy, Clone)]
+pub struct DroppedItem {
+    /// The server tick in which the item was dropped.
+    pub spawned: i64,
+}
+
+/// Spawns a dropped item entity in the given layer.
+pub fn spawn_item(
+    commands: &mut Commands,
+    layer: Entity,
+    position: DVec3,
+    stack: ItemStack,
+    now: i64,
+) -> Entity {
+    commands
+        .spawn((
+            ItemEntityBundle {
+                layer: EntityLayerId(layer),
+                position: Position(position),
+                item_stack: Stack(stack),
+                ..Default::default()
+            },
+            DroppedItem { spawned: now },
+        ))
+        .id()
+}
+
+/// Drops the loot of blocks that survival players break.
+pub fn drop_block_loot(
+    mut commands: Commands,
+    clients: Query<(&GameMode, &HeldItem, &Inventory)>,
+    server: Res<Server>,
+    mut events: EventReader<BlockBreakEvent>,
+) {
+    events.iter().for_each(|event| {
+        let Ok((game_mode, held_item, inventory)) = clients.get(event.client) else {
+            return;
+        };
+        if *game_mode != GameMode::Survival {
+            return;
+        }
+
+        let position = DVec3::new(
+            event.position.x as f64 + 0.5,
+            event.position.y as f64 + 0.25,
+            event.position.z as f64 + 0.5,
+        );
+        for stack in block_drops(event.state, inventory.slot(held_item.slot())) {
+            spawn_item(
+                &mut commands,
+                event.layer,
+                position,
+                stack,
+                server.current_tick(),
+            );
+        }
+    });
+}
+
+/// Lets dropped items fall until they land on a block. Items in chunks that aren't
+/// loaded stay where they are.
+pub fn item_gravity(
+    mut items: Query<(&EntityLayerId, &mut Position), With<DroppedItem>>,
+    layers: Query<&ChunkLayer>,
+) {
+    items.for_each_mut(|(layer_id, mut position)| {
+        let Ok(layer) = layers.get(layer_id.0) else {
+            return;
+        };
+
+        let below = DVec3::new(position.0.x, position.0.y - FALL_SPEED, position.0.z);
+        let block_pos = BlockPos::new(
+            below.x.floor() as i32,
+            below.y.floor() as i32,
+            below.z.floor() as i32,
+        );
+        if layer.chunk(ChunkPos::from_block_pos(block_pos)).is_none() {
+            return;
+        }
+        match layer.block(block_pos) {
+            Some(block) if block.state.is_air() => position.set(below),
+            Some(_) if position.0.y > below.y.ceil() => {
+                // land on top of the block
+                position.set(DVec3::new(below.x, below.y.ceil(), below.z));
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Merges dropped items of the same world that lie next to each other into a
+/// single stack.
+pub fn merge_items(
+    mut commands: Commands,
+    mut items: Query<
+        (Entity, &EntityLayerId, &Position, &mut Stack, &DroppedItem),
+        Without<Despawned>,
+    >,
+) {
+    let mut candidates = items
+        .iter()
+        .map(|(entity, layer_id, position, stack, dropped)| {
+            (
+                entity,
+                layer_id.0,
+                position.0,
+                stack.0.clone(),
+                dropped.spawned,
+            )
+        })
+        .collect::<Vec<_>>();
+    // older items absorb younger ones
+    candidates.sort_by_key(|(_, _, _, _, spawned)| *spawned);
+
+    // items merge within half a block, so only items in neighbouring blocks of the
+    // same layer have to be compared
+    let mut cells = HashMap::<_, Vec<usize>>::new();
+    for (index, (_, layer, position, _, _)) in candidates.iter().enumerate() {
+        cells
+            .entry((*layer, cell_of(*position)))
+            .or_default()
+            .push(index);
+    }
+
+    for i in 0..candidates.len() {
+        let (layer, (x, y, z)) = (candidates[i].1, cell_of(candidates[i].2));
+        let mut neighbours = vec![];
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(indices) = cells.get(&(layer, (x + dx, y + dy, z + dz))) {
+                        neighbours.extend(indices.iter().copied().filter(|j| *j > i));
+                    }
+                }
+            }
+        }
+        neighbours.sort_unstable();
+
+        for j in neighbours {
+            let (into, _, into_pos, into_stack, _) = &candidates[i];
+            let (from, _, from_pos, from_stack, _) = &candidates[j];
+            if into_stack.is_empty()
+                || from_stack.is_empty()
+                || into_stack.item != from_stack.item
+                || into_stack.nbt != from_stack.nbt
+                || (into_pos.x - from_pos.x).abs() > 0.5
+                || (into_pos.z - from_pos.z).abs() > 0.5
+                || (into_pos.y - from_pos.y).abs() > 0.5
+            {
+                continue;
+            }
+
+            let max = into_stack.item.max_stack();
+            if into_stack.count as i32 + from_stack.count as i32 > max as i32 {
+                continue;
+            }
+
+            let (into, from) = (*into, *from);
+            let count = into_stack.count + from_stack.count;
+            candidates[i].3.count = count;
+            candidates[j].3 = ItemStack::EMPTY;
+
+            if let Ok((_, _, _, mut stack, _)) = items.get_mut(into) {
+                stack.0.count = count;
+            }
+            commands.entity(from).insert(Despawned);
+        }
+    }
+}
+
+fn cell_of(position: DVec3) -> (i32, i32, i32) {
+    (
+        position.x.floor() as i32,
+        position.y.floor() as i32,
+        position.z.floor() as i32,
+    )
+}
+
+/// Moves dropped items that are close to a player into the player's inventory.
+pub fn pick_up_items(
+    mut commands: Commands,
+    mut clients: Query<
+        (
+            &mut Client,
+            &EntityId,
+            &EntityLayerId,
+            &Position,
+            &GameMode,
+            &mut Inventory,
+        ),
+        Without<Dead>,
+    >,
+    mut items: Query<
+        (
+            Entity,
+            &EntityId,
+            &EntityLayerId,
+            &Position,
+            &mut Stack,
+            &DroppedItem,
+        ),
+        Without<Despawned>,
+    >,
+    server: Res<Server>,
+) {
+    let now = server.current_tick();
+    clients.for_each_mut(
+        |(mut client, client_id, client_layer, client_pos, game_mode, mut inventory)| {
+            if *game_mode == GameMode::Spectator {
+                return;
+            }
+
+            items.for_each_mut(
+                |(entity, item_id, item_layer, item_pos, mut stack, dropped)| {
+                    if item_layer.0 != client_layer.0
+                        || now - dropped.spawned < PICKUP_DELAY
+                        || stack.0.is_empty()
+                    {
+                        return;
+                    }
+                    // the player hitbox, grown by 1 block horizontally and half a block vertically
+                    let offset = item_pos.0 - client_pos.0;
+                    if offset.x.abs() > 1.3
+                        || offset.z.abs() > 1.3
+                        || !(-0.5..2.3).contains(&offset.y)
+                    {
+                        return;
+                    }
+
+                    let before = stack.0.count;
+                    let remaining = insert_into_inventory(&mut inventory, stack.0.clone());
+                    if remaining == before {
+                        // inventory is full
+                        return;
+                    }
+
+                    client.write_packet(&ItemPickupAnimationS2c {
+                        collected_entity_id: VarInt(item_id.get()),
+                        collector_entity_id: VarInt(client_id.get()),
+                        pickup_item_count: VarInt((before - remaining) as i32),
+                    });
+                    if remaining == 0 {
+                        stack.0 = ItemStack::EMPTY;
+                        commands.entity(entity).insert(Despawned);
+                    } else {
+                        stack.0.count = remaining;
+                    }
+                },
+            );
+        },
+    );
+}
+
+/// Despawns dropped items that nobody picked up in time.
+pub fn despawn_old_items(
+    mut commands: Commands,
+    items: Query<(Entity, &DroppedItem), Without<Despawned>>,
+    server: Res<Server>,
+) {
+    let now = server.current_tick();
+    items.for_each(|(entity, dropped)| {
+        if now - dropped.spawned >= ITEM_LIFETIME {
+            commands.entity(entity).insert(Despawned);
+        }
+    });
+}
+
+/// Puts the given stack into the player's hotbar and main inventory, filling up
+/// matching stacks first. Returns the number of items that didn't fit.
+pub fn insert_into_inventory(inventory: &mut Inventory, stack: ItemStack) -> i8 {
+    const SLOTS: [std::ops::Range<u16>; 2] = [36..45, 9..36];
+
+    let mut remaining = stack.count;
+    let max = stack.item.max_stack();
+
+    for slot in SLOTS.into_iter().flatten() {
+        if remaining == 0 {
+            return 0;
+        }
+        let current = inventory.slot(slot);
+        if current.is_empty()
+            || current.item != stack.item
+            || current.nbt != stack.nbt
+            || current.count >= max
+        {
+            continue;
+        }
+        let moved = remaining.min(max - current.count);
+        let count = current.count + moved;
+        inventory.set_slot_amount(slot, count);
+        remaining -= moved;
+    }
+
+    for slot in SLOTS.into_iter().flatten() {
+        if remaining == 0 {
+            return 0;
+        }
+        if !inventory.slot(slot).is_empty() {
+            continue;
+        }
+        let moved = remaining.min(max);
+        inventory.set_slot(slot, stack.clone().with_count(moved));
+        remaining -= moved;
+    }
+
+    remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::testing::ScenarioSingleClient;
+
+    fn setup() -> ScenarioSingleClient {
+        let mut scenario = ScenarioSingleClient::new();
+        scenario.app.add_systems(
+            Update,
+            (item_gravity, merge_items, pick_up_items, despawn_old_items),
+        );
+        scenario.app.update();
+
+        let mut layer = scenario
+            .app
+            .world
+            .get_mut::<ChunkLayer>(scenario.layer)
+            .unwrap();
+        layer.insert_chunk([0, 0], UnloadedChunk::new());
+        layer.set_block(BlockPos::new(0, 0, 0), BlockState::STONE);
+        scenario.app.update();
+        scenario
+    }
+
+    fn spawn(scenario: &mut ScenarioSingleClient, position: DVec3, stack: ItemStack) -> Entity {
+        let now = scenario.app.world.resource::<Server>().current_tick();
+        spawn_at(scenario, position, stack, now)
+    }
+
+    fn spawn_at(
+        scenario: &mut ScenarioSingleClient,
+        position: DVec3,
+        stack: ItemStack,
+        spawned: i64,
+    ) -> Entity {
+        let layer = scenario.layer;
+        scenario
+            .app
+            .world
+            .spawn((
+                ItemEntityBundle {
+                    layer: EntityLayerId(layer),
+                    position: Position(position),
+                    item_stack: Stack(stack),
+                    ..Default::default()
+                },
+                DroppedItem { spawned },
+            ))
+            .id()
+    }
+
+    fn is_alive(scenario: &ScenarioSingleClient, entity: Entity) -> bool {
+        scenario
+            .app
+            .world
+            .get_entity(entity)
+            .is_some_and(|e| !e.contains::<Despawned>())
+    }
+
+    fn move_client(scenario: &mut ScenarioSingleClient, position: DVec3) {
+        scenario
+            .app
+            .world
+            .get_mut::<Position>(scenario.client)
+            .unwrap()
+            .set(position);
+    }
+
+    #[test]
+    fn test_insert_into_inventory() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.set_slot(37, ItemStack::new(ItemKind::Dirt, 60, None));
+        assert_eq!(
+            0,
+            insert_into_inventory(&mut inventory, ItemStack::new(ItemKind::Dirt, 10, None))
+        );
+        assert_eq!(64, inventory.slot(37).count);
+        assert_eq!(ItemStack::new(ItemKind::Dirt, 6, None), *inventory.slot(36));
+
+        for slot in 9..45 {
+            inventory.set_slot(slot, ItemStack::new(ItemKind::Stone, 64, None));
+        }
+        assert_eq!(
+            3,
+            insert_into_inventory(&mut inventory, ItemStack::new(ItemKind::Dirt, 3, None))
+        );
+    }
+
+    #[test]
+    fn test_pick_up_after_delay() {
+        let mut scenario = setup();
+        move_client(&mut scenario, DVec3::new(5.5, 1.0, 0.5));
+        let item = spawn(
+            &mut scenario,
+            DVec3::new(0.5, 1.0, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 1, None),
+        );
+        scenario.app.update();
+        move_client(&mut scenario, DVec3::new(0.5, 1.0, 0.5));
+        scenario.app.update();
+        assert!(is_alive(&scenario, item), "picked up before the delay");
+
+        for _ in 0..PICKUP_DELAY {
+            scenario.app.update();
+        }
+        assert!(!is_alive(&scenario, item));
+        let inventory = scenario
+            .app
+            .world
+            .get::<Inventory>(scenario.client)
+            .unwrap();
+        assert_eq!(
+            ItemStack::new(ItemKind::Cobblestone, 1, None),
+            *inventory.slot(36)
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut scenario = setup();
+        move_client(&mut scenario, DVec3::new(20.5, 1.0, 0.5));
+        let a = spawn(
+            &mut scenario,
+            DVec3::new(0.5, 1.0, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 2, None),
+        );
+        scenario.app.update();
+        let b = spawn(
+            &mut scenario,
+            DVec3::new(0.7, 1.0, 0.4),
+            ItemStack::new(ItemKind::Cobblestone, 3, None),
+        );
+        let c = spawn(
+            &mut scenario,
+            DVec3::new(0.6, 1.0, 0.5),
+            ItemStack::new(ItemKind::Dirt, 1, None),
+        );
+        scenario.app.update();
+
+        assert!(is_alive(&scenario, a));
+        assert!(!is_alive(&scenario, b));
+        assert!(is_alive(&scenario, c));
+        assert_eq!(5, scenario.app.world.get::<Stack>(a).unwrap().0.count);
+    }
+
+    #[test]
+    fn test_no_merge_across_worlds() {
+        let mut scenario = setup();
+        move_client(&mut scenario, DVec3::new(20.5, 1.0, 0.5));
+        let other_world = scenario.app.world.spawn_empty().id();
+        let a = spawn(
+            &mut scenario,
+            DVec3::new(0.5, 1.0, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 2, None),
+        );
+        let b = spawn(
+            &mut scenario,
+            DVec3::new(0.5, 1.0, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 3, None),
+        );
+        scenario
+            .app
+            .world
+            .entity_mut(b)
+            .insert(EntityLayerId(other_world));
+        scenario.app.update();
+
+        assert!(is_alive(&scenario, a));
+        assert!(is_alive(&scenario, b));
+        assert_eq!(2, scenario.app.world.get::<Stack>(a).unwrap().0.count);
+    }
+
+    #[test]
+    fn test_despawn() {
+        let mut scenario = setup();
+        move_client(&mut scenario, DVec3::new(20.5, 1.0, 0.5));
+        let now = scenario.app.world.resource::<Server>().current_tick();
+        let old = spawn_at(
+            &mut scenario,
+            DVec3::new(0.5, 1.0, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 1, None),
+            now - ITEM_LIFETIME,
+        );
+        let young = spawn_at(
+            &mut scenario,
+            DVec3::new(3.5, 1.0, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 1, None),
+            now - ITEM_LIFETIME + 100,
+        );
+        scenario.app.update();
+        assert!(!is_alive(&scenario, old));
+        assert!(is_alive(&scenario, young));
+    }
+
+    #[test]
+    fn test_gravity() {
+        let mut scenario = setup();
+        move_client(&mut scenario, DVec3::new(20.5, 1.0, 0.5));
+        let item = spawn(
+            &mut scenario,
+            DVec3::new(0.5, 3.1, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 1, None),
+        );
+        for _ in 0..20 {
+            scenario.app.update();
+        }
+        assert_eq!(1.0, scenario.app.world.get::<Position>(item).unwrap().0.y);
+    }
+
+    #[test]
+    fn test_no_gravity_in_unloaded_chunks() {
+        let mut scenario = setup();
+        move_client(&mut scenario, DVec3::new(20.5, 1.0, 0.5));
+        let item = spawn(
+            &mut scenario,
+            DVec3::new(80.5, 3.0, 0.5),
+            ItemStack::new(ItemKind::Cobblestone, 1, None),
+        );
+        for _ in 0..20 {
+            scenario.app.update();
+        }
+        assert_eq!(3.0, scenario.app.world.get::<Position>(item).unwrap().0.y);
+    }
+}
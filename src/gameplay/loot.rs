@@ -0,0 +1,186 @@
+use crate::{can_harvest, enchantment_level, Tool, ToolKind};
+use valence::prelude::*;
+
+/// Returns the items that breaking the given block with the given held item drops.
+pub fn block_drops(state: BlockState, held: &ItemStack) -> Vec<ItemStack> {
+    let kind = state.to_kind();
+    let tool = Tool::from_stack(held);
+    if !can_harvest(kind, tool) {
+        return vec![];
+    }
+
+    // only one part of a block that takes up two positions drops anything
+    if state.get(PropName::Half) == Some(PropValue::Upper)
+        || state.get(PropName::Part) == Some(PropValue::Foot)
+    {
+        return vec![];
+    }
+
+    let name = kind.to_str();
+    let shears = matches!(
+        tool,
+        Some(Tool {
+            kind: ToolKind::Shears,
+            ..
+        })
+    );
+    if enchantment_level(held, "minecraft:silk_touch") > 0 || (shears && is_shearable(name)) {
+        return single(kind.to_item_kind(), 1);
+    }
+
+    match name {
+        "stone" => single(ItemKind::Cobblestone, 1),
+        "deepslate" => single(ItemKind::CobbledDeepslate, 1),
+        "grass_block" | "mycelium" | "podzol" | "dirt_path" | "farmland" => {
+            single(ItemKind::Dirt, 1)
+        }
+        "coal_ore" | "deepslate_coal_ore" => single(ItemKind::Coal, 1),
+        "iron_ore" | "deepslate_iron_ore" => single(ItemKind::RawIron, 1),
+        "gold_ore" | "deepslate_gold_ore" => single(ItemKind::RawGold, 1),
+        "copper_ore" | "deepslate_copper_ore" => single(ItemKind::RawCopper, 2),
+        "diamond_ore" | "deepslate_diamond_ore" => single(ItemKind::Diamond, 1),
+        "emerald_ore" | "deepslate_emerald_ore" => single(ItemKind::Emerald, 1),
+        "lapis_ore" | "deepslate_lapis_ore" => single(ItemKind::LapisLazuli, 4),
+        "redstone_ore" | "deepslate_redstone_ore" => single(ItemKind::Redstone, 4),
+        "nether_quartz_ore" => single(ItemKind::Quartz, 1),
+        "nether_gold_ore" => single(ItemKind::GoldNugget, 2),
+        "bookshelf" => single(ItemKind::Book, 3),
+        "clay" => single(ItemKind::ClayBall, 4),
+        "glowstone" => single(ItemKind::GlowstoneDust, 3),
+        "melon" => single(ItemKind::MelonSlice, 5),
+        "sea_lantern" => single(ItemKind::PrismarineCrystals, 2),
+        "snow_block" => single(ItemKind::Snowball, 4),
+        "snow" => match tool {
+            Some(Tool {
+                kind: ToolKind::Shovel,
+                ..
+            }) => single(
+                ItemKind::Snowball,
+                state
+                    .get(PropName::Layers)
+                    .and_then(|layers| layers.to_u16())
+                    .unwrap_or(1) as i8,
+            ),
+            _ => vec![],
+        },
+        _ if is_shearable(name)
+            || name == "ice"
+            || name.ends_with("glass")
+            || name.ends_with("glass_pane")
+            || name.contains("infested") =>
+        {
+            vec![]
+        }
+        _ if state.get(PropName::Type) == Some(PropValue::Double) => {
+            // a double slab is two slabs
+            single(kind.to_item_kind(), 2)
+        }
+        _ => single(kind.to_item_kind(), 1),
+    }
+}
+
+/// Blocks that only drop themselves when broken with shears or silk touch.
+fn is_shearable(name: &str) -> bool {
+    name.ends_with("_leaves")
+        || matches!(
+            name,
+            "grass" | "tall_grass" | "fern" | "large_fern" | "dead_bush" | "seagrass" | "vine"
+        )
+}
+
+fn single(item: ItemKind, count: i8) -> Vec<ItemStack> {
+    if item == ItemKind::Air {
+        return vec![];
+    }
+    vec![ItemStack::new(item, count, None)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::nbt::{compound, List};
+
+    fn drops(kind: BlockKind, held: ItemKind) -> Vec<ItemStack> {
+        let held = if held == ItemKind::Air {
+            ItemStack::EMPTY
+        } else {
+            ItemStack::new(held, 1, None)
+        };
+        block_drops(BlockState::from_kind(kind), &held)
+    }
+
+    #[test]
+    fn test_block_drops() {
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::Cobblestone, 1, None)],
+            drops(BlockKind::Stone, ItemKind::WoodenPickaxe)
+        );
+        assert!(drops(BlockKind::Stone, ItemKind::Air).is_empty());
+        assert!(drops(BlockKind::Glass, ItemKind::Air).is_empty());
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::Dirt, 1, None)],
+            drops(BlockKind::GrassBlock, ItemKind::Air)
+        );
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::OakLog, 1, None)],
+            drops(BlockKind::OakLog, ItemKind::Air)
+        );
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::RawIron, 1, None)],
+            drops(BlockKind::IronOre, ItemKind::StonePickaxe)
+        );
+        assert!(drops(BlockKind::IronOre, ItemKind::WoodenPickaxe).is_empty());
+        assert!(drops(BlockKind::OakLeaves, ItemKind::Air).is_empty());
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::OakLeaves, 1, None)],
+            drops(BlockKind::OakLeaves, ItemKind::Shears)
+        );
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::Torch, 1, None)],
+            drops(BlockKind::WallTorch, ItemKind::Air)
+        );
+    }
+
+    #[test]
+    fn test_silk_touch() {
+        let pickaxe = ItemStack::new(
+            ItemKind::DiamondPickaxe,
+            1,
+            Some(compound! {
+                "Enchantments" => List::Compound(vec![
+                    compound! { "id" => "minecraft:silk_touch", "lvl" => 1_i16 },
+                ]),
+            }),
+        );
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::Glass, 1, None)],
+            block_drops(BlockState::GLASS, &pickaxe)
+        );
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::Stone, 1, None)],
+            block_drops(BlockState::STONE, &pickaxe)
+        );
+    }
+
+    #[test]
+    fn test_upper_half_drops_nothing() {
+        let door = BlockState::OAK_DOOR.set(PropName::Half, PropValue::Upper);
+        assert!(block_drops(door, &ItemStack::EMPTY).is_empty());
+        let door = BlockState::OAK_DOOR.set(PropName::Half, PropValue::Lower);
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::OakDoor, 1, None)],
+            block_drops(door, &ItemStack::EMPTY)
+        );
+    }
+
+    #[test]
+    fn test_bed_drops_once() {
+        let foot = BlockState::RED_BED.set(PropName::Part, PropValue::Foot);
+        assert!(block_drops(foot, &ItemStack::EMPTY).is_empty());
+        let head = BlockState::RED_BED.set(PropName::Part, PropValue::Head);
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::RedBed, 1, None)],
+            block_drops(head, &ItemStack::EMPTY)
+        );
+    }
+}
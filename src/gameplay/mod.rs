@@ -1,54 +1,141 @@
+use crate::{BlockChangeEvent, WorldInfo, WorldRegistry};
 use bevy_ecs::component::Component;
 use bevy_ecs::entity::Entity;
-use bevy_ecs::event::EventReader;
-use bevy_ecs::prelude::{Commands, Query, With};
+use bevy_ecs::event::{EventReader, EventWriter};
+use bevy_ecs::prelude::{Commands, Query, Res, With};
 use valence::client::{VisibleChunkLayer, VisibleEntityLayers};
-use valence::entity::EntityLayerId;
-use valence::prelude::RespawnPosition;
+use valence::entity::entity::Air;
+use valence::entity::{EntityLayerId, Position};
+use valence::message::SendMessage;
+use valence::prelude::{BlockPos, ChunkLayer, Client, DVec3, RespawnPosition};
 use valence::status::RequestRespawnEvent;
-use valence::{BlockPos, ChunkLayer, EntityLayer};
 
+mod blocks;
 mod building;
+mod death;
 mod environment;
+mod experience;
+mod health;
+mod interaction;
+mod items;
+mod loot;
+mod placement;
+mod sleep;
+mod time;
+mod tools;
+mod updates;
+mod weather;
 
+pub use blocks::*;
 pub use building::*;
+pub use death::*;
 pub use environment::*;
+pub use experience::*;
+pub use health::*;
+pub use interaction::*;
+pub use items::*;
+pub use loot::*;
+pub use placement::*;
+pub use sleep::*;
+pub use time::*;
+pub use tools::*;
+pub use updates::*;
+pub use weather::*;
 
 /// A marker component that is added when a client dies.
 /// This marker must be removed when the client respawns.
 #[derive(Component)]
 pub struct Dead;
 
+/// Respawns dead players at their [`SpawnPoint`], or at the spawn of the default
+/// world if they don't have one.
 pub fn respawn(
     mut commands: Commands,
     mut clients: Query<
         (
             Entity,
+            &mut Client,
             &mut EntityLayerId,
             &mut VisibleChunkLayer,
             &mut VisibleEntityLayers,
+            &mut Position,
             &mut RespawnPosition,
+            Option<&SpawnPoint>,
         ),
         With<Dead>,
     >,
     mut events: EventReader<RequestRespawnEvent>,
-    layers: Query<Entity, (With<ChunkLayer>, With<EntityLayer>)>,
+    registry: Res<WorldRegistry>,
+    worlds: Query<&WorldInfo>,
+    mut layers: Query<&mut ChunkLayer>,
+    mut changes: EventWriter<BlockChangeEvent>,
 ) {
-    let layer = layers.single();
+    let default_layer = registry.default_world();
+    let Ok(default_world) = worlds.get(default_layer) else {
+        return;
+    };
+
     events.iter().for_each(|event| {
-        if let Ok((
+        let Ok((
             entity,
+            mut client,
             mut layer_id,
             mut visible_chunk_layer,
             mut visible_entity_layers,
+            mut pos,
             mut respawn_pos,
+            spawn_point,
         )) = clients.get_mut(event.client)
-        {
-            commands.entity(entity).remove::<Dead>();
-            layer_id.0 = layer;
-            visible_chunk_layer.0 = layer;
-            visible_entity_layers.0.insert(layer);
-            respawn_pos.pos = BlockPos::new(0, 65, 0);
-        }
+        else {
+            return;
+        };
+
+        let spawn = spawn_point.and_then(|point| {
+            let mut layer = layers.get_mut(point.world).ok()?;
+            let position = match use_spawn_point(&mut layer, point.position) {
+                Some(position) => {
+                    // respawn anchors lose a charge
+                    changes.send(BlockChangeEvent {
+                        layer: point.world,
+                        position: point.position,
+                    });
+                    position
+                }
+                None if point.forced => DVec3::new(
+                    point.position.x as f64 + 0.5,
+                    point.position.y as f64,
+                    point.position.z as f64 + 0.5,
+                ),
+                None => return None,
+            };
+            Some((point.world, position))
+        });
+        let (layer, position) = match spawn {
+            Some(spawn) => spawn,
+            None => {
+                if spawn_point.is_some() {
+                    commands.entity(entity).remove::<SpawnPoint>();
+                    client.send_chat_message(
+                        "You have no home bed or charged respawn anchor, or it was obstructed",
+                    );
+                }
+                (default_layer, default_world.spawn)
+            }
+        };
+
+        commands
+            .entity(entity)
+            .remove::<Dead>()
+            .insert((SurvivalBundle::default(), Air(MAX_AIR)));
+        visible_entity_layers.0.remove(&layer_id.0);
+        layer_id.0 = layer;
+        visible_chunk_layer.0 = layer;
+        visible_entity_layers.0.insert(layer);
+        pos.set(position);
+        respawn_pos.pos = BlockPos::new(
+            position.x.floor() as i32,
+            position.y.floor() as i32,
+            position.z.floor() as i32,
+        );
     });
 }
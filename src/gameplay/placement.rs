@@ -0,0 +1,907 @@
+use crate::is_replaceable;
+use bevy_ecs::prelude::*;
+use std::collections::HashMap;
+use valence::prelude::*;
+
+/// Everything that decides the state of a block that a client places.
+#[derive(Clone)]
+pub struct PlacementContext<'a> {
+    pub layer: &'a ChunkLayer,
+    /// The kind of the block that is placed.
+    pub kind: BlockKind,
+    /// The block that the client clicked on.
+    pub clicked: BlockPos,
+    /// The position that the block is placed at. This is either the clicked block
+    /// itself (if it is replaced), or its neighbor in the direction of `face`.
+    pub target: BlockPos,
+    /// The block that is currently at `target`.
+    pub existing: BlockState,
+    /// The face of the clicked block.
+    pub face: Direction,
+    /// The position on the clicked face, relative to the clicked block.
+    pub cursor: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl PlacementContext<'_> {
+    /// The direction that the player is looking in, ignoring the pitch.
+    pub fn horizontal_direction(&self) -> Direction {
+        horizontal_direction(self.yaw)
+    }
+
+    /// The direction that the player is looking in, including up and down.
+    pub fn looking_direction(&self) -> Direction {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        let x = -yaw.sin() * pitch.cos();
+        let y = -pitch.sin();
+        let z = yaw.cos() * pitch.cos();
+
+        if y.abs() >= x.abs() && y.abs() >= z.abs() {
+            if y > 0.0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            }
+        } else if x.abs() >= z.abs() {
+            if x > 0.0 {
+                Direction::East
+            } else {
+                Direction::West
+            }
+        } else if z > 0.0 {
+            Direction::South
+        } else {
+            Direction::North
+        }
+    }
+
+    /// Whether the block is placed into the clicked block instead of next to it.
+    pub fn in_place(&self) -> bool {
+        self.target == self.clicked
+    }
+
+    /// Whether the client clicked on the upper half of the clicked block.
+    pub fn clicked_upper_half(&self) -> bool {
+        self.cursor.y > 0.5
+    }
+}
+
+/// Returns the direction that a player with the given yaw is looking in, ignoring
+/// the pitch.
+pub fn horizontal_direction(yaw: f32) -> Direction {
+    match ((yaw / 90.0 + 0.5).floor() as i32).rem_euclid(4) {
+        0 => Direction::South,
+        1 => Direction::West,
+        2 => Direction::North,
+        _ => Direction::East,
+    }
+}
+
+/// Returns the state of a placed block, or `None` if it can't be placed in this context.
+pub type PlacementResolver = fn(&PlacementContext) -> Option<BlockState>;
+
+/// The placement resolvers of all block kinds. Kinds without a registered resolver
+/// use the vanilla behavior of their family (stairs, slabs, buttons, ...).
+#[derive(Resource, Default)]
+pub struct PlacementResolvers {
+    resolvers: HashMap<BlockKind, PlacementResolver>,
+}
+
+impl PlacementResolvers {
+    /// Replaces the resolver of a block kind.
+    pub fn register(&mut self, kind: BlockKind, resolver: PlacementResolver) {
+        self.resolvers.insert(kind, resolver);
+    }
+
+    pub fn resolve(&self, ctx: &PlacementContext) -> Option<BlockState> {
+        let resolver = self
+            .resolvers
+            .get(&ctx.kind)
+            .copied()
+            .unwrap_or_else(|| default_resolver(ctx.kind));
+        let state = resolver(ctx)?;
+
+        if ctx.existing == BlockState::WATER && state.get(PropName::Waterlogged).is_some() {
+            return Some(state.set(PropName::Waterlogged, PropValue::True));
+        }
+        Some(state)
+    }
+
+    /// Works out all blocks that placing `ctx.kind` sets, starting with the block at
+    /// the target. The target in `ctx` is adjusted if the clicked block can't be replaced.
+    /// Returns `None` if the block can't be placed.
+    pub fn plan(&self, mut ctx: PlacementContext) -> Option<Vec<(BlockPos, BlockState)>> {
+        if !is_replaceable(ctx.existing) && !can_merge(&ctx) {
+            ctx.target = ctx.clicked.get_in_direction(ctx.face);
+            ctx.existing = ctx.layer.block(ctx.target)?.state;
+            if !is_replaceable(ctx.existing) && !can_merge(&ctx) {
+                return None;
+            }
+        }
+
+        let state = self.resolve(&ctx)?;
+        let mut parts = vec![(ctx.target, state)];
+        if let Some((position, state)) = companion_part(ctx.target, state) {
+            if !ctx
+                .layer
+                .block(position)
+                .is_some_and(|b| is_replaceable(b.state))
+            {
+                return None;
+            }
+            parts.push((position, state));
+        }
+        Some(parts)
+    }
+}
+
+/// Returns the second block of blocks that take up two positions, like the upper
+/// half of a door or the head of a bed.
+pub fn companion_part(position: BlockPos, state: BlockState) -> Option<(BlockPos, BlockState)> {
+    if state.get(PropName::Half) == Some(PropValue::Lower) {
+        return Some((
+            position.get_in_direction(Direction::Up),
+            state.set(PropName::Half, PropValue::Upper),
+        ));
+    }
+    if state.get(PropName::Part) == Some(PropValue::Foot) {
+        let facing = state.get(PropName::Facing).and_then(direction_from_prop)?;
+        return Some((
+            position.get_in_direction(facing),
+            state.set(PropName::Part, PropValue::Head),
+        ));
+    }
+    None
+}
+
+/// Returns the position of the other block of a block that takes up two
+/// positions, from either of its parts.
+pub fn other_part(position: BlockPos, state: BlockState) -> Option<BlockPos> {
+    match state.get(PropName::Half) {
+        Some(PropValue::Lower) => return Some(position.get_in_direction(Direction::Up)),
+        Some(PropValue::Upper) => return Some(position.get_in_direction(Direction::Down)),
+        _ => {}
+    }
+    let facing = state.get(PropName::Facing).and_then(direction_from_prop)?;
+    match state.get(PropName::Part)? {
+        PropValue::Foot => Some(position.get_in_direction(facing)),
+        _ => Some(position.get_in_direction(opposite(facing))),
+    }
+}
+
+/// Returns whether the placed block combines with the block at the target, like a
+/// slab that is placed into a slab of the same kind.
+pub fn can_merge(ctx: &PlacementContext) -> bool {
+    if ctx.existing.to_kind() != ctx.kind {
+        return false;
+    }
+
+    if ctx.kind.to_str().ends_with("_slab") {
+        let horizontal = !matches!(ctx.face, Direction::Up | Direction::Down);
+        return match ctx.existing.get(PropName::Type) {
+            Some(PropValue::Bottom) => {
+                !ctx.in_place()
+                    || ctx.face == Direction::Up
+                    || (horizontal && ctx.clicked_upper_half())
+            }
+            Some(PropValue::Top) => {
+                !ctx.in_place()
+                    || ctx.face == Direction::Down
+                    || (horizontal && !ctx.clicked_upper_half())
+            }
+            _ => false,
+        };
+    }
+
+    match count_prop(ctx.kind) {
+        Some((prop, max)) => {
+            let count = ctx
+                .existing
+                .get(prop)
+                .and_then(|v| v.to_u16())
+                .unwrap_or(max);
+            count < max && (!ctx.in_place() || ctx.face == Direction::Up)
+        }
+        None => false,
+    }
+}
+
+/// Returns the shape that stairs at `position` take, given the stairs around them.
+pub fn stairs_shape(layer: &ChunkLayer, position: BlockPos, state: BlockState) -> PropValue {
+    let Some(facing) = state.get(PropName::Facing).and_then(direction_from_prop) else {
+        return PropValue::Straight;
+    };
+    let half = state.get(PropName::Half);
+    let stairs_at = |direction: Direction| {
+        layer
+            .block(position.get_in_direction(direction))
+            .map(|b| b.state)
+            .filter(|s| is_stairs(*s) && s.get(PropName::Half) == half)
+    };
+    // stairs can't turn towards a side that continues a straight staircase
+    let can_take_shape = |side: Direction| {
+        layer
+            .block(position.get_in_direction(side))
+            .map(|b| b.state)
+            .map_or(true, |s| {
+                !is_stairs(s)
+                    || s.get(PropName::Facing) != state.get(PropName::Facing)
+                    || s.get(PropName::Half) != half
+            })
+    };
+
+    if let Some(front) = stairs_at(facing) {
+        if let Some(other) = front.get(PropName::Facing).and_then(direction_from_prop) {
+            if is_perpendicular(facing, other) && can_take_shape(opposite(other)) {
+                return if other == rotate_counter_clockwise(facing) {
+                    PropValue::OuterLeft
+                } else {
+                    PropValue::OuterRight
+                };
+            }
+        }
+    }
+
+    if let Some(back) = stairs_at(opposite(facing)) {
+        if let Some(other) = back.get(PropName::Facing).and_then(direction_from_prop) {
+            if is_perpendicular(facing, other) && can_take_shape(other) {
+                return if other == rotate_counter_clockwise(facing) {
+                    PropValue::InnerLeft
+                } else {
+                    PropValue::InnerRight
+                };
+            }
+        }
+    }
+
+    PropValue::Straight
+}
+
+pub fn is_stairs(state: BlockState) -> bool {
+    state.to_kind().to_str().ends_with("_stairs")
+}
+
+fn default_resolver(kind: BlockKind) -> PlacementResolver {
+    let name = kind.to_str();
+    match name {
+        "observer" => resolve_looking,
+        "piston"
+        | "sticky_piston"
+        | "dispenser"
+        | "dropper"
+        | "barrel"
+        | "command_block"
+        | "chain_command_block"
+        | "repeating_command_block" => resolve_looking_opposite,
+        "end_rod" | "lightning_rod" | "amethyst_cluster" | "shulker_box" => resolve_clicked_face,
+        "hopper" => resolve_hopper,
+        "ladder" | "tripwire_hook" => resolve_wall_attached,
+        "lever" | "grindstone" => resolve_face_attached,
+        "torch" | "soul_torch" | "redstone_torch" => resolve_standing_or_wall,
+        "campfire" | "soul_campfire" => resolve_horizontal,
+        "snow" | "candle" | "sea_pickle" | "turtle_egg" => resolve_count,
+        "redstone_wire" => resolve_redstone_wire,
+        _ if name.ends_with("_door") => resolve_door,
+        _ if name.ends_with("_trapdoor") => resolve_trapdoor,
+        _ if name.ends_with("_slab") => resolve_slab,
+        _ if name.ends_with("_stairs") => resolve_stairs,
+        _ if name.ends_with("_button") => resolve_face_attached,
+        _ if name.ends_with("_hanging_sign") => resolve_hanging_sign,
+        _ if name.ends_with("_sign")
+            || name.ends_with("_banner")
+            || name.ends_with("_coral_fan")
+            || (name.ends_with("_head") && name != "piston_head")
+            || name.ends_with("_skull") =>
+        {
+            resolve_standing_or_wall
+        }
+        _ if name.ends_with("_shulker_box") || name.ends_with("_amethyst_bud") => {
+            resolve_clicked_face
+        }
+        _ if name.ends_with("rail") => resolve_rail,
+        _ if name.ends_with("_bed") => resolve_bed,
+        _ if name.ends_with("_fence_gate") => resolve_horizontal,
+        _ if name.ends_with("anvil") => resolve_anvil,
+        _ if name.ends_with("_candle") => resolve_count,
+        _ if kind.props().contains(&PropName::Axis) => resolve_axis,
+        _ if kind.props().contains(&PropName::Facing) => resolve_horizontal_opposite,
+        _ => resolve_default,
+    }
+}
+
+fn resolve_default(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(BlockState::from_kind(ctx.kind))
+}
+
+/// Logs, pillars and the like are aligned with the axis of the clicked face.
+fn resolve_axis(ctx: &PlacementContext) -> Option<BlockState> {
+    let axis = match ctx.face {
+        Direction::Down | Direction::Up => PropValue::Y,
+        Direction::North | Direction::South => PropValue::Z,
+        Direction::West | Direction::East => PropValue::X,
+    };
+    Some(BlockState::from_kind(ctx.kind).set(PropName::Axis, axis))
+}
+
+/// Fence gates and campfires face away from the player.
+fn resolve_horizontal(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(BlockState::from_kind(ctx.kind).set(
+        PropName::Facing,
+        direction_to_prop(ctx.horizontal_direction()),
+    ))
+}
+
+/// Furnaces, chests, pumpkins and most other blocks with a front face the player.
+fn resolve_horizontal_opposite(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(BlockState::from_kind(ctx.kind).set(
+        PropName::Facing,
+        direction_to_prop(opposite(ctx.horizontal_direction())),
+    ))
+}
+
+fn resolve_anvil(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(BlockState::from_kind(ctx.kind).set(
+        PropName::Facing,
+        direction_to_prop(rotate_clockwise(ctx.horizontal_direction())),
+    ))
+}
+
+/// Observers face the direction the player is looking in, including up and down.
+fn resolve_looking(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(
+        BlockState::from_kind(ctx.kind)
+            .set(PropName::Facing, direction_to_prop(ctx.looking_direction())),
+    )
+}
+
+/// Pistons, dispensers and droppers face the player, including up and down.
+fn resolve_looking_opposite(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(BlockState::from_kind(ctx.kind).set(
+        PropName::Facing,
+        direction_to_prop(opposite(ctx.looking_direction())),
+    ))
+}
+
+/// End rods, shulker boxes and amethyst point away from the clicked face.
+fn resolve_clicked_face(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(BlockState::from_kind(ctx.kind).set(PropName::Facing, direction_to_prop(ctx.face)))
+}
+
+/// Hoppers point into the clicked block, or down if it was clicked from above or below.
+fn resolve_hopper(ctx: &PlacementContext) -> Option<BlockState> {
+    let facing = match ctx.face {
+        Direction::Up | Direction::Down => Direction::Down,
+        face => opposite(face),
+    };
+    Some(BlockState::from_kind(ctx.kind).set(PropName::Facing, direction_to_prop(facing)))
+}
+
+/// Ladders and tripwire hooks can only hang on the side of a block.
+fn resolve_wall_attached(ctx: &PlacementContext) -> Option<BlockState> {
+    match ctx.face {
+        Direction::Up | Direction::Down => None,
+        face => {
+            Some(BlockState::from_kind(ctx.kind).set(PropName::Facing, direction_to_prop(face)))
+        }
+    }
+}
+
+/// Buttons, levers and grindstones attach to the floor, the ceiling or a wall.
+fn resolve_face_attached(ctx: &PlacementContext) -> Option<BlockState> {
+    let (face, facing) = match ctx.face {
+        Direction::Up => (PropValue::Floor, ctx.horizontal_direction()),
+        Direction::Down => (PropValue::Ceiling, ctx.horizontal_direction()),
+        face => (PropValue::Wall, face),
+    };
+    Some(
+        BlockState::from_kind(ctx.kind)
+            .set(PropName::Face, face)
+            .set(PropName::Facing, direction_to_prop(facing)),
+    )
+}
+
+/// Torches, signs, banners, heads and coral fans stand on the floor, or use their
+/// wall variant when placed on the side of a block.
+fn resolve_standing_or_wall(ctx: &PlacementContext) -> Option<BlockState> {
+    match ctx.face {
+        Direction::Up => Some(standing(ctx)),
+        // only heads stay up without support
+        Direction::Down
+            if ctx.kind.to_str().ends_with("_head") || ctx.kind.to_str().ends_with("_skull") =>
+        {
+            Some(standing(ctx))
+        }
+        Direction::Down => None,
+        face => Some(
+            BlockState::from_kind(wall_variant(ctx.kind)?)
+                .set(PropName::Facing, direction_to_prop(face)),
+        ),
+    }
+}
+
+fn resolve_hanging_sign(ctx: &PlacementContext) -> Option<BlockState> {
+    match ctx.face {
+        Direction::Down => Some(standing(ctx)),
+        Direction::Up => None,
+        face => {
+            let name = ctx.kind.to_str().strip_suffix("_hanging_sign")?;
+            let kind = BlockKind::from_str(&format!("{}_wall_hanging_sign", name))?;
+            // the sign hangs from a bracket that sticks out of the wall
+            Some(
+                BlockState::from_kind(kind)
+                    .set(PropName::Facing, direction_to_prop(rotate_clockwise(face))),
+            )
+        }
+    }
+}
+
+fn standing(ctx: &PlacementContext) -> BlockState {
+    let state = BlockState::from_kind(ctx.kind);
+    if !ctx.kind.props().contains(&PropName::Rotation) {
+        return state;
+    }
+    // heads look at the player, everything else shows its front to the player
+    let name = ctx.kind.to_str();
+    let yaw = if name.ends_with("_head") || name.ends_with("_skull") {
+        ctx.yaw
+    } else {
+        ctx.yaw + 180.0
+    };
+    let rotation = ((yaw * 16.0 / 360.0 + 0.5).floor() as i32).rem_euclid(16);
+    match PropValue::from_u16(rotation as u16) {
+        Some(rotation) => state.set(PropName::Rotation, rotation),
+        None => state,
+    }
+}
+
+fn resolve_slab(ctx: &PlacementContext) -> Option<BlockState> {
+    if ctx.existing.to_kind() == ctx.kind {
+        return Some(
+            ctx.existing
+                .set(PropName::Type, PropValue::Double)
+                .set(PropName::Waterlogged, PropValue::False),
+        );
+    }
+    let top = match ctx.face {
+        Direction::Down => true,
+        Direction::Up => false,
+        _ => ctx.clicked_upper_half(),
+    };
+    Some(BlockState::from_kind(ctx.kind).set(
+        PropName::Type,
+        if top {
+            PropValue::Top
+        } else {
+            PropValue::Bottom
+        },
+    ))
+}
+
+fn resolve_stairs(ctx: &PlacementContext) -> Option<BlockState> {
+    let top = match ctx.face {
+        Direction::Down => true,
+        Direction::Up => false,
+        _ => ctx.clicked_upper_half(),
+    };
+    let state = BlockState::from_kind(ctx.kind)
+        .set(
+            PropName::Facing,
+            direction_to_prop(ctx.horizontal_direction()),
+        )
+        .set(
+            PropName::Half,
+            if top {
+                PropValue::Top
+            } else {
+                PropValue::Bottom
+            },
+        );
+    Some(state.set(PropName::Shape, stairs_shape(ctx.layer, ctx.target, state)))
+}
+
+fn resolve_trapdoor(ctx: &PlacementContext) -> Option<BlockState> {
+    let (facing, top) = match ctx.face {
+        face @ (Direction::North | Direction::South | Direction::West | Direction::East)
+            if !ctx.in_place() =>
+        {
+            (face, ctx.clicked_upper_half())
+        }
+        face => (opposite(ctx.horizontal_direction()), face != Direction::Up),
+    };
+    Some(
+        BlockState::from_kind(ctx.kind)
+            .set(PropName::Facing, direction_to_prop(facing))
+            .set(
+                PropName::Half,
+                if top {
+                    PropValue::Top
+                } else {
+                    PropValue::Bottom
+                },
+            ),
+    )
+}
+
+/// Doors face away from the player, and their hinge is on the side of the door that
+/// the player clicked on, unless there is a door next to them to form a double door.
+fn resolve_door(ctx: &PlacementContext) -> Option<BlockState> {
+    let facing = ctx.horizontal_direction();
+    let (v, invert) = match facing {
+        Direction::South => (ctx.cursor.x, false),
+        Direction::North => (ctx.cursor.x, true),
+        Direction::East => (ctx.cursor.z, true),
+        _ => (ctx.cursor.z, false),
+    };
+    let hinge_right = if invert { v > 0.5 } else { v < 0.5 };
+    let hinge = if hinge_right {
+        PropValue::Right
+    } else {
+        let left = rotate_counter_clockwise(facing);
+        let next_to_door = ctx
+            .layer
+            .block(ctx.target.get_in_direction(left))
+            .is_some_and(|b| b.state.to_kind().to_str().ends_with("_door"));
+        if next_to_door {
+            PropValue::Right
+        } else {
+            PropValue::Left
+        }
+    };
+
+    Some(
+        BlockState::from_kind(ctx.kind)
+            .set(PropName::Facing, direction_to_prop(facing))
+            .set(PropName::Half, PropValue::Lower)
+            .set(PropName::Hinge, hinge),
+    )
+}
+
+fn resolve_bed(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(
+        BlockState::from_kind(ctx.kind)
+            .set(
+                PropName::Facing,
+                direction_to_prop(ctx.horizontal_direction()),
+            )
+            .set(PropName::Part, PropValue::Foot),
+    )
+}
+
+fn resolve_rail(ctx: &PlacementContext) -> Option<BlockState> {
+    let shape = match ctx.horizontal_direction() {
+        Direction::East | Direction::West => PropValue::EastWest,
+        _ => PropValue::NorthSouth,
+    };
+    Some(BlockState::from_kind(ctx.kind).set(PropName::Shape, shape))
+}
+
+/// Snow layers, candles, sea pickles and turtle eggs stack up in a single block.
+fn resolve_count(ctx: &PlacementContext) -> Option<BlockState> {
+    let (prop, _) = count_prop(ctx.kind)?;
+    if ctx.existing.to_kind() != ctx.kind {
+        return Some(BlockState::from_kind(ctx.kind));
+    }
+    let count = ctx.existing.get(prop)?.to_u16()?;
+    Some(ctx.existing.set(prop, PropValue::from_u16(count + 1)?))
+}
+
+/// Redstone wire starts out as a cross, which neighbor updates narrow down to
+/// its actual connections.
+fn resolve_redstone_wire(ctx: &PlacementContext) -> Option<BlockState> {
+    Some(
+        BlockState::from_kind(ctx.kind)
+            .set(PropName::North, PropValue::Side)
+            .set(PropName::East, PropValue::Side)
+            .set(PropName::South, PropValue::Side)
+            .set(PropName::West, PropValue::Side),
+    )
+}
+
+fn count_prop(kind: BlockKind) -> Option<(PropName, u16)> {
+    match kind.to_str() {
+        "snow" => Some((PropName::Layers, 8)),
+        "sea_pickle" => Some((PropName::Pickles, 4)),
+        "turtle_egg" => Some((PropName::Eggs, 4)),
+        name if name == "candle" || name.ends_with("_candle") => Some((PropName::Candles, 4)),
+        _ => None,
+    }
+}
+
+/// Returns the wall variant of a standing block, e.g. `oak_wall_sign` for `oak_sign`.
+fn wall_variant(kind: BlockKind) -> Option<BlockKind> {
+    let name = kind.to_str();
+    let wall = match name.rsplit_once('_') {
+        Some((prefix, suffix)) => format!("{}_wall_{}", prefix, suffix),
+        None => format!("wall_{}", name),
+    };
+    BlockKind::from_str(&wall)
+}
+
+pub fn is_perpendicular(a: Direction, b: Direction) -> bool {
+    a != b && a != opposite(b)
+}
+
+pub fn opposite(direction: Direction) -> Direction {
+    match direction {
+        Direction::Down => Direction::Up,
+        Direction::Up => Direction::Down,
+        Direction::North => Direction::South,
+        Direction::South => Direction::North,
+        Direction::West => Direction::East,
+        Direction::East => Direction::West,
+    }
+}
+
+pub fn rotate_clockwise(direction: Direction) -> Direction {
+    match direction {
+        Direction::North => Direction::East,
+        Direction::East => Direction::South,
+        Direction::South => Direction::West,
+        Direction::West => Direction::North,
+        vertical => vertical,
+    }
+}
+
+pub fn rotate_counter_clockwise(direction: Direction) -> Direction {
+    match direction {
+        Direction::North => Direction::West,
+        Direction::West => Direction::South,
+        Direction::South => Direction::East,
+        Direction::East => Direction::North,
+        vertical => vertical,
+    }
+}
+
+pub fn direction_to_prop(direction: Direction) -> PropValue {
+    match direction {
+        Direction::Down => PropValue::Down,
+        Direction::Up => PropValue::Up,
+        Direction::North => PropValue::North,
+        Direction::South => PropValue::South,
+        Direction::West => PropValue::West,
+        Direction::East => PropValue::East,
+    }
+}
+
+pub fn direction_from_prop(value: PropValue) -> Option<Direction> {
+    match value {
+        PropValue::Down => Some(Direction::Down),
+        PropValue::Up => Some(Direction::Up),
+        PropValue::North => Some(Direction::North),
+        PropValue::South => Some(Direction::South),
+        PropValue::West => Some(Direction::West),
+        PropValue::East => Some(Direction::East),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{eval_script, TestableEnvironment};
+    use crate::{
+        place_block, BlockChangeEvent, InteractionHandlers, OpenBlockScreenEvent, SleepEvent,
+    };
+    use valence::testing::ScenarioSingleClient;
+
+    struct PlacementEnvironment<T: TestableEnvironment = ScenarioSingleClient> {
+        env: T,
+    }
+
+    impl<T: TestableEnvironment> TestableEnvironment for PlacementEnvironment<T> {
+        fn new() -> Self {
+            let mut env = T::new();
+            env.app().add_event::<BlockChangeEvent>();
+            env.app().init_resource::<PlacementResolvers>();
+            env.app().init_resource::<InteractionHandlers>();
+            env.app().add_event::<OpenBlockScreenEvent>();
+            env.app().add_event::<SleepEvent>();
+            env.app().add_systems(Update, place_block);
+            env.app().update();
+            Self { env }
+        }
+
+        fn app(&mut self) -> &mut App {
+            self.env.app()
+        }
+
+        fn layer(&self) -> Entity {
+            self.env.layer()
+        }
+
+        fn client(&self) -> Entity {
+            self.env.client()
+        }
+    }
+
+    #[test]
+    fn test_stairs() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set player look 180 0
+            set inventory slot 36 item oak_stairs count 8
+            set held_item 36
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block oak_stairs[facing=north,half=bottom,shape=straight]
+
+            interact position 0 0 0 face down
+            assert position 0 -1 0 block oak_stairs[facing=north,half=top]
+
+            interact position 0 0 0 face east cursor 1 0.75 0.5
+            assert position 1 0 0 block oak_stairs[facing=north,half=top]
+
+            interact position 0 0 0 face west cursor 0 0.25 0.5
+            assert position -1 0 0 block oak_stairs[facing=north,half=bottom]
+
+            # stairs that face into the side of other stairs form an outer corner
+            set position -1 0 0 block stone
+            set player look -90 0
+            interact position -1 0 0 face up
+            assert position -1 1 0 block oak_stairs[facing=east,shape=outer_left]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_slabs() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item oak_slab count 8
+            set held_item 36
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block oak_slab[type=bottom]
+
+            # placing a slab into a slab of the same kind makes a double slab
+            interact position 0 1 0 face up
+            assert position 0 1 0 block oak_slab[type=double]
+            assert position 0 2 0 block air
+            assert inventory slot 36 item oak_slab count 6
+
+            interact position 0 0 0 face east cursor 1 0.75 0.5
+            assert position 1 0 0 block oak_slab[type=top]
+
+            interact position 0 0 0 face down
+            assert position 0 -1 0 block oak_slab[type=top]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_trapdoors() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set player look 0 0
+            set inventory slot 36 item oak_trapdoor count 8
+            set held_item 36
+
+            interact position 0 0 0 face east cursor 1 0.75 0.5
+            assert position 1 0 0 block oak_trapdoor[facing=east,half=top]
+
+            interact position 0 0 0 face west cursor 0 0.25 0.5
+            assert position -1 0 0 block oak_trapdoor[facing=west,half=bottom]
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block oak_trapdoor[facing=north,half=bottom]
+
+            interact position 0 0 0 face down
+            assert position 0 -1 0 block oak_trapdoor[facing=north,half=top]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_buttons_and_levers() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set player look 0 0
+            set inventory slot 36 item stone_button count 8
+            set inventory slot 37 item lever count 8
+            set held_item 36
+
+            interact position 0 0 0 face north
+            assert position 0 0 -1 block stone_button[face=wall,facing=north]
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block stone_button[face=floor,facing=south]
+
+            set held_item 37
+            interact position 0 0 0 face down
+            assert position 0 -1 0 block lever[face=ceiling,facing=south]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_torches() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item torch count 8
+            set held_item 36
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block torch
+
+            interact position 0 0 0 face west
+            assert position -1 0 0 block wall_torch[facing=west]
+
+            # torches can't hang from the ceiling
+            interact position 0 0 0 face down
+            assert position 0 -1 0 block air
+            assert inventory slot 36 item torch count 6
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_signs() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set player look 0 0
+            set inventory slot 36 item oak_sign count 8
+            set held_item 36
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block oak_sign[rotation=8]
+
+            interact position 0 0 0 face south
+            assert position 0 0 1 block oak_wall_sign[facing=south]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_vertical_facing() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set inventory slot 36 item observer count 8
+            set inventory slot 37 item piston count 8
+            set inventory slot 38 item dispenser count 8
+
+            set player look 0 90
+            set held_item 36
+            interact position 0 0 0 face up
+            assert position 0 1 0 block observer[facing=down]
+
+            set held_item 37
+            interact position 0 0 0 face east
+            assert position 1 0 0 block piston[facing=up]
+
+            set player look 0 -90
+            set held_item 38
+            interact position 0 0 0 face down
+            assert position 0 -1 0 block dispenser[facing=down]
+
+            set player look -90 0
+            set held_item 37
+            interact position 0 0 0 face west
+            assert position -1 0 0 block piston[facing=west]
+            "#,
+        );
+    }
+
+    #[test]
+    fn test_beds() {
+        eval_script::<PlacementEnvironment>(
+            r#"
+            set player position 2.5 0 2.5
+            set player look 0 0
+            set inventory slot 36 item red_bed count 1
+            set held_item 36
+
+            interact position 0 0 0 face up
+            assert position 0 1 0 block red_bed[facing=south,part=foot]
+            assert position 0 1 1 block red_bed[facing=south,part=head]
+            "#,
+        );
+    }
+}
@@ -0,0 +1,556 @@
+use crate::{
+    other_part, BlockChangeEvent, ChangeWorldEvent, DeathEvent, SpawnPoint, Weather, WeatherState,
+    WorldInfo, WorldTime,
+};
+use bevy_ecs::prelude::*;
+use std::collections::HashSet;
+use valence::client_command::LeaveBedEvent;
+use valence::entity::living::SleepingPosition;
+use valence::entity::{entity, EntityLayerId, Pose as EntityPose};
+use valence::message::SendMessage;
+use valence::prelude::*;
+
+/// Players have to sleep this long before they count towards skipping the night,
+/// in ticks.
+const MIN_SLEEP_TICKS: i64 = 100;
+
+/// A player that lies in a bed.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Sleeping {
+    /// The world of the bed.
+    pub world: Entity,
+    /// The head part of the bed.
+    pub bed: BlockPos,
+    /// The server tick in which the player went to bed.
+    pub since: i64,
+}
+
+/// Sent when a client right-clicks a bed.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct SleepEvent {
+    pub client: Entity,
+    pub layer: Entity,
+    pub position: BlockPos,
+}
+
+/// Sets the spawn point of players that use a bed, and puts them to sleep if it's
+/// night or a thunderstorm.
+pub fn go_to_bed(
+    mut commands: Commands,
+    mut clients: Query<(&mut Client, &mut Position, Option<&SpawnPoint>), Without<Sleeping>>,
+    mut layers: Query<(&mut ChunkLayer, Option<&WorldTime>, Option<&Weather>)>,
+    server: Res<Server>,
+    mut events: EventReader<SleepEvent>,
+    mut changes: EventWriter<BlockChangeEvent>,
+) {
+    events.iter().for_each(|event| {
+        let Ok((mut client, mut position, spawn_point)) = clients.get_mut(event.client) else {
+            return;
+        };
+        let Ok((mut layer, time, weather)) = layers.get_mut(event.layer) else {
+            return;
+        };
+        let Some(head) = bed_head(&layer, event.position) else {
+            return;
+        };
+
+        let bed = SpawnPoint {
+            world: event.layer,
+            position: head,
+            forced: false,
+        };
+        if spawn_point != Some(&bed) {
+            commands.entity(event.client).insert(bed);
+            client.send_chat_message("Respawn point set");
+        }
+
+        if !can_sleep(time, weather) {
+            client.send_chat_message("You can sleep only at night and during thunderstorms");
+            return;
+        }
+        let state = layer.block(head).map(|block| block.state).unwrap();
+        if state.get(PropName::Occupied) == Some(PropValue::True) {
+            client.send_chat_message("This bed is occupied");
+            return;
+        }
+
+        set_occupied(&mut layer, event.layer, head, true, &mut changes);
+        position.set(DVec3::new(
+            head.x as f64 + 0.5,
+            head.y as f64 + 0.5625,
+            head.z as f64 + 0.5,
+        ));
+        commands.entity(event.client).insert((
+            Sleeping {
+                world: event.layer,
+                bed: head,
+                since: server.current_tick(),
+            },
+            SleepingPosition(Some(head)),
+            entity::Pose(EntityPose::Sleeping),
+        ));
+    });
+}
+
+/// Gets players out of bed when they ask to, die or change worlds, or when their
+/// bed is gone.
+pub fn leave_beds(
+    mut commands: Commands,
+    clients: Query<(Entity, &Sleeping)>,
+    mut layers: Query<&mut ChunkLayer>,
+    mut events: EventReader<LeaveBedEvent>,
+    mut deaths: EventReader<DeathEvent>,
+    mut world_changes: EventReader<ChangeWorldEvent>,
+    mut changes: EventWriter<BlockChangeEvent>,
+) {
+    // beds can also be removed by commands or neighbor updates
+    let without_bed = clients
+        .iter()
+        .filter(|(_, sleeping)| {
+            layers.get(sleeping.world).map_or(false, |layer| {
+                bed_head(layer, sleeping.bed) != Some(sleeping.bed)
+            })
+        })
+        .map(|(client, _)| client)
+        .collect::<Vec<_>>();
+    let leaving = events
+        .iter()
+        .map(|event| event.client)
+        .chain(deaths.iter().map(|event| event.client))
+        .chain(world_changes.iter().map(|event| event.client))
+        .chain(without_bed)
+        .collect::<HashSet<_>>();
+    for client in leaving {
+        let Ok((_, sleeping)) = clients.get(client) else {
+            continue;
+        };
+        if let Ok(mut layer) = layers.get_mut(sleeping.world) {
+            wake_up(&mut commands, &mut layer, client, sleeping, &mut changes);
+        }
+    }
+}
+
+/// Frees the beds of players that disconnected or were kicked while sleeping. Must
+/// run before `despawn_disconnected_clients`, which despawns the entities of the
+/// players.
+pub fn free_beds_of_disconnected(
+    mut disconnected: RemovedComponents<Client>,
+    kicked: Query<Entity, (With<Client>, Added<Despawned>)>,
+    sleepers: Query<&Sleeping>,
+    mut layers: Query<&mut ChunkLayer>,
+    mut changes: EventWriter<BlockChangeEvent>,
+) {
+    disconnected.iter().chain(kicked.iter()).for_each(|entity| {
+        let Ok(sleeping) = sleepers.get(entity) else {
+            return;
+        };
+        if let Ok(mut layer) = layers.get_mut(sleeping.world) {
+            set_occupied(
+                &mut layer,
+                sleeping.world,
+                sleeping.bed,
+                false,
+                &mut changes,
+            );
+        }
+    });
+}
+
+/// Skips to the next morning once enough players of a world sleep, see
+/// [`WorldRules::players_sleeping_percentage`](crate::WorldRules::players_sleeping_percentage).
+pub fn skip_night(
+    mut commands: Commands,
+    mut worlds: Query<(
+        Entity,
+        &WorldInfo,
+        &mut WorldTime,
+        Option<&mut Weather>,
+        &mut ChunkLayer,
+    )>,
+    clients: Query<(Entity, &EntityLayerId, &GameMode, Option<&Sleeping>)>,
+    server: Res<Server>,
+    mut changes: EventWriter<BlockChangeEvent>,
+) {
+    let now = server.current_tick();
+    worlds.for_each_mut(|(world, info, mut time, mut weather, mut layer)| {
+        let players = clients
+            .iter()
+            .filter(|(_, layer_id, game_mode, _)| {
+                layer_id.0 == world && **game_mode != GameMode::Spectator
+            })
+            .collect::<Vec<_>>();
+        let asleep = players
+            .iter()
+            .filter(|(_, _, _, sleeping)| {
+                sleeping.map_or(false, |sleeping| {
+                    now - sleeping.since >= MIN_SLEEP_TICKS
+                        && bed_head(&layer, sleeping.bed) == Some(sleeping.bed)
+                })
+            })
+            .count();
+        if !can_sleep(Some(&*time), weather.as_deref())
+            || asleep == 0
+            || asleep < required_sleepers(players.len(), info)
+        {
+            return;
+        }
+
+        if time.is_night() {
+            time.skip_to_morning();
+        }
+        if let Some(weather) = weather.as_mut().filter(|weather| weather.is_raining()) {
+            weather.set(WeatherState::Clear, None);
+        }
+        for (entity, _, _, sleeping) in players {
+            if let Some(sleeping) = sleeping {
+                wake_up(&mut commands, &mut layer, entity, sleeping, &mut changes);
+            }
+        }
+    });
+}
+
+fn can_sleep(time: Option<&WorldTime>, weather: Option<&Weather>) -> bool {
+    time.map_or(false, WorldTime::is_night) || weather.map_or(false, Weather::is_thundering)
+}
+
+/// How many of the given number of players have to sleep to skip the night.
+fn required_sleepers(players: usize, info: &WorldInfo) -> usize {
+    let percentage = info.rules.players_sleeping_percentage as usize;
+    ((players * percentage + 99) / 100).max(1)
+}
+
+fn wake_up(
+    commands: &mut Commands,
+    layer: &mut ChunkLayer,
+    client: Entity,
+    sleeping: &Sleeping,
+    changes: &mut EventWriter<BlockChangeEvent>,
+) {
+    set_occupied(layer, sleeping.world, sleeping.bed, false, changes);
+    commands
+        .entity(client)
+        .remove::<Sleeping>()
+        .insert((SleepingPosition(None), entity::Pose(EntityPose::Standing)));
+}
+
+/// The head part of the bed at the given position.
+fn bed_head(layer: &ChunkLayer, position: BlockPos) -> Option<BlockPos> {
+    let state = layer.block(position)?.state;
+    let head = match state.get(PropName::Part)? {
+        PropValue::Head => position,
+        _ => other_part(position, state)?,
+    };
+    let kind = layer.block(head)?.state.to_kind();
+    (kind == state.to_kind()).then_some(head)
+}
+
+/// Marks both parts of a bed as occupied or free.
+fn set_occupied(
+    layer: &mut ChunkLayer,
+    world: Entity,
+    head: BlockPos,
+    occupied: bool,
+    changes: &mut EventWriter<BlockChangeEvent>,
+) {
+    let value = if occupied {
+        PropValue::True
+    } else {
+        PropValue::False
+    };
+    let Some(state) = layer.block(head).map(|block| block.state) else {
+        return;
+    };
+    if state.get(PropName::Occupied).is_none() {
+        // the bed was removed
+        return;
+    }
+    for position in std::iter::once(head).chain(other_part(head, state)) {
+        if let Some(part) = layer.block(position).map(|block| block.state) {
+            if part.to_kind() == state.to_kind() {
+                layer.set_block(position, part.set(PropName::Occupied, value));
+                changes.send(BlockChangeEvent {
+                    layer: world,
+                    position,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        place_block, BlockInventories, DamageCause, InteractionHandlers, OpenBlockScreenEvent,
+        PlacementResolvers, WorldRules, DAY_LENGTH,
+    };
+    use valence::interact_block::InteractBlockEvent;
+    use valence::testing::{create_mock_client, ScenarioSingleClient};
+
+    const BED: BlockPos = BlockPos { x: 0, y: 1, z: 0 };
+
+    struct SleepScenario {
+        scenario: ScenarioSingleClient,
+    }
+
+    impl SleepScenario {
+        fn new(time_of_day: i64) -> Self {
+            let mut scenario = ScenarioSingleClient::new();
+            scenario.app.add_event::<BlockChangeEvent>();
+            scenario.app.add_event::<OpenBlockScreenEvent>();
+            scenario.app.add_event::<SleepEvent>();
+            scenario.app.add_event::<DeathEvent>();
+            scenario.app.add_event::<ChangeWorldEvent>();
+            scenario.app.init_resource::<PlacementResolvers>();
+            scenario.app.init_resource::<InteractionHandlers>();
+            scenario.app.init_resource::<BlockInventories>();
+            scenario.app.add_systems(
+                Update,
+                (place_block, go_to_bed, leave_beds, skip_night).chain(),
+            );
+            scenario.app.world.entity_mut(scenario.layer).insert((
+                WorldInfo {
+                    name: "world".to_string(),
+                    spawn: DVec3::ZERO,
+                    rules: WorldRules {
+                        players_sleeping_percentage: 50,
+                        ..Default::default()
+                    },
+                },
+                WorldTime {
+                    age: 0,
+                    time_of_day,
+                },
+            ));
+
+            {
+                let mut layer = scenario
+                    .app
+                    .world
+                    .get_mut::<ChunkLayer>(scenario.layer)
+                    .unwrap();
+                layer.insert_chunk([0, 0], UnloadedChunk::new());
+                let bed = BlockState::RED_BED.set(PropName::Facing, PropValue::South);
+                layer.set_block(BED, bed.set(PropName::Part, PropValue::Foot));
+                layer.set_block(
+                    BlockPos::new(0, 1, 1),
+                    bed.set(PropName::Part, PropValue::Head),
+                );
+            }
+            scenario
+                .app
+                .world
+                .get_mut::<Position>(scenario.client)
+                .unwrap()
+                .set(DVec3::new(2.5, 1.0, 0.5));
+            scenario.app.update();
+
+            Self { scenario }
+        }
+
+        fn use_bed(&mut self) {
+            let client = self.scenario.client;
+            self.scenario.app.world.send_event(InteractBlockEvent {
+                client,
+                hand: Hand::Main,
+                position: BED,
+                face: Direction::Up,
+                cursor_pos: Vec3::new(0.5, 0.5625, 0.5),
+                head_inside_block: false,
+                sequence: 0,
+            });
+            self.scenario.app.update();
+        }
+
+        fn wait(&mut self, ticks: i64) {
+            for _ in 0..ticks {
+                self.scenario.app.update();
+            }
+        }
+
+        fn get<T: Component>(&self) -> Option<&T> {
+            self.scenario.app.world.get::<T>(self.scenario.client)
+        }
+
+        fn bed_occupied(&self) -> bool {
+            let layer = self
+                .scenario
+                .app
+                .world
+                .get::<ChunkLayer>(self.scenario.layer)
+                .unwrap();
+            [BED, BlockPos::new(0, 1, 1)].iter().all(|position| {
+                layer
+                    .block(*position)
+                    .unwrap()
+                    .state
+                    .get(PropName::Occupied)
+                    == Some(PropValue::True)
+            })
+        }
+
+        fn time(&self) -> WorldTime {
+            *self
+                .scenario
+                .app
+                .world
+                .get::<WorldTime>(self.scenario.layer)
+                .unwrap()
+        }
+    }
+
+    #[test]
+    fn test_bed_sets_spawn_point_during_the_day() {
+        let mut scenario = SleepScenario::new(1000);
+        scenario.use_bed();
+
+        assert_eq!(
+            Some(&SpawnPoint {
+                world: scenario.scenario.layer,
+                position: BlockPos::new(0, 1, 1),
+                forced: false,
+            }),
+            scenario.get::<SpawnPoint>()
+        );
+        assert!(scenario.get::<Sleeping>().is_none());
+    }
+
+    #[test]
+    fn test_sleeping_skips_the_night() {
+        let mut scenario = SleepScenario::new(13000);
+        // with 50%, one of the two players is enough
+        let (client, _) = create_mock_client("other");
+        let layer = scenario.scenario.layer;
+        scenario
+            .scenario
+            .app
+            .world
+            .spawn(client)
+            .insert(EntityLayerId(layer));
+
+        scenario.use_bed();
+        assert!(scenario.get::<Sleeping>().is_some());
+        assert!(scenario.bed_occupied());
+
+        scenario.wait(MIN_SLEEP_TICKS);
+        assert_eq!(DAY_LENGTH, scenario.time().time_of_day);
+        assert!(scenario.get::<Sleeping>().is_none());
+    }
+
+    #[test]
+    fn test_wake_up_when_bed_is_removed() {
+        let mut scenario = SleepScenario::new(13000);
+        scenario.use_bed();
+        assert!(scenario.get::<Sleeping>().is_some());
+
+        {
+            let mut layer = scenario
+                .scenario
+                .app
+                .world
+                .get_mut::<ChunkLayer>(scenario.scenario.layer)
+                .unwrap();
+            layer.set_block(BED, BlockState::AIR);
+            layer.set_block(BlockPos::new(0, 1, 1), BlockState::AIR);
+        }
+        scenario.wait(MIN_SLEEP_TICKS);
+
+        // the night isn't skipped without a bed
+        assert!(scenario.get::<Sleeping>().is_none());
+        assert_eq!(13000, scenario.time().time_of_day);
+    }
+
+    #[test]
+    fn test_not_enough_players_sleep() {
+        let mut scenario = SleepScenario::new(13000);
+        scenario
+            .scenario
+            .app
+            .world
+            .entity_mut(scenario.scenario.layer)
+            .get_mut::<WorldInfo>()
+            .unwrap()
+            .rules
+            .players_sleeping_percentage = 100;
+        for name in ["other", "another"] {
+            let (client, _) = create_mock_client(name);
+            let layer = scenario.scenario.layer;
+            scenario
+                .scenario
+                .app
+                .world
+                .spawn(client)
+                .insert(EntityLayerId(layer));
+        }
+
+        scenario.use_bed();
+        scenario.wait(MIN_SLEEP_TICKS);
+        assert!(scenario.time().is_night());
+        assert!(scenario.get::<Sleeping>().is_some());
+
+        let client = scenario.scenario.client;
+        scenario
+            .scenario
+            .app
+            .world
+            .send_event(LeaveBedEvent { client });
+        scenario.scenario.app.update();
+        assert!(scenario.get::<Sleeping>().is_none());
+        assert!(!scenario.bed_occupied());
+    }
+
+    #[test]
+    fn test_dying_frees_the_bed() {
+        let mut scenario = SleepScenario::new(13000);
+        scenario.use_bed();
+        assert!(scenario.bed_occupied());
+
+        let client = scenario.scenario.client;
+        scenario.scenario.app.world.send_event(DeathEvent {
+            client,
+            cause: DamageCause::Kill,
+            message: "test was killed".to_string(),
+        });
+        scenario.scenario.app.update();
+        assert!(scenario.get::<Sleeping>().is_none());
+        assert!(!scenario.bed_occupied());
+    }
+
+    #[test]
+    fn test_disconnecting_frees_the_bed() {
+        let mut scenario = SleepScenario::new(13000);
+        scenario
+            .scenario
+            .app
+            .add_systems(Update, free_beds_of_disconnected);
+        scenario.use_bed();
+        assert!(scenario.bed_occupied());
+
+        let client = scenario.scenario.client;
+        scenario
+            .scenario
+            .app
+            .world
+            .entity_mut(client)
+            .remove::<Client>();
+        scenario.scenario.app.update();
+        assert!(!scenario.bed_occupied());
+    }
+
+    #[test]
+    fn test_bed_changes_are_saved() {
+        let mut scenario = SleepScenario::new(13000);
+        scenario.use_bed();
+        let events = scenario
+            .scenario
+            .app
+            .world
+            .resource::<Events<BlockChangeEvent>>();
+        let changed = events
+            .iter_current_update_events()
+            .map(|event| event.position)
+            .collect::<Vec<_>>();
+        assert!(changed.contains(&BED));
+        assert!(changed.contains(&BlockPos::new(0, 1, 1)));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{argument, literal, source_world, ArgumentKind, CommandEvent, CommandFeedback};
+use crate::{CommandSpec, WorldInfo, WorldRegistry};
+use bevy_ecs::prelude::*;
+use valence::client::VisibleChunkLayer;
+use valence::entity::EntityLayerId;
+use valence::prelude::*;
+use valence::protocol::packets::play::WorldTimeUpdateS2c;
+use valence::protocol::WritePacket;
+
+/// The length of a day, in ticks.
+pub const DAY_LENGTH: i64 = 24000;
+/// The part of the day in which players can sleep.
+const NIGHT: std::ops::Range<i64> = 12542..23460;
+/// How often the time is sent to clients, in ticks. Clients advance it on their own
+/// in between.
+const TIME_SYNC_INTERVAL: i64 = 20;
+
+/// The clock of a world, lives on the world's layer entity.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct WorldTime {
+    /// Ticks since the world was created.
+    pub age: i64,
+    /// The time of day, `0` is sunrise. Keeps counting up after the first day.
+    pub time_of_day: i64,
+}
+
+impl WorldTime {
+    /// The time within the current day, from `0` to [`DAY_LENGTH`].
+    pub fn day_time(&self) -> i64 {
+        self.time_of_day.rem_euclid(DAY_LENGTH)
+    }
+
+    pub fn is_night(&self) -> bool {
+        NIGHT.contains(&self.day_time())
+    }
+
+    /// Moves the time of day forward to the next sunrise.
+    pub fn skip_to_morning(&mut self) {
+        self.time_of_day += DAY_LENGTH - self.day_time();
+    }
+}
+
+/// Advances the clock of every world by one tick, and the time of day by the
+/// [`time_rate`](crate::WorldRules::time_rate) of the world.
+pub fn advance_time(mut worlds: Query<(&WorldInfo, &mut WorldTime)>) {
+    worlds.for_each_mut(|(info, mut time)| {
+        if info.rules.time_rate == 1 {
+            // clients advance the time on their own, only jumps need to be sent right away
+            let time = time.bypass_change_detection();
+            time.age += 1;
+            time.time_of_day += 1;
+        } else {
+            time.age += 1;
+            time.time_of_day += info.rules.time_rate;
+        }
+    });
+}
+
+/// Sends the time of their world to clients, regularly and whenever they change
+/// worlds or the time jumps.
+pub fn sync_time(
+    mut clients: Query<(&mut Client, Ref<VisibleChunkLayer>)>,
+    worlds: Query<(&WorldInfo, Ref<WorldTime>)>,
+    server: Res<Server>,
+) {
+    let interval = server.current_tick() % TIME_SYNC_INTERVAL == 0;
+    clients.for_each_mut(|(mut client, layer)| {
+        let Ok((info, time)) = worlds.get(layer.0) else {
+            return;
+        };
+        if !interval && !layer.is_changed() && !time.is_changed() {
+            return;
+        }
+
+        // a negative time of day stops clients from advancing it on their own
+        let time_of_day = if info.rules.time_rate == 1 {
+            time.time_of_day
+        } else {
+            -time.time_of_day.max(1)
+        };
+        client.write_packet(&WorldTimeUpdateS2c {
+            world_age: time.age,
+            time_of_day,
+        });
+    });
+}
+
+/// `/time` shows the time of day, `/time set <day|noon|night|midnight|ticks>` and
+/// `/time add <ticks>` change it.
+pub fn time_command() -> CommandSpec {
+    CommandSpec::new("time", "justmine.command.time")
+        .usage([])
+        .usage([literal("query")])
+        .usage([
+            literal("set"),
+            argument(
+                "time",
+                ArgumentKind::Choice(&["day", "noon", "night", "midnight"]),
+            ),
+        ])
+        .usage([
+            literal("set"),
+            argument(
+                "time",
+                ArgumentKind::Integer {
+                    min: 0,
+                    max: DAY_LENGTH as i32 - 1,
+                },
+            ),
+        ])
+        .usage([
+            literal("add"),
+            argument(
+                "time",
+                ArgumentKind::Integer {
+                    min: 0,
+                    max: i32::MAX,
+                },
+            ),
+        ])
+}
+
+/// Handles `/time`, in the world of the source.
+pub fn handle_time_command(
+    players: Query<&EntityLayerId>,
+    registry: Option<Res<WorldRegistry>>,
+    mut worlds: Query<&mut WorldTime>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "time")
+        .for_each(|event| {
+            let Some(world) = source_world(event.source, &players, registry.as_deref()) else {
+                return;
+            };
+            let Ok(mut time) = worlds.get_mut(world) else {
+                return;
+            };
+
+            let message = match event.args.path() {
+                ["set"] => {
+                    let day_time = match event.args.choice("time") {
+                        Some(name) => named_day_time(name),
+                        None => event.args.integer("time").unwrap_or_default() as i64,
+                    };
+                    // keep the day count, so the moon phase doesn't jump
+                    time.time_of_day += day_time - time.day_time();
+                    format!("Set the time to {}", day_time)
+                }
+                ["add"] => {
+                    time.time_of_day += event.args.integer("time").unwrap_or_default() as i64;
+                    format!("Set the time to {}", time.day_time())
+                }
+                _ => format!("The time is {}", time.day_time()),
+            };
+            feedback.send(CommandFeedback::info(event.source, message));
+        });
+}
+
+fn named_day_time(name: &str) -> i64 {
+    match name {
+        "noon" => 6000,
+        "night" => 13000,
+        "midnight" => 18000,
+        _ => 1000,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParseContext;
+
+    #[test]
+    fn test_night() {
+        let mut time = WorldTime::default();
+        assert!(!time.is_night());
+
+        time.time_of_day = 13000;
+        assert!(time.is_night());
+        time.time_of_day += DAY_LENGTH;
+        assert!(time.is_night());
+        assert_eq!(13000, time.day_time());
+
+        time.skip_to_morning();
+        assert_eq!(2 * DAY_LENGTH, time.time_of_day);
+        assert!(!time.is_night());
+    }
+
+    #[test]
+    fn test_time_command() {
+        let ctx = ParseContext::default();
+        let spec = time_command();
+        assert!(spec.parse(&["set", "night"], &ctx).is_ok());
+        assert!(spec.parse(&["set", "500"], &ctx).is_ok());
+        assert!(spec.parse(&["set", "24000"], &ctx).is_err());
+        assert!(spec.parse(&["set", "dusk"], &ctx).is_err());
+        assert!(spec.parse(&["add", "-5"], &ctx).is_err());
+    }
+}
@@ -0,0 +1,201 @@
+use valence::nbt::{List, Value};
+use valence::prelude::*;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ToolKind {
+    Pickaxe,
+    Axe,
+    Shovel,
+    Hoe,
+    Sword,
+    Shears,
+}
+
+/// The material of a tool. Golden tools are fast, but can only harvest what
+/// wooden tools can harvest.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ToolTier {
+    Wood,
+    Gold,
+    Stone,
+    Iron,
+    Diamond,
+    Netherite,
+}
+
+impl ToolTier {
+    /// The harvest level, a tool can harvest every block that requires this level or lower.
+    pub fn level(self) -> u8 {
+        match self {
+            ToolTier::Wood | ToolTier::Gold => 0,
+            ToolTier::Stone => 1,
+            ToolTier::Iron => 2,
+            ToolTier::Diamond => 3,
+            ToolTier::Netherite => 4,
+        }
+    }
+
+    /// The mining speed multiplier of a tool of this tier on a block that it is made for.
+    pub fn speed(self) -> f32 {
+        match self {
+            ToolTier::Wood => 2.0,
+            ToolTier::Stone => 4.0,
+            ToolTier::Iron => 6.0,
+            ToolTier::Diamond => 8.0,
+            ToolTier::Netherite => 9.0,
+            ToolTier::Gold => 12.0,
+        }
+    }
+}
+
+/// The properties of a held item that matter for breaking blocks and fighting.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Tool {
+    pub kind: ToolKind,
+    /// `None` for tools without a tier, like shears.
+    pub tier: Option<ToolTier>,
+    pub efficiency: u8,
+}
+
+impl Tool {
+    /// Returns the tool that the given stack is, or `None` if it is not a tool.
+    pub fn from_stack(stack: &ItemStack) -> Option<Self> {
+        if stack.is_empty() {
+            return None;
+        }
+
+        let name = stack.item.to_str();
+        let (kind, tier) = if name == "shears" {
+            (ToolKind::Shears, None)
+        } else {
+            let (material, kind) = name.rsplit_once('_')?;
+            let kind = match kind {
+                "pickaxe" => ToolKind::Pickaxe,
+                "axe" => ToolKind::Axe,
+                "shovel" => ToolKind::Shovel,
+                "hoe" => ToolKind::Hoe,
+                "sword" => ToolKind::Sword,
+                _ => return None,
+            };
+            let tier = match material {
+                "wooden" => ToolTier::Wood,
+                "golden" => ToolTier::Gold,
+                "stone" => ToolTier::Stone,
+                "iron" => ToolTier::Iron,
+                "diamond" => ToolTier::Diamond,
+                "netherite" => ToolTier::Netherite,
+                _ => return None,
+            };
+            (kind, Some(tier))
+        };
+
+        Some(Self {
+            kind,
+            tier,
+            efficiency: enchantment_level(stack, "minecraft:efficiency"),
+        })
+    }
+
+    /// The damage of a hit with this tool, without enchantments.
+    pub fn attack_damage(&self) -> f32 {
+        let Some(tier) = self.tier else {
+            return 1.0;
+        };
+        let bonus = match tier {
+            ToolTier::Wood | ToolTier::Gold => 0.0,
+            ToolTier::Stone => 1.0,
+            ToolTier::Iron => 2.0,
+            ToolTier::Diamond => 3.0,
+            ToolTier::Netherite => 4.0,
+        };
+        match self.kind {
+            ToolKind::Sword => 4.0 + bonus,
+            ToolKind::Pickaxe => 2.0 + bonus,
+            ToolKind::Shovel => 2.5 + bonus,
+            ToolKind::Axe => match tier {
+                ToolTier::Wood | ToolTier::Gold => 7.0,
+                ToolTier::Stone | ToolTier::Iron | ToolTier::Diamond => 9.0,
+                ToolTier::Netherite => 10.0,
+            },
+            ToolKind::Hoe | ToolKind::Shears => 1.0,
+        }
+    }
+}
+
+/// Returns the level of the given enchantment on the stack, or 0 if it isn't enchanted with it.
+pub fn enchantment_level(stack: &ItemStack, enchantment: &str) -> u8 {
+    let Some(Value::List(List::Compound(enchantments))) =
+        stack.nbt.as_ref().and_then(|nbt| nbt.get("Enchantments"))
+    else {
+        return 0;
+    };
+
+    enchantments
+        .iter()
+        .find(|e| matches!(e.get("id"), Some(Value::String(id)) if id == enchantment))
+        .and_then(|e| match e.get("lvl") {
+            Some(Value::Short(lvl)) => Some(*lvl as u8),
+            Some(Value::Int(lvl)) => Some(*lvl as u8),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::nbt::compound;
+
+    #[test]
+    fn test_from_stack() {
+        assert_eq!(
+            Some(Tool {
+                kind: ToolKind::Pickaxe,
+                tier: Some(ToolTier::Diamond),
+                efficiency: 0,
+            }),
+            Tool::from_stack(&ItemStack::new(ItemKind::DiamondPickaxe, 1, None))
+        );
+        assert_eq!(
+            Some(Tool {
+                kind: ToolKind::Shears,
+                tier: None,
+                efficiency: 0,
+            }),
+            Tool::from_stack(&ItemStack::new(ItemKind::Shears, 1, None))
+        );
+        assert_eq!(
+            None,
+            Tool::from_stack(&ItemStack::new(ItemKind::OakPlanks, 1, None))
+        );
+        assert_eq!(
+            None,
+            Tool::from_stack(&ItemStack::new(ItemKind::PistonHead, 1, None))
+        );
+        assert_eq!(None, Tool::from_stack(&ItemStack::EMPTY));
+    }
+
+    #[test]
+    fn test_attack_damage() {
+        let damage = |item| {
+            Tool::from_stack(&ItemStack::new(item, 1, None)).map(|tool| tool.attack_damage())
+        };
+        assert_eq!(Some(4.0), damage(ItemKind::WoodenSword));
+        assert_eq!(Some(8.0), damage(ItemKind::NetheriteSword));
+        assert_eq!(Some(9.0), damage(ItemKind::StoneAxe));
+        assert_eq!(Some(4.5), damage(ItemKind::IronShovel));
+        assert_eq!(Some(1.0), damage(ItemKind::DiamondHoe));
+    }
+
+    #[test]
+    fn test_efficiency() {
+        let nbt = compound! {
+            "Enchantments" => List::Compound(vec![
+                compound! { "id" => "minecraft:unbreaking", "lvl" => 3_i16 },
+                compound! { "id" => "minecraft:efficiency", "lvl" => 5_i16 },
+            ]),
+        };
+        let tool = Tool::from_stack(&ItemStack::new(ItemKind::IronShovel, 1, Some(nbt))).unwrap();
+        assert_eq!(5, tool.efficiency);
+    }
+}
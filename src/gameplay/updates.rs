@@ -0,0 +1,829 @@
+use crate::{
+    block_drops, direction_from_prop, is_perpendicular, is_solid, is_stairs, opposite, other_part,
+    spawn_item, stairs_shape, BlockChangeEvent,
+};
+use bevy_ecs::prelude::*;
+use log::debug;
+use std::collections::VecDeque;
+use valence::prelude::*;
+
+/// How many blocks neighbor updates may change in a single tick, so that a
+/// runaway chain of updates can't stall the server. The remaining updates are
+/// carried over to the next tick.
+const MAX_UPDATES_PER_TICK: usize = 4096;
+
+const DIRECTIONS: [Direction; 6] = [
+    Direction::Down,
+    Direction::Up,
+    Direction::North,
+    Direction::South,
+    Direction::West,
+    Direction::East,
+];
+
+const HORIZONTAL: [(Direction, PropName); 4] = [
+    (Direction::North, PropName::North),
+    (Direction::East, PropName::East),
+    (Direction::South, PropName::South),
+    (Direction::West, PropName::West),
+];
+
+/// Recomputes the connections of every changed block and its neighbors, and
+/// breaks the ones that lost their support. Blocks that change because of that
+/// update their own neighbors in turn.
+///
+/// The changes made here are sent as [`BlockChangeEvent`]s as well, so that
+/// they are saved. Reading them again in the next tick finds nothing left to do.
+pub fn update_neighbors(
+    mut commands: Commands,
+    mut layers: Query<&mut ChunkLayer>,
+    server: Res<Server>,
+    mut events: ParamSet<(EventReader<BlockChangeEvent>, EventWriter<BlockChangeEvent>)>,
+    mut carried_over: Local<VecDeque<BlockChangeEvent>>,
+) {
+    let mut queue = std::mem::take(&mut *carried_over);
+    queue.extend(events.p0().iter().copied());
+    let mut changes = vec![];
+
+    while let Some(change) = queue.front().copied() {
+        if changes.len() >= MAX_UPDATES_PER_TICK {
+            debug!(
+                "too many block updates in one tick, {} are left for the next one",
+                queue.len()
+            );
+            *carried_over = queue;
+            break;
+        }
+        queue.pop_front();
+        let Ok(mut layer) = layers.get_mut(change.layer) else {
+            continue;
+        };
+
+        let neighbors = DIRECTIONS.map(|direction| change.position.get_in_direction(direction));
+        for position in std::iter::once(change.position).chain(neighbors) {
+            let Some(state) = layer.block(position).map(|block| block.state) else {
+                continue;
+            };
+            let updated = match updated_state(&layer, position, state) {
+                Some(updated) => updated,
+                None => {
+                    let position = DVec3::new(
+                        position.x as f64 + 0.5,
+                        position.y as f64 + 0.25,
+                        position.z as f64 + 0.5,
+                    );
+                    for stack in block_drops(state, &ItemStack::EMPTY) {
+                        spawn_item(
+                            &mut commands,
+                            change.layer,
+                            position,
+                            stack,
+                            server.current_tick(),
+                        );
+                    }
+                    broken_state(state)
+                }
+            };
+            if updated == state {
+                continue;
+            }
+
+            layer.set_block(position, updated);
+            let event = BlockChangeEvent {
+                layer: change.layer,
+                position,
+            };
+            queue.push_back(event);
+            changes.push(event);
+        }
+    }
+
+    let mut writer = events.p1();
+    for event in changes {
+        writer.send(event);
+    }
+}
+
+/// Returns the state that the block at the given position should have, given
+/// its neighbors, or `None` if the block lost its support and breaks.
+pub fn updated_state(
+    layer: &ChunkLayer,
+    position: BlockPos,
+    state: BlockState,
+) -> Option<BlockState> {
+    if !is_supported(layer, position, state) {
+        return None;
+    }
+
+    let name = state.to_kind().to_str();
+    let state = if is_stairs(state) {
+        state.set(PropName::Shape, stairs_shape(layer, position, state))
+    } else if is_fence(name) {
+        connect_fence(layer, position, state)
+    } else if is_wall(name) {
+        connect_wall(layer, position, state)
+    } else if is_pane(name) {
+        connect_pane(layer, position, state)
+    } else if name == "redstone_wire" {
+        connect_redstone_wire(layer, position, state)
+    } else if name.ends_with("_door") {
+        power_door(layer, position, state)
+    } else {
+        state
+    };
+    Some(state)
+}
+
+/// What is left of a block that broke, which is water for waterlogged blocks.
+fn broken_state(state: BlockState) -> BlockState {
+    if state.get(PropName::Waterlogged) == Some(PropValue::True) {
+        BlockState::WATER
+    } else {
+        BlockState::AIR
+    }
+}
+
+fn state_at(layer: &ChunkLayer, position: BlockPos) -> BlockState {
+    layer
+        .block(position)
+        .map(|block| block.state)
+        .unwrap_or(BlockState::AIR)
+}
+
+fn is_supported(layer: &ChunkLayer, position: BlockPos, state: BlockState) -> bool {
+    let kind = state.to_kind();
+    let name = kind.to_str();
+    let at = |direction: Direction| state_at(layer, position.get_in_direction(direction));
+    // blocks next to chunks that aren't loaded keep their support
+    let solid = |direction: Direction| {
+        layer
+            .block(position.get_in_direction(direction))
+            .map_or(true, |block| is_solid(block.state.to_kind()))
+    };
+    let below = at(Direction::Down).to_kind().to_str();
+
+    // the two halves of doors and tall plants hold each other
+    match state.get(PropName::Half) {
+        Some(PropValue::Upper) => {
+            let lower = at(Direction::Down);
+            return lower.to_kind() == kind && lower.get(PropName::Half) == Some(PropValue::Lower);
+        }
+        Some(PropValue::Lower) => {
+            let upper = at(Direction::Up);
+            if upper.to_kind() != kind || upper.get(PropName::Half) != Some(PropValue::Upper) {
+                return false;
+            }
+        }
+        _ => {}
+    }
+
+    // as do the foot and the head of a bed
+    if let (Some(part), Some(facing)) = (
+        state.get(PropName::Part),
+        state.get(PropName::Facing).and_then(direction_from_prop),
+    ) {
+        let (direction, other) = match part {
+            PropValue::Foot => (facing, PropValue::Head),
+            _ => (opposite(facing), PropValue::Foot),
+        };
+        let neighbor = at(direction);
+        return neighbor.to_kind() == kind && neighbor.get(PropName::Part) == Some(other);
+    }
+
+    let facing = state.get(PropName::Facing).and_then(direction_from_prop);
+    if let Some(face) = state.get(PropName::Face).filter(|_| name != "grindstone") {
+        return match face {
+            PropValue::Floor => solid(Direction::Down),
+            PropValue::Ceiling => solid(Direction::Up),
+            _ => facing.map_or(true, |facing| solid(opposite(facing))),
+        };
+    }
+
+    if is_wall_attached(name) {
+        return facing.map_or(true, |facing| solid(opposite(facing)));
+    }
+
+    match name {
+        "torch" | "soul_torch" | "redstone_torch" => {
+            solid(Direction::Down) || is_fence(below) || is_wall(below)
+        }
+        "redstone_wire" | "repeater" | "comparator" | "snow" => solid(Direction::Down),
+        "wheat" | "carrots" | "potatoes" | "beetroots" => below == "farmland",
+        "dead_bush" => is_soil(below) || is_sand(below) || below.ends_with("terracotta"),
+        "sugar_cane" => below == "sugar_cane" || is_soil(below) || is_sand(below),
+        "cactus" => below == "cactus" || is_sand(below),
+        _ if name.ends_with("_door")
+            || name.ends_with("_pressure_plate")
+            || name.ends_with("rail")
+            || (name.ends_with("_sign") && !name.ends_with("_hanging_sign"))
+            || name.ends_with("_banner") =>
+        {
+            solid(Direction::Down)
+        }
+        _ if name.ends_with("_carpet") => !at(Direction::Down).is_air(),
+        _ if is_plant(name) => is_soil(below),
+        _ => true,
+    }
+}
+
+/// Blocks that hang on the side of the block behind them.
+fn is_wall_attached(name: &str) -> bool {
+    name.ends_with("wall_torch")
+        || name.ends_with("_wall_sign")
+        || name.ends_with("_wall_banner")
+        || name.ends_with("_coral_wall_fan")
+        || matches!(name, "ladder" | "tripwire_hook")
+}
+
+/// Plants that grow on dirt and its variants.
+fn is_plant(name: &str) -> bool {
+    name.ends_with("_sapling")
+        || name.ends_with("_tulip")
+        || matches!(
+            name,
+            "grass"
+                | "fern"
+                | "tall_grass"
+                | "large_fern"
+                | "dandelion"
+                | "poppy"
+                | "blue_orchid"
+                | "allium"
+                | "azure_bluet"
+                | "oxeye_daisy"
+                | "cornflower"
+                | "lily_of_the_valley"
+                | "wither_rose"
+                | "torchflower"
+                | "sunflower"
+                | "lilac"
+                | "rose_bush"
+                | "peony"
+                | "sweet_berry_bush"
+        )
+}
+
+fn is_soil(name: &str) -> bool {
+    matches!(
+        name,
+        "dirt"
+            | "grass_block"
+            | "podzol"
+            | "coarse_dirt"
+            | "rooted_dirt"
+            | "mycelium"
+            | "farmland"
+            | "moss_block"
+            | "mud"
+            | "muddy_mangrove_roots"
+    )
+}
+
+fn is_sand(name: &str) -> bool {
+    matches!(name, "sand" | "red_sand" | "suspicious_sand")
+}
+
+fn is_fence(name: &str) -> bool {
+    name.ends_with("_fence")
+}
+
+fn is_wall(name: &str) -> bool {
+    name.ends_with("_wall")
+}
+
+fn is_pane(name: &str) -> bool {
+    name.ends_with("glass_pane") || name == "iron_bars"
+}
+
+/// Full blocks that fences, walls and panes still don't connect to.
+fn cannot_connect(name: &str) -> bool {
+    name.ends_with("_leaves")
+        || name.ends_with("shulker_box")
+        || matches!(
+            name,
+            "barrier" | "pumpkin" | "carved_pumpkin" | "jack_o_lantern" | "melon"
+        )
+}
+
+fn connects_to_solid(neighbor: BlockState) -> bool {
+    let kind = neighbor.to_kind();
+    is_solid(kind) && !cannot_connect(kind.to_str())
+}
+
+/// Fence gates connect to fences and walls on both sides of their hinges.
+fn gate_connects(neighbor: BlockState, direction: Direction) -> bool {
+    neighbor.to_kind().to_str().ends_with("_fence_gate")
+        && neighbor
+            .get(PropName::Facing)
+            .and_then(direction_from_prop)
+            .map_or(false, |facing| is_perpendicular(facing, direction))
+}
+
+fn connect_fence(layer: &ChunkLayer, position: BlockPos, state: BlockState) -> BlockState {
+    let name = state.to_kind().to_str();
+    HORIZONTAL.iter().fold(state, |state, &(direction, prop)| {
+        let neighbor = state_at(layer, position.get_in_direction(direction));
+        let neighbor_name = neighbor.to_kind().to_str();
+        // nether brick fences don't connect to wooden ones
+        let connects = if is_fence(neighbor_name) {
+            (neighbor_name == "nether_brick_fence") == (name == "nether_brick_fence")
+        } else {
+            gate_connects(neighbor, direction) || connects_to_solid(neighbor)
+        };
+        state.set(prop, bool_prop(connects))
+    })
+}
+
+fn connect_pane(layer: &ChunkLayer, position: BlockPos, state: BlockState) -> BlockState {
+    HORIZONTAL.iter().fold(state, |state, &(direction, prop)| {
+        let neighbor = state_at(layer, position.get_in_direction(direction));
+        let neighbor_name = neighbor.to_kind().to_str();
+        let connects =
+            is_pane(neighbor_name) || is_wall(neighbor_name) || connects_to_solid(neighbor);
+        state.set(prop, bool_prop(connects))
+    })
+}
+
+fn connect_wall(layer: &ChunkLayer, position: BlockPos, state: BlockState) -> BlockState {
+    let above = state_at(layer, position.get_in_direction(Direction::Up));
+    let above_name = above.to_kind().to_str();
+
+    let state = HORIZONTAL.iter().fold(state, |state, &(direction, prop)| {
+        let neighbor = state_at(layer, position.get_in_direction(direction));
+        let neighbor_name = neighbor.to_kind().to_str();
+        let connects = is_wall(neighbor_name)
+            || is_pane(neighbor_name)
+            || gate_connects(neighbor, direction)
+            || connects_to_solid(neighbor);
+        // a side is as tall as what sits on top of it
+        let height = if !connects {
+            PropValue::None
+        } else if is_solid(above.to_kind())
+            || (is_wall(above_name) && above.get(prop) != Some(PropValue::None))
+        {
+            PropValue::Tall
+        } else {
+            PropValue::Low
+        };
+        state.set(prop, height)
+    });
+
+    let side = |prop: PropName| state.get(prop).unwrap_or(PropValue::None);
+    let (north, east, south, west) = (
+        side(PropName::North),
+        side(PropName::East),
+        side(PropName::South),
+        side(PropName::West),
+    );
+    let none = |side: PropValue| side == PropValue::None;
+    let post = if is_wall(above_name) && above.get(PropName::Up) == Some(PropValue::True) {
+        true
+    } else if (none(north) && none(east) && none(south) && none(west))
+        || none(north) != none(south)
+        || none(east) != none(west)
+    {
+        // ends, corners and crossings always have a post
+        true
+    } else if (north == PropValue::Tall && south == PropValue::Tall)
+        || (east == PropValue::Tall && west == PropValue::Tall)
+    {
+        false
+    } else {
+        is_post_override(above_name) || is_solid(above.to_kind())
+    };
+    state.set(PropName::Up, bool_prop(post))
+}
+
+/// Blocks that give a straight wall below them a post.
+fn is_post_override(name: &str) -> bool {
+    (name.ends_with("_sign") && !name.contains("wall"))
+        || (name.ends_with("_banner") && !name.contains("wall"))
+        || matches!(
+            name,
+            "torch" | "soul_torch" | "redstone_torch" | "lantern" | "soul_lantern"
+        )
+}
+
+fn connect_redstone_wire(layer: &ChunkLayer, position: BlockPos, state: BlockState) -> BlockState {
+    let is_dot = |state: BlockState| {
+        HORIZONTAL
+            .iter()
+            .all(|&(_, prop)| state.get(prop) == Some(PropValue::None))
+    };
+    let was_dot = is_dot(state);
+    let is_wire =
+        |position: BlockPos| state_at(layer, position).to_kind() == BlockKind::RedstoneWire;
+    let covered = is_solid(state_at(layer, position.get_in_direction(Direction::Up)).to_kind());
+
+    let connected = HORIZONTAL.iter().fold(state, |state, &(direction, prop)| {
+        let neighbor_position = position.get_in_direction(direction);
+        let neighbor = state_at(layer, neighbor_position);
+        let neighbor_solid = is_solid(neighbor.to_kind());
+        let connection = if !covered
+            && neighbor_solid
+            && is_wire(neighbor_position.get_in_direction(Direction::Up))
+        {
+            // the wire climbs up the side of the neighbor
+            PropValue::Up
+        } else if wire_connects(neighbor, direction)
+            || (!neighbor_solid && is_wire(neighbor_position.get_in_direction(Direction::Down)))
+        {
+            PropValue::Side
+        } else {
+            PropValue::None
+        };
+        state.set(prop, connection)
+    });
+
+    // a dot that the player made stays a dot
+    if was_dot && is_dot(connected) {
+        return connected;
+    }
+
+    // a wire with connections on one axis only extends to both ends of that axis,
+    // and one without any connections is a cross
+    let connects = |prop: PropName| connected.get(prop) != Some(PropValue::None);
+    let north_south = connects(PropName::North) || connects(PropName::South);
+    let east_west = connects(PropName::East) || connects(PropName::West);
+    HORIZONTAL
+        .iter()
+        .fold(connected, |state, &(direction, prop)| {
+            let other_axis = match direction {
+                Direction::North | Direction::South => east_west,
+                _ => north_south,
+            };
+            if !connects(prop) && !other_axis {
+                state.set(prop, PropValue::Side)
+            } else {
+                state
+            }
+        })
+}
+
+/// Returns whether redstone wire connects to the given neighbor, which lies in
+/// the given direction of the wire.
+fn wire_connects(neighbor: BlockState, direction: Direction) -> bool {
+    let name = neighbor.to_kind().to_str();
+    let facing = neighbor.get(PropName::Facing).and_then(direction_from_prop);
+    match name {
+        "redstone_wire" => true,
+        "repeater" => facing.map_or(false, |facing| {
+            facing == direction || facing == opposite(direction)
+        }),
+        "observer" => facing == Some(direction),
+        "redstone_torch"
+        | "redstone_wall_torch"
+        | "redstone_block"
+        | "lever"
+        | "daylight_detector"
+        | "detector_rail"
+        | "trapped_chest"
+        | "comparator"
+        | "tripwire_hook"
+        | "target"
+        | "lectern"
+        | "sculk_sensor"
+        | "calibrated_sculk_sensor" => true,
+        _ => name.ends_with("_button") || name.ends_with("_pressure_plate"),
+    }
+}
+
+/// Doors open when either of their halves gets powered, and close again when the
+/// power goes away.
+fn power_door(layer: &ChunkLayer, position: BlockPos, state: BlockState) -> BlockState {
+    let powered = is_powered(layer, position)
+        || other_part(position, state).map_or(false, |other| is_powered(layer, other));
+    if state.get(PropName::Powered) == Some(bool_prop(powered)) {
+        return state;
+    }
+    state
+        .set(PropName::Powered, bool_prop(powered))
+        .set(PropName::Open, bool_prop(powered))
+}
+
+/// Returns whether a block next to the given position powers it.
+fn is_powered(layer: &ChunkLayer, position: BlockPos) -> bool {
+    DIRECTIONS.iter().any(|&direction| {
+        let neighbor = state_at(layer, position.get_in_direction(direction));
+        let on = |prop: PropName| neighbor.get(prop) == Some(PropValue::True);
+        let name = neighbor.to_kind().to_str();
+        match name {
+            "redstone_block" => true,
+            "redstone_torch" | "redstone_wall_torch" => on(PropName::Lit),
+            "lever" => on(PropName::Powered),
+            _ if name.ends_with("_button") => on(PropName::Powered),
+            _ if name.ends_with("weighted_pressure_plate") => neighbor
+                .get(PropName::Power)
+                .and_then(|power| power.to_u16())
+                .map_or(false, |power| power > 0),
+            _ if name.ends_with("_pressure_plate") => on(PropName::Powered),
+            _ => false,
+        }
+    })
+}
+
+fn bool_prop(value: bool) -> PropValue {
+    if value {
+        PropValue::True
+    } else {
+        PropValue::False
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DroppedItem;
+    use valence::testing::ScenarioSingleClient;
+
+    struct UpdateScenario {
+        scenario: ScenarioSingleClient,
+    }
+
+    impl UpdateScenario {
+        fn new() -> Self {
+            let mut scenario = ScenarioSingleClient::new();
+            scenario.app.add_event::<BlockChangeEvent>();
+            scenario.app.add_systems(Update, update_neighbors);
+            scenario.app.update();
+
+            scenario
+                .app
+                .world
+                .get_mut::<ChunkLayer>(scenario.layer)
+                .unwrap()
+                .insert_chunk([0, 0], UnloadedChunk::new());
+            scenario.app.update();
+
+            Self { scenario }
+        }
+
+        /// Changes a block like a player would, which updates its neighbors.
+        fn set(&mut self, position: impl Into<BlockPos>, state: BlockState) {
+            self.set_all(&[(position.into(), state)]);
+        }
+
+        /// Changes several blocks at once, before any of them update their neighbors.
+        fn set_all(&mut self, blocks: &[(BlockPos, BlockState)]) {
+            let layer = self.scenario.layer;
+            for &(position, state) in blocks {
+                self.scenario
+                    .app
+                    .world
+                    .get_mut::<ChunkLayer>(layer)
+                    .unwrap()
+                    .set_block(position, state);
+                self.scenario
+                    .app
+                    .world
+                    .send_event(BlockChangeEvent { layer, position });
+            }
+            self.scenario.app.update();
+        }
+
+        fn get(&self, position: impl Into<BlockPos>) -> BlockState {
+            self.scenario
+                .app
+                .world
+                .get::<ChunkLayer>(self.scenario.layer)
+                .unwrap()
+                .block(position)
+                .unwrap()
+                .state
+        }
+
+        fn dropped_items(&mut self) -> usize {
+            self.scenario
+                .app
+                .world
+                .query::<&DroppedItem>()
+                .iter(&self.scenario.app.world)
+                .count()
+        }
+    }
+
+    #[test]
+    fn test_fences_connect() {
+        let mut scenario = UpdateScenario::new();
+        scenario.set([1, 1, 1], BlockState::OAK_FENCE);
+        scenario.set([2, 1, 1], BlockState::OAK_FENCE);
+        scenario.set([1, 1, 0], BlockState::STONE);
+        scenario.set([1, 1, 2], BlockState::OAK_LEAVES);
+
+        let fence = scenario.get([1, 1, 1]);
+        assert_eq!(Some(PropValue::True), fence.get(PropName::East));
+        assert_eq!(Some(PropValue::True), fence.get(PropName::North));
+        assert_eq!(Some(PropValue::False), fence.get(PropName::South));
+        assert_eq!(Some(PropValue::False), fence.get(PropName::West));
+        assert_eq!(
+            Some(PropValue::True),
+            scenario.get([2, 1, 1]).get(PropName::West)
+        );
+
+        // nether brick fences keep to themselves
+        scenario.set([0, 1, 1], BlockState::NETHER_BRICK_FENCE);
+        assert_eq!(
+            Some(PropValue::False),
+            scenario.get([1, 1, 1]).get(PropName::West)
+        );
+
+        // and removing a fence disconnects its neighbor again
+        scenario.set([2, 1, 1], BlockState::AIR);
+        assert_eq!(
+            Some(PropValue::False),
+            scenario.get([1, 1, 1]).get(PropName::East)
+        );
+    }
+
+    #[test]
+    fn test_panes_connect() {
+        let mut scenario = UpdateScenario::new();
+        scenario.set([1, 1, 1], BlockState::GLASS_PANE);
+        scenario.set([1, 1, 2], BlockState::IRON_BARS);
+
+        assert_eq!(
+            Some(PropValue::True),
+            scenario.get([1, 1, 1]).get(PropName::South)
+        );
+        assert_eq!(
+            Some(PropValue::True),
+            scenario.get([1, 1, 2]).get(PropName::North)
+        );
+    }
+
+    #[test]
+    fn test_walls_connect() {
+        let mut scenario = UpdateScenario::new();
+        scenario.set([1, 1, 0], BlockState::COBBLESTONE_WALL);
+        scenario.set([1, 1, 1], BlockState::COBBLESTONE_WALL);
+        scenario.set([1, 1, 2], BlockState::COBBLESTONE_WALL);
+
+        // the middle of a straight wall has no post
+        let wall = scenario.get([1, 1, 1]);
+        assert_eq!(Some(PropValue::Low), wall.get(PropName::North));
+        assert_eq!(Some(PropValue::Low), wall.get(PropName::South));
+        assert_eq!(Some(PropValue::None), wall.get(PropName::East));
+        assert_eq!(Some(PropValue::False), wall.get(PropName::Up));
+
+        // a torch on top gives it one
+        scenario.set([1, 2, 1], BlockState::TORCH);
+        assert_eq!(
+            Some(PropValue::True),
+            scenario.get([1, 1, 1]).get(PropName::Up)
+        );
+
+        // and a block on top makes it tall
+        scenario.set([1, 2, 1], BlockState::STONE);
+        let wall = scenario.get([1, 1, 1]);
+        assert_eq!(Some(PropValue::Tall), wall.get(PropName::North));
+        assert_eq!(Some(PropValue::False), wall.get(PropName::Up));
+    }
+
+    #[test]
+    fn test_redstone_wire_connects() {
+        let mut scenario = UpdateScenario::new();
+        scenario.set([0, 0, 0], BlockState::STONE);
+        scenario.set([1, 0, 0], BlockState::STONE);
+        let cross = BlockState::REDSTONE_WIRE
+            .set(PropName::North, PropValue::Side)
+            .set(PropName::East, PropValue::Side)
+            .set(PropName::South, PropValue::Side)
+            .set(PropName::West, PropValue::Side);
+        scenario.set([0, 1, 0], cross);
+        assert_eq!(cross, scenario.get([0, 1, 0]));
+
+        // two wires next to each other form a line
+        scenario.set([1, 1, 0], cross);
+        let wire = scenario.get([0, 1, 0]);
+        assert_eq!(Some(PropValue::Side), wire.get(PropName::East));
+        assert_eq!(Some(PropValue::Side), wire.get(PropName::West));
+        assert_eq!(Some(PropValue::None), wire.get(PropName::North));
+        assert_eq!(Some(PropValue::None), wire.get(PropName::South));
+
+        // a dot stays a dot
+        scenario.set([3, 0, 3], BlockState::STONE);
+        scenario.set([3, 1, 3], BlockState::REDSTONE_WIRE);
+        assert_eq!(BlockState::REDSTONE_WIRE, scenario.get([3, 1, 3]));
+    }
+
+    #[test]
+    fn test_stairs_form_corners() {
+        let mut scenario = UpdateScenario::new();
+        let stairs = BlockState::OAK_STAIRS.set(PropName::Half, PropValue::Bottom);
+        scenario.set([1, 1, 1], stairs.set(PropName::Facing, PropValue::North));
+        scenario.set([1, 1, 0], stairs.set(PropName::Facing, PropValue::East));
+
+        // the stairs in front of the first ones turn them into an outer corner
+        assert_eq!(
+            Some(PropValue::OuterRight),
+            scenario.get([1, 1, 1]).get(PropName::Shape)
+        );
+    }
+
+    #[test]
+    fn test_unsupported_blocks_break() {
+        let mut scenario = UpdateScenario::new();
+        scenario.set([1, 0, 1], BlockState::GRASS_BLOCK);
+        scenario.set([1, 1, 1], BlockState::POPPY);
+        scenario.set([2, 0, 1], BlockState::STONE);
+        scenario.set([2, 1, 1], BlockState::TORCH);
+        assert_eq!(BlockState::POPPY, scenario.get([1, 1, 1]));
+
+        scenario.set([1, 0, 1], BlockState::AIR);
+        assert_eq!(BlockState::AIR, scenario.get([1, 1, 1]));
+        scenario.set([2, 0, 1], BlockState::AIR);
+        assert_eq!(BlockState::AIR, scenario.get([2, 1, 1]));
+        assert_eq!(2, scenario.dropped_items());
+    }
+
+    #[test]
+    fn test_updates_carry_over() {
+        let mut scenario = UpdateScenario::new();
+        // torches that don't touch each other, so every torch breaks on its own update
+        let torches = (0..66)
+            .flat_map(|y| (0..8).flat_map(move |z| (0..8).map(move |x| (x, y, z))))
+            .map(|(x, y, z)| (BlockPos::new(x * 2, y * 2 + 1, z * 2), BlockState::TORCH))
+            .collect::<Vec<_>>();
+        assert!(torches.len() > MAX_UPDATES_PER_TICK);
+
+        scenario.set_all(&torches);
+        let remaining = torches
+            .iter()
+            .filter(|(position, _)| scenario.get(*position) == BlockState::TORCH)
+            .count();
+        assert_eq!(torches.len() - MAX_UPDATES_PER_TICK, remaining);
+
+        scenario.scenario.app.update();
+        for (position, _) in torches {
+            assert_eq!(BlockState::AIR, scenario.get(position));
+        }
+    }
+
+    #[test]
+    fn test_door_halves_break_together() {
+        let mut scenario = UpdateScenario::new();
+        scenario.set([1, 0, 1], BlockState::STONE);
+        let door = BlockState::OAK_DOOR;
+        scenario.set_all(&[
+            (
+                BlockPos::new(1, 1, 1),
+                door.set(PropName::Half, PropValue::Lower),
+            ),
+            (
+                BlockPos::new(1, 2, 1),
+                door.set(PropName::Half, PropValue::Upper),
+            ),
+        ]);
+        assert_eq!(
+            Some(PropValue::Upper),
+            scenario.get([1, 2, 1]).get(PropName::Half)
+        );
+
+        scenario.set([1, 1, 1], BlockState::AIR);
+        assert_eq!(BlockState::AIR, scenario.get([1, 2, 1]));
+        // the upper half doesn't drop anything
+        assert_eq!(0, scenario.dropped_items());
+    }
+
+    #[test]
+    fn test_powered_doors_open() {
+        let mut scenario = UpdateScenario::new();
+        scenario.set([1, 0, 1], BlockState::STONE);
+        let door = BlockState::IRON_DOOR;
+        scenario.set_all(&[
+            (
+                BlockPos::new(1, 1, 1),
+                door.set(PropName::Half, PropValue::Lower),
+            ),
+            (
+                BlockPos::new(1, 2, 1),
+                door.set(PropName::Half, PropValue::Upper),
+            ),
+        ]);
+        assert_eq!(
+            Some(PropValue::False),
+            scenario.get([1, 2, 1]).get(PropName::Open)
+        );
+
+        // powering the lower half opens both halves
+        scenario.set([2, 1, 1], BlockState::REDSTONE_BLOCK);
+        assert_eq!(
+            Some(PropValue::True),
+            scenario.get([1, 1, 1]).get(PropName::Open)
+        );
+        assert_eq!(
+            Some(PropValue::True),
+            scenario.get([1, 2, 1]).get(PropName::Open)
+        );
+
+        scenario.set([2, 1, 1], BlockState::AIR);
+        assert_eq!(
+            Some(PropValue::False),
+            scenario.get([1, 1, 1]).get(PropName::Open)
+        );
+        assert_eq!(
+            Some(PropValue::False),
+            scenario.get([1, 2, 1]).get(PropName::Open)
+        );
+    }
+}
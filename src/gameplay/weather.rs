@@ -0,0 +1,354 @@
+use crate::{argument, source_world, ArgumentKind, CommandEvent, CommandFeedback, CommandSpec};
+use crate::{DamageCause, DamageEvent, Dead, Random, WorldInfo, WorldRegistry};
+use bevy_ecs::prelude::*;
+use std::ops::Range;
+use valence::client::VisibleChunkLayer;
+use valence::entity::lightning::LightningEntityBundle;
+use valence::entity::EntityLayerId;
+use valence::prelude::*;
+use valence::protocol::packets::play::game_state_change_s2c::GameEventKind;
+use valence::protocol::packets::play::GameStateChangeS2c;
+use valence::protocol::WritePacket;
+
+/// How long each kind of weather lasts when it starts by itself, in ticks.
+const CLEAR_DURATION: Range<i32> = 12000..180000;
+const RAIN_DURATION: Range<i32> = 12000..24000;
+const THUNDER_DURATION: Range<i32> = 3600..15600;
+/// The chance that bad weather comes with thunder.
+const THUNDER_CHANCE: f64 = 0.25;
+/// The chance per tick and player that lightning strikes near the player during
+/// a thunderstorm.
+const LIGHTNING_CHANCE: f64 = 1.0 / 2000.0;
+/// How far from a player lightning may strike, in blocks.
+const LIGHTNING_RANGE: i32 = 32;
+/// Players this close to a strike are hit.
+const LIGHTNING_HIT_RANGE: f64 = 3.0;
+const LIGHTNING_DAMAGE: f32 = 5.0;
+/// Ticks until a lightning bolt entity is removed again.
+const LIGHTNING_TICKS: i64 = 20;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WeatherState {
+    Clear,
+    Rain,
+    Thunder,
+}
+
+impl WeatherState {
+    fn durations(self) -> Range<i32> {
+        match self {
+            WeatherState::Clear => CLEAR_DURATION,
+            WeatherState::Rain => RAIN_DURATION,
+            WeatherState::Thunder => THUNDER_DURATION,
+        }
+    }
+}
+
+/// The weather of a world, lives on the world's layer entity.
+#[derive(Component, Debug, Clone)]
+pub struct Weather {
+    state: WeatherState,
+    /// Ticks until the weather changes by itself.
+    remaining: i64,
+    random: Random,
+}
+
+impl Weather {
+    /// Clear weather that lasts for a random time.
+    pub fn new(seed: u64) -> Self {
+        let mut random = Random::new(seed);
+        let remaining = random.range(CLEAR_DURATION) as i64;
+        Self {
+            state: WeatherState::Clear,
+            remaining,
+            random,
+        }
+    }
+
+    pub fn state(&self) -> WeatherState {
+        self.state
+    }
+
+    pub fn is_raining(&self) -> bool {
+        self.state != WeatherState::Clear
+    }
+
+    pub fn is_thundering(&self) -> bool {
+        self.state == WeatherState::Thunder
+    }
+
+    /// Ticks until the weather changes by itself.
+    pub fn remaining(&self) -> i64 {
+        self.remaining
+    }
+
+    /// Changes the weather for the given number of ticks, or for a random time.
+    pub fn set(&mut self, state: WeatherState, duration: Option<i64>) {
+        self.state = state;
+        self.remaining = duration.unwrap_or_else(|| self.random.range(state.durations()) as i64);
+    }
+}
+
+/// A lightning bolt, lives on the bolt entity.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct LightningStrike {
+    /// The server tick in which the lightning struck.
+    pub spawned: i64,
+}
+
+/// Counts down the weather of every world that has the weather cycle enabled, and
+/// changes it once its time is up.
+pub fn update_weather(mut worlds: Query<(&WorldInfo, &mut Weather)>) {
+    worlds.for_each_mut(|(info, mut weather)| {
+        if !info.rules.weather_cycle {
+            return;
+        }
+        if weather.remaining > 0 {
+            // counting down isn't a change that clients need to know about
+            weather.bypass_change_detection().remaining -= 1;
+            return;
+        }
+
+        let next = match weather.state {
+            WeatherState::Clear if weather.random.chance(THUNDER_CHANCE) => WeatherState::Thunder,
+            WeatherState::Clear => WeatherState::Rain,
+            WeatherState::Rain | WeatherState::Thunder => WeatherState::Clear,
+        };
+        weather.set(next, None);
+    });
+}
+
+/// Sends the weather of their world to clients whenever it changes, or when they
+/// change worlds.
+pub fn sync_weather(
+    mut clients: Query<(&mut Client, Ref<VisibleChunkLayer>)>,
+    worlds: Query<Ref<Weather>>,
+) {
+    clients.for_each_mut(|(mut client, layer)| {
+        let Ok(weather) = worlds.get(layer.0) else {
+            return;
+        };
+        if !layer.is_changed() && !weather.is_changed() {
+            return;
+        }
+
+        let (kind, rain, thunder) = match weather.state {
+            WeatherState::Clear => (GameEventKind::EndRaining, 0.0, 0.0),
+            WeatherState::Rain => (GameEventKind::BeginRaining, 1.0, 0.0),
+            WeatherState::Thunder => (GameEventKind::BeginRaining, 1.0, 1.0),
+        };
+        for (kind, value) in [
+            (kind, 0.0),
+            (GameEventKind::RainLevelChange, rain),
+            (GameEventKind::ThunderLevelChange, thunder),
+        ] {
+            client.write_packet(&GameStateChangeS2c { kind, value });
+        }
+    });
+}
+
+/// Lets lightning strike near players during thunderstorms, and hurts the players
+/// that it hits.
+pub fn strike_lightning(
+    mut commands: Commands,
+    clients: Query<(Entity, &EntityLayerId, &Position), Without<Dead>>,
+    mut worlds: Query<(Entity, &mut Weather, &ChunkLayer)>,
+    strikes: Query<(Entity, &LightningStrike)>,
+    server: Res<Server>,
+    mut damage: EventWriter<DamageEvent>,
+) {
+    let now = server.current_tick();
+    strikes.for_each(|(entity, strike)| {
+        if now - strike.spawned >= LIGHTNING_TICKS {
+            commands.entity(entity).insert(Despawned);
+        }
+    });
+
+    worlds.for_each_mut(|(world, mut weather, layer)| {
+        if !weather.is_thundering() {
+            return;
+        }
+        for (_, layer_id, position) in clients.iter() {
+            if layer_id.0 != world {
+                continue;
+            }
+            // the random state isn't part of the weather that clients see
+            let random = &mut weather.bypass_change_detection().random;
+            if !random.chance(LIGHTNING_CHANCE) {
+                continue;
+            }
+            let x = position.0.x.floor() as i32 + random.range(-LIGHTNING_RANGE..LIGHTNING_RANGE);
+            let z = position.0.z.floor() as i32 + random.range(-LIGHTNING_RANGE..LIGHTNING_RANGE);
+            let Some(y) = highest_block(layer, x, z) else {
+                continue;
+            };
+            let target = DVec3::new(x as f64 + 0.5, (y + 1) as f64, z as f64 + 0.5);
+
+            commands.spawn((
+                LightningEntityBundle {
+                    layer: EntityLayerId(world),
+                    position: Position(target),
+                    ..Default::default()
+                },
+                LightningStrike { spawned: now },
+            ));
+            for (client, client_layer, client_pos) in clients.iter() {
+                if client_layer.0 == world && client_pos.0.distance(target) <= LIGHTNING_HIT_RANGE {
+                    damage.send(DamageEvent {
+                        client,
+                        amount: LIGHTNING_DAMAGE,
+                        cause: DamageCause::Lightning,
+                    });
+                }
+            }
+        }
+    });
+}
+
+/// The y coordinate of the highest block that isn't air, if the column is loaded.
+fn highest_block(layer: &ChunkLayer, x: i32, z: i32) -> Option<i32> {
+    let top = layer.min_y() + layer.height() as i32 - 1;
+    (layer.min_y()..=top).rev().find(
+        |&y| matches!(layer.block(BlockPos::new(x, y, z)), Some(block) if !block.state.is_air()),
+    )
+}
+
+const WEATHER_STATES: ArgumentKind = ArgumentKind::Choice(&["clear", "rain", "thunder"]);
+
+/// `/weather <clear|rain|thunder> [seconds]`.
+pub fn weather_command() -> CommandSpec {
+    CommandSpec::new("weather", "justmine.command.weather")
+        .usage([argument("weather", WEATHER_STATES)])
+        .usage([
+            argument("weather", WEATHER_STATES),
+            argument(
+                "duration",
+                ArgumentKind::Integer {
+                    min: 1,
+                    max: 1_000_000,
+                },
+            ),
+        ])
+}
+
+/// Handles `/weather`, in the world of the source.
+pub fn handle_weather_command(
+    players: Query<&EntityLayerId>,
+    registry: Option<Res<WorldRegistry>>,
+    mut worlds: Query<&mut Weather>,
+    mut events: EventReader<CommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "weather")
+        .for_each(|event| {
+            let Some(world) = source_world(event.source, &players, registry.as_deref()) else {
+                return;
+            };
+            let Ok(mut weather) = worlds.get_mut(world) else {
+                return;
+            };
+
+            let state = match event.args.choice("weather") {
+                Some("rain") => WeatherState::Rain,
+                Some("thunder") => WeatherState::Thunder,
+                _ => WeatherState::Clear,
+            };
+            let duration = event
+                .args
+                .integer("duration")
+                .map(|seconds| seconds as i64 * 20);
+
+            weather.set(state, duration);
+            feedback.send(CommandFeedback::info(
+                event.source,
+                match state {
+                    WeatherState::Clear => "Set the weather to clear",
+                    WeatherState::Rain => "Set the weather to rain",
+                    WeatherState::Thunder => "Set the weather to rain & thunder",
+                },
+            ));
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::enable_commands;
+    use crate::{dispatch_commands, WorldRules};
+    use valence::message::CommandExecutionEvent;
+    use valence::testing::ScenarioSingleClient;
+
+    fn scenario(weather_cycle: bool) -> ScenarioSingleClient {
+        let mut scenario = ScenarioSingleClient::new();
+        enable_commands(&mut scenario.app, scenario.client);
+        scenario.app.add_systems(
+            Update,
+            (dispatch_commands, handle_weather_command, update_weather).chain(),
+        );
+        scenario.app.world.entity_mut(scenario.layer).insert((
+            WorldInfo {
+                name: "world".to_string(),
+                spawn: DVec3::ZERO,
+                rules: WorldRules {
+                    weather_cycle,
+                    ..Default::default()
+                },
+            },
+            Weather::new(1),
+        ));
+        scenario.app.update();
+        scenario
+    }
+
+    fn weather(scenario: &ScenarioSingleClient) -> &Weather {
+        scenario.app.world.get::<Weather>(scenario.layer).unwrap()
+    }
+
+    fn run_command(scenario: &mut ScenarioSingleClient, command: &str) {
+        let client = scenario.client;
+        scenario.app.world.send_event(CommandExecutionEvent {
+            command: command.into(),
+            client,
+        });
+        scenario.app.update();
+    }
+
+    #[test]
+    fn test_weather_changes_by_itself() {
+        let mut scenario = scenario(true);
+        scenario
+            .app
+            .world
+            .get_mut::<Weather>(scenario.layer)
+            .unwrap()
+            .set(WeatherState::Rain, Some(2));
+
+        scenario.app.update();
+        assert!(weather(&scenario).is_raining());
+        scenario.app.update();
+        scenario.app.update();
+        assert_eq!(WeatherState::Clear, weather(&scenario).state());
+        assert!(CLEAR_DURATION.start as i64 <= weather(&scenario).remaining);
+    }
+
+    #[test]
+    fn test_weather_command() {
+        let mut scenario = scenario(false);
+        run_command(&mut scenario, "weather thunder 10");
+        assert!(weather(&scenario).is_thundering());
+        assert_eq!(200, weather(&scenario).remaining);
+
+        // without the weather cycle, the weather stays as it is
+        for _ in 0..300 {
+            scenario.app.update();
+        }
+        assert!(weather(&scenario).is_thundering());
+
+        run_command(&mut scenario, "weather clear");
+        assert_eq!(WeatherState::Clear, weather(&scenario).state());
+        run_command(&mut scenario, "weather snow");
+        assert_eq!(WeatherState::Clear, weather(&scenario).state());
+    }
+}
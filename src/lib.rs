@@ -1,10 +1,26 @@
+mod access;
+mod chat;
+mod command;
+mod config;
 mod connection;
+mod console;
 mod gameplay;
+mod player_list;
+mod rcon;
 mod setup;
+mod world;
 
 #[cfg(test)]
 pub mod testing;
 
+pub use access::*;
+pub use chat::*;
+pub use command::*;
+pub use config::*;
 pub use connection::*;
+pub use console::*;
 pub use gameplay::*;
+pub use player_list::*;
+pub use rcon::*;
 pub use setup::*;
+pub use world::*;
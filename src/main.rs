@@ -1,24 +1,242 @@
 use bevy_log::{Level, LogPlugin};
-use justmine::{accept_connection, fell_out_of_world, place_block, remove_block, respawn, setup};
+use justmine::{
+    accept_connection, advance_time, apply_damage, attack_players, autosave,
+    broadcast_death_messages, broadcast_joins, change_world, close_block_screens,
+    close_crafting_grids, deliver_feedback, despawn_old_items, disconnect_clients_on_exit,
+    dispatch_commands, drop_block_inventories, drop_block_loot, drop_inventory_on_death,
+    environment_damage, fell_out_of_world, free_beds_of_disconnected, go_to_bed,
+    handle_access_commands, handle_block_commands, handle_chat, handle_chunk_loads,
+    handle_gamemode_command, handle_give_command, handle_kill_command, handle_leaves,
+    handle_msg_command, handle_mute_commands, handle_permission_commands, handle_save_command,
+    handle_shutdown_signal, handle_spawnpoint_command, handle_stop_command, handle_time_command,
+    handle_tp_command, handle_weather_command, handle_world_command, insert_generated_chunks,
+    item_gravity, leave_beds, limit_view_distance, load_chunks_in_view, mark_dirty_chunks,
+    measure_tick_rate, merge_items, open_block_screens, pick_up_experience, pick_up_items,
+    place_block, read_console, read_rcon, release_buttons, remove_block, reply_rcon, respawn,
+    return_held_items_on_leave, save_block_inventories, save_disconnected_players, save_players,
+    save_players_on_exit, save_worlds, save_worlds_on_exit, send_command_tree, setup, skip_night,
+    strike_lightning, sync_experience, sync_health, sync_player_list, sync_time, sync_weather,
+    track_movement, unload_unviewed_chunks, update_hunger, update_neighbors, update_tab_list,
+    update_weather, AccessControl, AccessLists, BlockBreakEvent, BlockChangeEvent,
+    BlockInventories, ChangeWorldEvent, ChatFilters, CommandEvent, CommandFeedback,
+    CommandRegistry, Console, DamageEvent, DeathEvent, ExecuteCommandEvent, InteractionHandlers,
+    LoginCallbacks, Mutes, OpenBlockScreenEvent, PermissionStore, PlacementResolvers, Rcon,
+    SaveWorldEvent, ServerConfig, ShutdownSignal, SleepEvent, TickRate,
+};
+use valence::client::FlushPacketsSet;
+use valence::network::NetworkSettings;
 use valence::prelude::*;
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins.set(LogPlugin {
-            filter: "justmine=trace".to_string(),
-            level: Level::INFO,
-        }))
-        .add_systems(Startup, setup)
-        .add_systems(
-            Update,
+    let config = match ServerConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("unable to start server: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let permissions = match PermissionStore::load(&config.permissions_file) {
+        Ok(permissions) => permissions,
+        Err(e) => {
+            eprintln!("unable to start server: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let mutes = match Mutes::load(&config.chat.mutes_file) {
+        Ok(mutes) => mutes,
+        Err(e) => {
+            eprintln!("unable to start server: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let access = match AccessLists::load(&config) {
+        Ok(lists) => AccessControl::new(lists),
+        Err(e) => {
+            eprintln!("unable to start server: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let rcon = if config.rcon.enabled {
+        match Rcon::bind(config.rcon.bind_address, config.rcon.password.clone()) {
+            Ok(rcon) => Some(rcon),
+            Err(e) => {
+                eprintln!(
+                    "unable to start server: unable to listen for RCON on {}: {}",
+                    config.rcon.bind_address, e
+                );
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut app = App::new();
+    app.insert_resource(NetworkSettings {
+        address: config.bind_address,
+        max_players: config.max_players,
+        connection_mode: config.connection_mode(),
+        callbacks: LoginCallbacks::new(&config, access.clone()).into(),
+        ..Default::default()
+    })
+    .add_plugins(DefaultPlugins.set(LogPlugin {
+        filter: format!("justmine={}", config.log_level),
+        level: config.log_level.min(Level::INFO),
+    }))
+    .insert_resource(ChatFilters::from_config(&config.chat))
+    .insert_resource(config)
+    .insert_resource(permissions)
+    .insert_resource(access)
+    .insert_resource(mutes)
+    .insert_resource(Console::spawn())
+    .insert_resource(ShutdownSignal::install())
+    .init_resource::<PlacementResolvers>()
+    .init_resource::<InteractionHandlers>()
+    .init_resource::<BlockInventories>()
+    .init_resource::<CommandRegistry>()
+    .init_resource::<TickRate>()
+    .add_event::<BlockChangeEvent>()
+    .add_event::<BlockBreakEvent>()
+    .add_event::<OpenBlockScreenEvent>()
+    .add_event::<SleepEvent>()
+    .add_event::<DamageEvent>()
+    .add_event::<DeathEvent>()
+    .add_event::<SaveWorldEvent>()
+    .add_event::<ChangeWorldEvent>()
+    .add_event::<ExecuteCommandEvent>()
+    .add_event::<CommandEvent>()
+    .add_event::<CommandFeedback>()
+    .add_systems(Startup, setup)
+    .add_systems(
+        Update,
+        (
+            accept_connection,
+            limit_view_distance,
+            (remove_block, (drop_block_loot, drop_block_inventories)).chain(),
             (
-                accept_connection,
-                fell_out_of_world,
-                remove_block,
+                close_block_screens.after(drop_block_inventories),
+                close_crafting_grids,
                 place_block,
-                respawn,
-                despawn_disconnected_clients,
+                (open_block_screens, release_buttons, go_to_bed),
+                save_block_inventories,
+            )
+                .chain(),
+            update_neighbors.after(remove_block).after(release_buttons),
+            respawn,
+            send_command_tree,
+            (handle_world_command, change_world)
+                .chain()
+                .after(dispatch_commands),
+            (return_held_items_on_leave, save_disconnected_players)
+                .chain()
+                .before(despawn_disconnected_clients),
+            free_beds_of_disconnected.before(despawn_disconnected_clients),
+            handle_leaves.before(despawn_disconnected_clients),
+            despawn_disconnected_clients,
+            (broadcast_joins, sync_player_list, update_tab_list).after(accept_connection),
+            measure_tick_rate,
+            handle_chat,
+            handle_shutdown_signal,
+        ),
+    )
+    .add_systems(
+        Update,
+        (
+            read_console,
+            read_rcon.run_if(resource_exists::<Rcon>()),
+            dispatch_commands,
+            (
+                handle_gamemode_command,
+                handle_tp_command,
+                handle_give_command,
+                handle_block_commands,
+                handle_spawnpoint_command,
+                handle_stop_command,
+                handle_permission_commands,
+                handle_access_commands,
+                handle_msg_command,
+                handle_mute_commands,
+            ),
+        )
+            .chain(),
+    )
+    .add_systems(
+        Update,
+        (
+            unload_unviewed_chunks,
+            load_chunks_in_view,
+            handle_chunk_loads,
+            insert_generated_chunks,
+        )
+            .chain(),
+    )
+    .add_systems(
+        Update,
+        (
+            handle_kill_command.after(dispatch_commands),
+            attack_players,
+            strike_lightning,
+            fell_out_of_world,
+            track_movement,
+            environment_damage,
+            update_hunger,
+            apply_damage,
+            (
+                sync_health,
+                drop_inventory_on_death,
+                broadcast_death_messages,
             ),
         )
-        .run();
+            .chain(),
+    )
+    .add_systems(
+        Update,
+        (item_gravity, merge_items, pick_up_items, despawn_old_items).chain(),
+    )
+    .add_systems(Update, (pick_up_experience, sync_experience).chain())
+    .add_systems(
+        Update,
+        (handle_weather_command, update_weather, sync_weather)
+            .chain()
+            .after(dispatch_commands),
+    )
+    .add_systems(
+        Update,
+        (
+            advance_time,
+            leave_beds,
+            skip_night,
+            handle_time_command,
+            sync_time,
+        )
+            .chain()
+            .after(go_to_bed)
+            .after(dispatch_commands),
+    )
+    .add_systems(
+        PostUpdate,
+        (
+            mark_dirty_chunks,
+            autosave,
+            handle_save_command,
+            (save_worlds, save_players),
+        )
+            .chain(),
+    )
+    .add_systems(PostUpdate, deliver_feedback.after(handle_save_command))
+    .add_systems(
+        PostUpdate,
+        reply_rcon
+            .run_if(resource_exists::<Rcon>())
+            .after(handle_save_command),
+    )
+    .add_systems(
+        PostUpdate,
+        disconnect_clients_on_exit.before(FlushPacketsSet),
+    )
+    .add_systems(Last, (save_worlds_on_exit, save_players_on_exit));
+    if let Some(rcon) = rcon {
+        app.insert_resource(rcon);
+    }
+    app.run();
 }
@@ -0,0 +1,243 @@
+use crate::ServerConfig;
+use bevy_ecs::prelude::*;
+use log::info;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use valence::keepalive::Ping;
+use valence::message::SendMessage;
+use valence::prelude::*;
+use valence::protocol::packets::play::PlayerListHeaderS2c;
+use valence::protocol::WritePacket;
+
+/// How often the header and footer of the player list are refreshed, in ticks.
+const TAB_LIST_INTERVAL: i64 = 20;
+/// How many of the latest ticks the TPS are averaged over.
+const TPS_WINDOW: usize = 100;
+
+/// The entity of the player list entry of a client. The entry is despawned when
+/// the client disconnects.
+#[derive(Component, Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PlayerListEntity(pub Entity);
+
+/// Measures how many ticks per second the server actually runs at.
+#[derive(Resource, Debug, Default)]
+pub struct TickRate {
+    ticks: VecDeque<Instant>,
+}
+
+impl TickRate {
+    pub fn tick(&mut self, now: Instant) {
+        if self.ticks.len() == TPS_WINDOW {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back(now);
+    }
+
+    /// The average ticks per second over the latest ticks, at most `max`, which is
+    /// also assumed until enough ticks were measured.
+    pub fn tps(&self, max: f64) -> f64 {
+        let (Some(first), Some(last)) = (self.ticks.front(), self.ticks.back()) else {
+            return max;
+        };
+        let elapsed = last.duration_since(*first);
+        if elapsed < Duration::from_secs(1) {
+            return max;
+        }
+        ((self.ticks.len() - 1) as f64 / elapsed.as_secs_f64()).min(max)
+    }
+}
+
+/// The values that the placeholders of the player list header and footer stand for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TabListValues<'a> {
+    pub player: &'a str,
+    pub online: usize,
+    pub max_players: usize,
+    pub tps: f64,
+    pub ping: i32,
+}
+
+/// Replaces `{player}`, `{online}`, `{max_players}`, `{tps}` and `{ping}` in a
+/// header or footer template.
+pub fn fill_placeholders(template: &str, values: &TabListValues) -> String {
+    template
+        .replace("{player}", values.player)
+        .replace("{online}", &values.online.to_string())
+        .replace("{max_players}", &values.max_players.to_string())
+        .replace("{tps}", &format!("{:.1}", values.tps))
+        .replace("{ping}", &values.ping.to_string())
+}
+
+pub fn measure_tick_rate(mut tick_rate: ResMut<TickRate>) {
+    tick_rate.tick(Instant::now());
+}
+
+/// Tells everyone who joined.
+pub fn broadcast_joins(mut clients: Query<(&mut Client, &Username)>) {
+    let joined = clients
+        .iter_mut()
+        .filter(|(client, _)| client.is_added())
+        .map(|(_, username)| username.0.clone())
+        .collect::<Vec<_>>();
+
+    for name in joined {
+        info!("{} joined the game", name);
+        clients.for_each_mut(|(mut client, _)| {
+            client.send_chat_message(format!("{} joined the game", name).color(Color::YELLOW));
+        });
+    }
+}
+
+/// Tells everyone who left, and removes them from the player list. Must run before
+/// `despawn_disconnected_clients`, which despawns the entities of the players.
+pub fn handle_leaves(
+    mut commands: Commands,
+    mut disconnected: RemovedComponents<Client>,
+    kicked: Query<Entity, (With<Client>, Added<Despawned>)>,
+    players: Query<(&Username, Option<&PlayerListEntity>)>,
+    mut clients: Query<&mut Client, Without<Despawned>>,
+) {
+    let left = disconnected.iter().chain(kicked.iter()).collect::<Vec<_>>();
+    for entity in left {
+        let Ok((username, entry)) = players.get(entity) else {
+            continue;
+        };
+        if let Some(entry) = entry {
+            if let Some(mut entry) = commands.get_entity(entry.0) {
+                entry.insert(Despawned);
+            }
+        }
+
+        info!("{} left the game", username.0);
+        clients.for_each_mut(|mut client| {
+            client.send_chat_message(format!("{} left the game", username.0).color(Color::YELLOW));
+        });
+    }
+}
+
+/// Keeps the game mode and the latency in the player list entries up to date.
+pub fn sync_player_list(
+    clients: Query<
+        (&GameMode, &Ping, &PlayerListEntity),
+        (With<Client>, Or<(Changed<GameMode>, Changed<Ping>)>),
+    >,
+    mut entries: Query<(&mut GameMode, &mut Ping), Without<Client>>,
+) {
+    clients.for_each(|(game_mode, ping, entry)| {
+        let Ok((mut entry_game_mode, mut entry_ping)) = entries.get_mut(entry.0) else {
+            return;
+        };
+        if *entry_game_mode != *game_mode {
+            *entry_game_mode = *game_mode;
+        }
+        if *entry_ping != *ping {
+            *entry_ping = ping.clone();
+        }
+    });
+}
+
+/// Sends the configured header and footer of the player list to every client.
+pub fn update_tab_list(
+    config: Res<ServerConfig>,
+    server: Res<Server>,
+    tick_rate: Res<TickRate>,
+    mut clients: Query<(&mut Client, &Username, &Ping)>,
+) {
+    if config.tab_header.is_empty() && config.tab_footer.is_empty() {
+        return;
+    }
+    if server.current_tick() % TAB_LIST_INTERVAL != 0 {
+        return;
+    }
+
+    let online = clients.iter().count();
+    let tps = tick_rate.tps(server.tick_rate().get() as f64);
+    clients.for_each_mut(|(mut client, username, ping)| {
+        let values = TabListValues {
+            player: &username.0,
+            online,
+            max_players: config.max_players,
+            tps,
+            ping: ping.0,
+        };
+        client.write_packet(&PlayerListHeaderS2c {
+            header: Cow::Owned(Text::from(fill_placeholders(&config.tab_header, &values))),
+            footer: Cow::Owned(Text::from(fill_placeholders(&config.tab_footer, &values))),
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::player_list::PlayerListEntryBundle;
+    use valence::testing::ScenarioSingleClient;
+
+    #[test]
+    fn test_fill_placeholders() {
+        let values = TabListValues {
+            player: "Notch",
+            online: 3,
+            max_players: 20,
+            tps: 19.96,
+            ping: 42,
+        };
+        assert_eq!(
+            "Hello Notch\n3/20 online, 20.0 TPS, 42 ms",
+            fill_placeholders(
+                "Hello {player}\n{online}/{max_players} online, {tps} TPS, {ping} ms",
+                &values
+            )
+        );
+    }
+
+    #[test]
+    fn test_tick_rate() {
+        let start = Instant::now();
+        let mut tick_rate = TickRate::default();
+        assert_eq!(20.0, tick_rate.tps(20.0));
+
+        // 10 ticks per second
+        for i in 0..TPS_WINDOW as u64 + 10 {
+            tick_rate.tick(start + Duration::from_millis(i * 100));
+        }
+        assert!((tick_rate.tps(20.0) - 10.0).abs() < 0.001);
+        assert_eq!(5.0, tick_rate.tps(5.0));
+    }
+
+    #[test]
+    fn test_player_list_entry_lifecycle() {
+        let mut scenario = ScenarioSingleClient::new();
+        scenario
+            .app
+            .add_systems(Update, (handle_leaves, sync_player_list));
+        let client = scenario.client;
+        let entry = scenario
+            .app
+            .world
+            .spawn(PlayerListEntryBundle::default())
+            .id();
+        scenario
+            .app
+            .world
+            .entity_mut(client)
+            .insert(PlayerListEntity(entry));
+        scenario.app.update();
+
+        *scenario.app.world.get_mut::<GameMode>(client).unwrap() = GameMode::Spectator;
+        scenario.app.update();
+        assert_eq!(
+            Some(&GameMode::Spectator),
+            scenario.app.world.get::<GameMode>(entry)
+        );
+
+        scenario.app.world.entity_mut(client).remove::<Client>();
+        scenario.app.update();
+        assert!(scenario
+            .app
+            .world
+            .get_entity(entry)
+            .map_or(true, |entry| entry.contains::<Despawned>()));
+    }
+}
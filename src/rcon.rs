@@ -0,0 +1,377 @@
+use crate::{CommandFeedback, CommandSource, ExecuteCommandEvent};
+use bevy_ecs::prelude::*;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// the packet types of the Source RCON protocol, a command and the response to a
+// login share the same number
+pub const RCON_RESPONSE_VALUE: i32 = 0;
+pub const RCON_EXEC_COMMAND: i32 = 2;
+pub const RCON_AUTH_RESPONSE: i32 = 2;
+pub const RCON_AUTH: i32 = 3;
+
+/// The longest body of a response packet. Longer output is split into several
+/// packets with the same id.
+const MAX_RESPONSE_LEN: usize = 4096;
+/// The longest packet that a client may send, excluding the length itself.
+const MAX_REQUEST_LEN: usize = 1446 + 10;
+/// How many clients may be connected at the same time. More connections are
+/// closed right away.
+pub const MAX_RCON_CONNECTIONS: usize = 16;
+/// How long a client may take to log in after it connected.
+pub const RCON_AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A packet of the Source RCON protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconPacket {
+    pub id: i32,
+    pub kind: i32,
+    pub body: String,
+}
+
+impl RconPacket {
+    pub fn new(id: i32, kind: i32, body: impl Into<String>) -> Self {
+        Self {
+            id,
+            kind,
+            body: body.into(),
+        }
+    }
+
+    /// Reads a packet: its length, id and type as little endian integers, then the
+    /// body and two null bytes.
+    pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut int = [0; 4];
+        reader.read_exact(&mut int)?;
+        let len = i32::from_le_bytes(int);
+        if !(10..=MAX_REQUEST_LEN as i32).contains(&len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid packet length {}", len),
+            ));
+        }
+
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        let id = i32::from_le_bytes(data[0..4].try_into().unwrap());
+        let kind = i32::from_le_bytes(data[4..8].try_into().unwrap());
+        let body = &data[8..data.len() - 2];
+        let body = body.split(|b| *b == 0).next().unwrap_or_default();
+        Ok(Self {
+            id,
+            kind,
+            body: String::from_utf8_lossy(body).into_owned(),
+        })
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        let mut data = Vec::with_capacity(self.body.len() + 14);
+        data.extend_from_slice(&(self.body.len() as i32 + 10).to_le_bytes());
+        data.extend_from_slice(&self.id.to_le_bytes());
+        data.extend_from_slice(&self.kind.to_le_bytes());
+        data.extend_from_slice(self.body.as_bytes());
+        data.extend_from_slice(&[0, 0]);
+        writer.write_all(&data)?;
+        writer.flush()
+    }
+}
+
+/// Splits the output of a command into the bodies of its response packets. Empty
+/// output is still one empty packet.
+pub fn split_response(output: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut rest = output;
+    loop {
+        if rest.len() <= MAX_RESPONSE_LEN {
+            parts.push(rest);
+            return parts;
+        }
+        let mut end = MAX_RESPONSE_LEN;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (part, tail) = rest.split_at(end);
+        parts.push(part);
+        rest = tail;
+    }
+}
+
+/// A command from an RCON client, with the channel that its output goes back on.
+#[derive(Debug)]
+pub struct RconRequest {
+    pub command: String,
+    pub reply: Sender<String>,
+}
+
+/// The output of a command that runs for an RCON client.
+#[derive(Debug)]
+struct PendingReply {
+    reply: Sender<String>,
+    output: Vec<String>,
+}
+
+/// The remote console. Clients connect on a separate thread, and their commands run
+/// like the ones from the console.
+#[derive(Resource, Debug)]
+pub struct Rcon {
+    address: SocketAddr,
+    requests: Mutex<Receiver<RconRequest>>,
+    pending: HashMap<u32, PendingReply>,
+    next_id: u32,
+}
+
+impl Rcon {
+    /// Listens for RCON clients on the given address, which must authenticate with
+    /// the password before they may run commands.
+    pub fn bind(address: SocketAddr, password: impl Into<String>) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let password = Arc::new(password.into());
+        let (sender, receiver) = mpsc::channel();
+        let connections = Arc::new(AtomicUsize::new(0));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("unable to accept RCON connection: {}", e);
+                        continue;
+                    }
+                };
+                let accepted = Instant::now();
+                let peer = stream.peer_addr().ok();
+                if connections.fetch_add(1, Ordering::SeqCst) >= MAX_RCON_CONNECTIONS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    warn!("refused RCON connection from {:?}: too many clients", peer);
+                    continue;
+                }
+                let password = password.clone();
+                let sender = sender.clone();
+                let connections = connections.clone();
+                thread::spawn(move || {
+                    if let Err(e) = serve_client(stream, accepted, &password, &sender) {
+                        debug!("RCON connection from {:?} closed: {}", peer, e);
+                    }
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+        info!("RCON listening on {}", address);
+        Ok(Self::new(address, receiver))
+    }
+
+    pub fn new(address: SocketAddr, requests: Receiver<RconRequest>) -> Self {
+        Self {
+            address,
+            requests: Mutex::new(requests),
+            pending: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// The address that the listener is bound to, with the actual port if it was
+    /// bound to port `0`.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.address
+    }
+}
+
+/// A stream that fails reads after a deadline, however many bytes arrive before.
+/// A read timeout alone restarts with every partial read.
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Option<Instant>,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "no login within the deadline",
+                ));
+            }
+            self.stream.set_read_timeout(Some(remaining))?;
+        }
+        self.stream.read(buf)
+    }
+}
+
+/// Answers the packets of one client until it disconnects. A wrong password, any
+/// other packet before the login, or no login within [`RCON_AUTH_TIMEOUT`] after
+/// the connection was accepted closes the connection.
+fn serve_client(
+    stream: TcpStream,
+    accepted: Instant,
+    password: &str,
+    requests: &Sender<RconRequest>,
+) -> io::Result<()> {
+    let peer = stream.peer_addr()?;
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: stream.try_clone()?,
+        deadline: Some(accepted + RCON_AUTH_TIMEOUT),
+    });
+    let mut writer = stream;
+    let mut authenticated = false;
+    loop {
+        let packet = match RconPacket::read(&mut reader) {
+            Ok(packet) => packet,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        match packet.kind {
+            RCON_AUTH => {
+                if packet.body != password {
+                    warn!("RCON client {} used a wrong password", peer);
+                    return RconPacket::new(-1, RCON_AUTH_RESPONSE, "").write(&mut writer);
+                }
+                authenticated = true;
+                // admins may keep the console open without sending anything
+                reader.get_mut().deadline = None;
+                reader.get_ref().stream.set_read_timeout(None)?;
+                RconPacket::new(packet.id, RCON_AUTH_RESPONSE, "").write(&mut writer)?;
+            }
+            RCON_EXEC_COMMAND if authenticated => {
+                let (reply, output) = mpsc::channel();
+                let request = RconRequest {
+                    command: packet.body,
+                    reply,
+                };
+                // both fail only when the server shuts down
+                if requests.send(request).is_err() {
+                    return Ok(());
+                }
+                let Ok(output) = output.recv() else {
+                    return Ok(());
+                };
+                for part in split_response(&output) {
+                    RconPacket::new(packet.id, RCON_RESPONSE_VALUE, part).write(&mut writer)?;
+                }
+            }
+            _ if !authenticated => {
+                return RconPacket::new(-1, RCON_AUTH_RESPONSE, "").write(&mut writer);
+            }
+            kind => {
+                let message = format!("Unknown request {:x}", kind);
+                RconPacket::new(packet.id, RCON_RESPONSE_VALUE, message).write(&mut writer)?;
+            }
+        }
+    }
+}
+
+/// Runs the commands of RCON clients. A leading slash is optional.
+pub fn read_rcon(mut rcon: ResMut<Rcon>, mut commands: EventWriter<ExecuteCommandEvent>) {
+    let Rcon {
+        requests,
+        pending,
+        next_id,
+        ..
+    } = &mut *rcon;
+    let requests = requests.lock().unwrap_or_else(|e| e.into_inner());
+    for request in requests.try_iter() {
+        *next_id = next_id.wrapping_add(1);
+        let command = request.command.trim();
+        let command = command.strip_prefix('/').unwrap_or(command);
+        if !command.is_empty() {
+            info!("RCON issued server command: /{}", command);
+            commands.send(ExecuteCommandEvent {
+                source: CommandSource::Rcon(*next_id),
+                command: command.to_string(),
+            });
+        }
+        pending.insert(
+            *next_id,
+            PendingReply {
+                reply: request.reply,
+                output: vec![],
+            },
+        );
+    }
+}
+
+/// Sends the feedback of the commands that ran for RCON clients back to them. Must
+/// run after all feedback of the tick was sent, a command without feedback gets
+/// an empty response.
+pub fn reply_rcon(mut rcon: ResMut<Rcon>, mut feedback: EventReader<CommandFeedback>) {
+    for event in feedback.iter() {
+        let CommandSource::Rcon(id) = event.source else {
+            continue;
+        };
+        if let Some(pending) = rcon.pending.get_mut(&id) {
+            pending.output.push(event.message.clone());
+        }
+    }
+
+    for (_, pending) in rcon.pending.drain() {
+        // the client may have disconnected in the meantime
+        let _ = pending.reply.send(pending.output.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_app::{App, Update};
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let packet = RconPacket::new(7, RCON_EXEC_COMMAND, "time set day");
+        let mut data = vec![];
+        packet.write(&mut data).unwrap();
+        assert_eq!(4 + 10 + 12, data.len());
+        assert_eq!(22, i32::from_le_bytes(data[0..4].try_into().unwrap()));
+        assert_eq!(packet, RconPacket::read(&mut data.as_slice()).unwrap());
+    }
+
+    #[test]
+    fn test_read_invalid_length() {
+        let data = 4000_i32.to_le_bytes();
+        let error = RconPacket::read(&mut data.as_slice()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn test_split_response() {
+        assert_eq!(vec![""], split_response(""));
+        let output = "ä".repeat(MAX_RESPONSE_LEN);
+        let parts = split_response(&output);
+        assert_eq!(2, parts.len());
+        assert_eq!(MAX_RESPONSE_LEN, parts[0].len());
+        assert_eq!(output, parts.concat());
+    }
+
+    #[test]
+    fn test_read_and_reply() {
+        let (sender, receiver) = mpsc::channel();
+        let mut app = App::new();
+        app.insert_resource(Rcon::new("127.0.0.1:0".parse().unwrap(), receiver))
+            .add_event::<ExecuteCommandEvent>()
+            .add_event::<CommandFeedback>()
+            .add_systems(Update, (read_rcon, reply_rcon).chain());
+
+        let (reply, output) = mpsc::channel();
+        sender
+            .send(RconRequest {
+                command: "/list".to_string(),
+                reply,
+            })
+            .unwrap();
+        app.update();
+
+        let events = app.world.resource::<Events<ExecuteCommandEvent>>();
+        let event = events.iter_current_update_events().next().unwrap();
+        assert_eq!(CommandSource::Rcon(1), event.source);
+        assert_eq!("list", event.command);
+        assert_eq!("", output.try_recv().unwrap());
+    }
+}
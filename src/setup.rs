@@ -1,42 +1,83 @@
+use crate::{
+    ChunkLoader, PlayerStorage, ServerConfig, Weather, WorldInfo, WorldRegistry, WorldStorage,
+    WorldTime,
+};
 use bevy_ecs::change_detection::Res;
 use bevy_ecs::prelude::Commands;
-use log::info;
+use log::{error, info};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 use valence::anvil::AnvilLevel;
-use valence::prelude::{BiomeRegistry, DimensionTypeRegistry};
-use valence::{ident, BlockState, ChunkPos, LayerBundle, Server};
+use valence::prelude::{BiomeRegistry, DVec3, DimensionTypeRegistry};
+use valence::{LayerBundle, Server};
 
 pub fn setup(
     mut commands: Commands,
     server: Res<Server>,
+    config: Res<ServerConfig>,
     biomes: Res<BiomeRegistry>,
     dimensions: Res<DimensionTypeRegistry>,
 ) {
-    let mut overworld_layer = LayerBundle::new(ident!("overworld"), &dimensions, &biomes, &server);
-
-    let mut level = AnvilLevel::new(
-        "/Users/tsatke/Library/Application Support/minecraft/saves/New World",
-        &biomes,
-    );
-
-    for z in -8..8 {
-        for x in -8..8 {
-            let pos = ChunkPos::new(x, z);
-            level.ignored_chunks.insert(pos);
-            level.force_chunk_load(pos);
-        }
-    }
+    let mut registry = WorldRegistry::new(&config.default_world);
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    // the weather doesn't need to be reproducible
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64);
+
+    for (i, (name, world)) in config.worlds.iter().enumerate() {
+        let layer = LayerBundle::new(world.dimension.ident(), &dimensions, &biomes, &server);
+
+        let level = AnvilLevel::new(&world.path, &biomes);
 
-    let start_x = 0;
-    let start_z = 0;
-    for x in -5..5 {
-        for z in -5..5 {
-            overworld_layer
-                .chunk
-                .set_block([start_x + x, 64, start_z + z], BlockState::GRASS_BLOCK);
+        // chunks that don't exist in the world are generated instead
+        let generator = world
+            .generator
+            .build(layer.chunk.height(), layer.chunk.min_y(), &biomes);
+        let spawn = world
+            .spawn
+            .map(DVec3::from_array)
+            .unwrap_or_else(|| DVec3::new(0.5, generator.spawn_height(0, 0) as f64, 0.5));
+        let loader = ChunkLoader::new(generator, workers);
+
+        let mut storage = WorldStorage::new(&world.path);
+        let mut time = WorldTime::default();
+        let mut weather = Weather::new(seed.wrapping_add(i as u64));
+        match storage.load_level() {
+            Ok(Some(data)) => {
+                time = data.time;
+                // like vanilla, a weather without a duration lasts for a random time
+                weather.set(data.weather, Some(data.weather_time).filter(|t| *t > 0));
+            }
+            Ok(None) => {}
+            Err(e) => error!("unable to load the time and weather of {}: {}", name, e),
         }
+
+        let entity = commands
+            .spawn((
+                layer,
+                level,
+                loader,
+                storage,
+                WorldInfo {
+                    name: name.clone(),
+                    spawn,
+                    rules: world.rules.clone(),
+                },
+                time,
+                weather,
+            ))
+            .id();
+        registry.insert(name, entity);
+
+        info!("loading world {} from {}", name, world.path.display());
     }
 
-    commands.spawn((overworld_layer, level));
+    commands.insert_resource(registry);
+    // like vanilla, the players are stored with the default world
+    commands.insert_resource(PlayerStorage::new(
+        config.default_world().path.join("playerdata"),
+    ));
 
     info!("setup complete");
 }
@@ -1,4 +1,6 @@
+use crate::{CommandEvent, CommandFeedback, CommandRegistry, ExecuteCommandEvent, Permissions};
 use test_script::{parse, Assert, Face, Gamemode, Interact, Line, Set};
+use valence::entity::entity::Flags;
 use valence::interact_block::InteractBlockEvent;
 use valence::inventory::HeldItem;
 use valence::prelude::*;
@@ -30,6 +32,16 @@ impl TestableEnvironment for ScenarioSingleClient {
     }
 }
 
+/// Sets up the command resources and events, and lets the client run every command.
+/// Tests add [`dispatch_commands`](crate::dispatch_commands) before their handlers.
+pub fn enable_commands(app: &mut App, client: Entity) {
+    app.init_resource::<CommandRegistry>()
+        .add_event::<ExecuteCommandEvent>()
+        .add_event::<CommandEvent>()
+        .add_event::<CommandFeedback>();
+    app.world.entity_mut(client).insert(Permissions::all());
+}
+
 pub fn eval_script<T>(input: &str)
 where
     T: TestableEnvironment,
@@ -100,6 +112,27 @@ where
             let mut current_held_item = q.get_single_mut(&mut env.app().world).unwrap();
             current_held_item.set_slot(slot);
         }
+        Set::PlayerPosition(x, y, z) => {
+            let client = env.client();
+            let mut position = env.app().world.get_mut::<Position>(client).unwrap();
+            position.set(DVec3::new(x, y, z));
+        }
+        Set::PlayerLook(yaw, pitch) => {
+            let client = env.client();
+            let mut look = env.app().world.get_mut::<Look>(client).unwrap();
+            look.yaw = yaw;
+            look.pitch = pitch;
+        }
+        Set::PlayerSneaking(sneaking) => {
+            let client = env.client();
+            let mut flags = env.app().world.get_mut::<Flags>(client).unwrap();
+            flags.set_sneaking(sneaking);
+        }
+        Set::Block(pos, block) => {
+            let mut q = env.app().world.query::<&mut ChunkLayer>();
+            let mut layer = q.get_single_mut(&mut env.app().world).unwrap();
+            layer.set_block(BlockPos::new(pos.x, pos.y, pos.z), block_state(&block));
+        }
     }
 }
 
@@ -116,8 +149,7 @@ where
                 .map(|b| b.state)
                 .or(Some(BlockState::AIR))
                 .unwrap();
-            let expected_block_state =
-                BlockState::from_kind(BlockKind::from_str(block.id.as_str()).unwrap());
+            let expected_block_state = block_state(&block);
             assert_eq!(
                 actual_block_state, expected_block_state,
                 "block at {:?} is not {:?}, but {:?}",
@@ -166,8 +198,34 @@ where
         hand: Hand::Main,
         position,
         face,
-        cursor_pos: Vec3::new(0.5, 0.5, 0.5), // FIXME: this may not do forever - compute this out of the direction/face
+        cursor_pos: match interact.cursor {
+            Some((x, y, z)) => Vec3::new(x, y, z),
+            // the center of the clicked face
+            None => match face {
+                Direction::Down => Vec3::new(0.5, 0.0, 0.5),
+                Direction::Up => Vec3::new(0.5, 1.0, 0.5),
+                Direction::North => Vec3::new(0.5, 0.5, 0.0),
+                Direction::South => Vec3::new(0.5, 0.5, 1.0),
+                Direction::West => Vec3::new(0.0, 0.5, 0.5),
+                Direction::East => Vec3::new(1.0, 0.5, 0.5),
+            },
+        },
         head_inside_block: false,
         sequence: 0,
     });
 }
+
+fn block_state(block: &test_script::Block) -> BlockState {
+    let kind = BlockKind::from_str(block.id.as_str())
+        .unwrap_or_else(|| panic!("unknown block: {}", block.id));
+    block
+        .properties
+        .iter()
+        .fold(BlockState::from_kind(kind), |state, (name, value)| {
+            state.set(
+                PropName::from_str(name).unwrap_or_else(|| panic!("unknown property: {}", name)),
+                PropValue::from_str(value)
+                    .unwrap_or_else(|| panic!("unknown property value: {}", value)),
+            )
+        })
+}
@@ -0,0 +1,716 @@
+use crate::world::{Noise, Random};
+use std::str::FromStr;
+use std::sync::Arc;
+use valence::biome::BiomeId;
+use valence::prelude::*;
+
+/// Produces chunks that don't exist in a world's storage yet.
+///
+/// Generators are called from worker threads, so they must not depend on any
+/// ECS state. The same generator must always produce the same chunk for the
+/// same position.
+pub trait ChunkGenerator: Send + Sync + 'static {
+    fn generate(&self, pos: ChunkPos) -> UnloadedChunk;
+
+    /// The height of the first free block above the generated terrain in the
+    /// given column, where players can spawn.
+    fn spawn_height(&self, x: i32, z: i32) -> i32;
+}
+
+/// The generator of a world as it is written in the config, either `flat`,
+/// `superflat:<layers>` or `noise:<seed>`.
+///
+/// Superflat layers are listed bottom to top and separated by commas, a layer
+/// may be repeated with a count, e.g. `superflat:bedrock,2*dirt,grass_block`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeneratorSettings {
+    Flat,
+    Superflat(Vec<BlockState>),
+    Noise(u64),
+}
+
+impl GeneratorSettings {
+    pub fn build(
+        &self,
+        height: u32,
+        min_y: i32,
+        biomes: &BiomeRegistry,
+    ) -> Arc<dyn ChunkGenerator> {
+        match self {
+            GeneratorSettings::Flat => Arc::new(FlatGenerator::grass(height, min_y)),
+            GeneratorSettings::Superflat(layers) => {
+                Arc::new(FlatGenerator::new(height, min_y, layers.clone()))
+            }
+            GeneratorSettings::Noise(seed) => Arc::new(NoiseGenerator::new(
+                *seed,
+                height,
+                min_y,
+                BiomePalette::from_registry(biomes),
+            )),
+        }
+    }
+}
+
+impl FromStr for GeneratorSettings {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, args) = s.split_once(':').unwrap_or((s, ""));
+        match kind {
+            "flat" if args.is_empty() => Ok(GeneratorSettings::Flat),
+            "superflat" => parse_layers(args).map(GeneratorSettings::Superflat),
+            "noise" if !args.is_empty() => Ok(GeneratorSettings::Noise(parse_seed(args))),
+            _ => Err(format!(
+                "unknown generator {:?}, expected flat, superflat:<layers> or noise:<seed>",
+                s
+            )),
+        }
+    }
+}
+
+fn parse_layers(s: &str) -> Result<Vec<BlockState>, String> {
+    let mut layers = Vec::new();
+    for layer in s.split(',').map(str::trim) {
+        let (count, name) = match layer.split_once('*') {
+            Some((count, name)) => (
+                count
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid layer count in {:?}", layer))?,
+                name.trim(),
+            ),
+            None => (1, layer),
+        };
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        let kind = BlockKind::from_str(name).ok_or_else(|| format!("unknown block {:?}", name))?;
+        layers.extend(std::iter::repeat(BlockState::from_kind(kind)).take(count));
+    }
+    Ok(layers)
+}
+
+/// Numeric seeds are used as they are, any other text is hashed, so that
+/// `noise:hello` works like in vanilla.
+fn parse_seed(s: &str) -> u64 {
+    s.parse::<i64>()
+        .map(|seed| seed as u64)
+        .unwrap_or_else(|_| {
+            s.bytes().fold(0xcbf29ce484222325, |hash, b| {
+                (hash ^ b as u64).wrapping_mul(0x100000001b3)
+            })
+        })
+}
+
+/// Fills every chunk with the same horizontal layers of blocks, starting at the
+/// bottom of the world like vanilla's superflat worlds.
+pub struct FlatGenerator {
+    height: u32,
+    min_y: i32,
+    layers: Vec<BlockState>,
+}
+
+impl FlatGenerator {
+    pub fn new(height: u32, min_y: i32, layers: Vec<BlockState>) -> Self {
+        Self {
+            height,
+            min_y,
+            layers,
+        }
+    }
+
+    /// The vanilla default superflat layers: bedrock, two dirt and a grass block.
+    pub fn grass(height: u32, min_y: i32) -> Self {
+        Self::new(
+            height,
+            min_y,
+            vec![
+                BlockState::BEDROCK,
+                BlockState::DIRT,
+                BlockState::DIRT,
+                BlockState::GRASS_BLOCK,
+            ],
+        )
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, _: ChunkPos) -> UnloadedChunk {
+        let mut chunk = UnloadedChunk::with_height(self.height);
+        for (y, state) in self.layers.iter().enumerate().take(self.height as usize) {
+            for z in 0..16 {
+                for x in 0..16 {
+                    chunk.set_block_state(x, y as u32, z, *state);
+                }
+            }
+        }
+        chunk
+    }
+
+    fn spawn_height(&self, _: i32, _: i32) -> i32 {
+        self.min_y + self.layers.len().min(self.height as usize) as i32
+    }
+}
+
+/// The biomes that the [`NoiseGenerator`] assigns, resolved from the [`BiomeRegistry`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BiomePalette {
+    pub plains: BiomeId,
+    pub forest: BiomeId,
+    pub desert: BiomeId,
+    pub taiga: BiomeId,
+    pub snowy_plains: BiomeId,
+    pub ocean: BiomeId,
+}
+
+impl BiomePalette {
+    /// Looks up the biomes by name. Biomes that are not registered fall back to the default biome.
+    pub fn from_registry(biomes: &BiomeRegistry) -> Self {
+        let find = |name: &str| {
+            biomes
+                .iter()
+                .find(|(_, ident, _)| ident.as_str() == name)
+                .map(|(id, _, _)| id)
+                .unwrap_or_default()
+        };
+        Self {
+            plains: find("minecraft:plains"),
+            forest: find("minecraft:forest"),
+            desert: find("minecraft:desert"),
+            taiga: find("minecraft:taiga"),
+            snowy_plains: find("minecraft:snowy_plains"),
+            ocean: find("minecraft:ocean"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Biome {
+    Plains,
+    Forest,
+    Desert,
+    Taiga,
+    SnowyPlains,
+    Ocean,
+}
+
+impl Biome {
+    fn is_cold(self) -> bool {
+        matches!(self, Biome::Taiga | Biome::SnowyPlains)
+    }
+
+    /// The average number of trees per chunk.
+    fn tree_density(self) -> f64 {
+        match self {
+            Biome::Forest => 6.0,
+            Biome::Taiga => 4.0,
+            Biome::Plains => 0.3,
+            Biome::SnowyPlains => 0.2,
+            Biome::Desert | Biome::Ocean => 0.0,
+        }
+    }
+}
+
+const SEA_LEVEL: i32 = 62;
+/// Below this height, caves are filled with lava instead of air.
+const LAVA_LEVEL: i32 = -54;
+
+const ORE_SALT: u64 = 0x6f7265;
+const TREE_SALT: u64 = 0x74726565;
+
+struct Ore {
+    stone: BlockState,
+    deepslate: BlockState,
+    attempts: u32,
+    size: u32,
+    heights: std::ops::Range<i32>,
+}
+
+const ORES: [Ore; 7] = [
+    Ore {
+        stone: BlockState::COAL_ORE,
+        deepslate: BlockState::DEEPSLATE_COAL_ORE,
+        attempts: 20,
+        size: 10,
+        heights: 0..128,
+    },
+    Ore {
+        stone: BlockState::COPPER_ORE,
+        deepslate: BlockState::DEEPSLATE_COPPER_ORE,
+        attempts: 8,
+        size: 8,
+        heights: -16..96,
+    },
+    Ore {
+        stone: BlockState::IRON_ORE,
+        deepslate: BlockState::DEEPSLATE_IRON_ORE,
+        attempts: 10,
+        size: 6,
+        heights: -24..72,
+    },
+    Ore {
+        stone: BlockState::GOLD_ORE,
+        deepslate: BlockState::DEEPSLATE_GOLD_ORE,
+        attempts: 4,
+        size: 6,
+        heights: -64..32,
+    },
+    Ore {
+        stone: BlockState::REDSTONE_ORE,
+        deepslate: BlockState::DEEPSLATE_REDSTONE_ORE,
+        attempts: 6,
+        size: 6,
+        heights: -64..16,
+    },
+    Ore {
+        stone: BlockState::LAPIS_ORE,
+        deepslate: BlockState::DEEPSLATE_LAPIS_ORE,
+        attempts: 2,
+        size: 5,
+        heights: -64..32,
+    },
+    Ore {
+        stone: BlockState::DIAMOND_ORE,
+        deepslate: BlockState::DEEPSLATE_DIAMOND_ORE,
+        attempts: 1,
+        size: 5,
+        heights: -64..16,
+    },
+];
+
+/// Generates terrain from a seed: a noise based heightmap with biomes, oceans,
+/// caves, ores and trees.
+pub struct NoiseGenerator {
+    seed: u64,
+    height: u32,
+    min_y: i32,
+    biomes: BiomePalette,
+    continents: Noise,
+    hills: Noise,
+    temperature: Noise,
+    humidity: Noise,
+    tunnels: Noise,
+    caverns: Noise,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: u64, height: u32, min_y: i32, biomes: BiomePalette) -> Self {
+        let noise = Noise::new(seed);
+        Self {
+            seed,
+            height,
+            min_y,
+            biomes,
+            continents: noise.derive(1),
+            hills: noise.derive(2),
+            temperature: noise.derive(3),
+            humidity: noise.derive(4),
+            tunnels: noise.derive(5),
+            caverns: noise.derive(6),
+        }
+    }
+
+    fn max_y(&self) -> i32 {
+        self.min_y + self.height as i32 - 1
+    }
+
+    /// The height of the topmost terrain block of the column at the given world coordinates.
+    fn surface_height(&self, x: i32, z: i32) -> i32 {
+        let (x, z) = (x as f64, z as f64);
+        let continent = self.continents.fractal2(x / 600.0, z / 600.0, 3);
+        let hills = self.hills.fractal2(x / 120.0, z / 120.0, 4);
+        // flat near the coast, hilly further inland
+        let inland = ((continent + 0.2) * 2.0).clamp(0.0, 1.0);
+        let height = SEA_LEVEL as f64 + 2.0 + continent * 30.0 + hills * (6.0 + 22.0 * inland);
+        (height as i32).clamp(self.min_y + 8, self.max_y() - 24)
+    }
+
+    fn biome(&self, x: i32, z: i32, surface: i32) -> Biome {
+        if surface < SEA_LEVEL - 1 {
+            return Biome::Ocean;
+        }
+
+        let temperature = self
+            .temperature
+            .fractal2(x as f64 / 800.0, z as f64 / 800.0, 2);
+        let humidity = self
+            .humidity
+            .fractal2(x as f64 / 600.0, z as f64 / 600.0, 2);
+        match (temperature, humidity) {
+            (t, h) if t > 0.3 && h < 0.0 => Biome::Desert,
+            (t, h) if t < -0.3 && h > 0.0 => Biome::Taiga,
+            (t, _) if t < -0.3 => Biome::SnowyPlains,
+            (_, h) if h > 0.15 => Biome::Forest,
+            _ => Biome::Plains,
+        }
+    }
+
+    fn biome_id(&self, biome: Biome) -> BiomeId {
+        match biome {
+            Biome::Plains => self.biomes.plains,
+            Biome::Forest => self.biomes.forest,
+            Biome::Desert => self.biomes.desert,
+            Biome::Taiga => self.biomes.taiga,
+            Biome::SnowyPlains => self.biomes.snowy_plains,
+            Biome::Ocean => self.biomes.ocean,
+        }
+    }
+
+    fn is_cave(&self, x: i32, y: i32, z: i32) -> bool {
+        let (fx, fy, fz) = (x as f64, y as f64, z as f64);
+        // long winding tunnels along the zero crossings of the noise, and a few larger caverns
+        let tunnel = self.tunnels.sample3(fx / 40.0, fy / 20.0, fz / 40.0).abs() < 0.06;
+        let cavern = self.caverns.sample3(fx / 60.0, fy / 30.0, fz / 60.0) > 0.55;
+        tunnel || cavern
+    }
+
+    fn local_y(&self, y: i32) -> u32 {
+        (y - self.min_y) as u32
+    }
+
+    fn generate_terrain(
+        &self,
+        chunk: &mut UnloadedChunk,
+        pos: ChunkPos,
+        surfaces: &[[i32; 16]; 16],
+        biomes: &[[Biome; 16]; 16],
+    ) {
+        let mut random = Random::for_chunk(self.seed, pos.x, pos.z, 0);
+        for (x, (surface_row, biome_row)) in surfaces.iter().zip(biomes.iter()).enumerate() {
+            for (z, (&surface, &biome)) in surface_row.iter().zip(biome_row.iter()).enumerate() {
+                let (world_x, world_z) = (pos.x * 16 + x as i32, pos.z * 16 + z as i32);
+                let (x, z) = (x as u32, z as u32);
+
+                let (top, filler) = match biome {
+                    Biome::Desert => (BlockState::SAND, BlockState::SANDSTONE),
+                    Biome::Ocean => (BlockState::SAND, BlockState::GRAVEL),
+                    Biome::SnowyPlains | Biome::Taiga => (
+                        BlockState::GRASS_BLOCK.set(PropName::Snowy, PropValue::True),
+                        BlockState::DIRT,
+                    ),
+                    Biome::Plains | Biome::Forest => (BlockState::GRASS_BLOCK, BlockState::DIRT),
+                };
+
+                for y in self.min_y..=surface.max(SEA_LEVEL) {
+                    let state = if y == self.min_y
+                        || (y < self.min_y + 5
+                            && random.chance(0.5 - (y - self.min_y) as f64 * 0.1))
+                    {
+                        BlockState::BEDROCK
+                    } else if y > surface {
+                        if y == SEA_LEVEL && biome.is_cold() {
+                            BlockState::ICE
+                        } else {
+                            BlockState::WATER
+                        }
+                    } else if surface >= SEA_LEVEL - 1
+                        && y > self.min_y + 4
+                        && self.is_cave(world_x, y, world_z)
+                    {
+                        if y <= LAVA_LEVEL {
+                            BlockState::LAVA
+                        } else {
+                            BlockState::AIR
+                        }
+                    } else if y == surface {
+                        top
+                    } else if y > surface - 4 {
+                        filler
+                    } else if y < 0 {
+                        BlockState::DEEPSLATE
+                    } else {
+                        BlockState::STONE
+                    };
+                    chunk.set_block_state(x, self.local_y(y), z, state);
+                }
+
+                if matches!(biome, Biome::SnowyPlains | Biome::Taiga)
+                    && surface >= SEA_LEVEL
+                    && chunk.block_state(x, self.local_y(surface), z) == top
+                {
+                    chunk.set_block_state(x, self.local_y(surface + 1), z, BlockState::SNOW);
+                }
+            }
+        }
+    }
+
+    fn generate_ores(&self, chunk: &mut UnloadedChunk, pos: ChunkPos) {
+        let mut random = Random::for_chunk(self.seed, pos.x, pos.z, ORE_SALT);
+        for ore in &ORES {
+            for _ in 0..ore.attempts {
+                let (mut x, mut z) = (random.range(0..16), random.range(0..16));
+                let mut y = random.range(ore.heights.start.max(self.min_y)..ore.heights.end);
+                for _ in 0..ore.size {
+                    if (0..16).contains(&x) && (0..16).contains(&z) && y > self.min_y {
+                        let (local_x, local_y, local_z) = (x as u32, self.local_y(y), z as u32);
+                        let current = chunk.block_state(local_x, local_y, local_z);
+                        if current == BlockState::STONE {
+                            chunk.set_block_state(local_x, local_y, local_z, ore.stone);
+                        } else if current == BlockState::DEEPSLATE {
+                            chunk.set_block_state(local_x, local_y, local_z, ore.deepslate);
+                        }
+                    }
+                    // veins grow by wandering randomly through the stone
+                    match random.range(0..3) {
+                        0 => x += random.range(-1..2),
+                        1 => y += random.range(-1..2),
+                        _ => z += random.range(-1..2),
+                    }
+                }
+            }
+        }
+    }
+
+    fn generate_trees(
+        &self,
+        chunk: &mut UnloadedChunk,
+        pos: ChunkPos,
+        surfaces: &[[i32; 16]; 16],
+        biome: Biome,
+    ) {
+        let mut random = Random::for_chunk(self.seed, pos.x, pos.z, TREE_SALT);
+        let density = biome.tree_density();
+        let mut count = density.floor() as u32;
+        if random.chance(density.fract()) {
+            count += 1;
+        }
+
+        for _ in 0..count {
+            // keep trees away from the chunk borders, so that they never cross into a neighbor
+            let (x, z) = (random.range(2..14), random.range(2..14));
+            let ground = surfaces[x as usize][z as usize];
+            let (x, z) = (x as u32, z as u32);
+            if ground < SEA_LEVEL || ground + 12 > self.max_y() {
+                continue;
+            }
+            let ground_state = chunk.block_state(x, self.local_y(ground), z);
+            if ground_state.to_kind() != BlockKind::GrassBlock {
+                continue;
+            }
+
+            if biome.is_cold() {
+                self.place_spruce(chunk, &mut random, x, ground, z);
+            } else {
+                self.place_oak(chunk, &mut random, x, ground, z);
+            }
+        }
+    }
+
+    fn place_oak(
+        &self,
+        chunk: &mut UnloadedChunk,
+        random: &mut Random,
+        x: u32,
+        ground: i32,
+        z: u32,
+    ) {
+        let leaves = BlockState::OAK_LEAVES.set(PropName::Persistent, PropValue::True);
+        let trunk = random.range(4..7);
+        let top = ground + trunk;
+
+        for y in top - 2..=top + 1 {
+            let radius: i32 = if y >= top { 1 } else { 2 };
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    let corner = dx.abs() == radius && dz.abs() == radius;
+                    if corner && (y == top + 1 || random.chance(0.5)) {
+                        continue;
+                    }
+                    self.set_if_air(chunk, x as i32 + dx, y, z as i32 + dz, leaves);
+                }
+            }
+        }
+
+        chunk.set_block_state(x, self.local_y(ground), z, BlockState::DIRT);
+        for y in ground + 1..=top {
+            chunk.set_block_state(x, self.local_y(y), z, BlockState::OAK_LOG);
+        }
+    }
+
+    fn place_spruce(
+        &self,
+        chunk: &mut UnloadedChunk,
+        random: &mut Random,
+        x: u32,
+        ground: i32,
+        z: u32,
+    ) {
+        let leaves = BlockState::SPRUCE_LEAVES.set(PropName::Persistent, PropValue::True);
+        let trunk = random.range(6..10);
+        let top = ground + trunk;
+
+        // a cone of leaves, with alternating wide and narrow layers
+        for (i, y) in (ground + 3..=top + 1).rev().enumerate() {
+            let radius = if i == 0 {
+                0
+            } else {
+                (1 + i as i32 / 2).min(2) - (i as i32 % 2)
+            };
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    if radius > 0 && dx.abs() == radius && dz.abs() == radius {
+                        continue;
+                    }
+                    self.set_if_air(chunk, x as i32 + dx, y, z as i32 + dz, leaves);
+                }
+            }
+        }
+
+        chunk.set_block_state(x, self.local_y(ground), z, BlockState::DIRT);
+        for y in ground + 1..=top {
+            chunk.set_block_state(x, self.local_y(y), z, BlockState::SPRUCE_LOG);
+        }
+    }
+
+    fn set_if_air(&self, chunk: &mut UnloadedChunk, x: i32, y: i32, z: i32, state: BlockState) {
+        let (x, y, z) = (x as u32, self.local_y(y), z as u32);
+        if chunk.block_state(x, y, z).is_air() || chunk.block_state(x, y, z) == BlockState::SNOW {
+            chunk.set_block_state(x, y, z, state);
+        }
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(&self, pos: ChunkPos) -> UnloadedChunk {
+        let mut chunk = UnloadedChunk::with_height(self.height);
+
+        let mut surfaces = [[0; 16]; 16];
+        let mut biomes = [[Biome::Plains; 16]; 16];
+        for x in 0..16 {
+            for z in 0..16 {
+                let (world_x, world_z) = (pos.x * 16 + x as i32, pos.z * 16 + z as i32);
+                surfaces[x][z] = self.surface_height(world_x, world_z);
+                biomes[x][z] = self.biome(world_x, world_z, surfaces[x][z]);
+            }
+        }
+
+        // biomes are stored in cells of 4x4x4 blocks, use the biome of the center column
+        for cell_x in 0..4 {
+            for cell_z in 0..4 {
+                let biome = self.biome_id(biomes[cell_x * 4 + 2][cell_z * 4 + 2]);
+                for cell_y in 0..self.height / 4 {
+                    chunk.set_biome(cell_x as u32, cell_y, cell_z as u32, biome);
+                }
+            }
+        }
+
+        self.generate_terrain(&mut chunk, pos, &surfaces, &biomes);
+        self.generate_ores(&mut chunk, pos);
+        self.generate_trees(&mut chunk, pos, &surfaces, biomes[8][8]);
+
+        chunk
+    }
+
+    fn spawn_height(&self, x: i32, z: i32) -> i32 {
+        // on the water instead of the sea floor
+        self.surface_height(x, z).max(SEA_LEVEL) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEIGHT: u32 = 384;
+    const MIN_Y: i32 = -64;
+
+    fn noise_generator(seed: u64) -> NoiseGenerator {
+        NoiseGenerator::new(seed, HEIGHT, MIN_Y, BiomePalette::default())
+    }
+
+    fn blocks(chunk: &UnloadedChunk) -> Vec<BlockState> {
+        let mut blocks = Vec::new();
+        for y in 0..HEIGHT {
+            for z in 0..16 {
+                for x in 0..16 {
+                    blocks.push(chunk.block_state(x, y, z));
+                }
+            }
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        assert_eq!(Ok(GeneratorSettings::Flat), "flat".parse());
+        assert_eq!(Ok(GeneratorSettings::Noise(1234)), "noise:1234".parse());
+        assert_eq!(
+            Ok(GeneratorSettings::Noise(-1_i64 as u64)),
+            "noise:-1".parse()
+        );
+        assert_eq!(
+            Ok(GeneratorSettings::Noise(parse_seed("hello"))),
+            "noise:hello".parse()
+        );
+        assert_eq!(
+            Ok(GeneratorSettings::Superflat(vec![
+                BlockState::BEDROCK,
+                BlockState::DIRT,
+                BlockState::DIRT,
+                BlockState::GRASS_BLOCK,
+            ])),
+            "superflat:minecraft:bedrock,2*dirt,grass_block".parse()
+        );
+
+        assert!("noise".parse::<GeneratorSettings>().is_err());
+        assert!("flat:3".parse::<GeneratorSettings>().is_err());
+        assert!("superflat:2*nothing".parse::<GeneratorSettings>().is_err());
+        assert!("amplified".parse::<GeneratorSettings>().is_err());
+    }
+
+    #[test]
+    fn test_superflat() {
+        let generator =
+            FlatGenerator::new(HEIGHT, MIN_Y, vec![BlockState::BEDROCK, BlockState::STONE]);
+        let chunk = generator.generate(ChunkPos::new(10, -10));
+        assert_eq!(BlockState::BEDROCK, chunk.block_state(3, 0, 4));
+        assert_eq!(BlockState::STONE, chunk.block_state(3, 1, 4));
+        assert_eq!(BlockState::AIR, chunk.block_state(3, 2, 4));
+        // right above the stone
+        assert_eq!(MIN_Y + 2, generator.spawn_height(51, -3));
+        assert_eq!(-60, FlatGenerator::grass(HEIGHT, MIN_Y).spawn_height(0, 0));
+    }
+
+    #[test]
+    fn test_noise_same_seed_same_chunks() {
+        let a = noise_generator(42);
+        let b = noise_generator(42);
+        for pos in [
+            ChunkPos::new(0, 0),
+            ChunkPos::new(-7, 13),
+            ChunkPos::new(100, -250),
+        ] {
+            assert_eq!(
+                blocks(&a.generate(pos)),
+                blocks(&b.generate(pos)),
+                "chunk {:?} differs",
+                pos
+            );
+        }
+    }
+
+    #[test]
+    fn test_noise_different_seeds_differ() {
+        let pos = ChunkPos::new(3, 3);
+        assert_ne!(
+            blocks(&noise_generator(1).generate(pos)),
+            blocks(&noise_generator(2).generate(pos))
+        );
+    }
+
+    #[test]
+    fn test_noise_terrain() {
+        let generator = noise_generator(7);
+        let chunk = generator.generate(ChunkPos::new(0, 0));
+
+        for z in 0..16 {
+            for x in 0..16 {
+                assert_eq!(BlockState::BEDROCK, chunk.block_state(x, 0, z));
+                let surface = generator.surface_height(x as i32, z as i32);
+                // nothing but air above the terrain, trees and the sea
+                let top = surface.max(SEA_LEVEL) + 12;
+                assert!(chunk.block_state(x, generator.local_y(top), z).is_air());
+                let spawn = generator.spawn_height(x as i32, z as i32);
+                assert!(spawn > surface && spawn > SEA_LEVEL);
+            }
+        }
+    }
+}
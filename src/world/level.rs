@@ -0,0 +1,124 @@
+use crate::{WeatherState, WorldTime};
+use valence::nbt::{Compound, Value};
+
+/// The state of a world that isn't stored in its chunks, kept in the `Data`
+/// compound of vanilla's `level.dat`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct LevelData {
+    pub time: WorldTime,
+    pub weather: WeatherState,
+    /// Ticks until the weather changes by itself.
+    pub weather_time: i64,
+}
+
+impl LevelData {
+    /// Writes the data into the `Data` compound of a `level.dat`, and keeps the
+    /// values that vanilla stores there and that we don't know about.
+    pub fn write_to(&self, data: &mut Compound) {
+        let (raining, thundering) = match self.weather {
+            WeatherState::Clear => (false, false),
+            WeatherState::Rain => (true, false),
+            WeatherState::Thunder => (true, true),
+        };
+        let weather_time = self.weather_time.clamp(0, i32::MAX as i64) as i32;
+
+        data.insert("Time", self.time.age);
+        data.insert("DayTime", self.time.time_of_day);
+        data.insert("raining", raining as i8);
+        data.insert("thundering", thundering as i8);
+        data.insert("rainTime", weather_time);
+        data.insert("thunderTime", weather_time);
+        data.insert("clearWeatherTime", 0);
+    }
+
+    /// Reads the data from the `Data` compound of a `level.dat`, which may also have
+    /// been written by vanilla. Values that are missing get their defaults.
+    pub fn from_nbt(data: &Compound) -> Self {
+        let weather = match (flag(data, "raining"), flag(data, "thundering")) {
+            (true, true) => WeatherState::Thunder,
+            (true, false) => WeatherState::Rain,
+            _ => WeatherState::Clear,
+        };
+        // vanilla counts down the time until /weather clear ends separately
+        let clear_time = int(data, "clearWeatherTime").filter(|time| *time > 0);
+        let weather_time = match weather {
+            WeatherState::Clear => clear_time.or(int(data, "rainTime")),
+            WeatherState::Rain => int(data, "rainTime"),
+            WeatherState::Thunder => int(data, "thunderTime"),
+        };
+
+        Self {
+            time: WorldTime {
+                age: long(data, "Time").unwrap_or_default(),
+                time_of_day: long(data, "DayTime").unwrap_or_default(),
+            },
+            weather,
+            weather_time: weather_time.unwrap_or_default().max(0) as i64,
+        }
+    }
+}
+
+fn flag(nbt: &Compound, key: &str) -> bool {
+    matches!(nbt.get(key), Some(Value::Byte(1)))
+}
+
+fn int(nbt: &Compound, key: &str) -> Option<i32> {
+    match nbt.get(key) {
+        Some(Value::Int(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+fn long(nbt: &Compound, key: &str) -> Option<i64> {
+    match nbt.get(key) {
+        Some(Value::Long(l)) => Some(*l),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::nbt::compound;
+
+    #[test]
+    fn test_nbt_round_trip() {
+        let level = LevelData {
+            time: WorldTime {
+                age: 123456,
+                time_of_day: 30000,
+            },
+            weather: WeatherState::Thunder,
+            weather_time: 4000,
+        };
+        let mut data = compound! {
+            "LevelName" => "world",
+        };
+        level.write_to(&mut data);
+        assert_eq!(level, LevelData::from_nbt(&data));
+        assert_eq!(
+            Some(&Value::String("world".to_string())),
+            data.get("LevelName")
+        );
+    }
+
+    #[test]
+    fn test_vanilla_level() {
+        let data = compound! {
+            "Time" => 5000_i64,
+            "DayTime" => 7000_i64,
+            "raining" => 0_i8,
+            "rainTime" => 100,
+            "clearWeatherTime" => 6000,
+        };
+        let level = LevelData::from_nbt(&data);
+        assert_eq!(5000, level.time.age);
+        assert_eq!(7000, level.time.time_of_day);
+        assert_eq!(WeatherState::Clear, level.weather);
+        assert_eq!(6000, level.weather_time);
+
+        let level = LevelData::from_nbt(&Compound::new());
+        assert_eq!(WorldTime::default(), level.time);
+        assert_eq!(WeatherState::Clear, level.weather);
+    }
+}
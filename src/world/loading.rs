@@ -0,0 +1,336 @@
+use crate::world::{ChunkGenerator, WorldStorage};
+use bevy_ecs::prelude::*;
+use log::{error, warn};
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use valence::anvil::{AnvilLevel, ChunkLoadEvent, ChunkLoadStatus};
+use valence::client::{View, ViewDistance, VisibleChunkLayer};
+use valence::prelude::*;
+
+/// Keeps track of the chunks of a world that are being generated, and generates
+/// chunks that are missing from the world's storage.
+///
+/// Generation happens on worker threads, finished chunks are picked up by
+/// [`insert_generated_chunks`].
+///
+/// In worlds with an [`AnvilLevel`], valence requests the chunks in the view of
+/// clients and reports them with a [`ChunkLoadEvent`]. Every loaded chunk is then
+/// added to the level's `ignored_chunks`, so that only [`unload_unviewed_chunks`]
+/// unloads chunks.
+#[derive(Component)]
+pub struct ChunkLoader {
+    requested: HashSet<ChunkPos>,
+    jobs: Sender<ChunkPos>,
+    results: Mutex<Receiver<(ChunkPos, UnloadedChunk)>>,
+}
+
+impl ChunkLoader {
+    pub fn new(generator: Arc<dyn ChunkGenerator>, workers: usize) -> Self {
+        let (jobs, job_receiver) = channel::<ChunkPos>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        for _ in 0..workers.max(1) {
+            let generator = generator.clone();
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            thread::spawn(move || loop {
+                // the lock guard is dropped before generating, so workers don't block each other
+                let job = job_receiver.lock().unwrap().recv();
+                let Ok(pos) = job else {
+                    // the loader was dropped
+                    return;
+                };
+                if result_sender.send((pos, generator.generate(pos))).is_err() {
+                    return;
+                }
+            });
+        }
+
+        Self {
+            requested: HashSet::new(),
+            jobs,
+            results: Mutex::new(results),
+        }
+    }
+
+    pub fn is_requested(&self, pos: ChunkPos) -> bool {
+        self.requested.contains(&pos)
+    }
+
+    /// Queues the chunk for generation on a worker thread, unless it already is.
+    pub fn generate(&mut self, pos: ChunkPos) {
+        if self.requested.insert(pos) {
+            // workers only stop when the loader is dropped, so this can't fail
+            let _ = self.jobs.send(pos);
+        }
+    }
+
+    /// Marks a requested chunk as loaded.
+    pub fn loaded(&mut self, pos: ChunkPos) {
+        self.requested.remove(&pos);
+    }
+
+    /// Returns all chunks that were generated since the last call.
+    pub fn poll(&mut self) -> Vec<(ChunkPos, UnloadedChunk)> {
+        let generated = self.results.lock().unwrap().try_iter().collect::<Vec<_>>();
+        generated.iter().for_each(|(pos, _)| self.loaded(*pos));
+        generated
+    }
+}
+
+/// Removes chunks that no client can see anymore. Dirty chunks are handed to the
+/// world's region writer instead, so this doesn't wait for the disk.
+///
+/// Chunks that were changed while the server runs stay loaded. Valence's anvil
+/// worker keeps the region files open that it read, and doesn't see the ones that
+/// were replaced or created since, so it would load them without the changes.
+///
+/// Must run before new chunks are inserted in the same tick, because valence only
+/// counts the viewers of a chunk after it was sent to the clients.
+pub fn unload_unviewed_chunks(
+    biomes: Res<BiomeRegistry>,
+    mut layers: Query<(
+        &mut ChunkLayer,
+        Option<&mut WorldStorage>,
+        Option<&mut AnvilLevel>,
+    )>,
+) {
+    layers.for_each_mut(|(mut layer, mut storage, level)| {
+        let unviewed = layer
+            .chunks()
+            .filter(|(_, chunk)| !chunk.is_viewed())
+            .map(|(pos, _)| pos)
+            .collect::<HashSet<_>>();
+        if unviewed.is_empty() {
+            return;
+        }
+
+        if let Some(storage) = storage.as_mut() {
+            let dirty = unviewed
+                .iter()
+                .copied()
+                .filter(|pos| storage.is_dirty(*pos))
+                .collect::<Vec<_>>();
+            if !dirty.is_empty() {
+                if let Err(e) = storage.save_chunks(&layer, &biomes, &dirty) {
+                    error!("unable to save unviewed chunks: {}", e);
+                }
+            }
+        }
+        let unviewed = unviewed
+            .into_iter()
+            .filter(|pos| {
+                storage.as_ref().map_or(true, |storage| {
+                    !storage.is_dirty(*pos) && !storage.is_written(*pos)
+                })
+            })
+            .collect::<HashSet<_>>();
+
+        layer.retain_chunks(|pos, _| !unviewed.contains(&pos));
+        if let Some(mut level) = level {
+            // valence requests them again once they come into view
+            level.ignored_chunks.retain(|pos| !unviewed.contains(pos));
+        }
+    });
+}
+
+/// Generates every chunk in the view of a client that isn't loaded yet, in worlds
+/// without an [`AnvilLevel`]. Valence requests the chunks of the other worlds.
+pub fn load_chunks_in_view(
+    mut layers: Query<(&ChunkLayer, &mut ChunkLoader), Without<AnvilLevel>>,
+    clients: Query<
+        (&VisibleChunkLayer, View),
+        Or<(
+            Changed<VisibleChunkLayer>,
+            Changed<Position>,
+            Changed<ViewDistance>,
+        )>,
+    >,
+) {
+    clients.for_each(|(visible_chunk_layer, view)| {
+        let Ok((layer, mut loader)) = layers.get_mut(visible_chunk_layer.0) else {
+            return;
+        };
+
+        for pos in view.get().iter() {
+            if layer.chunk(pos).is_none() {
+                loader.generate(pos);
+            }
+        }
+    });
+}
+
+/// Takes over the chunks that valence loaded from the Anvil world, and hands the
+/// ones that don't exist in it to the generator.
+pub fn handle_chunk_loads(
+    mut events: EventReader<ChunkLoadEvent>,
+    mut loaders: Query<(&mut ChunkLoader, &mut AnvilLevel)>,
+) {
+    events.iter().for_each(|event| {
+        let Ok((mut loader, mut level)) = loaders.get_mut(event.entity) else {
+            return;
+        };
+
+        match &event.status {
+            ChunkLoadStatus::Success { .. } => {
+                loader.loaded(event.pos);
+                level.ignored_chunks.insert(event.pos);
+            }
+            ChunkLoadStatus::Empty => loader.generate(event.pos),
+            ChunkLoadStatus::Failed(e) => {
+                error!("unable to load chunk {:?}: {:#}", event.pos, e);
+                warn!("generating chunk {:?} instead", event.pos);
+                loader.generate(event.pos);
+            }
+        }
+    });
+}
+
+pub fn insert_generated_chunks(
+    mut layers: Query<(&mut ChunkLayer, &mut ChunkLoader, Option<&mut AnvilLevel>)>,
+) {
+    layers.for_each_mut(|(mut layer, mut loader, mut level)| {
+        for (pos, chunk) in loader.poll() {
+            if layer.chunk(pos).is_none() {
+                layer.insert_chunk(pos, chunk);
+            }
+            if let Some(level) = level.as_mut() {
+                level.ignored_chunks.insert(pos);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{FlatGenerator, WorldStorage};
+    use std::time::{Duration, Instant};
+    use valence::testing::ScenarioSingleClient;
+
+    #[test]
+    fn test_generate_on_worker() {
+        let mut loader = ChunkLoader::new(Arc::new(FlatGenerator::grass(384, -64)), 2);
+        let positions = [ChunkPos::new(0, 0), ChunkPos::new(-3, 5)];
+        positions.iter().for_each(|pos| loader.generate(*pos));
+        assert!(loader.is_requested(positions[0]));
+
+        let mut generated = Vec::new();
+        let start = Instant::now();
+        while generated.len() < positions.len() && start.elapsed() < Duration::from_secs(5) {
+            generated.extend(loader.poll());
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(positions.len(), generated.len());
+        for (pos, chunk) in generated {
+            assert!(!loader.is_requested(pos));
+            assert_eq!(BlockState::BEDROCK, chunk.block_state(0, 0, 0));
+            assert_eq!(BlockState::GRASS_BLOCK, chunk.block_state(15, 3, 15));
+            assert_eq!(BlockState::AIR, chunk.block_state(8, 4, 8));
+        }
+    }
+
+    #[test]
+    fn test_generated_chunks_are_owned() {
+        let mut scenario = ScenarioSingleClient::new();
+        let world_dir = std::env::temp_dir().join(format!(
+            "justmine-test-generated-chunks-{}",
+            std::process::id()
+        ));
+        let biomes = scenario.app.world.resource::<BiomeRegistry>();
+        let level = AnvilLevel::new(&world_dir, biomes);
+        let mut loader = ChunkLoader::new(Arc::new(FlatGenerator::grass(384, -64)), 1);
+        // far away from the client, so nobody views it
+        let pos = ChunkPos::new(50, 50);
+        loader.generate(pos);
+        let layer = scenario.layer;
+        scenario
+            .app
+            .add_systems(Update, insert_generated_chunks)
+            .world
+            .entity_mut(layer)
+            .insert((level, loader));
+
+        let start = Instant::now();
+        while scenario
+            .app
+            .world
+            .get::<ChunkLayer>(layer)
+            .unwrap()
+            .chunk(pos)
+            .is_none()
+            && start.elapsed() < Duration::from_secs(5)
+        {
+            scenario.app.update();
+            thread::sleep(Duration::from_millis(1));
+        }
+        let level = scenario.app.world.get::<AnvilLevel>(layer).unwrap();
+        assert!(level.ignored_chunks.contains(&pos));
+
+        scenario.app.add_systems(Update, unload_unviewed_chunks);
+        scenario.app.update();
+        let world = &scenario.app.world;
+        assert!(world.get::<ChunkLayer>(layer).unwrap().chunk(pos).is_none());
+        assert!(!world
+            .get::<AnvilLevel>(layer)
+            .unwrap()
+            .ignored_chunks
+            .contains(&pos));
+    }
+
+    #[test]
+    fn test_edited_chunks_stay_loaded() {
+        let mut scenario = ScenarioSingleClient::new();
+        let world_dir = std::env::temp_dir().join(format!(
+            "justmine-test-edited-chunks-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&world_dir);
+        let biomes = scenario.app.world.resource::<BiomeRegistry>();
+        let mut level = AnvilLevel::new(&world_dir, biomes);
+        // far away from the client, so nobody views them
+        let edited = ChunkPos::new(50, 50);
+        let untouched = ChunkPos::new(52, 52);
+        level.ignored_chunks.extend([edited, untouched]);
+        let mut storage = WorldStorage::new(&world_dir);
+        storage.mark_dirty(edited);
+
+        let layer = scenario.layer;
+        let position = BlockPos::new(800, 0, 800);
+        {
+            let mut entity_mut = scenario.app.world.entity_mut(layer);
+            let mut chunks = entity_mut.get_mut::<ChunkLayer>().unwrap();
+            let height = chunks.height();
+            chunks.insert_chunk(edited, UnloadedChunk::with_height(height));
+            chunks.insert_chunk(untouched, UnloadedChunk::with_height(height));
+            chunks.set_block(position, BlockState::STONE);
+            entity_mut.insert((level, storage));
+        }
+
+        scenario.app.add_systems(Update, unload_unviewed_chunks);
+        scenario.app.update();
+
+        let world = &scenario.app.world;
+        let storage = world.get::<WorldStorage>(layer).unwrap();
+        storage.flush().unwrap();
+        assert!(!storage.is_dirty(edited));
+        assert!(storage.is_written(edited));
+
+        // valence doesn't get to load the edited chunk from the replaced region file
+        let chunks = world.get::<ChunkLayer>(layer).unwrap();
+        let level = world.get::<AnvilLevel>(layer).unwrap();
+        assert_eq!(
+            Some(BlockState::STONE),
+            chunks.block(position).map(|b| b.state)
+        );
+        assert!(level.ignored_chunks.contains(&edited));
+        assert!(chunks.chunk(untouched).is_none());
+        assert!(!level.ignored_chunks.contains(&untouched));
+
+        let _ = std::fs::remove_dir_all(&world_dir);
+    }
+}
@@ -0,0 +1,17 @@
+mod generator;
+mod level;
+mod loading;
+mod noise;
+mod playerdata;
+mod region;
+mod registry;
+mod storage;
+
+pub use generator::*;
+pub use level::*;
+pub use loading::*;
+pub use noise::*;
+pub use playerdata::*;
+pub use region::*;
+pub use registry::*;
+pub use storage::*;
@@ -0,0 +1,192 @@
+/// Seeded gradient noise. All values are derived from the seed and the sample
+/// position only, so the same seed always produces the same terrain.
+#[derive(Clone, Debug)]
+pub struct Noise {
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    /// Returns a noise that is independent of this one, e.g. for a different terrain feature.
+    pub fn derive(&self, salt: u64) -> Self {
+        Self::new(hash(self.seed, salt as i64, 0, 0))
+    }
+
+    /// 2D gradient noise in the range `-1.0..=1.0`.
+    pub fn sample2(&self, x: f64, z: f64) -> f64 {
+        let (x0, z0) = (x.floor(), z.floor());
+        let (fx, fz) = (x - x0, z - z0);
+        let (x0, z0) = (x0 as i64, z0 as i64);
+
+        let corner = |dx: i64, dz: i64| {
+            let h = hash(self.seed, x0 + dx, 0, z0 + dz);
+            let angle = (h >> 11) as f64 / (1_u64 << 53) as f64 * std::f64::consts::TAU;
+            angle.cos() * (fx - dx as f64) + angle.sin() * (fz - dz as f64)
+        };
+
+        let (u, v) = (fade(fx), fade(fz));
+        let a = lerp(corner(0, 0), corner(1, 0), u);
+        let b = lerp(corner(0, 1), corner(1, 1), u);
+        (lerp(a, b, v) * std::f64::consts::SQRT_2).clamp(-1.0, 1.0)
+    }
+
+    /// 3D gradient noise in the range `-1.0..=1.0`.
+    pub fn sample3(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+        let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+        let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+
+        let corner = |dx: i64, dy: i64, dz: i64| {
+            let h = hash(self.seed, x0 + dx, y0 + dy, z0 + dz);
+            let (gx, gy, gz) = GRADIENTS_3D[(h % GRADIENTS_3D.len() as u64) as usize];
+            gx * (fx - dx as f64) + gy * (fy - dy as f64) + gz * (fz - dz as f64)
+        };
+
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        let a = lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        );
+        let b = lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        );
+        lerp(a, b, w).clamp(-1.0, 1.0)
+    }
+
+    /// Fractal 2D noise, every octave has twice the frequency and half the amplitude
+    /// of the previous one. The result is normalized to `-1.0..=1.0`.
+    pub fn fractal2(&self, x: f64, z: f64, octaves: u32) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0;
+        let mut max = 0.0;
+        for octave in 0..octaves {
+            total += self
+                .derive(octave as u64)
+                .sample2(x * frequency, z * frequency)
+                * amplitude;
+            max += amplitude;
+            amplitude /= 2.0;
+            frequency *= 2.0;
+        }
+        total / max
+    }
+}
+
+/// A small deterministic random number generator (SplitMix64), used for
+/// placing ores and trees and for the weather.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Creates a generator for a specific chunk of a world with the given seed.
+    pub fn for_chunk(seed: u64, x: i32, z: i32, salt: u64) -> Self {
+        Self::new(hash(seed ^ salt, x as i64, 0, z as i64))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        mix(self.state)
+    }
+
+    /// Returns a value in `range`, which must not be empty.
+    pub fn range(&mut self, range: std::ops::Range<i32>) -> i32 {
+        let len = (range.end - range.start) as u64;
+        range.start + (self.next_u64() % len) as i32
+    }
+
+    /// Returns `true` with the given probability.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1_u64 << 53) as f64) < probability
+    }
+}
+
+const GRADIENTS_3D: [(f64, f64, f64); 12] = [
+    (1.0, 1.0, 0.0),
+    (-1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (-1.0, -1.0, 0.0),
+    (1.0, 0.0, 1.0),
+    (-1.0, 0.0, 1.0),
+    (1.0, 0.0, -1.0),
+    (-1.0, 0.0, -1.0),
+    (0.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (0.0, 1.0, -1.0),
+    (0.0, -1.0, -1.0),
+];
+
+fn hash(seed: u64, x: i64, y: i64, z: i64) -> u64 {
+    let mut h = mix(seed);
+    h = mix(h ^ x as u64);
+    h = mix(h ^ y as u64);
+    mix(h ^ z as u64)
+}
+
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_is_deterministic() {
+        let a = Noise::new(42);
+        let b = Noise::new(42);
+        let c = Noise::new(43);
+
+        let mut differs = false;
+        for i in 0..100 {
+            let (x, z) = (i as f64 * 0.37, i as f64 * -1.13);
+            assert_eq!(a.sample2(x, z), b.sample2(x, z));
+            assert_eq!(a.sample3(x, 1.5, z), b.sample3(x, 1.5, z));
+            differs |= a.sample2(x, z) != c.sample2(x, z);
+        }
+        assert!(differs, "different seeds should produce different noise");
+    }
+
+    #[test]
+    fn test_noise_range() {
+        let noise = Noise::new(7);
+        for i in 0..1000 {
+            let (x, y, z) = (i as f64 * 0.173, i as f64 * 0.071, i as f64 * -0.29);
+            assert!((-1.0..=1.0).contains(&noise.sample2(x, z)));
+            assert!((-1.0..=1.0).contains(&noise.sample3(x, y, z)));
+            assert!((-1.0..=1.0).contains(&noise.fractal2(x, z, 4)));
+        }
+        // gradient noise is zero on integer coordinates
+        assert_eq!(0.0, noise.sample2(3.0, -5.0));
+    }
+
+    #[test]
+    fn test_random_range() {
+        let mut random = Random::for_chunk(1, 2, 3, 4);
+        for _ in 0..1000 {
+            assert!((-5..5).contains(&random.range(-5..5)));
+        }
+    }
+}
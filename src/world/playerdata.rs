@@ -0,0 +1,574 @@
+use crate::{insert_into_inventory, spawn_item, write_replacing};
+use crate::{Experience, Food, Health, Saturation, SaveWorldEvent, SpawnPoint, WorldInfo};
+use bevy_app::AppExit;
+use bevy_ecs::prelude::*;
+use bevy_ecs::query::WorldQuery;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{error, info};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use valence::entity::EntityLayerId;
+use valence::inventory::CursorItem;
+use valence::nbt::{compound, from_binary, to_binary, Compound, List, Value};
+use valence::prelude::*;
+use valence::UniqueId;
+
+/// Vanilla saves the hotbar in the slots 0 to 8, the rest of the main inventory in
+/// 9 to 35, the armor from the feet up in 100 to 103 and the offhand in -106.
+const VANILLA_FEET_SLOT: i8 = 100;
+const VANILLA_OFFHAND_SLOT: i8 = -106;
+/// The 2x2 crafting grid of the player inventory, without the result in slot 0.
+const CRAFTING_GRID: std::ops::Range<u16> = 1..5;
+
+/// The state of a player that is kept between sessions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerData {
+    /// The name of the world the player is in.
+    pub world: String,
+    pub position: DVec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub game_mode: GameMode,
+    pub health: f32,
+    pub food: i32,
+    pub saturation: f32,
+    pub experience: i32,
+    /// The non-empty slots of the player inventory, by valence slot index.
+    pub inventory: Vec<(u16, ItemStack)>,
+    pub spawn_point: Option<StoredSpawnPoint>,
+}
+
+/// A [`SpawnPoint`] with the name of its world instead of the world's entity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredSpawnPoint {
+    pub world: String,
+    pub position: BlockPos,
+    pub forced: bool,
+}
+
+impl PlayerData {
+    /// Encodes the data in the format of vanilla's `playerdata/<uuid>.dat`.
+    pub fn to_nbt(&self) -> Compound {
+        let inventory = self
+            .inventory
+            .iter()
+            .filter_map(|(slot, stack)| {
+                let mut nbt = compound! {
+                    "Slot" => to_vanilla_slot(*slot)?,
+                    "id" => format!("minecraft:{}", stack.item.to_str()),
+                    "Count" => stack.count,
+                };
+                if let Some(tag) = &stack.nbt {
+                    nbt.insert("tag", tag.clone());
+                }
+                Some(nbt)
+            })
+            .collect();
+
+        let mut nbt = compound! {
+            "Dimension" => self.world.clone(),
+            "Pos" => List::Double(vec![self.position.x, self.position.y, self.position.z]),
+            "Rotation" => List::Float(vec![self.yaw, self.pitch]),
+            "playerGameType" => game_mode_id(self.game_mode),
+            "Health" => self.health,
+            "foodLevel" => self.food,
+            "foodSaturationLevel" => self.saturation,
+            "XpTotal" => self.experience,
+            "Inventory" => List::Compound(inventory),
+        };
+        if let Some(spawn) = &self.spawn_point {
+            nbt.insert("SpawnDimension", spawn.world.clone());
+            nbt.insert("SpawnX", spawn.position.x);
+            nbt.insert("SpawnY", spawn.position.y);
+            nbt.insert("SpawnZ", spawn.position.z);
+            nbt.insert("SpawnForced", spawn.forced as i8);
+        }
+        nbt
+    }
+
+    /// Decodes data that was written by [`to_nbt`](Self::to_nbt) or by vanilla.
+    /// Values that are missing get their defaults, only the position is required.
+    ///
+    /// The `minecraft:` prefix of vanilla dimensions is removed, so that vanilla
+    /// players in `minecraft:the_nether` end up in the world called `the_nether`.
+    pub fn from_nbt(nbt: &Compound) -> Option<Self> {
+        let Some(Value::List(List::Double(pos))) = nbt.get("Pos") else {
+            return None;
+        };
+        let [x, y, z] = pos[..] else {
+            return None;
+        };
+        let (yaw, pitch) = match nbt.get("Rotation") {
+            Some(Value::List(List::Float(rotation))) if rotation.len() == 2 => {
+                (rotation[0], rotation[1])
+            }
+            _ => (0.0, 0.0),
+        };
+
+        let inventory = match nbt.get("Inventory") {
+            Some(Value::List(List::Compound(slots))) => {
+                slots.iter().filter_map(decode_slot).collect()
+            }
+            _ => vec![],
+        };
+
+        let spawn_point = match (
+            string(nbt, "SpawnDimension"),
+            int(nbt, "SpawnX"),
+            int(nbt, "SpawnY"),
+            int(nbt, "SpawnZ"),
+        ) {
+            (Some(world), Some(x), Some(y), Some(z)) => Some(StoredSpawnPoint {
+                world: strip_namespace(world).to_string(),
+                position: BlockPos::new(x, y, z),
+                forced: matches!(nbt.get("SpawnForced"), Some(Value::Byte(1))),
+            }),
+            _ => None,
+        };
+
+        Some(Self {
+            world: strip_namespace(string(nbt, "Dimension").unwrap_or_default()).to_string(),
+            position: DVec3::new(x, y, z),
+            yaw,
+            pitch,
+            game_mode: int(nbt, "playerGameType")
+                .and_then(game_mode_from_id)
+                .unwrap_or_default(),
+            health: float(nbt, "Health").unwrap_or(Health::default().0),
+            food: int(nbt, "foodLevel").unwrap_or(Food::default().0),
+            saturation: float(nbt, "foodSaturationLevel").unwrap_or(Saturation::default().0),
+            experience: int(nbt, "XpTotal").unwrap_or_default(),
+            inventory,
+            spawn_point,
+        })
+    }
+}
+
+/// Where the data of the players is stored, `<dir>/<uuid>.dat` for each player.
+#[derive(Resource, Debug, Clone)]
+pub struct PlayerStorage {
+    dir: PathBuf,
+}
+
+impl PlayerStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, uuid: &UniqueId) -> PathBuf {
+        self.dir.join(format!("{}.dat", uuid.0))
+    }
+
+    /// Reads the data of a player, `None` if the player never joined before.
+    pub fn load(&self, uuid: &UniqueId) -> io::Result<Option<PlayerData>> {
+        let path = self.path(uuid);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut data = Vec::new();
+        GzDecoder::new(fs::File::open(&path)?).read_to_end(&mut data)?;
+        let (nbt, _) = from_binary::<String>(&mut data.as_slice())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        PlayerData::from_nbt(&nbt).map(Some).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has no position", path.display()),
+            )
+        })
+    }
+
+    /// Writes the data of a player. The file is replaced at once, so that a crash
+    /// while saving doesn't lose the data.
+    pub fn save(&self, uuid: &UniqueId, data: &PlayerData) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        to_binary(&data.to_nbt(), &mut bytes, "")
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes)?;
+
+        write_replacing(&self.path(uuid), encoder.finish()?)
+    }
+}
+
+#[derive(WorldQuery)]
+pub struct PlayerDataQuery {
+    uuid: &'static UniqueId,
+    username: &'static Username,
+    layer_id: &'static EntityLayerId,
+    pos: &'static Position,
+    look: &'static Look,
+    game_mode: &'static GameMode,
+    inventory: &'static Inventory,
+    cursor_item: &'static CursorItem,
+    health: &'static Health,
+    food: &'static Food,
+    saturation: &'static Saturation,
+    experience: &'static Experience,
+    spawn_point: Option<&'static SpawnPoint>,
+}
+
+impl PlayerDataQueryItem<'_> {
+    /// The data of the player, `None` if the player isn't in a known world.
+    pub fn data(&self, worlds: &Query<&WorldInfo>) -> Option<PlayerData> {
+        let world = worlds.get(self.layer_id.0).ok()?;
+        let spawn_point = self.spawn_point.and_then(|spawn| {
+            Some(StoredSpawnPoint {
+                world: worlds.get(spawn.world).ok()?.name.clone(),
+                position: spawn.position,
+                forced: spawn.forced,
+            })
+        });
+        // the player keeps the items in the grid and on the cursor while online, but
+        // they have to be in the inventory to be saved
+        let mut inventory = self.inventory.clone();
+        let mut cursor_item = self.cursor_item.clone();
+        return_held_items(&mut inventory, &mut cursor_item);
+        let inventory = (0..inventory.slot_count())
+            .filter(|slot| !inventory.slot(*slot).is_empty())
+            .map(|slot| (slot, inventory.slot(slot).clone()))
+            .collect();
+
+        Some(PlayerData {
+            world: world.name.clone(),
+            position: self.pos.0,
+            yaw: self.look.yaw,
+            pitch: self.look.pitch,
+            game_mode: *self.game_mode,
+            health: self.health.0,
+            food: self.food.0,
+            saturation: self.saturation.0,
+            experience: self.experience.0,
+            inventory,
+            spawn_point,
+        })
+    }
+
+    fn save(&self, storage: &PlayerStorage, worlds: &Query<&WorldInfo>) -> bool {
+        let Some(data) = self.data(worlds) else {
+            return false;
+        };
+        match storage.save(self.uuid, &data) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("unable to save the data of {}: {}", self.username.0, e);
+                false
+            }
+        }
+    }
+}
+
+/// Moves the items in the crafting grid and on the cursor into the inventory, like
+/// vanilla does when the inventory is closed. Returns the items that didn't fit.
+pub fn return_held_items(
+    inventory: &mut Inventory,
+    cursor_item: &mut CursorItem,
+) -> Vec<ItemStack> {
+    let held = CRAFTING_GRID
+        .map(|slot| inventory.replace_slot(slot, ItemStack::EMPTY))
+        .chain(std::iter::once(std::mem::replace(
+            &mut cursor_item.0,
+            ItemStack::EMPTY,
+        )))
+        .filter(|stack| !stack.is_empty())
+        .collect::<Vec<_>>();
+
+    held.into_iter()
+        .filter_map(|stack| {
+            let remaining = insert_into_inventory(inventory, stack.clone());
+            (remaining > 0).then(|| stack.with_count(remaining))
+        })
+        .collect()
+}
+
+/// Puts the items in the crafting grid and on the cursor of players that leave into
+/// their inventory, and drops the ones that don't fit. Must run before
+/// [`save_disconnected_players`].
+pub fn return_held_items_on_leave(
+    mut commands: Commands,
+    mut disconnected: RemovedComponents<Client>,
+    kicked: Query<Entity, (With<Client>, Added<Despawned>)>,
+    mut players: Query<(&EntityLayerId, &Position, &mut Inventory, &mut CursorItem)>,
+    server: Res<Server>,
+) {
+    disconnected.iter().chain(kicked.iter()).for_each(|entity| {
+        let Ok((layer, position, mut inventory, mut cursor_item)) = players.get_mut(entity) else {
+            return;
+        };
+        for stack in return_held_items(&mut inventory, &mut cursor_item) {
+            spawn_item(
+                &mut commands,
+                layer.0,
+                position.0,
+                stack,
+                server.current_tick(),
+            );
+        }
+    });
+}
+
+/// Saves players when they disconnect, or are kicked. Must run before
+/// `despawn_disconnected_clients`, which despawns the entities of the players.
+pub fn save_disconnected_players(
+    mut disconnected: RemovedComponents<Client>,
+    kicked: Query<Entity, (With<Client>, Added<Despawned>)>,
+    players: Query<PlayerDataQuery>,
+    worlds: Query<&WorldInfo>,
+    storage: Res<PlayerStorage>,
+) {
+    disconnected.iter().chain(kicked.iter()).for_each(|entity| {
+        if let Ok(player) = players.get(entity) {
+            player.save(&storage, &worlds);
+        }
+    });
+}
+
+/// Saves all online players together with the worlds.
+pub fn save_players(
+    mut events: EventReader<SaveWorldEvent>,
+    players: Query<PlayerDataQuery, With<Client>>,
+    worlds: Query<&WorldInfo>,
+    storage: Res<PlayerStorage>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    save_all_players(&players, &worlds, &storage);
+}
+
+/// Saves all online players when the app is about to exit. Must run in [`Last`].
+pub fn save_players_on_exit(
+    mut exit: EventReader<AppExit>,
+    players: Query<PlayerDataQuery, With<Client>>,
+    worlds: Query<&WorldInfo>,
+    storage: Res<PlayerStorage>,
+) {
+    if exit.iter().count() == 0 {
+        return;
+    }
+
+    save_all_players(&players, &worlds, &storage);
+}
+
+fn save_all_players(
+    players: &Query<PlayerDataQuery, With<Client>>,
+    worlds: &Query<&WorldInfo>,
+    storage: &PlayerStorage,
+) {
+    let saved = players
+        .iter()
+        .filter(|player| player.save(storage, worlds))
+        .count();
+    info!("saved {} players to {}", saved, storage.dir().display());
+}
+
+/// The vanilla slot of a valence player inventory slot, `None` for the crafting
+/// slots, which vanilla doesn't save.
+fn to_vanilla_slot(slot: u16) -> Option<i8> {
+    match slot {
+        // head, chest, legs, feet
+        5..=8 => Some(VANILLA_FEET_SLOT + (8 - slot) as i8),
+        9..=35 => Some(slot as i8),
+        // the hotbar
+        36..=44 => Some((slot - 36) as i8),
+        45 => Some(VANILLA_OFFHAND_SLOT),
+        _ => None,
+    }
+}
+
+fn from_vanilla_slot(slot: i8) -> Option<u16> {
+    match slot {
+        0..=8 => Some(slot as u16 + 36),
+        9..=35 => Some(slot as u16),
+        100..=103 => Some(8 - (slot - VANILLA_FEET_SLOT) as u16),
+        VANILLA_OFFHAND_SLOT => Some(45),
+        _ => None,
+    }
+}
+
+fn decode_slot(nbt: &Compound) -> Option<(u16, ItemStack)> {
+    let Some(Value::Byte(slot)) = nbt.get("Slot") else {
+        return None;
+    };
+    let Some(Value::Byte(count)) = nbt.get("Count") else {
+        return None;
+    };
+    let item = ItemKind::from_str(strip_namespace(string(nbt, "id")?))?;
+    let tag = match nbt.get("tag") {
+        Some(Value::Compound(tag)) => Some(tag.clone()),
+        _ => None,
+    };
+    Some((from_vanilla_slot(*slot)?, ItemStack::new(item, *count, tag)))
+}
+
+fn game_mode_id(game_mode: GameMode) -> i32 {
+    match game_mode {
+        GameMode::Survival => 0,
+        GameMode::Creative => 1,
+        GameMode::Adventure => 2,
+        GameMode::Spectator => 3,
+    }
+}
+
+fn game_mode_from_id(id: i32) -> Option<GameMode> {
+    match id {
+        0 => Some(GameMode::Survival),
+        1 => Some(GameMode::Creative),
+        2 => Some(GameMode::Adventure),
+        3 => Some(GameMode::Spectator),
+        _ => None,
+    }
+}
+
+fn strip_namespace(name: &str) -> &str {
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+fn string<'a>(nbt: &'a Compound, key: &str) -> Option<&'a str> {
+    match nbt.get(key) {
+        Some(Value::String(s)) => Some(s.as_str()),
+        _ => None,
+    }
+}
+
+fn int(nbt: &Compound, key: &str) -> Option<i32> {
+    match nbt.get(key) {
+        Some(Value::Int(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+fn float(nbt: &Compound, key: &str) -> Option<f32> {
+    match nbt.get(key) {
+        Some(Value::Float(f)) => Some(*f),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data() -> PlayerData {
+        PlayerData {
+            world: "the_nether".to_string(),
+            position: DVec3::new(10.5, 70.0, -3.25),
+            yaw: 90.0,
+            pitch: -12.5,
+            game_mode: GameMode::Adventure,
+            health: 13.5,
+            food: 7,
+            saturation: 0.5,
+            experience: 1234,
+            inventory: vec![
+                (5, ItemStack::new(ItemKind::DiamondHelmet, 1, None)),
+                (8, ItemStack::new(ItemKind::IronBoots, 1, None)),
+                (9, ItemStack::new(ItemKind::Stone, 64, None)),
+                (36, ItemStack::new(ItemKind::Torch, 12, None)),
+                (
+                    45,
+                    ItemStack::new(ItemKind::Shield, 1, Some(compound! { "Damage" => 3 })),
+                ),
+            ],
+            spawn_point: Some(StoredSpawnPoint {
+                world: "overworld".to_string(),
+                position: BlockPos::new(1, 64, -1),
+                forced: true,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_vanilla_slots() {
+        assert_eq!(Some(103), to_vanilla_slot(5));
+        assert_eq!(Some(100), to_vanilla_slot(8));
+        assert_eq!(Some(9), to_vanilla_slot(9));
+        assert_eq!(Some(0), to_vanilla_slot(36));
+        assert_eq!(Some(-106), to_vanilla_slot(45));
+        assert_eq!(None, to_vanilla_slot(1));
+        for slot in 5..=45 {
+            assert_eq!(
+                Some(slot),
+                from_vanilla_slot(to_vanilla_slot(slot).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn test_nbt_round_trip() {
+        let data = data();
+        assert_eq!(Some(data.clone()), PlayerData::from_nbt(&data.to_nbt()));
+    }
+
+    #[test]
+    fn test_vanilla_player() {
+        let nbt = compound! {
+            "Dimension" => "minecraft:overworld",
+            "Pos" => List::Double(vec![0.5, 65.0, 0.5]),
+            "Inventory" => List::Compound(vec![compound! {
+                "Slot" => 0_i8,
+                "id" => "minecraft:oak_log",
+                "Count" => 3_i8,
+            }]),
+        };
+        let data = PlayerData::from_nbt(&nbt).unwrap();
+        assert_eq!("overworld", data.world);
+        assert_eq!(GameMode::Survival, data.game_mode);
+        assert_eq!(Health::default().0, data.health);
+        assert_eq!(
+            vec![(36, ItemStack::new(ItemKind::OakLog, 3, None))],
+            data.inventory
+        );
+        assert_eq!(None, data.spawn_point);
+
+        assert_eq!(None, PlayerData::from_nbt(&Compound::new()));
+    }
+
+    #[test]
+    fn test_return_held_items() {
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        let mut cursor_item = CursorItem(ItemStack::new(ItemKind::Dirt, 5, None));
+        inventory.set_slot(0, ItemStack::new(ItemKind::OakPlanks, 4, None));
+        inventory.set_slot(1, ItemStack::new(ItemKind::OakLog, 1, None));
+        assert!(return_held_items(&mut inventory, &mut cursor_item).is_empty());
+        assert!(cursor_item.0.is_empty());
+        assert!(inventory.slot(1).is_empty());
+        assert_eq!(ItemKind::OakLog, inventory.slot(36).item);
+        assert_eq!(ItemKind::Dirt, inventory.slot(37).item);
+
+        for slot in 9..45 {
+            inventory.set_slot(slot, ItemStack::new(ItemKind::Stone, 64, None));
+        }
+        inventory.set_slot(2, ItemStack::new(ItemKind::OakLog, 1, None));
+        assert_eq!(
+            vec![ItemStack::new(ItemKind::OakLog, 1, None)],
+            return_held_items(&mut inventory, &mut cursor_item)
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir =
+            std::env::temp_dir().join(format!("justmine-test-player-data-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let storage = PlayerStorage::new(&dir);
+        let uuid = UniqueId(Uuid::from_u128(0x069a79f444e94726a5befca90e38aaf5));
+
+        assert_eq!(None, storage.load(&uuid).unwrap());
+        storage.save(&uuid, &data()).unwrap();
+        assert!(dir
+            .join("069a79f4-44e9-4726-a5be-fca90e38aaf5.dat")
+            .exists());
+        assert_eq!(Some(data()), storage.load(&uuid).unwrap());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
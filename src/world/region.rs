@@ -0,0 +1,265 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+use valence::ChunkPos;
+
+const SECTOR_SIZE: usize = 4096;
+const CHUNKS_PER_REGION: usize = 32 * 32;
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+const COMPRESSION_NONE: u8 = 3;
+
+/// An in-memory copy of an Anvil region file (`r.<x>.<z>.mca`).
+///
+/// Chunks are kept in their compressed on-disk form, so chunks that are not
+/// touched survive a read/write cycle unchanged.
+pub struct RegionFile {
+    chunks: Vec<Option<StoredChunk>>,
+}
+
+struct StoredChunk {
+    timestamp: u32,
+    compression: u8,
+    data: Vec<u8>,
+}
+
+impl RegionFile {
+    pub fn new() -> Self {
+        Self {
+            chunks: (0..CHUNKS_PER_REGION).map(|_| None).collect(),
+        }
+    }
+
+    /// Returns the path of the region file that contains the given chunk.
+    pub fn path_for(region_dir: impl AsRef<Path>, pos: ChunkPos) -> PathBuf {
+        region_dir.as_ref().join(format!(
+            "r.{}.{}.mca",
+            pos.x.div_euclid(32),
+            pos.z.div_euclid(32)
+        ))
+    }
+
+    /// Reads the region file at the given path, or returns an empty region if
+    /// the file doesn't exist yet.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut region = Self::new();
+        if bytes.is_empty() {
+            return Ok(region);
+        }
+        if bytes.len() < HEADER_SIZE {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "region file is smaller than its header",
+            ));
+        }
+
+        for index in 0..CHUNKS_PER_REGION {
+            let location = read_u32(bytes, index * 4);
+            let timestamp = read_u32(bytes, SECTOR_SIZE + index * 4);
+            let sector_offset = (location >> 8) as usize;
+            if sector_offset == 0 {
+                continue;
+            }
+
+            let start = sector_offset * SECTOR_SIZE;
+            if start + 5 > bytes.len() {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("chunk {} points outside of the region file", index),
+                ));
+            }
+            let length = read_u32(bytes, start) as usize;
+            if length == 0 || start + 4 + length > bytes.len() {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("chunk {} has an invalid length", index),
+                ));
+            }
+
+            region.chunks[index] = Some(StoredChunk {
+                timestamp,
+                compression: bytes[start + 4],
+                data: bytes[start + 5..start + 4 + length].to_vec(),
+            });
+        }
+
+        Ok(region)
+    }
+
+    /// Returns the uncompressed NBT data of the given chunk, if it is present.
+    pub fn chunk_data(&self, pos: ChunkPos) -> io::Result<Option<Vec<u8>>> {
+        let Some(chunk) = &self.chunks[index_of(pos)] else {
+            return Ok(None);
+        };
+
+        let mut data = Vec::new();
+        match chunk.compression {
+            COMPRESSION_GZIP => GzDecoder::new(chunk.data.as_slice()).read_to_end(&mut data)?,
+            COMPRESSION_ZLIB => ZlibDecoder::new(chunk.data.as_slice()).read_to_end(&mut data)?,
+            COMPRESSION_NONE => {
+                data.extend_from_slice(&chunk.data);
+                data.len()
+            }
+            other => {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unsupported chunk compression {}", other),
+                ))
+            }
+        };
+        Ok(Some(data))
+    }
+
+    /// Stores the given uncompressed NBT data for the chunk at `pos`.
+    pub fn set_chunk_data(&mut self, pos: ChunkPos, nbt: &[u8]) -> io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(nbt)?;
+
+        self.chunks[index_of(pos)] = Some(StoredChunk {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs() as u32)
+                .unwrap_or_default(),
+            compression: COMPRESSION_ZLIB,
+            data: encoder.finish()?,
+        });
+        Ok(())
+    }
+
+    /// Fails if a chunk takes more than 255 sectors, which would need an external
+    /// `.mcc` file that we don't write.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0_u8; HEADER_SIZE];
+
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let Some(chunk) = chunk else {
+                continue;
+            };
+
+            let sector_offset = bytes.len() / SECTOR_SIZE;
+            bytes.extend_from_slice(&(chunk.data.len() as u32 + 1).to_be_bytes());
+            bytes.push(chunk.compression);
+            bytes.extend_from_slice(&chunk.data);
+            bytes.resize(bytes.len().next_multiple_of(SECTOR_SIZE), 0);
+            let sector_count = bytes.len() / SECTOR_SIZE - sector_offset;
+            if sector_count > 255 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "chunk {} takes {} sectors, at most 255 are supported",
+                        index, sector_count
+                    ),
+                ));
+            }
+
+            let location = ((sector_offset as u32) << 8) | sector_count as u32;
+            bytes[index * 4..index * 4 + 4].copy_from_slice(&location.to_be_bytes());
+            bytes[SECTOR_SIZE + index * 4..SECTOR_SIZE + index * 4 + 4]
+                .copy_from_slice(&chunk.timestamp.to_be_bytes());
+        }
+
+        Ok(bytes)
+    }
+
+    /// Writes the region to the given path. The file is written to a temporary
+    /// file first and then renamed, so a crash never leaves a half-written region behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let tmp_path = path.with_extension("mca.tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&self.to_bytes()?)?;
+        file.sync_all()?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+impl Default for RegionFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn index_of(pos: ChunkPos) -> usize {
+    (pos.x.rem_euclid(32) + pos.z.rem_euclid(32) * 32) as usize
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_for() {
+        assert_eq!(
+            PathBuf::from("region/r.0.0.mca"),
+            RegionFile::path_for("region", ChunkPos::new(31, 0))
+        );
+        assert_eq!(
+            PathBuf::from("region/r.-1.1.mca"),
+            RegionFile::path_for("region", ChunkPos::new(-1, 32))
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut region = RegionFile::new();
+        let large = vec![7_u8; 3 * SECTOR_SIZE];
+        region
+            .set_chunk_data(ChunkPos::new(0, 0), b"first")
+            .unwrap();
+        region
+            .set_chunk_data(ChunkPos::new(-1, -1), &large)
+            .unwrap();
+
+        let bytes = region.to_bytes().unwrap();
+        assert_eq!(0, bytes.len() % SECTOR_SIZE);
+
+        let region = RegionFile::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            Some(b"first".to_vec()),
+            region.chunk_data(ChunkPos::new(0, 0)).unwrap()
+        );
+        assert_eq!(
+            Some(large),
+            region.chunk_data(ChunkPos::new(31, 31)).unwrap()
+        );
+        assert_eq!(None, region.chunk_data(ChunkPos::new(1, 0)).unwrap());
+    }
+
+    #[test]
+    fn test_chunk_too_large() {
+        // random bytes don't compress, so they take more than 255 sectors
+        let mut seed = 1_u32;
+        let data = (0..256 * SECTOR_SIZE)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                (seed >> 16) as u8
+            })
+            .collect::<Vec<_>>();
+        let mut region = RegionFile::new();
+        region.set_chunk_data(ChunkPos::new(0, 0), &data).unwrap();
+        assert!(region.to_bytes().is_err());
+    }
+}
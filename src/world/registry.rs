@@ -0,0 +1,217 @@
+use crate::WorldRules;
+use crate::{argument, ArgumentKind, CommandEvent, CommandFeedback, CommandSource, CommandSpec};
+use bevy_ecs::prelude::*;
+use std::collections::BTreeMap;
+use valence::client::{VisibleChunkLayer, VisibleEntityLayers};
+use valence::entity::EntityLayerId;
+use valence::prelude::*;
+
+/// All worlds of the server, keyed by their name. Every world is an entity with
+/// a [`ChunkLayer`], an [`EntityLayer`] and a [`WorldInfo`].
+#[derive(Resource, Debug)]
+pub struct WorldRegistry {
+    worlds: BTreeMap<String, Entity>,
+    default_world: String,
+}
+
+impl WorldRegistry {
+    pub fn new(default_world: impl Into<String>) -> Self {
+        Self {
+            worlds: BTreeMap::new(),
+            default_world: default_world.into(),
+        }
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, world: Entity) {
+        self.worlds.insert(name.into(), world);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.worlds.get(name).copied()
+    }
+
+    /// The world that players join and respawn in.
+    ///
+    /// # Panics
+    /// Panics if the default world was never inserted.
+    pub fn default_world(&self) -> Entity {
+        self.worlds[&self.default_world]
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.worlds.keys().map(String::as_str)
+    }
+}
+
+/// Information about a world, lives on the world's layer entity.
+#[derive(Component, Debug, Clone)]
+pub struct WorldInfo {
+    pub name: String,
+    pub spawn: DVec3,
+    pub rules: WorldRules,
+}
+
+impl WorldInfo {
+    pub fn spawn_block_position(&self) -> BlockPos {
+        BlockPos::new(
+            self.spawn.x.floor() as i32,
+            self.spawn.y.floor() as i32,
+            self.spawn.z.floor() as i32,
+        )
+    }
+}
+
+/// Moves a client to the spawn of another world.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct ChangeWorldEvent {
+    pub client: Entity,
+    pub world: Entity,
+}
+
+/// `/world` lists all worlds, `/world <name>` moves the player to another world.
+pub fn world_command() -> CommandSpec {
+    CommandSpec::new("world", "justmine.command.world")
+        .usage([])
+        .usage([argument("name", ArgumentKind::Word)])
+}
+
+/// Handles `/world`.
+pub fn handle_world_command(
+    registry: Res<WorldRegistry>,
+    mut events: EventReader<CommandEvent>,
+    mut changes: EventWriter<ChangeWorldEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "world")
+        .for_each(|event| match event.args.string("name") {
+            None => {
+                let names = registry.names().collect::<Vec<_>>().join(", ");
+                feedback.send(CommandFeedback::info(
+                    event.source,
+                    format!("Worlds: {}", names),
+                ));
+            }
+            Some(name) => {
+                let CommandSource::Player(client) = event.source else {
+                    feedback.send(CommandFeedback::error(
+                        event.source,
+                        "Only players can change worlds",
+                    ));
+                    return;
+                };
+                match registry.get(name) {
+                    Some(world) => changes.send(ChangeWorldEvent { client, world }),
+                    None => feedback.send(CommandFeedback::error(
+                        event.source,
+                        format!("Unknown world: {}", name),
+                    )),
+                }
+            }
+        });
+}
+
+pub fn change_world(
+    mut clients: Query<(
+        &mut EntityLayerId,
+        &mut VisibleChunkLayer,
+        &mut VisibleEntityLayers,
+        &mut Position,
+    )>,
+    worlds: Query<&WorldInfo>,
+    mut events: EventReader<ChangeWorldEvent>,
+) {
+    events.iter().for_each(|event| {
+        let Ok(info) = worlds.get(event.world) else {
+            return;
+        };
+        let Ok((mut layer_id, mut visible_chunk_layer, mut visible_entity_layers, mut pos)) =
+            clients.get_mut(event.client)
+        else {
+            return;
+        };
+
+        visible_entity_layers.0.remove(&layer_id.0);
+        layer_id.0 = event.world;
+        visible_chunk_layer.0 = event.world;
+        visible_entity_layers.0.insert(event.world);
+        pos.set(info.spawn);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::protocol::packets::play::{ChunkDataS2c, RespawnS2c};
+    use valence::testing::ScenarioSingleClient;
+
+    #[test]
+    fn test_registry() {
+        let mut world = World::new();
+        let overworld = world.spawn_empty().id();
+        let nether = world.spawn_empty().id();
+        let mut registry = WorldRegistry::new("world");
+        registry.insert("world_nether", nether);
+        registry.insert("world", overworld);
+
+        assert_eq!(overworld, registry.default_world());
+        assert_eq!(Some(nether), registry.get("world_nether"));
+        assert_eq!(None, registry.get("world_the_end"));
+        assert_eq!(
+            vec!["world", "world_nether"],
+            registry.names().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_change_world() {
+        let mut scenario = ScenarioSingleClient::new();
+        scenario
+            .app
+            .add_event::<ChangeWorldEvent>()
+            .add_systems(Update, change_world);
+
+        let spawn = DVec3::new(8.5, 70.0, 8.5);
+        let world = {
+            let app_world = &scenario.app.world;
+            let mut layer = LayerBundle::new(
+                ident!("overworld"),
+                app_world.resource::<DimensionTypeRegistry>(),
+                app_world.resource::<BiomeRegistry>(),
+                app_world.resource::<Server>(),
+            );
+            layer
+                .chunk
+                .insert_chunk([0, 0], UnloadedChunk::with_height(layer.chunk.height()));
+            let info = WorldInfo {
+                name: "other".to_string(),
+                spawn,
+                rules: WorldRules::default(),
+            };
+            scenario.app.world.spawn((layer, info)).id()
+        };
+        scenario.app.update();
+        scenario.helper.clear_received();
+
+        let client = scenario.client;
+        scenario
+            .app
+            .world
+            .send_event(ChangeWorldEvent { client, world });
+        scenario.app.update();
+
+        let entity = scenario.app.world.entity(client);
+        assert_eq!(world, entity.get::<EntityLayerId>().unwrap().0);
+        assert_eq!(world, entity.get::<VisibleChunkLayer>().unwrap().0);
+        let visible = &entity.get::<VisibleEntityLayers>().unwrap().0;
+        assert!(visible.contains(&world));
+        assert!(!visible.contains(&scenario.layer));
+        assert_eq!(spawn, entity.get::<Position>().unwrap().0);
+
+        // the client is sent the new dimension and the chunks around the spawn
+        let received = scenario.helper.collect_received();
+        received.assert_count::<RespawnS2c>(1);
+        received.assert_count::<ChunkDataS2c>(1);
+    }
+}
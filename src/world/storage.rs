@@ -0,0 +1,646 @@
+use crate::world::{LevelData, RegionFile};
+use crate::{CommandEvent, CommandFeedback, CommandSpec, ServerConfig, Weather, WorldTime};
+use bevy_app::AppExit;
+use bevy_ecs::prelude::*;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{error, info};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use valence::biome::BiomeId;
+use valence::nbt::{compound, from_binary, to_binary, Compound, List, Value};
+use valence::prelude::*;
+
+/// The data version of Minecraft 1.20.1, which is the version that valence speaks.
+const DATA_VERSION: i32 = 3465;
+
+/// Sent whenever a system changes a block in a [`ChunkLayer`].
+#[derive(Event, Copy, Clone, Debug)]
+pub struct BlockChangeEvent {
+    pub layer: Entity,
+    pub position: BlockPos,
+}
+
+/// Requests that all dirty chunks of all worlds are written to disk.
+#[derive(Event, Copy, Clone, Debug, Default)]
+pub struct SaveWorldEvent;
+
+/// The on-disk location of a world and the chunks of it that changed since the
+/// last save. Lives on the same entity as the world's [`ChunkLayer`].
+///
+/// Chunks and the `level.dat` are encoded by the caller, but written on a separate
+/// thread, so saving never waits for the disk. Use [`WorldStorage::flush`] to wait
+/// until everything is written.
+#[derive(Component, Debug)]
+pub struct WorldStorage {
+    path: PathBuf,
+    dirty: HashSet<ChunkPos>,
+    /// The chunks that were queued for writing since the server started.
+    written: HashSet<ChunkPos>,
+    /// The `level.dat` as it was loaded, so that the values we don't know about
+    /// are kept when it is written again.
+    level: Compound,
+    writer: Sender<WriteJob>,
+}
+
+#[derive(Debug)]
+enum WriteJob {
+    Chunks(Vec<(ChunkPos, Vec<u8>)>),
+    Level(Vec<u8>),
+    Flush(Sender<io::Result<()>>),
+}
+
+impl WorldStorage {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let region_dir = path.join("region");
+        let level_path = path.join("level.dat");
+        let (writer, jobs) = channel();
+        thread::spawn(move || {
+            // chunks that couldn't be written yet, they are retried with the next job
+            let mut pending = HashMap::new();
+            for job in jobs {
+                match job {
+                    WriteJob::Chunks(chunks) => {
+                        pending.extend(chunks);
+                        if let Err(e) = write_regions(&region_dir, &mut pending) {
+                            error!("unable to save chunks to {}: {}", region_dir.display(), e);
+                        }
+                    }
+                    WriteJob::Level(bytes) => {
+                        if let Err(e) = write_replacing(&level_path, &bytes) {
+                            error!("unable to save {}: {}", level_path.display(), e);
+                        }
+                    }
+                    WriteJob::Flush(done) => {
+                        let _ = done.send(write_regions(&region_dir, &mut pending));
+                    }
+                }
+            }
+        });
+
+        Self {
+            path,
+            dirty: HashSet::new(),
+            written: HashSet::new(),
+            level: Compound::new(),
+            writer,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn region_dir(&self) -> PathBuf {
+        self.path.join("region")
+    }
+
+    pub fn mark_dirty(&mut self, pos: ChunkPos) {
+        self.dirty.insert(pos);
+    }
+
+    pub fn is_dirty(&self, pos: ChunkPos) -> bool {
+        self.dirty.contains(&pos)
+    }
+
+    /// Whether the chunk was written to its region file since the server started.
+    pub fn is_written(&self, pos: ChunkPos) -> bool {
+        self.written.contains(&pos)
+    }
+
+    /// Queues all dirty chunks that are still loaded in the given layer for writing
+    /// and returns how many chunks were queued. Chunks that aren't loaded stay dirty.
+    pub fn save(&mut self, layer: &ChunkLayer, biomes: &BiomeRegistry) -> io::Result<usize> {
+        let chunks = self
+            .dirty
+            .iter()
+            .copied()
+            .filter(|pos| layer.chunk(*pos).is_some())
+            .collect::<Vec<_>>();
+        self.save_chunks(layer, biomes, &chunks)?;
+        Ok(chunks.len())
+    }
+
+    /// Queues the given chunks for writing, regardless of whether they are dirty,
+    /// and removes them from the dirty set.
+    pub fn save_chunks(
+        &mut self,
+        layer: &ChunkLayer,
+        biomes: &BiomeRegistry,
+        chunks: &[ChunkPos],
+    ) -> io::Result<()> {
+        let biome_names = biomes
+            .iter()
+            .map(|(id, name, _)| (id, name.to_string()))
+            .collect::<HashMap<_, _>>();
+
+        let mut encoded = Vec::with_capacity(chunks.len());
+        for pos in chunks {
+            let Some(chunk) = layer.chunk(*pos) else {
+                continue;
+            };
+            let nbt = encode_chunk(*pos, layer.min_y(), chunk, &biome_names);
+            let mut bytes = Vec::new();
+            to_binary(&nbt, &mut bytes, "")
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            encoded.push((*pos, bytes));
+        }
+
+        encoded.iter().for_each(|(pos, _)| {
+            self.dirty.remove(pos);
+            self.written.insert(*pos);
+        });
+        self.send(WriteJob::Chunks(encoded))
+    }
+
+    /// Reads the `level.dat` of the world, `None` if the world doesn't have one yet.
+    pub fn load_level(&mut self) -> io::Result<Option<LevelData>> {
+        let path = self.path.join("level.dat");
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut bytes = Vec::new();
+        GzDecoder::new(fs::File::open(&path)?).read_to_end(&mut bytes)?;
+        let (level, _) = from_binary::<String>(&mut bytes.as_slice())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let data = match level.get("Data") {
+            Some(Value::Compound(data)) => LevelData::from_nbt(data),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} has no data", path.display()),
+                ))
+            }
+        };
+        self.level = level;
+        Ok(Some(data))
+    }
+
+    /// Queues the `level.dat` of the world for writing.
+    pub fn save_level(&mut self, data: &LevelData) -> io::Result<()> {
+        match self.level.get_mut("Data") {
+            Some(Value::Compound(nbt)) => data.write_to(nbt),
+            _ => {
+                let mut nbt = Compound::new();
+                data.write_to(&mut nbt);
+                self.level.insert("Data", nbt);
+            }
+        }
+
+        let mut bytes = Vec::new();
+        to_binary(&self.level, &mut bytes, "")
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&bytes)?;
+        self.send(WriteJob::Level(encoder.finish()?))
+    }
+
+    /// Waits until all queued chunks are written, and fails if some of them couldn't be.
+    pub fn flush(&self) -> io::Result<()> {
+        let (done, result) = channel();
+        self.send(WriteJob::Flush(done))?;
+        result.recv().map_err(|_| writer_stopped())?
+    }
+
+    fn send(&self, job: WriteJob) -> io::Result<()> {
+        self.writer.send(job).map_err(|_| writer_stopped())
+    }
+}
+
+fn writer_stopped() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "the region writer stopped")
+}
+
+/// Replaces the file at once, so that a crash while writing doesn't leave it
+/// truncated: the content is written next to it first, and then renamed.
+pub fn write_replacing(path: &Path, content: impl AsRef<[u8]>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(tmp, path)
+}
+
+/// Writes the chunks to their region files, and removes the ones that were written.
+fn write_regions(region_dir: &Path, chunks: &mut HashMap<ChunkPos, Vec<u8>>) -> io::Result<()> {
+    // group the chunks by region, so that every region file is only rewritten once
+    let mut regions = BTreeMap::<PathBuf, Vec<ChunkPos>>::new();
+    for pos in chunks.keys() {
+        regions
+            .entry(RegionFile::path_for(region_dir, *pos))
+            .or_default()
+            .push(*pos);
+    }
+
+    let mut result = Ok(());
+    for (path, positions) in regions {
+        let written = RegionFile::open(&path).and_then(|mut region| {
+            for pos in &positions {
+                region.set_chunk_data(*pos, &chunks[pos])?;
+            }
+            region.save(&path)
+        });
+        match written {
+            Ok(()) => positions.iter().for_each(|pos| {
+                chunks.remove(pos);
+            }),
+            Err(e) => result = Err(e),
+        }
+    }
+    result
+}
+
+pub fn mark_dirty_chunks(
+    mut events: EventReader<BlockChangeEvent>,
+    mut worlds: Query<&mut WorldStorage>,
+) {
+    events.iter().for_each(|event| {
+        if let Ok(mut storage) = worlds.get_mut(event.layer) {
+            storage.mark_dirty(ChunkPos::from_block_pos(event.position));
+        }
+    });
+}
+
+pub fn autosave(
+    config: Res<ServerConfig>,
+    mut last_save: Local<Option<Instant>>,
+    mut saves: EventWriter<SaveWorldEvent>,
+) {
+    if config.autosave_interval == 0 {
+        return;
+    }
+
+    let last = *last_save.get_or_insert_with(Instant::now);
+    if last.elapsed() >= Duration::from_secs(config.autosave_interval) {
+        *last_save = Some(Instant::now());
+        saves.send(SaveWorldEvent);
+    }
+}
+
+/// `/save-all`.
+pub fn save_all_command() -> CommandSpec {
+    CommandSpec::new("save-all", "justmine.command.save-all").usage([])
+}
+
+pub fn handle_save_command(
+    mut events: EventReader<CommandEvent>,
+    mut saves: EventWriter<SaveWorldEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    events
+        .iter()
+        .filter(|event| event.name == "save-all")
+        .for_each(|event| {
+            feedback.send(CommandFeedback::info(event.source, "Saving the game..."));
+            saves.send(SaveWorldEvent);
+        });
+}
+
+pub fn save_worlds(
+    mut events: EventReader<SaveWorldEvent>,
+    biomes: Res<BiomeRegistry>,
+    mut worlds: Query<(&ChunkLayer, &mut WorldStorage, &WorldTime, &Weather)>,
+) {
+    if events.iter().count() == 0 {
+        return;
+    }
+
+    save_all_worlds(&biomes, &mut worlds);
+}
+
+/// Flushes all worlds to disk when the app is about to exit, and waits until they
+/// are written. Must run in [`Last`], after everything else had the chance to
+/// change blocks.
+pub fn save_worlds_on_exit(
+    mut exit: EventReader<AppExit>,
+    biomes: Res<BiomeRegistry>,
+    mut worlds: Query<(&ChunkLayer, &mut WorldStorage, &WorldTime, &Weather)>,
+) {
+    if exit.iter().count() == 0 {
+        return;
+    }
+
+    save_all_worlds(&biomes, &mut worlds);
+    worlds.for_each(|(_, storage, _, _)| {
+        if let Err(e) = storage.flush() {
+            error!("unable to save {}: {}", storage.path().display(), e);
+        }
+    });
+}
+
+fn save_all_worlds(
+    biomes: &BiomeRegistry,
+    worlds: &mut Query<(&ChunkLayer, &mut WorldStorage, &WorldTime, &Weather)>,
+) {
+    worlds.for_each_mut(|(layer, mut storage, time, weather)| {
+        let level = LevelData {
+            time: *time,
+            weather: weather.state(),
+            weather_time: weather.remaining(),
+        };
+        if let Err(e) = storage.save_level(&level) {
+            error!("unable to save {}: {}", storage.path().display(), e);
+        }
+        match storage.save(layer, biomes) {
+            Ok(count) => info!("saving {} chunks to {}", count, storage.path().display()),
+            Err(e) => error!("unable to save {}: {}", storage.path().display(), e),
+        }
+    });
+}
+
+fn encode_chunk(
+    pos: ChunkPos,
+    min_y: i32,
+    chunk: &LoadedChunk,
+    biome_names: &HashMap<BiomeId, String>,
+) -> Compound {
+    let min_section_y = min_y.div_euclid(16);
+    let section_count = chunk.height() / 16;
+
+    let sections = (0..section_count)
+        .map(|section| encode_section(chunk, section, min_section_y, biome_names))
+        .collect();
+
+    let mut block_entities = Vec::new();
+    for y in 0..chunk.height() {
+        for z in 0..16 {
+            for x in 0..16 {
+                let Some(kind) = chunk.block_state(x, y, z).block_entity_kind() else {
+                    continue;
+                };
+                let mut nbt = chunk.block_entity(x, y, z).cloned().unwrap_or_default();
+                nbt.insert("id", kind.ident().to_string());
+                nbt.insert("x", pos.x * 16 + x as i32);
+                nbt.insert("y", min_y + y as i32);
+                nbt.insert("z", pos.z * 16 + z as i32);
+                block_entities.push(nbt);
+            }
+        }
+    }
+
+    compound! {
+        "DataVersion" => DATA_VERSION,
+        "xPos" => pos.x,
+        "zPos" => pos.z,
+        "yPos" => min_section_y,
+        "Status" => "minecraft:full",
+        // let the client and vanilla server recompute the light, we don't track it
+        "isLightOn" => 0_i8,
+        "sections" => List::Compound(sections),
+        "block_entities" => List::Compound(block_entities),
+    }
+}
+
+fn encode_section(
+    chunk: &LoadedChunk,
+    section: u32,
+    min_section_y: i32,
+    biome_names: &HashMap<BiomeId, String>,
+) -> Compound {
+    let mut block_palette = Vec::<BlockState>::new();
+    let mut block_indices = Vec::with_capacity(16 * 16 * 16);
+    for y in 0..16 {
+        for z in 0..16 {
+            for x in 0..16 {
+                let state = chunk.block_state(x, section * 16 + y, z);
+                block_indices.push(palette_index(&mut block_palette, state));
+            }
+        }
+    }
+
+    let mut biome_palette = Vec::<BiomeId>::new();
+    let mut biome_indices = Vec::with_capacity(4 * 4 * 4);
+    for y in 0..4 {
+        for z in 0..4 {
+            for x in 0..4 {
+                let biome = chunk.biome(x, section * 4 + y, z);
+                biome_indices.push(palette_index(&mut biome_palette, biome));
+            }
+        }
+    }
+
+    let mut block_states = compound! {
+        "palette" => List::Compound(block_palette.iter().map(|s| encode_block_state(*s)).collect()),
+    };
+    if block_palette.len() > 1 {
+        let bits = bits_for(block_palette.len()).max(4);
+        block_states.insert("data", Value::LongArray(pack(&block_indices, bits)));
+    }
+
+    let mut biomes = compound! {
+        "palette" => List::String(
+            biome_palette
+                .iter()
+                .map(|id| biome_names.get(id).cloned().unwrap_or_else(|| "minecraft:plains".to_string()))
+                .collect(),
+        ),
+    };
+    if biome_palette.len() > 1 {
+        let bits = bits_for(biome_palette.len());
+        biomes.insert("data", Value::LongArray(pack(&biome_indices, bits)));
+    }
+
+    compound! {
+        "Y" => (min_section_y + section as i32) as i8,
+        "block_states" => block_states,
+        "biomes" => biomes,
+    }
+}
+
+fn encode_block_state(state: BlockState) -> Compound {
+    let kind = state.to_kind();
+    let mut nbt = compound! {
+        "Name" => format!("minecraft:{}", kind.to_str()),
+    };
+
+    if !kind.props().is_empty() {
+        let mut properties = Compound::new();
+        for prop in kind.props() {
+            if let Some(value) = state.get(*prop) {
+                properties.insert(prop.to_str(), value.to_str());
+            }
+        }
+        nbt.insert("Properties", properties);
+    }
+
+    nbt
+}
+
+fn palette_index<T: PartialEq>(palette: &mut Vec<T>, value: T) -> usize {
+    match palette.iter().position(|v| *v == value) {
+        Some(index) => index,
+        None => {
+            palette.push(value);
+            palette.len() - 1
+        }
+    }
+}
+
+/// The number of bits needed to store indices into a palette of the given length.
+fn bits_for(palette_len: usize) -> usize {
+    (usize::BITS - (palette_len - 1).leading_zeros()) as usize
+}
+
+/// Packs the given indices into longs, without letting a value span two longs.
+fn pack(indices: &[usize], bits: usize) -> Vec<i64> {
+    let per_long = 64 / bits;
+    indices
+        .chunks(per_long)
+        .map(|chunk| {
+            chunk.iter().enumerate().fold(0_u64, |long, (i, index)| {
+                long | (*index as u64) << (i * bits)
+            }) as i64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valence::anvil::AnvilLevel;
+    use valence::testing::ScenarioSingleClient;
+    use valence::{ident, LayerBundle, Server};
+
+    #[test]
+    fn test_pack() {
+        assert_eq!(1, bits_for(2));
+        assert_eq!(2, bits_for(3));
+        assert_eq!(4, bits_for(16));
+        assert_eq!(5, bits_for(17));
+
+        // 12 values of 5 bits fit into one long, the 13th starts the next one
+        let indices = (0..13).map(|i| i % 17).collect::<Vec<_>>();
+        let packed = pack(&indices, 5);
+        assert_eq!(2, packed.len());
+        assert_eq!(11, (packed[0] as u64 >> 55) & 0b11111);
+        assert_eq!(12, packed[1]);
+    }
+
+    #[test]
+    fn test_save_and_load_level() {
+        let world_dir =
+            std::env::temp_dir().join(format!("justmine-test-level-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&world_dir);
+
+        let level = LevelData {
+            time: WorldTime {
+                age: 50000,
+                time_of_day: 13000,
+            },
+            weather: crate::WeatherState::Rain,
+            weather_time: 1200,
+        };
+        let mut storage = WorldStorage::new(&world_dir);
+        assert_eq!(None, storage.load_level().unwrap());
+        storage.save_level(&level).unwrap();
+        storage.flush().unwrap();
+
+        let mut storage = WorldStorage::new(&world_dir);
+        assert_eq!(Some(level), storage.load_level().unwrap());
+
+        let _ = std::fs::remove_dir_all(&world_dir);
+    }
+
+    #[test]
+    fn test_save_and_reload() {
+        let world_dir = std::env::temp_dir().join(format!(
+            "justmine-test-save-and-reload-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&world_dir);
+
+        let mut scenario = ScenarioSingleClient::new();
+        let pos = ChunkPos::new(0, 0);
+
+        let placed = [
+            (BlockPos::new(0, 0, 0), BlockState::STONE),
+            (BlockPos::new(3, -64, 7), BlockState::BEDROCK),
+            (
+                BlockPos::new(15, 100, 15),
+                BlockState::OAK_LOG.set(PropName::Axis, PropValue::X),
+            ),
+        ];
+
+        {
+            let mut entity_mut = scenario.app.world.entity_mut(scenario.layer);
+            let mut layer = entity_mut.get_mut::<ChunkLayer>().unwrap();
+            layer.insert_chunk(pos, UnloadedChunk::with_height(layer.height()));
+            for (position, state) in placed {
+                layer.set_block(position, state);
+            }
+        }
+
+        {
+            let world = &scenario.app.world;
+            let layer = world.entity(scenario.layer).get::<ChunkLayer>().unwrap();
+            let mut storage = WorldStorage::new(&world_dir);
+            let unloaded = ChunkPos::new(100, 100);
+            storage.mark_dirty(pos);
+            storage.mark_dirty(unloaded);
+            assert_eq!(1, storage.save(layer, world.resource()).unwrap());
+            assert!(!storage.is_dirty(pos));
+            assert!(storage.is_dirty(unloaded));
+            storage.flush().unwrap();
+        }
+
+        let reloaded = {
+            let world = &scenario.app.world;
+            let biomes = world.resource::<BiomeRegistry>();
+            let bundle = LayerBundle::new(
+                ident!("overworld"),
+                world.resource::<DimensionTypeRegistry>(),
+                biomes,
+                world.resource::<Server>(),
+            );
+            let mut level = AnvilLevel::new(&world_dir, biomes);
+            level.force_chunk_load(pos);
+            scenario.app.world.spawn((bundle, level)).id()
+        };
+
+        // chunks are loaded on a separate thread, so give it some time
+        for _ in 0..200 {
+            scenario.app.update();
+            let layer = scenario.app.world.get::<ChunkLayer>(reloaded).unwrap();
+            if layer.chunk(pos).is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        let world = &scenario.app.world;
+        let original = world.get::<ChunkLayer>(scenario.layer).unwrap();
+        let reloaded = world.get::<ChunkLayer>(reloaded).unwrap();
+        assert!(reloaded.chunk(pos).is_some(), "chunk was not reloaded");
+
+        for (position, state) in placed {
+            assert_eq!(Some(state), reloaded.block(position).map(|b| b.state));
+        }
+
+        let min_y = original.min_y();
+        for y in min_y..min_y + original.height() as i32 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let position = BlockPos::new(x, y, z);
+                    assert_eq!(
+                        original.block(position).map(|b| b.state),
+                        reloaded.block(position).map(|b| b.state),
+                        "block at {:?} differs after reload",
+                        position,
+                    );
+                }
+            }
+        }
+
+        let _ = std::fs::remove_dir_all(&world_dir);
+    }
+}
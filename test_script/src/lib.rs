@@ -11,7 +11,7 @@ where
         .collect()
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Line {
     Set(Set),
     Assert(Assert),
@@ -79,10 +79,12 @@ impl Assert {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Interact {
     pub position: Position,
     pub face: Face,
+    /// The clicked point relative to the block, the center of the face if not given.
+    pub cursor: Option<(f32, f32, f32)>,
 }
 
 impl Interact {
@@ -101,7 +103,16 @@ impl Interact {
             .filter(|&s| s == "face")
             .expect("expected 'face' keyword");
         let face = Face::parse(fragments.by_ref());
-        Self { position, face }
+        let cursor = fragments.next().map(|keyword| {
+            assert_eq!("cursor", keyword, "expected 'cursor' keyword");
+            let mut coordinate = || fragments.next().unwrap().parse().unwrap();
+            (coordinate(), coordinate(), coordinate())
+        });
+        Self {
+            position,
+            face,
+            cursor,
+        }
     }
 }
 
@@ -154,9 +165,12 @@ impl Position {
     }
 }
 
+/// A block, optionally with properties like `oak_stairs[facing=east,half=top]`.
+/// Properties that are not given have their default value.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Block {
     pub id: String,
+    pub properties: Vec<(String, String)>,
 }
 
 impl Block {
@@ -169,16 +183,41 @@ impl Block {
             .next()
             .filter(|&s| s == "block")
             .expect("expected 'block' keyword");
-        let id = fragments.next().unwrap().to_string();
-        Self { id }
+        let block = fragments.next().unwrap();
+        let (id, properties) = match block.split_once('[') {
+            Some((id, properties)) => (
+                id,
+                properties
+                    .strip_suffix(']')
+                    .expect("expected ']' after block properties")
+                    .split(',')
+                    .map(|property| {
+                        let (name, value) = property
+                            .split_once('=')
+                            .expect("expected block property as name=value");
+                        (name.to_string(), value.to_string())
+                    })
+                    .collect(),
+            ),
+            None => (block, vec![]),
+        };
+        Self {
+            id: id.to_string(),
+            properties,
+        }
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Set {
     Gamemode(Gamemode),
     Inventory(Inventory),
     HeldItem(u16),
+    PlayerPosition(f64, f64, f64),
+    /// Yaw and pitch of the player.
+    PlayerLook(f32, f32),
+    PlayerSneaking(bool),
+    Block(Position, Block),
 }
 
 impl Set {
@@ -192,6 +231,27 @@ impl Set {
             Some("gamemode") => Self::Gamemode(Gamemode::parse(fragments)),
             Some("inventory") => Self::Inventory(Inventory::parse(fragments)),
             Some("held_item") => Self::HeldItem(fragments.next().unwrap().parse().unwrap()),
+            Some("player") => match fragments.next() {
+                Some("position") => {
+                    let mut coordinate = || fragments.next().unwrap().parse().unwrap();
+                    Self::PlayerPosition(coordinate(), coordinate(), coordinate())
+                }
+                Some("look") => {
+                    let mut angle = || fragments.next().unwrap().parse().unwrap();
+                    Self::PlayerLook(angle(), angle())
+                }
+                Some("sneaking") => {
+                    Self::PlayerSneaking(fragments.next().unwrap().parse().unwrap())
+                }
+                other => panic!(
+                    "expected 'position', 'look' or 'sneaking' keyword, got {:?}",
+                    other
+                ),
+            },
+            Some("position") => Self::Block(
+                Position::parse(fragments.by_ref()),
+                Block::parse(fragments.by_ref()),
+            ),
             _ => panic!("unknown command: {:?}", cmd),
         }
     }
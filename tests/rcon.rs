@@ -0,0 +1,274 @@
+use bevy_app::{App, Update};
+use bevy_ecs::prelude::*;
+use justmine::{
+    deliver_feedback, dispatch_commands, handle_time_command, read_rcon, reply_rcon, CommandEvent,
+    CommandFeedback, CommandRegistry, ExecuteCommandEvent, Rcon, RconPacket, WorldRegistry,
+    WorldTime, MAX_RCON_CONNECTIONS, RCON_AUTH, RCON_AUTH_RESPONSE, RCON_AUTH_TIMEOUT,
+    RCON_EXEC_COMMAND, RCON_RESPONSE_VALUE,
+};
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use valence::testing::ScenarioSingleClient;
+
+const PASSWORD: &str = "hunter2";
+
+/// Stands in for the command handlers, and answers every command with its words.
+fn echo_commands(
+    mut commands: EventReader<ExecuteCommandEvent>,
+    mut feedback: EventWriter<CommandFeedback>,
+) {
+    for event in commands.iter() {
+        feedback.send(CommandFeedback::info(
+            event.source,
+            format!("ran {}", event.command),
+        ));
+        if event.command == "long" {
+            feedback.send(CommandFeedback::info(event.source, "x".repeat(5000)));
+        }
+    }
+}
+
+fn start_server() -> (App, SocketAddr) {
+    let rcon = Rcon::bind("127.0.0.1:0".parse().unwrap(), PASSWORD).unwrap();
+    let address = rcon.local_addr();
+    let mut app = App::new();
+    app.insert_resource(rcon)
+        .add_event::<ExecuteCommandEvent>()
+        .add_event::<CommandFeedback>()
+        .add_systems(Update, (read_rcon, echo_commands, reply_rcon).chain());
+    (app, address)
+}
+
+/// A server with the real command framework and `/time`, in the world of the
+/// scenario.
+fn start_command_server() -> (ScenarioSingleClient, SocketAddr) {
+    let rcon = Rcon::bind("127.0.0.1:0".parse().unwrap(), PASSWORD).unwrap();
+    let address = rcon.local_addr();
+    let mut scenario = ScenarioSingleClient::new();
+    let mut registry = WorldRegistry::new("world");
+    registry.insert("world", scenario.layer);
+    scenario
+        .app
+        .world
+        .entity_mut(scenario.layer)
+        .insert(WorldTime::default());
+    scenario
+        .app
+        .insert_resource(rcon)
+        .insert_resource(registry)
+        .init_resource::<CommandRegistry>()
+        .add_event::<ExecuteCommandEvent>()
+        .add_event::<CommandEvent>()
+        .add_event::<CommandFeedback>()
+        .add_systems(
+            Update,
+            (
+                read_rcon,
+                dispatch_commands,
+                handle_time_command,
+                deliver_feedback,
+                reply_rcon,
+            )
+                .chain(),
+        );
+    (scenario, address)
+}
+
+/// Updates the server until the client, which runs on another thread, is done.
+fn run_client<T: Send + 'static>(app: &mut App, client: impl FnOnce() -> T + Send + 'static) -> T {
+    let handle = thread::spawn(client);
+    while !handle.is_finished() {
+        app.update();
+        thread::sleep(Duration::from_millis(5));
+    }
+    handle.join().unwrap()
+}
+
+/// A minimal RCON client, like the ones that admins use.
+struct RconClient(TcpStream);
+
+impl RconClient {
+    fn connect(address: SocketAddr) -> Self {
+        let stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        Self(stream)
+    }
+
+    fn send(&mut self, id: i32, kind: i32, body: &str) {
+        RconPacket::new(id, kind, body).write(&mut self.0).unwrap();
+    }
+
+    fn receive(&mut self) -> io::Result<RconPacket> {
+        RconPacket::read(&mut self.0)
+    }
+
+    fn login(address: SocketAddr) -> Self {
+        let mut client = Self::connect(address);
+        client.send(1, RCON_AUTH, PASSWORD);
+        assert_eq!(1, client.receive().unwrap().id);
+        client
+    }
+}
+
+#[test]
+fn test_execute_commands() {
+    let (mut app, address) = start_server();
+    run_client(&mut app, move || {
+        let mut client = RconClient::connect(address);
+        client.send(1, RCON_AUTH, PASSWORD);
+        assert_eq!(
+            RconPacket::new(1, RCON_AUTH_RESPONSE, ""),
+            client.receive().unwrap()
+        );
+
+        client.send(2, RCON_EXEC_COMMAND, "/time set day");
+        assert_eq!(
+            RconPacket::new(2, RCON_RESPONSE_VALUE, "ran time set day"),
+            client.receive().unwrap()
+        );
+
+        // long output is split into several packets
+        client.send(3, RCON_EXEC_COMMAND, "long");
+        let first = client.receive().unwrap();
+        let second = client.receive().unwrap();
+        assert_eq!((3, 3), (first.id, second.id));
+        assert_eq!(4096, first.body.len());
+        assert_eq!(
+            format!("ran long\n{}", "x".repeat(5000)),
+            first.body + &second.body
+        );
+    });
+}
+
+#[test]
+fn test_wrong_password() {
+    let (mut app, address) = start_server();
+    run_client(&mut app, move || {
+        let mut client = RconClient::connect(address);
+        client.send(1, RCON_AUTH, "letmein");
+        assert_eq!(-1, client.receive().unwrap().id);
+        // the server closes the connection
+        assert_eq!(
+            io::ErrorKind::UnexpectedEof,
+            client.receive().unwrap_err().kind()
+        );
+    });
+}
+
+#[test]
+fn test_command_without_auth() {
+    let (mut app, address) = start_server();
+    run_client(&mut app, move || {
+        let mut client = RconClient::connect(address);
+        client.send(1, RCON_EXEC_COMMAND, "stop");
+        assert_eq!(
+            RconPacket::new(-1, RCON_AUTH_RESPONSE, ""),
+            client.receive().unwrap()
+        );
+    });
+}
+
+#[test]
+fn test_unknown_packet_without_auth() {
+    let (mut app, address) = start_server();
+    run_client(&mut app, move || {
+        let mut client = RconClient::connect(address);
+        client.send(1, 7, "");
+        assert_eq!(-1, client.receive().unwrap().id);
+        assert_eq!(
+            io::ErrorKind::UnexpectedEof,
+            client.receive().unwrap_err().kind()
+        );
+    });
+}
+
+#[test]
+fn test_trickled_login() {
+    let (mut app, address) = start_server();
+    run_client(&mut app, move || {
+        let mut client = RconClient::connect(address);
+        client
+            .0
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        // a long login packet, one byte at a time
+        let mut packet = 1000_i32
+            .to_le_bytes()
+            .into_iter()
+            .chain(std::iter::repeat(0));
+        let connected = Instant::now();
+        let mut byte = [0; 1];
+        let closed = loop {
+            assert!(
+                connected.elapsed() < RCON_AUTH_TIMEOUT * 2,
+                "still connected"
+            );
+            let _ = client.0.write_all(&[packet.next().unwrap()]);
+            match client.0.read(&mut byte) {
+                Ok(0) => break connected.elapsed(),
+                Ok(_) => panic!("unexpected response"),
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => break connected.elapsed(),
+                Err(e) => assert!(matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                )),
+            }
+        };
+        assert!(closed >= RCON_AUTH_TIMEOUT);
+    });
+}
+
+#[test]
+fn test_builtin_command() {
+    let (mut scenario, address) = start_command_server();
+    run_client(&mut scenario.app, move || {
+        let mut client = RconClient::login(address);
+        // RCON may run commands that need op, in the default world
+        client.send(2, RCON_EXEC_COMMAND, "time set noon");
+        assert_eq!(
+            RconPacket::new(2, RCON_RESPONSE_VALUE, "Set the time to 6000"),
+            client.receive().unwrap()
+        );
+
+        client.send(3, RCON_EXEC_COMMAND, "/time");
+        assert_eq!(
+            RconPacket::new(3, RCON_RESPONSE_VALUE, "The time is 6000"),
+            client.receive().unwrap()
+        );
+
+        // errors of the parser come back as well
+        client.send(4, RCON_EXEC_COMMAND, "time set dusk");
+        let response = client.receive().unwrap();
+        assert_eq!(4, response.id);
+        assert!(!response.body.is_empty());
+    });
+
+    let time = scenario.app.world.get::<WorldTime>(scenario.layer).unwrap();
+    assert_eq!(6000, time.day_time());
+}
+
+#[test]
+fn test_too_many_connections() {
+    let (mut app, address) = start_server();
+    run_client(&mut app, move || {
+        let clients = (0..MAX_RCON_CONNECTIONS)
+            .map(|_| RconClient::login(address))
+            .collect::<Vec<_>>();
+
+        let mut refused = RconClient::connect(address);
+        let error = refused.receive().unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset
+        ));
+
+        // the connections that were accepted still work
+        let mut client = clients.into_iter().next().unwrap();
+        client.send(2, RCON_EXEC_COMMAND, "list");
+        assert_eq!("ran list", client.receive().unwrap().body);
+    });
+}